@@ -1,7 +1,39 @@
 use palimp_core::{Application, CrawlResult};
+use palimp_core::audit::Audit;
+use palimp_core::crawl::{CrawlMode, CrawlOptions, CrawlStatus, StopSignal};
+use palimp_core::crawl_meta::{self, CrawlMeta};
+use palimp_core::manifest::CrawlManifest;
+use palimp_core::jobs::{JobKind, JobStatus, Worker, DEFAULT_CONCURRENCY_BUDGET};
+use palimp_core::estimate::{format_bytes, format_duration, CrawlEstimate};
+use palimp_core::export::{self, ExportOptions};
+use palimp_core::http_client::HTTPClient;
+use palimp_core::monitor::MonitorKind;
+use palimp_core::preview::SelectorPreview;
+use palimp_core::query_history::QueryHistory;
+use palimp_core::result_entry::{ResultEntry, ResultFilter, ResultStatus};
+use palimp_core::result_match::{MatchChangeKind, ResultMatch};
+use palimp_core::site::SiteValidation;
+use palimp_core::sql;
+use palimp_core::storage_report::StorageReport;
+use palimp_core::suite::SuiteCheck;
+use palimp_core::template;
+use palimp_core::trash::TrashKind;
+use palimp_core::audit_log::AuditAction;
+use palimp_core::site_config::SiteConfig;
+use palimp_core::sitemap_change::SitemapChangeKind;
+use palimp_core::link_graph::GraphFormat;
+use palimp_core::link_crawl::{LinkFollowing, DEFAULT_LINK_DEPTH, DEFAULT_LINK_PAGES};
+use palimp_core::sitemap::{SitemapLimits, DEFAULT_SITEMAP_DEPTH};
+use palimp_core::assertion::{RuleSet, Severity};
+use palimp_core::asset::{AssetKind, AssetLimits};
+#[cfg(feature = "google-sheets")]
+use palimp_core::google_sheets::{SheetTarget, SheetWrite, SheetsClient};
 use std::env;
 use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
 use std::process;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
@@ -12,8 +44,31 @@ async fn main() {
 }
 
 async fn run() -> Result<(), Box<dyn Error>> {
-    let app = Application::new("palimp.db")?;
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // Lets stakeholders browse an archive without crawling into or deleting from it
+    let mut read_only = env::var("PALIMP_READ_ONLY").is_ok_and(|value| value == "1");
+    if args.get(1).is_some_and(|arg| arg == "--read-only") {
+        args.remove(1);
+        read_only = true;
+    }
+
+    // Checks never open the archive, so they work from any directory
+    if args.get(1).is_some_and(|command| command == "check") {
+        return handle_check(&args[2..]).await;
+    }
+
+    let mut app = Application::with_http_client("palimp.db", HTTPClient::from_env()?)?.with_read_only(read_only);
+    if let Ok(user) = env::var("PALIMP_USER").or_else(|_| env::var("USER")).or_else(|_| env::var("USERNAME")) {
+        app = app.with_actor(&user);
+    }
+
+    // Credentials are only used to crawl
+    if let Ok(passphrase) = env::var("PALIMP_PASSPHRASE")
+        && !read_only
+    {
+        app.unlock_secrets(&passphrase).await?;
+    }
 
     if args.len() < 2 {
         print_help();
@@ -26,6 +81,21 @@ async fn run() -> Result<(), Box<dyn Error>> {
         "queries" => handle_queries(&app, &args[2..]).await?,
         "results" => handle_results(&app, &args[2..]).await?,
         "export" => handle_export(&app, &args[2..]).await?,
+        "import" => handle_import(&app, &args[2..]).await?,
+        "audits" => handle_audits(&app, &args[2..]).await?,
+        "reports" => handle_reports(&app, &args[2..]).await?,
+        "pages" => handle_pages(&app, &args[2..]).await?,
+        "monitors" => handle_monitors(&app, &args[2..]).await?,
+        "rules" => handle_rules(&app, &args[2..]).await?,
+        "alerts" => handle_alerts(&app, &args[2..]).await?,
+        "suites" => handle_suites(&app, &args[2..]).await?,
+        "jobs" => handle_jobs(&app, &args[2..]).await?,
+        "daemon" => handle_daemon(&app, &args[2..]).await?,
+        "archive" => handle_archive(&app, &args[2..]).await?,
+        "trash" => handle_trash(&app, &args[2..]).await?,
+        "audit-log" => handle_audit_log(&app, &args[2..]).await?,
+        "assert" => handle_assert(&app, &args[2..]).await?,
+        "sql" => handle_sql(&app, &args[2..]).await?,
         _ => print_help(),
     }
 
@@ -44,25 +114,55 @@ async fn handle_sites(app: &Application, args: &[String]) -> Result<(), Box<dyn
             if sites.is_empty() {
                 println!("No sites found.");
             } else {
-                println!("{:<5} {:<30} {:<50}", "ID", "Domain", "Sitemap URL");
-                println!("{:-<5} {:-<30} {:-<50}", "", "", "");
+                println!("{:<5} {:<30} {:<50} {:<10} {:<20}", "ID", "Domain", "Sitemap URL", "TLS", "Tags");
+                println!("{:-<5} {:-<30} {:-<50} {:-<10} {:-<20}", "", "", "", "", "");
                 for site in sites {
+                    let tags = app.list_site_tags(site.id.unwrap_or(0)).await?;
                     println!(
-                        "{:<5} {:<30} {:<50}",
+                        "{:<5} {:<30} {:<50} {:<10} {:<20}",
                         site.id.unwrap_or(0),
                         site.domain,
-                        site.sitemap_url
+                        site.sitemap_url,
+                        if site.insecure_tls { "INSECURE" } else { "verified" },
+                        tags.join(", ")
                     );
                 }
             }
         }
         "new" => {
-            if args.len() != 3 {
-                println!("Usage: sites new <domain> <sitemap_url>");
+            let skip_validation = args.len() == 4 && args[3] == "--skip-validation";
+            if args.len() != 3 && !skip_validation {
+                println!("Usage: sites new <domain> <sitemap_url> [--skip-validation]");
+                return Ok(());
+            }
+            let validation = if skip_validation { SiteValidation::Skip } else { SiteValidation::Full };
+            let site = app.new_site_with_validation(&args[1], &args[2], validation).await?;
+            println!("Site {} saved: {} ({}).", site.id.unwrap_or(0), site.domain, site.sitemap_url);
+        }
+        "export" => {
+            if args.len() != 2 {
+                println!("Usage: sites export <file.yaml>");
+                return Ok(());
+            }
+            let config = app.export_config().await?;
+            std::fs::write(&args[1], config.to_yaml()?)?;
+            println!("Exported {} site(s) and {} suite(s) to '{}'.", config.sites.len(), config.suites.len(), args[1]);
+        }
+        "import" => {
+            if args.len() != 2 {
+                println!("Usage: sites import <file.yaml>");
                 return Ok(());
             }
-            app.new_site(&args[1], &args[2]).await?;
-            println!("Site created successfully.");
+            let config = SiteConfig::from_yaml(&std::fs::read_to_string(&args[1])?)?;
+            let import = app.import_config(&config).await?;
+            println!(
+                "Created {} site(s), updated {}, added {} monitor(s) and {} rule(s), and saved {} suite(s).",
+                import.sites_created,
+                import.sites_updated,
+                import.monitors_added,
+                import.rules_added,
+                import.suites_saved
+            );
         }
         "delete" => {
             if args.len() != 2 {
@@ -71,7 +171,225 @@ async fn handle_sites(app: &Application, args: &[String]) -> Result<(), Box<dyn
             }
             let id = args[1].parse::<i64>()?;
             app.delete_site(id).await?;
-            println!("Site deleted successfully.");
+            println!("Site moved to the trash.");
+        }
+        "edit" => {
+            if args.len() < 4 {
+                println!("Usage: sites edit <id> [--domain <domain>] [--sitemap <sitemap_url>]");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let mut domain = None;
+            let mut sitemap_url = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--domain" => domain = Some(rest.next().ok_or("--domain requires a value")?.as_str()),
+                    "--sitemap" => sitemap_url = Some(rest.next().ok_or("--sitemap requires a URL")?.as_str()),
+                    other => return Err(format!("Unknown option '{}'", other).into()),
+                }
+            }
+            let site = app.update_site(id, domain, sitemap_url).await?;
+            println!("Site {} updated: {} ({}).", id, site.domain, site.sitemap_url);
+        }
+        "insecure-tls" => {
+            if args.len() != 3 || !matches!(args[2].as_str(), "on" | "off") {
+                println!("Usage: sites insecure-tls <id> <on|off>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let enabled = args[2] == "on";
+            app.set_site_insecure_tls(id, enabled).await?;
+            if enabled {
+                eprintln!("WARNING: TLS certificate verification is now DISABLED for site {}.", id);
+                eprintln!("WARNING: Only use this for staging servers you trust; responses could be intercepted or forged.");
+            } else {
+                println!("TLS certificate verification enabled for site {}.", id);
+            }
+        }
+        "fetch-override" => {
+            if args.len() != 3 {
+                println!("Usage: sites fetch-override <id> <ip|base_url|none>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            if args[2] == "none" {
+                app.set_site_fetch_override(id, None).await?;
+                println!("Pages of site {} will be fetched from their sitemap URLs.", id);
+            } else {
+                app.set_site_fetch_override(id, Some(&args[2])).await?;
+                println!("Pages of site {} will be fetched from {}.", id, args[2]);
+            }
+        }
+        "soft-404" => {
+            if args.len() < 3 {
+                println!("Usage: sites soft-404 <id> [--min-bytes <n>] [--phrases <phrase,phrase,...>] | reset");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            if args[2] == "reset" {
+                app.set_site_soft_404_heuristics(id, None, None).await?;
+                println!("Site {} uses the default soft 404 heuristics.", id);
+                return Ok(());
+            }
+
+            let mut min_text_bytes = None;
+            let mut phrases = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--min-bytes" => {
+                        let value = rest.next().ok_or("--min-bytes requires a number")?;
+                        min_text_bytes = Some(value.parse::<usize>()?);
+                    }
+                    "--phrases" => {
+                        let value = rest.next().ok_or("--phrases requires a comma-separated list")?;
+                        phrases = Some(value.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect());
+                    }
+                    other => return Err(format!("Unknown option '{}'", other).into()),
+                }
+            }
+
+            app.set_site_soft_404_heuristics(id, min_text_bytes, phrases).await?;
+            println!("Soft 404 heuristics of site {} updated.", id);
+        }
+        "politeness" => {
+            if args.len() < 3 {
+                println!("Usage: sites politeness <id> [--rps <n>] [--delay <ms>] | off");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            if args[2] == "off" {
+                app.set_site_politeness(id, None, None).await?;
+                println!("Crawls of site {} are no longer paced.", id);
+                return Ok(());
+            }
+
+            let mut max_requests_per_sec = None;
+            let mut min_delay_ms = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--rps" => {
+                        let value = rest.next().ok_or("--rps requires a number")?;
+                        max_requests_per_sec = Some(value.parse::<f64>()?);
+                    }
+                    "--delay" => {
+                        let value = rest.next().ok_or("--delay requires milliseconds")?;
+                        min_delay_ms = Some(value.parse::<u64>()?);
+                    }
+                    other => return Err(format!("Unknown option '{}'", other).into()),
+                }
+            }
+
+            app.set_site_politeness(id, max_requests_per_sec, min_delay_ms).await?;
+            let site = app.list_sites().await?.into_iter().find(|site| site.id == Some(id)).ok_or("Site not found")?;
+            match site.request_pacer()? {
+                Some(pacer) => println!("Crawls of site {} start a request at most every {} ms.", id, pacer.interval().as_millis()),
+                None => println!("Crawls of site {} are no longer paced.", id),
+            }
+        }
+        "tag" => {
+            if args.len() != 3 {
+                println!("Usage: sites tag <id> <tag>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let tag = palimp_core::site_tag::normalize(&args[2])?;
+            if app.tag_site(id, &tag).await? {
+                println!("Site {} tagged {}.", id, tag);
+            } else {
+                println!("Site {} was already tagged {}.", id, tag);
+            }
+        }
+        "untag" => {
+            if args.len() != 3 {
+                println!("Usage: sites untag <id> <tag>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let tag = palimp_core::site_tag::normalize(&args[2])?;
+            if app.untag_site(id, &tag).await? {
+                println!("Tag {} removed from site {}.", tag, id);
+            } else {
+                println!("Site {} isn't tagged {}.", id, tag);
+            }
+        }
+        "credentials" => {
+            if args.len() < 3 {
+                println!("Usage: sites credentials <id> <list|set|delete> [header] [value]");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            match (args[2].as_str(), &args[3..]) {
+                ("list", []) => {
+                    let names = app.list_site_credentials(id).await?;
+                    if names.is_empty() {
+                        println!("No credentials stored for site {}.", id);
+                    }
+                    for name in names {
+                        println!("{}: ********", name);
+                    }
+                }
+                ("set", [name, value]) => {
+                    app.set_site_credential(id, name, value).await?;
+                    println!("Credential {} stored encrypted for site {}.", name, id);
+                }
+                ("delete", [name]) => {
+                    app.delete_site_credential(id, name).await?;
+                    println!("Credential {} deleted.", name);
+                }
+                _ => println!("Usage: sites credentials <id> <list|set|delete> [header] [value]"),
+            }
+        }
+        "dashboard" => {
+            if args.len() != 2 {
+                println!("Usage: sites dashboard <id>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let dashboard = app.site_dashboard(id).await?;
+            println!("Site: {} ({})", dashboard.site.domain, dashboard.site.sitemap_url);
+
+            match &dashboard.latest_crawl {
+                Some(stats) => println!(
+                    "Latest crawl: #{} at {} - {} pages, {} errors ({:.1}% error rate)",
+                    stats.crawl_id,
+                    stats.started_at.as_deref().unwrap_or("Unknown"),
+                    stats.page_count,
+                    stats.error_count,
+                    stats.error_rate() * 100.0
+                ),
+                None => println!("Latest crawl: none"),
+            }
+
+            if !dashboard.crawl_trend.is_empty() {
+                println!();
+                println!("{:<10} {:<25} {:<10} {:<10} {:<10}", "Crawl ID", "Started At", "Pages", "Errors", "Error %");
+                println!("{:-<10} {:-<25} {:-<10} {:-<10} {:-<10}", "", "", "", "", "");
+                for stats in &dashboard.crawl_trend {
+                    println!(
+                        "{:<10} {:<25} {:<10} {:<10} {:<10.1}",
+                        stats.crawl_id,
+                        stats.started_at.as_deref().unwrap_or("Unknown"),
+                        stats.page_count,
+                        stats.error_count,
+                        stats.error_rate() * 100.0
+                    );
+                }
+            }
+
+            if !dashboard.recent_queries.is_empty() {
+                println!();
+                println!("{:<10} {:<10} {:<30} {:<15} {:<10}", "Query ID", "Crawl ID", "Query", "Pages Matched", "Total");
+                println!("{:-<10} {:-<10} {:-<30} {:-<15} {:-<10}", "", "", "", "", "");
+                for summary in &dashboard.recent_queries {
+                    println!(
+                        "{:<10} {:<10} {:<30} {:<15} {:<10}",
+                        summary.query_id, summary.crawl_id, summary.label(), summary.matching_pages, summary.total_count
+                    );
+                }
+            }
         }
         _ => print_help(),
     }
@@ -97,8 +415,11 @@ async fn handle_crawls(app: &Application, args: &[String]) -> Result<(), Box<dyn
             if crawls.is_empty() {
                 println!("No crawls found.");
             } else {
-                println!("{:<5} {:<40} {:<30}", "ID", "Site", "Started At");
-                println!("{:-<5} {:-<40} {:-<30}", "", "", "");
+                println!(
+                    "{:<5} {:<40} {:<20} {:<20} {:<10} {:<21} {:<10}",
+                    "ID", "Site", "Started At", "Finished At", "Duration", "Status", "Fetched"
+                );
+                println!("{:-<5} {:-<40} {:-<20} {:-<20} {:-<10} {:-<21} {:-<10}", "", "", "", "", "", "", "");
                 for crawl in crawls {
                     let site_display = match site_map.get(&crawl.site_id) {
                         Some(domain) => format!("{} (ID: {})", domain, crawl.site_id),
@@ -106,220 +427,2371 @@ async fn handle_crawls(app: &Application, args: &[String]) -> Result<(), Box<dyn
                     };
 
                     println!(
-                        "{:<5} {:<40} {:<30}",
+                        "{:<5} {:<40} {:<20} {:<20} {:<10} {:<21} {:<10}",
                         crawl.id.unwrap_or(0),
                         site_display,
-                        crawl.started_at.as_deref().unwrap_or("Unknown")
+                        crawl.started_at.as_deref().unwrap_or("Unknown"),
+                        crawl.finished_at.as_deref().unwrap_or("-"),
+                        crawl.duration.map(format_duration).unwrap_or_else(|| "-".to_string()),
+                        if crawl.pinned { format!("{} (pinned)", crawl.status.name()) } else { crawl.status.name().to_string() },
+                        format_bytes(crawl.bytes_fetched)
                     );
                 }
             }
         }
         "new" => {
             if args.len() < 2 {
-                println!("Usage: crawls new <site_id> [max_concurrent]");
+                println!("Usage: crawls new <site_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>] [--user-agent <ua>]... [--sitemap-depth <n>] [--max-urls <n>] [--meta <key=value>]... [--urls <file> | --follow-links [--seed <url>]... [--depth <n>] [--max-pages <n>]] [--assets [--asset-limit <kind=size>]...] [--ignore-robots] [--yes]");
                 return Ok(());
             }
             let site_id = args[1].parse::<i64>()?;
-            let max_concurrent = if args.len() >= 3 {
-                args[2].parse::<usize>().unwrap_or(5)
-            } else {
-                5
+
+            let confirmed = args[2..].iter().any(|arg| arg == "--yes");
+            let mut crawl_args: Vec<String> = args[2..].iter().filter(|arg| *arg != "--yes").cloned().collect();
+
+            // A URL list (one per line, or a sitemap) replaces the site's sitemap
+            let urls = match crawl_args.iter().position(|arg| arg == "--urls") {
+                Some(i) => {
+                    let file = crawl_args.get(i + 1).ok_or("--urls requires a file name")?;
+                    let urls = palimp_core::url_list::parse(&std::fs::read_to_string(file)?)?;
+                    crawl_args.drain(i..=i + 1);
+                    Some(urls)
+                }
+                None => None,
             };
 
-            println!("Starting crawl for site {} with concurrency {}...", site_id, max_concurrent);
-            
-            app.new_crawl(site_id, max_concurrent, |result| {
-                match result {
-                    CrawlResult::CrawlStarted(total) => {
-                        println!("Crawling {} pages...", total);
-                    }
-                    CrawlResult::PageSucceeded(url) => println!("  [OK] {}", url),
-                    CrawlResult::PageFailed(url, err) => eprintln!("  [ERR] {}: {}", url, err),
+            let options = parse_crawl_options(&crawl_args)?;
+            let follow_links = matches!(options.mode, CrawlMode::FollowLinks(_));
+            if follow_links && urls.is_some() {
+                return Err("--urls and --follow-links can't be combined, give the start pages with --seed".into());
+            }
+
+            // Someone at a terminal gets to see what the crawl costs first;
+            // how many pages links lead to isn't known up front
+            if urls.is_none() && !follow_links && !confirmed && std::io::stdin().is_terminal() {
+                print_estimate(&app.estimate_crawl(site_id).await?, &options);
+                if !confirm("Start the crawl?")? {
+                    println!("Crawl cancelled.");
+                    return Ok(());
                 }
-            }).await?;
-            
-            println!("Crawl completed.");
-        }
-        "delete" => {
-            if args.len() != 2 {
-                println!("Usage: crawls delete <id>");
-                return Ok(());
             }
-            let id = args[1].parse::<i64>()?;
-            app.delete_crawl(id).await?;
-            println!("Crawl deleted successfully.");
-        }
-        _ => print_help(),
-    }
-    Ok(())
-}
 
-async fn handle_queries(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
-    if args.is_empty() {
-        print_help();
-        return Ok(());
-    }
+            let stop = stop_on_ctrl_c();
+            let options = options.with_stop_signal(stop.clone());
 
-    match args[0].as_str() {
-        "list" => {
-            let queries = app.list_queries().await?;
-            let crawls = app.list_crawls().await?;
-            let sites = app.list_sites().await?;
+            println!(
+                "Starting crawl for site {} with concurrency {} ({} per host)...",
+                site_id,
+                options.max_concurrent,
+                options.per_host_limit()
+            );
+            
+            match urls {
+                Some(urls) => app.new_crawl_from_urls(site_id, urls, options, print_crawl_result).await?,
+                None => app.new_crawl_with_options(site_id, options, print_crawl_result).await?,
+            }
+            
+            if !stop.is_stopped() {
+                println!("Crawl completed.");
+            }
+        }
+        "new-all" => {
+            // --tag and --budget are ours, the rest are the options of each crawl
+            let mut tag = None;
+            let mut budget = DEFAULT_CONCURRENCY_BUDGET;
+            let mut crawl_args = Vec::new();
+            let mut rest = args[1..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--tag" => tag = Some(rest.next().ok_or("--tag requires a tag")?.clone()),
+                    "--budget" => budget = rest.next().ok_or("--budget requires a number")?.parse::<usize>()?,
+                    "--urls" | "--seed" => {
+                        return Err(format!("{} names pages of one site, use crawls new for it", arg).into());
+                    }
+                    _ => crawl_args.push(arg.clone()),
+                }
+            }
 
-            let site_map: std::collections::HashMap<i64, String> = sites
+            let stop = stop_on_ctrl_c();
+            let options = parse_crawl_options(&crawl_args)?.with_stop_signal(stop.clone());
+            let domains: std::collections::HashMap<i64, String> = app
+                .list_sites()
+                .await?
                 .into_iter()
-                .filter_map(|s| s.id.map(|id| (id, s.domain)))
+                .filter_map(|site| site.id.map(|id| (id, site.domain)))
                 .collect();
 
-            let crawl_map: std::collections::HashMap<i64, (i64, Option<String>)> = crawls
-                .into_iter()
-                .filter_map(|c| c.id.map(|id| (id, (c.site_id, c.started_at))))
-                .collect();
+            println!(
+                "Crawling {} with concurrency {} each and {} in all...",
+                match &tag {
+                    Some(tag) => format!("the sites tagged {}", tag),
+                    None => "every site".to_string(),
+                },
+                options.max_concurrent,
+                budget.max(options.max_concurrent)
+            );
+            let crawls = app
+                .new_crawls(tag.as_deref(), options, budget, move |site_id, result| {
+                    let domain = domains.get(&site_id).map(String::as_str).unwrap_or("?");
+                    print_site_crawl_result(domain, result);
+                })
+                .await?;
+            if crawls.is_empty() {
+                println!("No sites to crawl.");
+                return Ok(());
+            }
 
-            if queries.is_empty() {
-                println!("No queries found.");
-            } else {
-                println!("{:<5} {:<60} {:<30}", "ID", "Crawl", "Selector");
-                println!("{:-<5} {:-<60} {:-<30}", "", "", "");
-                for query in queries {
-                    let crawl_display = match crawl_map.get(&query.crawl_id) {
-                        Some((site_id, started_at)) => {
-                            let domain = site_map.get(site_id).map(|s| s.as_str()).unwrap_or("Unknown Site");
-                            let timestamp = started_at.as_deref().unwrap_or("Unknown Time");
-                            format!("{} (Crawl ID: {}) {}", domain, query.crawl_id, timestamp)
-                        }
-                        None => format!("Unknown Crawl (ID: {})", query.crawl_id),
-                    };
+            println!();
+            println!("{:<5} {:<30} {:<8} {:<8} {:<8} {:<40}", "Site", "Domain", "Crawl", "Pages", "Failed", "Error");
+            println!("{:-<5} {:-<30} {:-<8} {:-<8} {:-<8} {:-<40}", "", "", "", "", "", "");
+            for crawl in &crawls {
+                println!(
+                    "{:<5} {:<30} {:<8} {:<8} {:<8} {:<40}",
+                    crawl.site_id,
+                    crawl.domain,
+                    crawl.crawl_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()),
+                    crawl.pages_succeeded,
+                    crawl.pages_failed,
+                    crawl.error.as_deref().unwrap_or("")
+                );
+            }
+            let failed = crawls.iter().filter(|crawl| crawl.error.is_some()).count();
+            println!(
+                "{} site(s) crawled, {} pages fetched and {} failed; {} site(s) with errors.",
+                crawls.len() - failed,
+                crawls.iter().map(|crawl| crawl.pages_succeeded).sum::<usize>(),
+                crawls.iter().map(|crawl| crawl.pages_failed).sum::<usize>(),
+                failed
+            );
+        }
+        "estimate" => {
+            if args.len() < 2 {
+                println!("Usage: crawls estimate <site_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>]");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let options = parse_crawl_options(&args[2..])?;
+            print_estimate(&app.estimate_crawl(site_id).await?, &options);
+        }
+        "resume" => {
+            if args.len() < 2 {
+                println!("Usage: crawls resume <crawl_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>] [--user-agent <ua>]...");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let stop = stop_on_ctrl_c();
+            let options = parse_crawl_options(&args[2..])?.with_stop_signal(stop.clone());
 
-                    println!(
-                        "{:<5} {:<60} {:<30}",
-                        query.id.unwrap_or(0),
-                        crawl_display,
-                        query.selector
-                    );
+            let pending = app.pending_urls(crawl_id).await?;
+            if pending > 0 {
+                println!("Resuming crawl {} with {} pending URLs...", crawl_id, pending);
+            } else {
+                // Unfinished without a frontier, the sitemap tells what's missing
+                let crawls = app.list_crawls().await?;
+                let status = crawls.iter().find(|crawl| crawl.id == Some(crawl_id)).map(|crawl| crawl.status);
+                if !matches!(status, Some(CrawlStatus::Running | CrawlStatus::Interrupted)) {
+                    println!("Crawl {} has no pending URLs.", crawl_id);
+                    return Ok(());
                 }
+                println!("Resuming crawl {}, fetching the sitemap URLs it has no page for...", crawl_id);
+            }
+            app.resume_crawl(crawl_id, options, print_crawl_result).await?;
+
+            if !stop.is_stopped() {
+                println!("Crawl completed.");
             }
         }
-        "new" => {
-            if args.len() != 3 {
-                println!("Usage: queries new <crawl_id> <selector>");
+        "cancel" => {
+            if args.len() != 2 {
+                println!("Usage: crawls cancel <crawl_id>");
                 return Ok(());
             }
             let crawl_id = args[1].parse::<i64>()?;
-            let selector = &args[2];
-            
-            println!("Running query '{}' on crawl {}...", selector, crawl_id);
-            let results = app.query(crawl_id, selector).await?;
-            println!("Query completed. Found {} matching results across pages.", results.len());
+            let dropped = app.cancel_crawl(crawl_id).await?;
+            println!("Cancelled crawl {}, dropped {} URLs it had left.", crawl_id, dropped);
         }
         "delete" => {
+            if args.len() < 2 {
+                println!("Usage: crawls delete <id> [id...]");
+                return Ok(());
+            }
+            let ids = parse_ids(&args[1..])?;
+            app.delete_crawls(&ids).await?;
+            println!("Deleted {} crawl(s).", ids.len());
+        }
+        "pin" | "unpin" => {
             if args.len() != 2 {
-                println!("Usage: queries delete <id>");
+                println!("Usage: crawls {} <crawl_id>", args[0]);
                 return Ok(());
             }
-            let id = args[1].parse::<i64>()?;
-            app.delete_query(id).await?;
-            println!("Query deleted successfully.");
+            let crawl_id = args[1].parse::<i64>()?;
+            let pinned = args[0] == "pin";
+            app.set_crawl_pinned(crawl_id, pinned).await?;
+            if pinned {
+                println!("Crawl {} pinned, the archive quota will keep it.", crawl_id);
+            } else {
+                println!("Crawl {} unpinned.", crawl_id);
+            }
         }
-        _ => print_help(),
-    }
-    Ok(())
-}
+        "unlock" => {
+            if args.len() != 2 {
+                println!("Usage: crawls unlock <site_id>");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            app.unlock_site_crawls(site_id).await?;
+            println!("Crawl lock of site {} removed.", site_id);
+        }
+        "errors" => {
+            if args.len() != 2 {
+                println!("Usage: crawls errors <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let errors = app.list_crawl_errors(crawl_id).await?;
 
-async fn handle_results(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
-    if args.is_empty() {
-        print_help();
-        return Ok(());
-    }
+            if errors.is_empty() {
+                println!("No errors recorded for crawl {}.", crawl_id);
+            } else {
+                println!("{:<20} {:<50} Message", "Kind", "URL");
+                println!("{:-<20} {:-<50} {:-<30}", "", "", "");
+                for error in errors {
+                    println!("{:<20} {:<50} {}", error.kind.name(), error.url, error.message);
+                }
+            }
+        }
+        "skipped" => {
+            if args.len() != 2 {
+                println!("Usage: crawls skipped <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let skipped = app.list_skipped_urls(crawl_id).await?;
 
-    // We expect the first argument to be the query_id
-    let query_id = match args[0].parse::<i64>() {
-        Ok(id) => id,
-        Err(_) => {
-            println!("Invalid Query ID. Please provide a numeric ID.");
-            print_help();
-            return Ok(());
+            if skipped.is_empty() {
+                println!("Crawl {} left out no URLs.", crawl_id);
+            } else {
+                println!("{:<50} Reason", "URL");
+                println!("{:-<50} {:-<30}", "", "");
+                for skipped_url in skipped {
+                    println!("{:<50} {}", skipped_url.url, skipped_url.reason);
+                }
+            }
         }
-    };
+        "sitemap-changes" => {
+            if args.len() != 2 {
+                println!("Usage: crawls sitemap-changes <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let changes = app.list_sitemap_changes(crawl_id).await?;
 
-    let results = app.list_results_for_query(query_id).await?;
+            match changes.first() {
+                None => println!("No sitemap changes recorded for crawl {}.", crawl_id),
+                Some(first) => {
+                    println!("Sitemap of crawl {} compared with crawl {}:", crawl_id, first.previous_crawl_id);
+                    for change in &changes {
+                        let sign = match change.kind {
+                            SitemapChangeKind::Added => "+",
+                            SitemapChangeKind::Removed => "-",
+                        };
+                        println!("{} {}", sign, change.url);
+                    }
+                }
+            }
+        }
+        "links" => {
+            if args.len() != 3 && args.len() != 5 {
+                println!("Usage: crawls links <crawl_id> <file> [--format dot|graphml|csv]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let filename = &args[2];
+            let format = match args.get(3).map(String::as_str) {
+                Some("--format") => GraphFormat::from_name(&args[4])
+                    .ok_or_else(|| format!("Unknown format '{}'. Available: dot, graphml, csv", args[4]))?,
+                Some(other) => return Err(format!("Unknown option '{}'", other).into()),
+                None => Path::new(filename)
+                    .extension()
+                    .and_then(|extension| GraphFormat::from_name(&extension.to_string_lossy().to_lowercase()))
+                    .ok_or("Cannot tell the format from the file name, pass --format dot|graphml|csv")?,
+            };
 
-    if results.is_empty() {
-        println!("No results found for query ID {}.", query_id);
-    } else {
-        println!("{:<5} {:<60} {:<10}", "ID", "Page URL", "Count");
-        println!("{:-<5} {:-<60} {:-<10}", "", "", "");
-        for (res, url) in results {
+            let graph = app.link_graph(crawl_id).await?;
+            graph.write(format, std::fs::File::create(filename)?)?;
             println!(
-                "{:<5} {:<60} {:<10}",
-                res.id.unwrap_or(0),
-                url,
-                res.count
+                "Wrote the link graph of crawl {} ({} pages, {} links) to '{}'.",
+                crawl_id,
+                graph.nodes.len(),
+                graph.edges.len(),
+                filename
             );
         }
-    }
-
-    Ok(())
+        "link-metrics" => {
+            if args.len() < 2 {
+                println!("Usage: crawls link-metrics <crawl_id> [--sort score|inlinks] [--limit <n>] [--recompute]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let mut by_inlinks = false;
+            let mut limit = None;
+            let mut recompute = false;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--sort" => match rest.next().map(String::as_str) {
+                        Some("score") => by_inlinks = false,
+                        Some("inlinks") => by_inlinks = true,
+                        _ => return Err("--sort requires 'score' or 'inlinks'".into()),
+                    },
+                    "--limit" => limit = Some(rest.next().ok_or("--limit requires a number")?.parse::<usize>()?),
+                    "--recompute" => recompute = true,
+                    other => return Err(format!("Unknown option '{}'", other).into()),
+                }
+            }
+
+            let mut metrics = app.list_link_metrics(crawl_id).await?;
+            // Imported crawls and crawls from older versions have none yet
+            if recompute || metrics.is_empty() {
+                app.compute_link_metrics(crawl_id).await?;
+                metrics = app.list_link_metrics(crawl_id).await?;
+            }
+            if metrics.is_empty() {
+                println!("No pages archived in crawl {}.", crawl_id);
+                return Ok(());
+            }
+            // Least linked first, to spot pages that are hard to reach
+            if by_inlinks {
+                metrics.sort_by_key(|metric| metric.inlinks);
+            }
+
+            println!("{:>8} {:>8} {:>8} URL", "Score", "Inlinks", "Outlinks");
+            println!("{:->8} {:->8} {:->8} {:-<50}", "", "", "", "");
+            for metric in metrics.iter().take(limit.unwrap_or(usize::MAX)) {
+                println!("{:>8.2} {:>8} {:>8} {}", metric.score, metric.inlinks, metric.outlinks, metric.url);
+            }
+        }
+        "meta" => {
+            if args.len() != 2 {
+                println!("Usage: crawls meta <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let meta = app.crawl_meta(crawl_id).await?;
+            if meta.is_empty() {
+                println!("Crawl {} was started without metadata.", crawl_id);
+            }
+            for (key, value) in meta {
+                println!("{}={}", key, value);
+            }
+        }
+        "assets" => {
+            if args.len() != 2 {
+                println!("Usage: crawls assets <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let assets = app.list_assets(crawl_id).await?;
+            if assets.is_empty() {
+                println!("No assets archived with crawl {}, start a crawl with --assets to keep them.", crawl_id);
+                return Ok(());
+            }
+            println!("{:<6} {:<10} {:<60} {:<30}", "Kind", "Size", "URL", "Content-Type");
+            println!("{:-<6} {:-<10} {:-<60} {:-<30}", "", "", "", "");
+            for asset in &assets {
+                println!(
+                    "{:<6} {:<10} {:<60} {:<30}",
+                    asset.kind.name(),
+                    format_bytes(asset.size() as u64),
+                    asset.url,
+                    asset.content_type
+                );
+            }
+            for kind in AssetKind::all() {
+                let of_kind: Vec<_> = assets.iter().filter(|asset| asset.kind == kind).collect();
+                if !of_kind.is_empty() {
+                    let total: usize = of_kind.iter().map(|asset| asset.size()).sum();
+                    println!("{} {} file(s), {} in all", of_kind.len(), kind.name(), format_bytes(total as u64));
+                }
+            }
+        }
+        "asset" => {
+            if args.len() != 3 {
+                println!("Usage: crawls asset <crawl_id> <url>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            print!("{}", app.fetch_asset(crawl_id, &args[2]).await?.content);
+        }
+        "export-db" => {
+            if args.len() != 3 {
+                println!("Usage: crawls export-db <crawl_id> <out.db>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let counts = app.export_crawl_db(crawl_id, Path::new(&args[2])).await?;
+            println!(
+                "Exported crawl {} ({} pages, {} queries, {} results) to '{}'.",
+                crawl_id, counts.pages, counts.queries, counts.results, args[2]
+            );
+        }
+        "import-db" => {
+            if args.len() != 2 && !(args.len() == 4 && args[2] == "--site") {
+                println!("Usage: crawls import-db <file.db> [--site <site_id>]");
+                return Ok(());
+            }
+            let site_id = args.get(3).map(|id| id.parse::<i64>()).transpose()?;
+            let imported = app.import_crawl_db(Path::new(&args[1]), site_id).await?;
+            let site = if imported.created_site { "new site" } else { "site" };
+            println!(
+                "Imported crawl {} into {} {} ({} pages, {} queries, {} results).",
+                imported.crawl_id,
+                site,
+                imported.site_id,
+                imported.counts.pages,
+                imported.counts.queries,
+                imported.counts.results
+            );
+        }
+        "manifest" => {
+            if args.len() != 3 {
+                println!("Usage: crawls manifest <crawl_id> <file.json>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let manifest = app.crawl_manifest(crawl_id).await?;
+            std::fs::write(&args[2], manifest.to_json()?)?;
+            println!(
+                "Wrote the manifest of crawl {} ({} pages, {} errors, {} queries) to '{}'.",
+                crawl_id,
+                manifest.stats.pages,
+                manifest.stats.errors,
+                manifest.queries.len(),
+                args[2]
+            );
+        }
+        "verify" => {
+            if args.len() != 2 {
+                println!("Usage: crawls verify <manifest.json>");
+                return Ok(());
+            }
+            let manifest = CrawlManifest::from_json(&std::fs::read_to_string(&args[1])?)?;
+            let mismatches = app.verify_manifest(&manifest).await?;
+
+            if mismatches.is_empty() {
+                println!("Crawl {} matches its manifest ({} pages).", manifest.crawl_id, manifest.pages.len());
+            } else {
+                for mismatch in &mismatches {
+                    println!("{}", mismatch);
+                }
+                return Err(format!("Crawl {} differs from its manifest in {} page(s)", manifest.crawl_id, mismatches.len()).into());
+            }
+        }
+        "replay" => {
+            if args.len() != 2 {
+                println!("Usage: crawls replay <manifest.json>");
+                return Ok(());
+            }
+            let manifest = CrawlManifest::from_json(&std::fs::read_to_string(&args[1])?)?;
+            let replays = app.replay_manifest(&manifest).await?;
+
+            if replays.is_empty() {
+                println!("The manifest of crawl {} records no queries.", manifest.crawl_id);
+                return Ok(());
+            }
+            for replay in &replays {
+                let status = if replay.passed() { "PASS" } else { "FAIL" };
+                println!("[{}] {}", status, replay.label());
+                for difference in &replay.differences {
+                    println!("    {}: expected {} matches, got {}", difference.url, difference.expected, difference.actual);
+                }
+            }
+            let failed = replays.iter().filter(|replay| !replay.passed()).count();
+            if failed > 0 {
+                return Err(format!("{} of {} queries no longer match their recorded results", failed, replays.len()).into());
+            }
+            println!("All {} queries match their recorded results.", replays.len());
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+/// Parses `[max_concurrent] [--per-host <n>] [--max-bytes <size>]
+/// [--user-agent <ua>]... [--sitemap-depth <n>] [--max-urls <n>]
+/// [--meta <key=value>]... [--follow-links [--seed <url>]... [--depth <n>]
+/// [--max-pages <n>]] [--assets [--asset-limit <kind=size>]...] [--ignore-robots]`.
+fn parse_crawl_options(args: &[String]) -> Result<CrawlOptions, Box<dyn Error>> {
+    let mut max_concurrent = 5;
+    let mut max_per_host = None;
+    let mut max_bytes = None;
+    let mut user_agents = Vec::new();
+    let mut sitemap_limits = SitemapLimits::default();
+    let mut meta = CrawlMeta::new();
+    let mut follow_links = false;
+    let mut following = LinkFollowing::default();
+    let mut link_options = false;
+    let mut assets = false;
+    let mut asset_limits = AssetLimits::default();
+    let mut asset_options = false;
+    let mut respect_robots = true;
+
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--per-host" => {
+                let value = rest.next().ok_or("--per-host requires a number")?;
+                max_per_host = Some(value.parse::<usize>()?);
+            }
+            "--max-bytes" => {
+                let value = rest.next().ok_or("--max-bytes requires a size, e.g. 500M")?;
+                max_bytes = Some(parse_size(value)?);
+            }
+            "--user-agent" => {
+                let value = rest.next().ok_or("--user-agent requires a value")?;
+                user_agents.push(value.clone());
+            }
+            "--sitemap-depth" => {
+                let value = rest.next().ok_or("--sitemap-depth requires a number")?;
+                sitemap_limits.max_depth = value.parse::<usize>()?;
+            }
+            "--max-urls" => {
+                let value = rest.next().ok_or("--max-urls requires a number")?;
+                sitemap_limits.max_urls = Some(value.parse::<usize>()?);
+            }
+            "--meta" => {
+                let value = rest.next().ok_or("--meta requires key=value, e.g. release=1.4")?;
+                let (key, value) = crawl_meta::parse_pair(value)?;
+                meta.insert(key, value);
+            }
+            "--follow-links" => follow_links = true,
+            "--seed" => {
+                let value = rest.next().ok_or("--seed requires a URL")?;
+                following.seeds.push(value.clone());
+                link_options = true;
+            }
+            "--depth" => {
+                let value = rest.next().ok_or("--depth requires a number")?;
+                following.max_depth = value.parse::<usize>()?;
+                link_options = true;
+            }
+            "--max-pages" => {
+                let value = rest.next().ok_or("--max-pages requires a number")?;
+                following.max_pages = value.parse::<usize>()?;
+                if following.max_pages == 0 {
+                    return Err("--max-pages must be at least 1".into());
+                }
+                link_options = true;
+            }
+            "--assets" => assets = true,
+            "--asset-limit" => {
+                let value = rest.next().ok_or("--asset-limit requires kind=size, e.g. css=200K")?;
+                let (kind, size) = value.split_once('=').ok_or("--asset-limit requires kind=size, e.g. css=200K")?;
+                let kind = AssetKind::from_name(kind.trim())
+                    .ok_or_else(|| format!("Unknown asset kind '{}', expected css, js or json", kind))?;
+                asset_limits = asset_limits.with_max_bytes(kind, parse_size(size)?);
+                asset_options = true;
+            }
+            "--ignore-robots" => respect_robots = false,
+            value => max_concurrent = value.parse::<usize>().unwrap_or(5),
+        }
+    }
+
+    let mut options = CrawlOptions::new(max_concurrent);
+    if let Some(per_host) = max_per_host {
+        options = options.with_max_per_host(per_host);
+    }
+    if let Some(max_bytes) = max_bytes {
+        options = options.with_max_bytes(max_bytes);
+    }
+    if !user_agents.is_empty() {
+        options = options.with_user_agents(user_agents);
+    }
+    if follow_links {
+        options = options.with_mode(CrawlMode::FollowLinks(following));
+    } else if link_options {
+        return Err("--seed, --depth and --max-pages only apply with --follow-links".into());
+    }
+    if assets {
+        options = options.with_assets(asset_limits);
+    } else if asset_options {
+        return Err("--asset-limit only applies with --assets".into());
+    }
+    Ok(options
+        .with_sitemap_limits(sitemap_limits)
+        .with_meta(meta)
+        .with_respect_robots(respect_robots))
+}
+
+/// Parses a byte count with an optional K, M or G suffix (powers of 1024).
+fn parse_size(value: &str) -> Result<u64, Box<dyn Error>> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last().map(|unit| unit.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1024),
+        Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    let invalid = || format!("Invalid size '{}', expected e.g. 2048, 500K, 20M or 1G", value);
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    Ok(number.checked_mul(multiplier).ok_or_else(invalid)?)
+}
+
+fn print_estimate(estimate: &CrawlEstimate, options: &CrawlOptions) {
+    println!("URLs:       {} on {} host(s)", estimate.urls, estimate.hosts);
+    if estimate.duplicate_urls > 0 {
+        println!("            {} listed more than once, fetched each time", estimate.duplicate_urls);
+    }
+    if estimate.invalid_urls > 0 {
+        println!("            {} sitemap entries skipped, not http(s) URLs", estimate.invalid_urls);
+    }
+    println!("Requests:   {}", estimate.requests());
+    println!(
+        "Duration:   ~{} at concurrency {} ({} per host)",
+        format_duration(estimate.duration(options)),
+        options.max_concurrent,
+        options.per_host_limit()
+    );
+    let measured = match estimate.measured_crawl_id {
+        Some(crawl_id) => format!("average page of crawl {}", crawl_id),
+        None => "no earlier crawl, assuming the default page size".to_string(),
+    };
+    println!("Bandwidth:  ~{} ({})", format_bytes(estimate.bandwidth()), measured);
+    if let Some(max_bytes) = options.max_bytes
+        && estimate.bandwidth() > max_bytes
+    {
+        println!("            over the {} budget, the crawl will stop early", format_bytes(max_bytes));
+    }
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_crawl_result(result: CrawlResult) {
+    match result {
+        CrawlResult::CrawlStarted(total) => {
+            println!("Crawling {} pages...", total);
+        }
+        CrawlResult::PageSucceeded(url) => println!("  [OK] {}", url),
+        CrawlResult::PageFailed(url, err) => eprintln!("  [ERR] {}: {}", url, err),
+        CrawlResult::AlertRaised(url, message) => println!("  [ALERT] {}: {}", url, message),
+        CrawlResult::Warning(message) => eprintln!("  [WARNING] {}", message),
+        CrawlResult::Interrupted(crawl_id, pending) => {
+            println!("Crawl {} interrupted with {} URLs left.", crawl_id, pending);
+            println!("Resume it with: crawls resume {}, or give it up with: crawls cancel {}", crawl_id, crawl_id);
+        }
+        CrawlResult::Cancelled(crawl_id, dropped) => {
+            println!("Crawl {} cancelled, {} URLs left unfetched.", crawl_id, dropped);
+        }
+        // Each page is printed as it finishes, the running totals would repeat it
+        CrawlResult::Stats(_) => {}
+    }
+}
+
+/// Like [`print_crawl_result`], with each line prefixed by the site's
+/// domain, as the updates of several crawls interleave.
+fn print_site_crawl_result(domain: &str, result: CrawlResult) {
+    match result {
+        CrawlResult::CrawlStarted(total) => println!("[{}] Crawling {} pages...", domain, total),
+        CrawlResult::PageSucceeded(url) => println!("[{}]   [OK] {}", domain, url),
+        CrawlResult::PageFailed(url, err) => eprintln!("[{}]   [ERR] {}: {}", domain, url, err),
+        CrawlResult::AlertRaised(url, message) => println!("[{}]   [ALERT] {}: {}", domain, url, message),
+        CrawlResult::Warning(message) => eprintln!("[{}]   [WARNING] {}", domain, message),
+        CrawlResult::Interrupted(crawl_id, pending) => {
+            println!(
+                "[{}] Crawl {} interrupted with {} URLs left, resume it with: crawls resume {}",
+                domain, crawl_id, pending, crawl_id
+            );
+        }
+        CrawlResult::Cancelled(crawl_id, dropped) => {
+            println!("[{}] Crawl {} cancelled, {} URLs left unfetched.", domain, crawl_id, dropped);
+        }
+        CrawlResult::Stats(_) => {}
+    }
+}
+
+/// Stops the crawl gracefully on the first Ctrl-C and exits on the second.
+fn stop_on_ctrl_c() -> StopSignal {
+    let stop = StopSignal::new();
+    let signal = stop.clone();
+
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("Stopping after the pages in flight, press Ctrl-C again to quit now...");
+        signal.stop();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            process::exit(130);
+        }
+    });
+
+    stop
+}
+
+async fn handle_queries(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "list" => {
+            let queries = app.list_queries().await?;
+            let crawls = app.list_crawls().await?;
+            let sites = app.list_sites().await?;
+
+            let site_map: std::collections::HashMap<i64, String> = sites
+                .into_iter()
+                .filter_map(|s| s.id.map(|id| (id, s.domain)))
+                .collect();
+
+            let crawl_map: std::collections::HashMap<i64, (i64, Option<String>)> = crawls
+                .into_iter()
+                .filter_map(|c| c.id.map(|id| (id, (c.site_id, c.started_at))))
+                .collect();
+
+            if queries.is_empty() {
+                println!("No queries found.");
+            } else {
+                println!("{:<5} {:<60} {:<30} {:<30}", "ID", "Crawl", "Name", "Selector");
+                println!("{:-<5} {:-<60} {:-<30} {:-<30}", "", "", "", "");
+                for query in queries {
+                    let crawl_display = match crawl_map.get(&query.crawl_id) {
+                        Some((site_id, started_at)) => {
+                            let domain = site_map.get(site_id).map(|s| s.as_str()).unwrap_or("Unknown Site");
+                            let timestamp = started_at.as_deref().unwrap_or("Unknown Time");
+                            format!("{} (Crawl ID: {}) {}", domain, query.crawl_id, timestamp)
+                        }
+                        None => format!("Unknown Crawl (ID: {})", query.crawl_id),
+                    };
+
+                    println!(
+                        "{:<5} {:<60} {:<30} {:<30}",
+                        query.id.unwrap_or(0),
+                        crawl_display,
+                        query.name.as_deref().unwrap_or("-"),
+                        query.selector
+                    );
+                    if let Some(description) = &query.description {
+                        println!("      {}", description);
+                    }
+                }
+            }
+        }
+        "new" => {
+            if args.len() < 3 {
+                println!("Usage: queries new <crawl_id> <selector|@template> [--name <name>] [--description <text>]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let selector = &template::resolve_selector(&args[2])?;
+            let (name, description) = parse_query_details(&args[3..])?;
+            
+            println!("Running query '{}' on crawl {}...", name.unwrap_or(selector), crawl_id);
+            let results = app.query_with_details(crawl_id, selector, name, description).await?;
+            println!("Query completed. Found {} matching results across pages.", results.len());
+        }
+        "describe" => {
+            if args.len() < 3 {
+                println!("Usage: queries describe <id> [--name <name>] [--description <text>]");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            let (name, description) = parse_query_details(&args[2..])?;
+            let query = app.describe_query(id, name, description).await?;
+            println!("Query {} saved as '{}'.", id, query.label());
+        }
+        "preview" => {
+            if args.len() != 3 && args.len() != 4 {
+                println!("Usage: queries preview [crawl_id] <url> <selector|@template>");
+                return Ok(());
+            }
+            let selector = &template::resolve_selector(&args[args.len() - 1])?;
+
+            // Without a crawl the URL is fetched live instead of read from the archive
+            let preview = if args.len() == 4 {
+                let crawl_id = args[1].parse::<i64>()?;
+                app.preview_selector(crawl_id, &args[2], selector).await?
+            } else {
+                app.preview(&args[1], selector).await?
+            };
+            print_preview(selector, &preview);
+        }
+        "suggest" => {
+            if args.len() < 4 {
+                println!("Usage: queries suggest <crawl_id> <url> <example text...>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let example = args[3..].join(" ");
+            let suggestions = app.suggest_selectors(crawl_id, &args[2], &example).await?;
+
+            println!("{:<40} {:>11} Text", "Selector", "Other pages");
+            println!("{:-<40} {:->11} {:-<30}", "", "", "");
+            for suggestion in suggestions {
+                println!("{:<40} {:>11} {}", suggestion.selector, suggestion.other_pages, suggestion.text);
+            }
+        }
+        "history" => {
+            if args.len() < 3 {
+                println!("Usage: queries history <site_id> <selector|@template> [--per-url] [--json] [--output <file>]");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let selector = &template::resolve_selector(&args[2])?;
+
+            let mut per_url = false;
+            let mut json = false;
+            let mut output = None;
+            let mut rest = args[3..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--per-url" => per_url = true,
+                    "--json" => json = true,
+                    "--output" => output = Some(rest.next().ok_or("--output requires a file name")?),
+                    other => return Err(format!("Unknown option '{}'", other).into()),
+                }
+            }
+
+            let history = app.query_history(site_id, selector).await?;
+            let mut writer: Box<dyn std::io::Write> = match output {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            if json {
+                writeln!(writer, "{}", history.to_json()?)?;
+            } else {
+                write_history_csv(&history, per_url, writer)?;
+            }
+
+            if let Some(path) = output {
+                println!("Exported the history of '{}' over {} crawls to '{}'.", selector, history.crawls.len(), path);
+            }
+        }
+        "compare" => {
+            if args.len() < 2 {
+                println!("Usage: queries compare <selector|@template> [site_id...]");
+                return Ok(());
+            }
+            let selector = &template::resolve_selector(&args[1])?;
+            let site_ids = args[2..]
+                .iter()
+                .map(|id| id.parse::<i64>())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let comparisons = app.compare_sites(&site_ids, selector).await?;
+            if comparisons.is_empty() {
+                println!("No sites found.");
+                return Ok(());
+            }
+
+            println!("Matches of '{}' in the latest completed crawl of each site:", selector);
+            println!("{:<5} {:<30} {:<8} {:<20} {:<8} {:<10} {:<8} {:<8}", "ID", "Site", "Crawl", "Started At", "Pages", "Matching", "Count", "Per Page");
+            println!("{:-<5} {:-<30} {:-<8} {:-<20} {:-<8} {:-<10} {:-<8} {:-<8}", "", "", "", "", "", "", "", "");
+            for comparison in comparisons {
+                match &comparison.latest {
+                    Some(latest) => println!(
+                        "{:<5} {:<30} {:<8} {:<20} {:<8} {:<10} {:<8} {:<8.2}",
+                        comparison.site_id,
+                        comparison.domain,
+                        latest.crawl_id,
+                        latest.started_at.as_deref().unwrap_or("Unknown"),
+                        latest.pages,
+                        latest.matching_pages,
+                        latest.total,
+                        comparison.matches_per_page()
+                    ),
+                    None => println!("{:<5} {:<30} no completed crawl", comparison.site_id, comparison.domain),
+                }
+            }
+        }
+        "templates" => {
+            println!("{:<20} {:<40} {:<40}", "Template", "Description", "Selector");
+            println!("{:-<20} {:-<40} {:-<40}", "", "", "");
+            for t in template::all() {
+                println!("{:<20} {:<40} {:<40}", format!("@{}", t.name), t.description, t.selector);
+            }
+        }
+        "delete" => {
+            if args.len() < 2 {
+                println!("Usage: queries delete <id> [id...]");
+                return Ok(());
+            }
+            let ids = parse_ids(&args[1..])?;
+            app.delete_queries(&ids).await?;
+            println!("Deleted {} query(s).", ids.len());
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_results(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    if args[0] == "delete" {
+        if args.len() < 2 {
+            println!("Usage: results delete <id> [id...]");
+            return Ok(());
+        }
+        let ids = parse_ids(&args[1..])?;
+        app.delete_results(&ids).await?;
+        println!("Deleted {} result(s).", ids.len());
+        return Ok(());
+    }
+
+    if args[0] == "mark" {
+        let status = args.get(1).and_then(|name| ResultStatus::from_name(name));
+        let (Some(status), true) = (status, args.len() >= 3) else {
+            println!("Usage: results mark <new|reviewed|ignored> <id> [id...]");
+            println!("       results mark <new|reviewed|ignored> [search options...]");
+            return Ok(());
+        };
+        // Options select the results like `results search`, IDs name them
+        let updated = if args[2].starts_with("--") {
+            app.set_matching_result_status(&parse_result_filter(&args[2..])?, status).await?
+        } else {
+            app.set_result_status(&parse_ids(&args[2..])?, status).await?
+        };
+        println!("Marked {} result(s) as {}.", updated, status.name());
+        return Ok(());
+    }
+
+    if args[0] == "matches" {
+        if args.len() != 2 {
+            println!("Usage: results matches <result_id>");
+            return Ok(());
+        }
+        let result_id = args[1].parse::<i64>()?;
+        let matches = app.list_result_matches(result_id).await?;
+        if matches.is_empty() {
+            println!("No match positions stored for result {}. Run its query again to record them.", result_id);
+        } else {
+            println!("{:<5} {:<50} Text", "Index", "Path");
+            println!("{:-<5} {:-<50} {:-<30}", "", "", "");
+            for found in matches {
+                println!("{:<5} {:<50} {}", found.index, found.path, found.text);
+            }
+        }
+        return Ok(());
+    }
+
+    if args[0] == "search" {
+        let filter = match parse_result_filter(&args[1..]) {
+            Ok(filter) => filter,
+            Err(e) => {
+                println!("{}", e);
+                println!("Usage: results search [--crawl <id>] [--query <id>] [--selector <selector|@template>] [--min-count <n>] [--max-count <n>] [--url <text>] [--status <new|reviewed|ignored>] [--modified-since <date>] [--limit <n>] [--offset <n>]");
+                return Ok(());
+            }
+        };
+        let results = app.search_results(&filter).await?;
+        if results.is_empty() {
+            println!("No matching results.");
+        } else {
+            print_results(&results);
+        }
+        return Ok(());
+    }
+
+    // We expect the first argument to be the query_id
+    let query_id = match args[0].parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            println!("Invalid Query ID. Please provide a numeric ID.");
+            print_help();
+            return Ok(());
+        }
+    };
+
+    let results = app.list_results_for_query(query_id).await?;
+
+    if results.is_empty() {
+        println!("No results found for query ID {}.", query_id);
+    } else {
+        print_results(&results);
+    }
+
+    Ok(())
+}
+
+fn print_results(results: &[(ResultEntry, String)]) {
+    println!("{:<5} {:<60} {:<10} {:<10}", "ID", "Page URL", "Count", "Status");
+    println!("{:-<5} {:-<60} {:-<10} {:-<10}", "", "", "", "");
+    for (res, url) in results {
+        println!(
+            "{:<5} {:<60} {:<10} {:<10}",
+            res.id.unwrap_or(0),
+            url,
+            res.count,
+            res.status.name()
+        );
+    }
+}
+
+/// Parses the `--flag value` options of `results search`.
+fn parse_result_filter(args: &[String]) -> Result<ResultFilter, Box<dyn Error>> {
+    let mut filter = ResultFilter::default();
+    let mut rest = args.iter();
+    while let Some(flag) = rest.next() {
+        let value = rest.next().ok_or_else(|| format!("{} requires a value", flag))?;
+        match flag.as_str() {
+            "--crawl" => filter.crawl_id = Some(value.parse()?),
+            "--query" => filter.query_id = Some(value.parse()?),
+            "--selector" => filter.selector = Some(template::resolve_selector(value)?),
+            "--min-count" => filter.min_count = Some(value.parse()?),
+            "--max-count" => filter.max_count = Some(value.parse()?),
+            "--url" => filter.url_pattern = Some(value.clone()),
+            "--modified-since" => filter.modified_since = Some(value.clone()),
+            "--status" => {
+                filter.status = Some(ResultStatus::from_name(value).ok_or_else(|| format!("Unknown status '{}'", value))?)
+            }
+            "--limit" => filter.limit = Some(value.parse()?),
+            "--offset" => filter.offset = value.parse()?,
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
+    }
+    Ok(filter)
+}
+
+/// Parses a list of numeric IDs, e.g. for bulk deletes.
+fn parse_ids(args: &[String]) -> Result<Vec<i64>, Box<dyn Error>> {
+    args.iter()
+        .map(|arg| arg.parse::<i64>().map_err(|_| format!("Invalid ID '{}'", arg).into()))
+        .collect()
+}
+
+type QueryDetails<'a> = (Option<&'a str>, Option<&'a str>);
+
+/// The `--name` and `--description` options of a query.
+fn parse_query_details(args: &[String]) -> Result<QueryDetails<'_>, Box<dyn Error>> {
+    let (mut name, mut description) = (None, None);
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--name" => name = Some(rest.next().ok_or("--name requires a value")?.as_str()),
+            "--description" => description = Some(rest.next().ok_or("--description requires a value")?.as_str()),
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
+    }
+    Ok((name, description))
+}
+
+/// Writes a query history as long-format CSV: one row per crawl, or per
+/// crawl and URL.
+fn write_history_csv(history: &QueryHistory, per_url: bool, writer: impl std::io::Write) -> Result<(), Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_writer(writer);
+
+    if per_url {
+        wtr.write_record(["Crawl ID", "Crawl Started At", "Selector", "Page URL", "Count"])?;
+        for row in &history.urls {
+            wtr.write_record(&[
+                row.crawl_id.to_string(),
+                row.started_at.clone().unwrap_or_default(),
+                history.selector.clone(),
+                row.url.clone(),
+                row.count.to_string(),
+            ])?;
+        }
+    } else {
+        wtr.write_record(["Crawl ID", "Crawl Started At", "Selector", "Pages", "Matching Pages", "Count"])?;
+        for row in &history.crawls {
+            wtr.write_record(&[
+                row.crawl_id.to_string(),
+                row.started_at.clone().unwrap_or_default(),
+                history.selector.clone(),
+                row.pages.to_string(),
+                row.matching_pages.to_string(),
+                row.total.to_string(),
+            ])?;
+        }
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+async fn handle_export(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() < 2 {
+        println!("Usage: export <query_id> <csv_filename> [--columns <list>] [--delimiter <char|tab>]");
+        return Ok(());
+    }
+
+    let query_id = match args[0].parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            println!("Invalid Query ID. Please provide a numeric ID.");
+            return Ok(());
+        }
+    };
+    
+    #[cfg(feature = "google-sheets")]
+    if args[1] == "--sheet" {
+        return export_to_sheet(app, query_id, &args[1..]).await;
+    }
+
+    let filename = &args[1];
+
+    let mut options = ExportOptions::from_env()?;
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--columns" => options.columns = export::parse_columns(rest.next().ok_or("--columns requires a list")?)?,
+            "--delimiter" => options.delimiter = export::parse_delimiter(rest.next().ok_or("--delimiter requires a value")?)?,
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
+    }
+    
+    let results = app.list_results_for_query(query_id).await?;
+
+    if results.is_empty() {
+        println!("No results found for query ID {}. Nothing to export.", query_id);
+        return Ok(());
+    }
+
+    let file = std::fs::File::create(filename)?;
+    let rows = app.export_query_results(query_id, &options, file).await?;
+    println!("Successfully exported {} results to '{}'.", rows, filename);
+
+    Ok(())
+}
+
+#[cfg(feature = "google-sheets")]
+async fn export_to_sheet(app: &Application, query_id: i64, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (target, mode, rest) = parse_sheet_args(args)?;
+    let mut options = ExportOptions::from_env()?;
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--columns" => options.columns = export::parse_columns(rest.next().ok_or("--columns requires a list")?)?,
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
+    }
+
+    let sheets = SheetsClient::from_env().await?;
+    let rows = app.push_query_results_to_sheet(query_id, &options, &sheets, &target, mode).await?;
+    println!("Wrote {} results to spreadsheet {}.", rows, target.spreadsheet_id);
+    Ok(())
+}
+
+/// Parses `--sheet <id|url> [--tab <name>] [--append]`, returning the
+/// arguments left over.
+#[cfg(feature = "google-sheets")]
+fn parse_sheet_args(args: &[String]) -> Result<(SheetTarget, SheetWrite, Vec<String>), Box<dyn Error>> {
+    let mut target = None;
+    let mut tab = None;
+    let mut mode = SheetWrite::Replace;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sheet" => target = Some(SheetTarget::parse(args.next().ok_or("--sheet requires a spreadsheet ID or URL")?)?),
+            "--tab" => tab = Some(args.next().ok_or("--tab requires a name")?),
+            "--append" => mode = SheetWrite::Append,
+            _ => rest.push(arg.clone()),
+        }
+    }
+    let mut target = target.ok_or("--sheet requires a spreadsheet ID or URL")?;
+    if let Some(tab) = tab {
+        target = target.with_sheet(tab);
+    }
+    Ok((target, mode, rest))
+}
+
+async fn handle_import(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "folder" => {
+            if args.len() != 3 {
+                println!("Usage: import folder <site_id> <dir>");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let (crawl_id, pages) = app.import_folder(site_id, Path::new(&args[2])).await?;
+            println!("Imported {} pages as crawl {}.", pages, crawl_id);
+        }
+        "har" => {
+            if args.len() != 3 {
+                println!("Usage: import har <site_id> <har_file>");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let (crawl_id, pages) = app.import_har(site_id, Path::new(&args[2])).await?;
+            println!("Imported {} pages as crawl {}.", pages, crawl_id);
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_audits(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "run" => {
+            if args.len() < 3 {
+                println!("Usage: audits run <crawl_id> <audit> [--check-dead]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let audit = parse_audit(&args[2])?;
+            let check_dead = args[3..].iter().any(|a| a == "--check-dead");
+
+            println!("Running audit '{}' on crawl {}...", audit.name(), crawl_id);
+            let findings = app.run_audit(crawl_id, audit, check_dead).await?;
+            println!("Audit completed. Recorded {} findings.", findings.len());
+        }
+        "list" => {
+            if args.len() < 2 {
+                println!("Usage: audits list <crawl_id> [audit]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let audit = match args.get(2) {
+                Some(name) => Some(parse_audit(name)?),
+                None => None,
+            };
+
+            let findings = app.list_findings(crawl_id, audit).await?;
+
+            if findings.is_empty() {
+                println!("No findings for crawl {}.", crawl_id);
+            } else {
+                println!("{:<20} {:<15} {:<60} {:<40}", "Audit", "Rule", "URL", "Detail");
+                println!("{:-<20} {:-<15} {:-<60} {:-<40}", "", "", "", "");
+                for finding in findings {
+                    println!(
+                        "{:<20} {:<15} {:<60} {:<40}",
+                        finding.audit,
+                        finding.rule,
+                        finding.url,
+                        finding.detail
+                    );
+                }
+            }
+        }
+        #[cfg(feature = "google-sheets")]
+        "export" if args.get(3).is_some_and(|arg| arg == "--sheet") => {
+            let crawl_id = args[1].parse::<i64>()?;
+            let audit = parse_audit(&args[2])?;
+            let (target, mode, rest) = parse_sheet_args(&args[3..])?;
+            if let Some(other) = rest.first() {
+                return Err(format!("Unknown option '{}'", other).into());
+            }
+
+            let sheets = SheetsClient::from_env().await?;
+            let rows = app.push_findings_to_sheet(crawl_id, Some(audit), &sheets, &target, mode).await?;
+            println!("Wrote {} findings to spreadsheet {}.", rows, target.spreadsheet_id);
+        }
+        "export" => {
+            if args.len() != 4 {
+                println!("Usage: audits export <crawl_id> <audit> <csv_filename>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let audit = parse_audit(&args[2])?;
+            let filename = &args[3];
+
+            let findings = app.list_findings(crawl_id, Some(audit)).await?;
+
+            if findings.is_empty() {
+                println!("No findings for audit '{}' on crawl {}. Nothing to export.", audit.name(), crawl_id);
+                return Ok(());
+            }
+
+            let mut wtr = csv::Writer::from_path(filename)?;
+            for record in export::finding_table(&findings) {
+                wtr.write_record(&record)?;
+            }
+
+            wtr.flush()?;
+            println!("Successfully exported findings to '{}'.", filename);
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_reports(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "third-party" => {
+            if args.len() != 2 {
+                println!("Usage: reports third-party <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let domains = app.third_party_domains(crawl_id).await?;
+
+            if domains.is_empty() {
+                println!("No third-party domains referenced by crawl {}.", crawl_id);
+            } else {
+                println!("{:<40} {:<10} {:<40}", "Domain", "Pages", "Resources");
+                println!("{:-<40} {:-<10} {:-<40}", "", "", "");
+                for usage in domains {
+                    println!(
+                        "{:<40} {:<10} {:<40}",
+                        usage.domain,
+                        usage.page_count,
+                        usage.kinds.join(", ")
+                    );
+                }
+            }
+        }
+        "storage" => {
+            if args.len() < 2 {
+                println!("Usage: reports storage <crawl_id> [--top <n>]");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let top = match args.iter().position(|arg| arg == "--top") {
+                Some(i) => args.get(i + 1).ok_or("--top requires a number")?.parse::<usize>()?,
+                None => 10,
+            };
+            print_storage_report(&app.storage_report(crawl_id, top).await?);
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+fn print_storage_report(report: &StorageReport) {
+    println!("Crawl {}: {} pages, {} on average", report.crawl_id, report.pages, format_bytes(report.average_page_bytes()));
+    println!("Raw HTML:    {}", format_bytes(report.raw_bytes));
+    println!(
+        "Stored:      {} (savings {:.1}%, shared bodies stored once, headers included)",
+        format_bytes(report.stored_bytes),
+        report.savings_ratio() * 100.0
+    );
+    println!(
+        "Duplicates:  {} pages, {} ({:.1}% of the raw HTML)",
+        report.duplicate_pages,
+        format_bytes(report.duplicate_bytes),
+        report.duplicate_ratio() * 100.0
+    );
+
+    if !report.largest_pages.is_empty() {
+        println!();
+        println!("{:<12} {:<80}", "Size", "Largest pages");
+        println!("{:-<12} {:-<80}", "", "");
+        for page in &report.largest_pages {
+            println!("{:<12} {:<80}", format_bytes(page.raw_bytes), page.url);
+        }
+    }
+}
+
+async fn handle_pages(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "diff" => {
+            if args.len() != 4 {
+                println!("Usage: pages diff <url> <before_crawl_id> <after_crawl_id>");
+                return Ok(());
+            }
+            let url = &args[1];
+            let before_crawl_id = args[2].parse::<i64>()?;
+            let after_crawl_id = args[3].parse::<i64>()?;
+
+            let regions = app.diff_page(url, before_crawl_id, after_crawl_id).await?;
+
+            if regions.is_empty() {
+                println!("No changes to {} between crawls {} and {}.", url, before_crawl_id, after_crawl_id);
+            } else {
+                for region in regions {
+                    println!("@@ {} @@", region.path);
+                    for line in region.removed {
+                        println!("- {}", line);
+                    }
+                    for line in region.added {
+                        println!("+ {}", line);
+                    }
+                }
+            }
+        }
+        "match-diff" => {
+            if args.len() != 5 {
+                println!("Usage: pages match-diff <url> <selector|@template> <before_crawl_id> <after_crawl_id>");
+                return Ok(());
+            }
+            let url = &args[1];
+            let selector = template::resolve_selector(&args[2])?;
+            let before_crawl_id = args[3].parse::<i64>()?;
+            let after_crawl_id = args[4].parse::<i64>()?;
+
+            let changes = app.match_diff(url, &selector, before_crawl_id, after_crawl_id).await?;
+
+            if changes.is_empty() {
+                println!("Matches of '{}' on {} are unchanged between crawls {} and {}.", selector, url, before_crawl_id, after_crawl_id);
+            }
+            for change in changes {
+                let path = |found: &Option<ResultMatch>| found.as_ref().map(|found| found.path.clone()).unwrap_or_default();
+                let text = |found: &Option<ResultMatch>| found.as_ref().map(|found| found.text.clone()).unwrap_or_default();
+                match change.kind {
+                    MatchChangeKind::Moved => {
+                        println!("moved    {} -> {}  \"{}\"", path(&change.before), path(&change.after), text(&change.after))
+                    }
+                    MatchChangeKind::Changed => {
+                        println!("changed  {}  \"{}\" -> \"{}\"", path(&change.before), text(&change.before), text(&change.after))
+                    }
+                    MatchChangeKind::Removed => println!("removed  {}  \"{}\"", path(&change.before), text(&change.before)),
+                    MatchChangeKind::Added => println!("added    {}  \"{}\"", path(&change.after), text(&change.after)),
+                }
+            }
+        }
+        "text-diff" => {
+            if args.len() != 4 {
+                println!("Usage: pages text-diff <url> <before_crawl_id> <after_crawl_id>");
+                return Ok(());
+            }
+            let url = &args[1];
+            let before_crawl_id = args[2].parse::<i64>()?;
+            let after_crawl_id = args[3].parse::<i64>()?;
+
+            let output = app.text_diff(url, before_crawl_id, after_crawl_id).await?;
+
+            if output.is_empty() {
+                println!("Visible text of {} is unchanged between crawls {} and {}.", url, before_crawl_id, after_crawl_id);
+            } else {
+                print!("{}", output);
+            }
+        }
+        "history" => {
+            let json = args.iter().any(|arg| arg == "--json");
+            let args: Vec<&String> = args.iter().filter(|arg| *arg != "--json").collect();
+            if args.len() != 3 {
+                println!("Usage: pages history <site_id> <url> [--json]");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let url = args[2];
+
+            let history = app.page_history(site_id, url).await?;
+
+            if json {
+                println!("{}", history.to_json()?);
+            } else if history.versions.is_empty() {
+                println!("{} was never crawled for site {}.", url, site_id);
+            } else {
+                println!("{:<8} {:<20} {:<10} {:<12} {:<10} {:<8}", "Crawl", "Started", "Status", "Hash", "Size", "Changed");
+                println!("{:-<8} {:-<20} {:-<10} {:-<12} {:-<10} {:-<8}", "", "", "", "", "", "");
+                for version in &history.versions {
+                    println!(
+                        "{:<8} {:<20} {:<10} {:<12} {:<10} {:<8}",
+                        version.crawl_id,
+                        version.started_at.as_deref().unwrap_or("-"),
+                        version.status.name(),
+                        version.hash.as_deref().map(|hash| &hash[..12]).unwrap_or("-"),
+                        if version.hash.is_some() { format_bytes(version.bytes) } else { "-".to_string() },
+                        if version.changed { "yes" } else { "" }
+                    );
+                }
+                println!();
+                println!("{} versions, {} with a new body.", history.versions.len(), history.changes());
+                println!("Diff two versions with: pages text-diff {} <before_crawl_id> <after_crawl_id>", url);
+            }
+        }
+        "search" => {
+            if args.len() != 3 {
+                println!("Usage: pages search <crawl_id> <phrase>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let phrase = &args[2];
+
+            let hits = app.search_pages(crawl_id, phrase).await?;
+
+            if hits.is_empty() {
+                println!("No pages in crawl {} contain '{}'.", crawl_id, phrase);
+            } else {
+                for hit in hits {
+                    println!("{}", hit.url);
+                    println!("    {}", hit.snippet.replace('\n', " "));
+                }
+            }
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_monitors(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "list" => {
+            let monitors = app.list_monitors().await?;
+            if monitors.is_empty() {
+                println!("No monitors found.");
+            } else {
+                println!("{:<5} {:<10} {:<70}", "ID", "Site ID", "Alert when");
+                println!("{:-<5} {:-<10} {:-<70}", "", "", "");
+                for monitor in monitors {
+                    println!(
+                        "{:<5} {:<10} {:<70}",
+                        monitor.id.unwrap_or(0),
+                        monitor.site_id,
+                        monitor.describe()
+                    );
+                }
+            }
+        }
+        "new" => {
+            if args.len() < 4 || args.len() > 5 {
+                println!("Usage: monitors new <site_id> selector-count <selector> [url_pattern]");
+                println!("       monitors new <site_id> text-change <percent> [url_pattern]");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let kind = match args[2].as_str() {
+                "selector-count" => MonitorKind::SelectorCount(args[3].clone()),
+                "text-change" => MonitorKind::TextChange(args[3].parse::<f64>()?),
+                other => return Err(format!("Unknown monitor kind '{}'", other).into()),
+            };
+            let url_pattern = args.get(4).map(|p| p.as_str());
+
+            let monitor = app.new_monitor(site_id, kind, url_pattern).await?;
+            println!("Monitor {} created: alert when {}.", monitor.id.unwrap_or(0), monitor.describe());
+        }
+        "delete" => {
+            if args.len() != 2 {
+                println!("Usage: monitors delete <id>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            app.delete_monitor(id).await?;
+            println!("Monitor deleted successfully.");
+        }
+        _ => print_help(),
+    }
+    Ok(())
 }
 
-async fn handle_export(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
-    if args.len() != 2 {
-        println!("Usage: export <query_id> <csv_filename>");
+async fn handle_rules(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
         return Ok(());
     }
 
-    let query_id = match args[0].parse::<i64>() {
-        Ok(id) => id,
-        Err(_) => {
-            println!("Invalid Query ID. Please provide a numeric ID.");
-            return Ok(());
+    match args[0].as_str() {
+        "list" => {
+            let site_rules = app.list_site_rules().await?;
+            if site_rules.is_empty() {
+                println!("No site rules found.");
+            } else {
+                println!("{:<5} {:<10} {:<10} {:<60}", "ID", "Site ID", "Severity", "Rule");
+                println!("{:-<5} {:-<10} {:-<10} {:-<60}", "", "", "", "");
+                for site_rule in site_rules {
+                    let scope = match &site_rule.rule.url_pattern {
+                        Some(pattern) => format!(" on URLs containing '{}'", pattern),
+                        None => String::new(),
+                    };
+                    println!(
+                        "{:<5} {:<10} {:<10} {}{}",
+                        site_rule.id.unwrap_or(0),
+                        site_rule.site_id,
+                        site_rule.rule.severity.name(),
+                        site_rule.rule.label(),
+                        scope
+                    );
+                }
+            }
+        }
+        "add" => {
+            if args.len() != 3 {
+                println!("Usage: rules add <site_id> <rules.yaml>");
+                return Ok(());
+            }
+            let site_id = args[1].parse::<i64>()?;
+            let rules = RuleSet::from_yaml(&std::fs::read_to_string(&args[2])?)?;
+            let added = app.add_site_rules(site_id, &rules).await?;
+            println!("Added {} rule(s) to site {}, checked after each of its crawls.", added.len(), site_id);
+        }
+        "delete" => {
+            if args.len() != 2 {
+                println!("Usage: rules delete <id>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            app.delete_site_rule(id).await?;
+            println!("Site rule {} deleted with its outcomes and alerts.", id);
+        }
+        "outcomes" => {
+            if args.len() != 2 {
+                println!("Usage: rules outcomes <crawl_id>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let outcomes = app.list_rule_outcomes(crawl_id).await?;
+            if outcomes.is_empty() {
+                println!("No site rules were checked on crawl {}.", crawl_id);
+                return Ok(());
+            }
+            let labels: std::collections::HashMap<i64, String> = app
+                .list_site_rules()
+                .await?
+                .into_iter()
+                .filter_map(|site_rule| site_rule.id.map(|id| (id, site_rule.rule.label())))
+                .collect();
+            for outcome in outcomes {
+                let status = if outcome.passed() { "PASS" } else { "FAIL" };
+                println!(
+                    "[{}] {} ({} of {} pages fail)",
+                    status,
+                    labels.get(&outcome.rule_id).map(String::as_str).unwrap_or("?"),
+                    outcome.violations,
+                    outcome.checked
+                );
+            }
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_archive(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        None | Some("status") => {
+            let usage = app.archive_usage().await?;
+            match usage.quota {
+                Some(quota) => println!("Archive: {} of {} quota", format_bytes(usage.bytes), format_bytes(quota)),
+                None => println!("Archive: {}, no quota", format_bytes(usage.bytes)),
+            }
+        }
+        Some("quota") => {
+            let Some(value) = args.get(1) else {
+                println!("Usage: archive quota <size|none>");
+                return Ok(());
+            };
+            if value == "none" {
+                app.set_archive_quota(None).await?;
+                println!("Archive quota removed.");
+            } else {
+                let quota = parse_size(value)?;
+                app.set_archive_quota(Some(quota)).await?;
+                println!("Archive quota set to {}.", format_bytes(quota));
+                print_pruned(&app.prune_archive().await?);
+            }
+        }
+        Some("prune") => {
+            let pruned = app.prune_archive().await?;
+            if pruned.is_empty() {
+                println!("Nothing to prune.");
+            }
+            print_pruned(&pruned);
+        }
+        Some(_) => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_trash(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            let entries = app.list_trash().await?;
+            if entries.is_empty() {
+                println!("The trash is empty.");
+            } else {
+                println!("{:<8} {:<8} {:<20} {:<60}", "Kind", "ID", "Deleted At", "What");
+                println!("{:-<8} {:-<8} {:-<20} {:-<60}", "", "", "", "");
+                for entry in entries {
+                    println!("{:<8} {:<8} {:<20} {:<60}", entry.kind.name(), entry.id, entry.deleted_at, entry.label);
+                }
+            }
+        }
+        Some("restore") => {
+            if args.len() != 3 {
+                println!("Usage: trash restore <site|crawl|query|result> <id>");
+                return Ok(());
+            }
+            let kind = TrashKind::from_name(&args[1]).ok_or_else(|| format!("Unknown kind '{}'", args[1]))?;
+            let id = args[2].parse::<i64>()?;
+            app.restore(kind, id).await?;
+            println!("Restored {} {}.", kind.name(), id);
+        }
+        Some("purge") => {
+            let older_than = match args.get(1).map(String::as_str) {
+                None => None,
+                Some("--older-than") => {
+                    let days = args.get(2).ok_or("--older-than requires a number of days")?.parse::<u64>()?;
+                    Some(Duration::from_secs(days * 24 * 60 * 60))
+                }
+                Some(other) => return Err(format!("Unknown option '{}'", other).into()),
+            };
+            let purged = app.purge_trash(older_than).await?;
+            println!(
+                "Purged {} site(s), {} crawl(s), {} query(ies) and {} result(s).",
+                purged.sites, purged.crawls, purged.queries, purged.results
+            );
+        }
+        Some(_) => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_audit_log(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut action = None;
+    let mut limit = 50;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--action" => {
+                let name = rest.next().ok_or("--action requires a value")?;
+                action = Some(AuditAction::from_name(name).ok_or_else(|| format!("Unknown action '{}'", name))?);
+            }
+            "--limit" => limit = rest.next().ok_or("--limit requires a number")?.parse::<usize>()?,
+            other => return Err(format!("Unknown option '{}'", other).into()),
         }
+    }
+
+    let events = app.list_audit_log(action, limit).await?;
+    if events.is_empty() {
+        println!("Nothing recorded yet.");
+        return Ok(());
+    }
+
+    println!("{:<20} {:<15} {:<13} {:<8} {:<60}", "When", "Who", "Action", "ID", "What");
+    println!("{:-<20} {:-<15} {:-<13} {:-<8} {:-<60}", "", "", "", "", "");
+    for event in events {
+        println!(
+            "{:<20} {:<15} {:<13} {:<8} {:<60}",
+            event.created_at.unwrap_or_default(),
+            event.actor.as_deref().unwrap_or("-"),
+            event.action.name(),
+            event.target_id.map(|id| id.to_string()).unwrap_or_default(),
+            event.detail
+        );
+    }
+    Ok(())
+}
+
+async fn handle_assert(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "Usage: assert <crawl_id> --rules <rules.yaml> [--format text|github|junit] [--output <file>]";
+    let Some(crawl_id) = args.first() else {
+        println!("{}", usage);
+        return Ok(());
     };
-    
-    let filename = &args[1];
-    
-    let results = app.list_results_for_query(query_id).await?;
+    let crawl_id = crawl_id.parse::<i64>()?;
 
-    if results.is_empty() {
-        println!("No results found for query ID {}. Nothing to export.", query_id);
+    let mut rules_file = None;
+    let mut format = "text";
+    let mut output = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--rules" => rules_file = Some(rest.next().ok_or("--rules requires a file name")?),
+            "--format" => format = rest.next().ok_or("--format requires text, github or junit")?,
+            "--output" => output = Some(rest.next().ok_or("--output requires a file name")?),
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
+    }
+    let Some(rules_file) = rules_file else {
+        println!("{}", usage);
+        return Ok(());
+    };
+    if !["text", "github", "junit"].contains(&format) {
+        return Err(format!("Unknown format '{}', expected text, github or junit", format).into());
+    }
+    if format == "text" && output.is_some() {
+        return Err("--output requires --format github or junit".into());
+    }
+
+    let rules = RuleSet::from_yaml(&std::fs::read_to_string(rules_file)?)?;
+    let report = app.run_assertions(crawl_id, &rules).await?;
+
+    let rendered = match format {
+        "github" => report.github_annotations(),
+        "junit" => report.junit_xml(),
+        _ => {
+            for outcome in &report.outcomes {
+                let status = match (outcome.passed(), outcome.rule.severity) {
+                    (true, _) => "PASS",
+                    (false, Severity::Warning) => "WARN",
+                    (false, Severity::Error) => "FAIL",
+                };
+                println!("[{}] {} ({} pages checked)", status, outcome.rule.label(), outcome.checked.len());
+                for violation in &outcome.violations {
+                    println!("    {}: {} matches", violation.url, violation.count);
+                }
+            }
+            String::new()
+        }
+    };
+    match output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => print!("{}", rendered),
+    }
+
+    if !report.passed() {
+        let failed = report
+            .outcomes
+            .iter()
+            .filter(|outcome| !outcome.passed() && outcome.rule.severity == Severity::Error)
+            .count();
+        return Err(format!("{} of {} rules failed on crawl {}", failed, report.outcomes.len(), crawl_id).into());
+    }
+    // Keep the JUnit report on stdout valid XML
+    if format != "junit" || output.is_some() {
+        println!("All {} rules hold on the {} pages of crawl {}.", report.outcomes.len(), report.pages, crawl_id);
+    }
+    Ok(())
+}
+
+fn print_pruned(crawl_ids: &[i64]) {
+    if !crawl_ids.is_empty() {
+        let ids: Vec<String> = crawl_ids.iter().map(i64::to_string).collect();
+        println!("Pruned {} crawl(s) to fit the quota: {}", crawl_ids.len(), ids.join(", "));
+    }
+}
+
+async fn handle_sql(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let Some(statement) = args.first() else {
+        println!("Usage: sql \"<select ...>\" [--output <csv_filename>] [--delimiter <char|tab>]");
         return Ok(());
+    };
+
+    let mut output = None;
+    let mut delimiter = b',';
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--output" => output = Some(rest.next().ok_or("--output requires a filename")?),
+            "--delimiter" => delimiter = export::parse_delimiter(rest.next().ok_or("--delimiter requires a value")?)?,
+            other => return Err(format!("Unknown option '{}'", other).into()),
+        }
     }
 
-    let mut wtr = csv::Writer::from_path(filename)?;
-    
-    // Write header
-    wtr.write_record(&["ID", "Page URL", "Count"])?;
+    let rows = app.raw_query(statement).await?;
 
-    for (res, url) in results {
-        wtr.write_record(&[
-            res.id.unwrap_or(0).to_string(),
-            url,
-            res.count.to_string(),
-        ])?;
+    if let Some(filename) = output {
+        let written = rows.write_csv(delimiter, std::fs::File::create(filename)?)?;
+        println!("Successfully exported {} rows to '{}'.", written, filename);
+        return Ok(());
     }
-    
-    wtr.flush()?;
-    println!("Successfully exported results to '{}'.", filename);
 
+    // Columns as wide as their longest value, up to a limit
+    const MAX_WIDTH: usize = 60;
+    let cells: Vec<Vec<String>> = rows.rows.iter()
+        .map(|row| row.iter().map(|value| sql::display(value).replace('\n', " ")).collect())
+        .collect();
+    let widths: Vec<usize> = rows.columns.iter().enumerate()
+        .map(|(i, column)| {
+            cells.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or(0)
+                .min(MAX_WIDTH)
+        })
+        .collect();
+    let line = |values: Vec<String>| {
+        values.iter().zip(&widths)
+            .map(|(value, width)| {
+                let value: String = value.chars().take(*width).collect();
+                format!("{:<width$}", value, width = *width)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", line(rows.columns.clone()));
+    println!("{}", line(widths.iter().map(|width| "-".repeat(*width)).collect()));
+    for row in cells {
+        println!("{}", line(row));
+    }
+    println!();
+    println!("{} row(s).", rows.rows.len());
+    Ok(())
+}
+
+async fn handle_alerts(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let crawl_id = match args.first() {
+        Some(id) => Some(id.parse::<i64>()?),
+        None => None,
+    };
+
+    let alerts = app.list_alerts(crawl_id).await?;
+
+    if alerts.is_empty() {
+        println!("No alerts found.");
+    } else {
+        println!("{:<5} {:<8} {:<20} {:<50} {:<50}", "ID", "Crawl", "Raised At", "URL", "Message");
+        println!("{:-<5} {:-<8} {:-<20} {:-<50} {:-<50}", "", "", "", "", "");
+        for alert in alerts {
+            println!(
+                "{:<5} {:<8} {:<20} {:<50} {:<50}",
+                alert.id.unwrap_or(0),
+                alert.crawl_id,
+                alert.created_at.as_deref().unwrap_or("Unknown"),
+                alert.url,
+                alert.message
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_jobs(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "list" => {
+            let jobs = app.list_jobs(50).await?;
+            if jobs.is_empty() {
+                println!("No jobs found.");
+            } else {
+                println!("{:<5} {:<10} {:<12} {:<9} {:<12} {:<20} {:<50}", "ID", "Kind", "Status", "Priority", "Progress", "Created At", "Job");
+                println!("{:-<5} {:-<10} {:-<12} {:-<9} {:-<12} {:-<20} {:-<50}", "", "", "", "", "", "", "");
+                for job in jobs {
+                    println!(
+                        "{:<5} {:<10} {:<12} {:<9} {:<12} {:<20} {:<50}",
+                        job.id.unwrap_or(0),
+                        job.kind.name(),
+                        job.status.name(),
+                        job.priority,
+                        format!("{}/{}", job.progress_done, job.progress_total),
+                        job.created_at.as_deref().unwrap_or("Unknown"),
+                        job.kind.describe()
+                    );
+                }
+            }
+        }
+        "show" => {
+            if args.len() != 2 {
+                println!("Usage: jobs show <id>");
+                return Ok(());
+            }
+            let job = app.fetch_job(args[1].parse::<i64>()?).await?;
+            println!("Job {}: {}", job.id.unwrap_or(0), job.kind.describe());
+            println!("  Status:   {}", job.status.name());
+            println!("  Priority: {}", job.priority);
+            println!("  Progress: {}/{}", job.progress_done, job.progress_total);
+            println!("  Created:  {}", job.created_at.as_deref().unwrap_or("-"));
+            println!("  Started:  {}", job.started_at.as_deref().unwrap_or("-"));
+            println!("  Finished: {}", job.finished_at.as_deref().unwrap_or("-"));
+            if let Some(message) = &job.message {
+                println!("  Message:  {}", message);
+            }
+        }
+        "submit" => {
+            let (args, priority) = match args.iter().position(|arg| arg == "--priority") {
+                Some(index) => {
+                    let value = args.get(index + 1).ok_or("--priority requires a number")?;
+                    let mut rest = args.to_vec();
+                    rest.drain(index..index + 2);
+                    (rest, value.parse::<i64>()?)
+                }
+                None => (args.to_vec(), 0),
+            };
+
+            let kind = match &args[1..] {
+                [kind, site_id, rest @ ..] if kind == "crawl" && rest.len() <= 1 => JobKind::Crawl {
+                    site_id: site_id.parse::<i64>()?,
+                    max_concurrent: match rest.first() {
+                        Some(value) => value.parse::<usize>()?,
+                        None => 5,
+                    },
+                },
+                [kind, crawl_id, selector] if kind == "query" => JobKind::Query {
+                    crawl_id: crawl_id.parse::<i64>()?,
+                    selector: selector.clone(),
+                },
+                _ => {
+                    println!("Usage: jobs submit crawl <site_id> [max_concurrent] [--priority n]");
+                    println!("       jobs submit query <crawl_id> <selector> [--priority n]");
+                    return Ok(());
+                }
+            };
+
+            let job = app.submit_job(kind, priority).await?;
+            println!("Job {} queued. Run 'daemon' to process it.", job.id.unwrap_or(0));
+        }
+        "priority" => {
+            if args.len() != 3 {
+                println!("Usage: jobs priority <id> <priority>");
+                return Ok(());
+            }
+            let job_id = args[1].parse::<i64>()?;
+            app.set_job_priority(job_id, args[2].parse::<i64>()?).await?;
+            println!("Job {} priority set to {}.", job_id, args[2]);
+        }
+        "cancel" => {
+            if args.len() != 2 {
+                println!("Usage: jobs cancel <id>");
+                return Ok(());
+            }
+            let job_id = args[1].parse::<i64>()?;
+            match app.cancel_job(job_id).await? {
+                JobStatus::Running => println!("Job {} will stop at its next progress update.", job_id),
+                _ => println!("Job {} cancelled.", job_id),
+            }
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+async fn handle_daemon(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut poll_seconds = 5;
+    let mut budget = DEFAULT_CONCURRENCY_BUDGET;
+
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--budget" => {
+                let value = rest.next().ok_or("--budget requires a number")?;
+                budget = value.parse::<usize>()?;
+            }
+            value => poll_seconds = value.parse::<u64>()?,
+        }
+    }
+
+    println!("Waiting for jobs (polling every {}s, Ctrl-C to stop)...", poll_seconds);
+    let stop = stop_on_ctrl_c();
+    Worker::new(app)
+        .with_budget(budget)
+        .run(Duration::from_secs(poll_seconds), &stop, |job| {
+            println!(
+                "Job {} {}: {}{}",
+                job.id.unwrap_or(0),
+                job.status.name(),
+                job.kind.describe(),
+                job.message.as_deref().map(|message| format!(" ({})", message)).unwrap_or_default()
+            )
+        })
+        .await?;
+
+    println!("Daemon stopped.");
+    Ok(())
+}
+
+async fn handle_suites(app: &Application, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        print_help();
+        return Ok(());
+    }
+
+    match args[0].as_str() {
+        "list" => {
+            let suites = app.list_suites().await?;
+            println!("{:<5} {:<20} {:<80}", "ID", "Name", "Checks");
+            println!("{:-<5} {:-<20} {:-<80}", "", "", "");
+            for suite in suites {
+                let checks: Vec<String> = suite
+                    .checks
+                    .iter()
+                    .map(|check| format!("{}:{}", check.kind(), check.value()))
+                    .collect();
+                let id = match suite.id {
+                    Some(id) => id.to_string(),
+                    None => "-".to_string(),
+                };
+                println!("{:<5} {:<20} {:<80}", id, suite.name, checks.join(" "));
+            }
+        }
+        "new" => {
+            if args.len() < 3 {
+                println!("Usage: suites new <name> <check>...");
+                println!("       where <check> is audit:<audit>, require:<selector> or forbid:<selector>");
+                return Ok(());
+            }
+            let mut checks = Vec::new();
+            for arg in &args[2..] {
+                let check = arg
+                    .split_once(':')
+                    .and_then(|(kind, value)| SuiteCheck::from_parts(kind, value))
+                    .ok_or_else(|| format!("Invalid check '{}'", arg))?;
+                checks.push(check);
+            }
+
+            let suite = app.new_suite(&args[1], checks).await?;
+            println!("Suite '{}' created with ID {}.", suite.name, suite.id.unwrap_or(0));
+        }
+        "delete" => {
+            if args.len() != 2 {
+                println!("Usage: suites delete <id>");
+                return Ok(());
+            }
+            let id = args[1].parse::<i64>()?;
+            app.delete_suite(id).await?;
+            println!("Suite deleted successfully.");
+        }
+        "run" => {
+            if args.len() != 3 {
+                println!("Usage: suites run <crawl_id> <suite>");
+                return Ok(());
+            }
+            let crawl_id = args[1].parse::<i64>()?;
+            let summaries = app.run_suite(crawl_id, &args[2]).await?;
+
+            let passed = summaries.iter().filter(|s| s.passed()).count();
+            for summary in &summaries {
+                let status = if summary.passed() { "PASS" } else { "FAIL" };
+                println!("[{}] {}", status, summary.url);
+                for failure in &summary.failures {
+                    println!("    {}", failure);
+                }
+            }
+            println!("{} of {} pages passed suite '{}'.", passed, summaries.len(), args[2]);
+        }
+        _ => print_help(),
+    }
+    Ok(())
+}
+
+fn parse_audit(name: &str) -> Result<Audit, Box<dyn Error>> {
+    Audit::from_name(name).ok_or_else(|| {
+        let known: Vec<&str> = Audit::all().iter().map(|a| a.name()).collect();
+        format!("Unknown audit '{}'. Available audits: {}", name, known.join(", ")).into()
+    })
+}
+
+async fn handle_check(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() != 2 {
+        println!("Usage: check <url> <selector|@template>");
+        return Ok(());
+    }
+    let selector = &template::resolve_selector(&args[1])?;
+
+    let preview = SelectorPreview::fetch(&HTTPClient::from_env()?, &args[0], selector).await?;
+    print_preview(selector, &preview);
     Ok(())
 }
 
+fn print_preview(selector: &str, preview: &SelectorPreview) {
+    println!("'{}' matches {} element(s) on {}", selector, preview.count(), preview.url);
+    for (i, m) in preview.matches.iter().enumerate() {
+        println!("\n[{}] {}", i + 1, m.text);
+        println!("    {}", m.html);
+    }
+}
+
 fn print_help() {
-    println!("Usage: palimp-cli <command> [subcommand] [args]");
+    println!("Usage: palimp-cli [--read-only] <command> [subcommand] [args]");
+    println!("       --read-only allows listing, queries, exports and viewing pages, but no crawls or deletions");
     println!("\nCommands:");
     println!("  sites list");
-    println!("  sites new <domain> <sitemap_url>");
+    println!("  sites new <domain> <sitemap_url> [--skip-validation]");
+    println!("        checks the domain and that the sitemap can be fetched and parsed, unless skipped;");
+    println!("        an existing site with the same domain and sitemap is returned instead");
+    println!("  sites edit <id> [--domain <domain>] [--sitemap <sitemap_url>]");
+    println!("  sites export <file.yaml>");
+    println!("  sites import <file.yaml>");
+    println!("        sites with their settings and monitors, and user-defined suites; importing adds");
+    println!("        and updates, matching sites by domain and sitemap, and never deletes");
     println!("  sites delete <id>");
+    println!("  sites dashboard <id>");
+    println!("  sites insecure-tls <id> <on|off>");
+    println!("  sites fetch-override <id> <ip|base_url|none>");
+    println!("  sites soft-404 <id> [--min-bytes <n>] [--phrases <phrase,phrase,...>]");
+    println!("  sites soft-404 <id> reset");
+    println!("  sites politeness <id> [--rps <n>] [--delay <ms>]");
+    println!("        paces every crawl of the site, however concurrent: at most <n> requests per second");
+    println!("        and at least <ms> between two requests, whichever is stricter");
+    println!("  sites politeness <id> off");
+    println!("  sites tag <id> <tag>");
+    println!("  sites untag <id> <tag>");
+    println!("  sites credentials <id> list");
+    println!("  sites credentials <id> set <header> <value>");
+    println!("  sites credentials <id> delete <header>");
     println!();
     println!("  crawls list");
-    println!("  crawls new <site_id> [max_concurrent]");
-    println!("  crawls delete <id>");
+    println!("  crawls new <site_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>] [--user-agent <ua>]... [--sitemap-depth <n>] [--max-urls <n>] [--meta <key=value>]... [--urls <file> | --follow-links [--seed <url>]... [--depth <n>] [--max-pages <n>]] [--assets [--asset-limit <kind=size>]...] [--ignore-robots] [--yes]");
+    println!("        shows an estimate and asks before crawling when run from a terminal, unless --yes");
+    println!("        --max-bytes stops the crawl, resumable, once that many page bytes (K, M, G) are fetched");
+    println!("        sitemap indexes are followed {} level(s) deep unless --sitemap-depth, --max-urls caps the URLs taken", DEFAULT_SITEMAP_DEPTH);
+    println!("        --user-agent can be repeated to send each agent in turn, the one used is kept with each page");
+    println!("        --follow-links crawls from the site's root, or each --seed, following links within the site");
+    println!("        up to --depth hops (default {}) and --max-pages pages (default {}) instead of reading the sitemap", DEFAULT_LINK_DEPTH, DEFAULT_LINK_PAGES);
+    println!("        --meta stores e.g. release=1.4 or sha=4f2a9c1 with the crawl, shown in manifests and exports");
+    println!("        --assets also archives the site's stylesheets, scripts and JSON documents the pages reference,");
+    println!("        skipping any over 1M (css, json) or 2M (js) unless --asset-limit, e.g. css=200K, says otherwise");
+    println!("        URLs a host's robots.txt disallows are left out and listed by 'crawls skipped', unless --ignore-robots");
+    println!("  crawls new-all [--tag <tag>] [--budget <n>] [max_concurrent] [--per-host <n>] [--max-bytes <size>] [--user-agent <ua>]... [--meta <key=value>]... [--follow-links [--depth <n>] [--max-pages <n>]] [--assets] [--ignore-robots]");
+    println!("        crawls every site, or those tagged <tag>, several at once with at most --budget pages");
+    println!("        (default {}) fetched at once in all, then sums up how each crawl went", DEFAULT_CONCURRENCY_BUDGET);
+    println!("  crawls estimate <site_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>]");
+    println!("  crawls resume <crawl_id> [max_concurrent] [--per-host <n>] [--max-bytes <size>] [--user-agent <ua>]... [--ignore-robots]");
+    println!("        fetches the URLs left in the crawl's frontier, or when it has none but never finished, e.g.");
+    println!("        as the process died, the sitemap URLs it has no page or error for");
+    println!("  crawls cancel <crawl_id>   (gives up an interrupted crawl so it can't be resumed, keeping its pages)");
+    println!("  crawls delete <id> [id...]");
+    println!("  crawls errors <crawl_id>");
+    println!("  crawls skipped <crawl_id>   (URLs left out without being requested, e.g. disallowed by robots.txt)");
+    println!("  crawls sitemap-changes <crawl_id>   (URLs added to or removed from the sitemap since the previous crawl)");
+    println!("  crawls links <crawl_id> <file> [--format dot|graphml|csv]   (internal link graph, format from the extension by default)");
+    println!("  crawls link-metrics <crawl_id> [--sort score|inlinks] [--limit <n>] [--recompute]   (1.00 is an average page)");
+    println!("  crawls meta <crawl_id>   (the --meta key=value pairs the crawl was started with)");
+    println!("  crawls assets <crawl_id>   (stylesheets, scripts and JSON archived with --assets, with their sizes)");
+    println!("  crawls asset <crawl_id> <url>   (prints an archived asset)");
+    println!("  crawls export-db <crawl_id> <out.db>   (a standalone SQLite file with just that crawl)");
+    println!("  crawls import-db <file.db> [--site <site_id>]   (merge an exported crawl, by default into its own site)");
+    println!("  crawls manifest <crawl_id> <file.json>");
+    println!("  crawls verify <manifest.json>   (exits with 1 if pages are missing, changed or unlisted)");
+    println!("  crawls replay <manifest.json>   (re-runs the recorded queries, exits with 1 if results differ)");
+    println!("  crawls unlock <site_id>");
+    println!("  crawls pin <crawl_id>");
+    println!("  crawls unpin <crawl_id>");
     println!();
     println!("  queries list");
-    println!("  queries new <crawl_id> <selector>");
-    println!("  queries delete <id>");
+    println!("  queries new <crawl_id> <selector|@template> [--name <name>] [--description <text>]");
+    println!("        selectors combine with AND, OR, NOT and count(), e.g. '.hero AND NOT link[rel=canonical]' or 'count(.a) > 3'");
+    println!("        header:<name> tests a response header, e.g. 'header:x-robots-tag~=noindex' or 'header:cache-control missing'");
+    println!("  queries describe <id> [--name <name>] [--description <text>]");
+    println!("        an empty name or description clears it");
+    println!("  queries preview [crawl_id] <url> <selector|@template>");
+    println!("  queries suggest <crawl_id> <url> <example text...>   (selectors matching just that text on the page)");
+    println!("  queries history <site_id> <selector|@template> [--per-url] [--json] [--output <file>]");
+    println!("  queries compare <selector|@template> [site_id...]");
+    println!("  queries templates");
+    println!("  queries delete <id> [id...]");
     println!();
     println!("  results <query_id>");
+    println!("  results search [--crawl <id>] [--query <id>] [--selector <selector|@template>]");
+    println!("                 [--min-count <n>] [--max-count <n>] [--url <text>] [--status <new|reviewed|ignored>]");
+    println!("                 [--modified-since <date>] [--limit <n>] [--offset <n>]");
+    println!("                 --modified-since compares with the <lastmod> of the page's sitemap entry");
+    println!("  results mark <new|reviewed|ignored> <id> [id...]");
+    println!("  results mark <new|reviewed|ignored> [search options...]");
+    println!("  results delete <id> [id...]");
+    println!("  results matches <result_id>   (index, element path and text of each match)");
+    println!();
+    println!("  export <query_id> <csv_filename> [--columns <list>] [--delimiter <char|tab>]");
+    println!("         columns: id, site, crawl-started-at, selector, url, final-url, count, snippet, text,");
+    println!("                  lastmod, changefreq, priority (from the sitemap entry of the page), status");
+    #[cfg(feature = "google-sheets")]
+    println!("  export <query_id> --sheet <id|url> [--tab <name>] [--append] [--columns <list>]");
+    println!();
+    println!("  import folder <site_id> <dir>");
+    println!("  import har <site_id> <har_file>");
+    println!();
+    println!("  audits run <crawl_id> <audit> [--check-dead]");
+    println!("  audits list <crawl_id> [audit]");
+    println!("  audits export <crawl_id> <audit> <csv_filename>");
+    #[cfg(feature = "google-sheets")]
+    println!("  audits export <crawl_id> <audit> --sheet <id|url> [--tab <name>] [--append]");
+    println!();
+    println!("  reports third-party <crawl_id>");
+    println!("  reports storage <crawl_id> [--top <n>]");
+    println!();
+    println!("  pages diff <url> <before_crawl_id> <after_crawl_id>");
+    println!("  pages text-diff <url> <before_crawl_id> <after_crawl_id>");
+    println!("  pages match-diff <url> <selector|@template> <before_crawl_id> <after_crawl_id>");
+    println!("                   (matches moved, changed, removed or added; both crawls must have run the query)");
+    println!("  pages search <crawl_id> <phrase>");
+    println!("  pages history <site_id> <url> [--json]");
     println!();
-    println!("  export <query_id> <csv_filename>");
+    println!("  monitors list");
+    println!("  monitors new <site_id> selector-count <selector> [url_pattern]");
+    println!("  monitors new <site_id> text-change <percent> [url_pattern]");
+    println!("  monitors delete <id>");
+    println!();
+    println!("  rules list");
+    println!("  rules add <site_id> <rules.yaml>");
+    println!("        rules in the format of assert, checked after every crawl of the site; alerts when one starts failing");
+    println!("  rules delete <id>");
+    println!("  rules outcomes <crawl_id>");
+    println!();
+    println!("  alerts [crawl_id]");
+    println!();
+    println!("  suites list");
+    println!("  suites new <name> <audit:name|require:selector|forbid:selector>...");
+    println!("  suites delete <id>");
+    println!("  suites run <crawl_id> <suite>");
+    println!();
+    println!("  jobs list");
+    println!("  jobs show <id>");
+    println!("  jobs submit crawl <site_id> [max_concurrent] [--priority n]");
+    println!("  jobs submit query <crawl_id> <selector> [--priority n]");
+    println!("  jobs priority <id> <priority>");
+    println!("  jobs cancel <id>");
+    println!();
+    println!("  daemon [poll_seconds] [--budget n]");
+    println!();
+    println!("  archive [status]");
+    println!("  archive quota <size|none>");
+    println!("        after each crawl, the oldest unpinned crawls are pruned to fit, e.g. 2G");
+    println!("  archive prune");
+    println!();
+    println!("  trash [list]");
+    println!("  trash restore <site|crawl|query|result> <id>");
+    println!("  trash purge [--older-than <days>]");
+    println!("        deletes only move rows to the trash; purging removes them for good");
+    println!();
+    println!("  audit-log [--action <delete-site|delete-crawl|delete-query|purge-trash|start-crawl>] [--limit <n>]");
+    println!("        who deleted or crawled what and when, newest first");
+    println!();
+    println!("  assert <crawl_id> --rules <rules.yaml> [--format text|github|junit] [--output <file>]");
+    println!("        checks expected selector counts on every page, e.g. exactly one h1; exits with 1 on violations");
+    println!("        rules with severity: warning are reported but don't fail; github prints workflow annotations,");
+    println!("        junit a JUnit XML report with a suite per rule and a test case per page");
+    println!();
+    println!("  sql \"<select ...>\" [--output <csv_filename>] [--delimiter <char|tab>]");
+    println!("        runs read-only SQL against the archive, e.g. sql \"SELECT url FROM pages WHERE crawl_id = 3\"");
+    println!("        reporting views: latest_crawls, query_totals, latest_pages");
+    println!();
+    println!("  check <url> <selector|@template>");
+    println!("        fetches one page and prints the matches, without opening the database");
+    println!("\nEnvironment:");
+    println!("  PALIMP_PASSPHRASE    unlocks encrypted site credentials");
+    println!("  PALIMP_READ_ONLY     set to 1 to act as if --read-only was given");
+    println!("  PALIMP_USER          name recorded in the audit log, defaults to the OS user");
+    println!("  PALIMP_EXPORT_COLUMNS    default export columns, e.g. url,count,text");
+    println!("  PALIMP_EXPORT_DELIMITER  default export delimiter, e.g. ; or tab");
+    #[cfg(feature = "google-sheets")]
+    {
+        println!("  PALIMP_GOOGLE_ACCESS_TOKEN      OAuth token used by --sheet");
+        println!("  GOOGLE_APPLICATION_CREDENTIALS  service account key file used by --sheet without a token");
+    }
+    println!("  PALIMP_HTTP_CACHE        directory to cache fetched pages in, revalidated on each fetch");
+    println!("  PALIMP_HTTP_CACHE_MAX_AGE  seconds a cached page is used without revalidating");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("2048").unwrap(), 2048);
+        assert_eq!(parse_size(" 500k ").unwrap(), 500 * 1024);
+        assert_eq!(parse_size("1G").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_size("20X").is_err());
+        // Too large to count in bytes
+        assert!(parse_size("99999999999G").unwrap_err().to_string().starts_with("Invalid size"));
+    }
 }
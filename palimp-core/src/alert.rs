@@ -0,0 +1,109 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+/// Raised when a monitor is triggered by a page of a crawl, when a site rule
+/// starts failing, or for the crawl as a whole, e.g. when its sitemap lost
+/// URLs.
+pub struct Alert {
+    pub id: Option<i64>,
+    pub monitor_id: Option<i64>,
+    pub rule_id: Option<i64>,
+    pub crawl_id: i64,
+    pub url: String,
+    pub message: String,
+    pub created_at: Option<String>,
+}
+
+impl Alert {
+    pub fn new(id: Option<i64>, monitor_id: i64, crawl_id: i64, url: &str, message: &str) -> Self {
+        Self {
+            id,
+            monitor_id: Some(monitor_id),
+            rule_id: None,
+            crawl_id,
+            url: url.to_string(),
+            message: message.to_string(),
+            created_at: None,
+        }
+    }
+
+    /// An alert of a [`crate::site_rule::SiteRule`], `url` being one of the
+    /// pages it fails on.
+    pub fn for_rule(rule_id: i64, crawl_id: i64, url: &str, message: &str) -> Self {
+        Self {
+            id: None,
+            monitor_id: None,
+            rule_id: Some(rule_id),
+            crawl_id,
+            url: url.to_string(),
+            message: message.to_string(),
+            created_at: None,
+        }
+    }
+
+    /// An alert about the crawl itself rather than a monitor or rule.
+    pub fn for_crawl(crawl_id: i64, url: &str, message: &str) -> Self {
+        Self {
+            id: None,
+            monitor_id: None,
+            rule_id: None,
+            crawl_id,
+            url: url.to_string(),
+            message: message.to_string(),
+            created_at: None,
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE alerts SET url = ?1, message = ?2 WHERE id = ?3",
+                    params![self.url, self.message, existing_id],
+                )?;
+            }
+            None => {
+                let sql = "INSERT INTO alerts (monitor_id, rule_id, crawl_id, url, message, created_at) VALUES (?1, ?2, ?3, ?4, ?5, palimp_now()) RETURNING id, created_at";
+
+                let (new_id, time): (i64, String) = database.conn.query_row(
+                    sql,
+                    params![self.monitor_id, self.rule_id, self.crawl_id, self.url, self.message],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+
+                self.id = Some(new_id);
+                self.created_at = Some(time);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_all(crawl_id: Option<i64>, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, monitor_id, rule_id, crawl_id, url, message, created_at
+             FROM alerts
+             WHERE ?1 IS NULL OR crawl_id = ?1
+             ORDER BY id",
+        )?;
+
+        let alert_iter = stmt.query_map(params![crawl_id], |row| {
+            Ok(Alert {
+                id: Some(row.get(0)?),
+                monitor_id: row.get(1)?,
+                rule_id: row.get(2)?,
+                crawl_id: row.get(3)?,
+                url: row.get(4)?,
+                message: row.get(5)?,
+                created_at: Some(row.get(6)?),
+            })
+        })?;
+
+        let mut alerts = Vec::new();
+        for alert in alert_iter {
+            alerts.push(alert?);
+        }
+
+        Ok(alerts)
+    }
+}
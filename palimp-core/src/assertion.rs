@@ -0,0 +1,452 @@
+//! Baseline assertions: expected selector counts that every page of a crawl
+//! must meet, read from YAML so they can sit next to a deployment pipeline.
+//!
+//! ```yaml
+//! rules:
+//!   - name: one h1 per page
+//!     selector: h1
+//!     count: 1
+//!   - selector: .debug-banner
+//!     max: 0
+//!   - selector: .price
+//!     min: 1
+//!     url_pattern: /products/
+//!     severity: warning
+//! ```
+
+use crate::database::Database;
+use crate::expression::SelectorExpression;
+use crate::page_archive::PageArchive;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+/// Whether a failing rule fails the whole check or is only reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        *self == Severity::Error
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            _ => None,
+        }
+    }
+}
+
+/// An expected match count of a selector, or a [`SelectorExpression`], on
+/// each page. `count` asks for an exact number; `min` and `max` bound it.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub selector: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+    /// Only pages whose URL contains this are checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Severity::is_error")]
+    pub severity: Severity,
+}
+
+impl Rule {
+    /// The rule's name, or a description of it, e.g. "exactly 1 'h1'".
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{} '{}'", self.expectation(), self.selector),
+        }
+    }
+
+    /// The expected count in words, e.g. "at least 1".
+    pub fn expectation(&self) -> String {
+        match (self.count, self.min, self.max) {
+            (Some(0), _, _) | (None, None | Some(0), Some(0)) => "no".to_string(),
+            (Some(count), _, _) => format!("exactly {}", count),
+            (None, Some(min), Some(max)) if min == max => format!("exactly {}", min),
+            (None, Some(min), Some(max)) => format!("{} to {}", min, max),
+            (None, Some(min), None) => format!("at least {}", min),
+            (None, None, Some(max)) => format!("at most {}", max),
+            (None, None, None) => "any number of".to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        SelectorExpression::parse(&self.selector)?;
+        match (self.count, self.min, self.max) {
+            (None, None, None) => Err(format!("Rule '{}' needs a count, min or max", self.selector).into()),
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                Err(format!("Rule '{}' can't combine count with min or max", self.selector).into())
+            }
+            (None, Some(min), Some(max)) if min > max => {
+                Err(format!("Rule '{}' has a min above its max", self.selector).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn holds(&self, count: usize) -> bool {
+        self.count.is_none_or(|expected| count == expected)
+            && self.min.is_none_or(|min| count >= min)
+            && self.max.is_none_or(|max| count <= max)
+    }
+
+    fn applies_to(&self, url: &str) -> bool {
+        self.url_pattern.as_deref().is_none_or(|pattern| url.contains(pattern))
+    }
+}
+
+/// A page where a rule doesn't hold, with the count found there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub url: String,
+    pub count: usize,
+}
+
+/// How one rule fared across a crawl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleOutcome {
+    pub rule: Rule,
+    /// URLs of the pages the rule applied to.
+    pub checked: Vec<String>,
+    pub violations: Vec<Violation>,
+}
+
+impl RuleOutcome {
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// What went wrong on a page, e.g. "2 matches, expected exactly 1 'h1'".
+    pub fn describe(&self, violation: &Violation) -> String {
+        format!("{} matches, expected {} '{}'", violation.count, self.rule.expectation(), self.rule.selector)
+    }
+}
+
+impl fmt::Display for RuleOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} of {} pages fail", self.rule.label(), self.violations.len(), self.checked.len())
+    }
+}
+
+/// The outcome of every rule of a [`RuleSet`] on one crawl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionReport {
+    pub crawl_id: i64,
+    pub pages: usize,
+    pub outcomes: Vec<RuleOutcome>,
+}
+
+impl AssertionReport {
+    /// Whether every rule holds, not counting rules that are warnings.
+    pub fn passed(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|outcome| outcome.passed() || outcome.rule.severity == Severity::Warning)
+    }
+
+    pub fn violations(&self) -> usize {
+        self.outcomes.iter().map(|outcome| outcome.violations.len()).sum()
+    }
+
+    /// One GitHub Actions workflow command per violation, e.g.
+    /// `::error title=one h1 per page::https://a.com/: 2 matches, expected exactly 1 'h1'`,
+    /// so they show up as annotations on the run and its pull request.
+    pub fn github_annotations(&self) -> String {
+        let mut annotations = String::new();
+        for outcome in &self.outcomes {
+            let rule = &outcome.rule;
+            for violation in &outcome.violations {
+                let message = format!("{}: {}", violation.url, outcome.describe(violation));
+                annotations.push_str(&format!(
+                    "::{} title={}::{}\n",
+                    rule.severity.name(),
+                    escape_property(&rule.label()),
+                    escape_data(&message)
+                ));
+            }
+        }
+        annotations
+    }
+
+    /// A JUnit XML report with a test suite per rule and a test case per
+    /// page it checked. Violations of warning rules are passing cases with
+    /// the problem in their output.
+    pub fn junit_xml(&self) -> String {
+        let failures = |outcome: &RuleOutcome| match outcome.rule.severity {
+            Severity::Error => outcome.violations.len(),
+            Severity::Warning => 0,
+        };
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"palimp crawl {}\" tests=\"{}\" failures=\"{}\">\n",
+            self.crawl_id,
+            self.outcomes.iter().map(|outcome| outcome.checked.len()).sum::<usize>(),
+            self.outcomes.iter().map(failures).sum::<usize>()
+        ));
+        for outcome in &self.outcomes {
+            let label = escape_xml(&outcome.rule.label());
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+                label,
+                outcome.checked.len(),
+                failures(outcome)
+            ));
+            for url in &outcome.checked {
+                let case = format!("    <testcase classname=\"{}\" name=\"{}\"", label, escape_xml(url));
+                match outcome.violations.iter().find(|violation| &violation.url == url) {
+                    None => xml.push_str(&format!("{}/>\n", case)),
+                    Some(violation) => {
+                        let message = escape_xml(&outcome.describe(violation));
+                        let detail = match outcome.rule.severity {
+                            Severity::Error => format!("<failure message=\"{}\"/>", message),
+                            Severity::Warning => format!("<system-out>warning: {}</system-out>", message),
+                        };
+                        xml.push_str(&format!("{}>\n      {}\n    </testcase>\n", case, detail));
+                    }
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+pub(crate) fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Escapes a workflow command message.
+fn escape_data(value: &str) -> String {
+    value.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a workflow command property such as `title`.
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl RuleSet {
+    /// Parses and validates rules, so a typo fails before any page is checked.
+    pub fn from_yaml(yaml: &str) -> Result<Self, Box<dyn Error>> {
+        let rules: RuleSet = serde_yaml::from_str(yaml)?;
+        for rule in &rules.rules {
+            rule.validate()?;
+        }
+        Ok(rules)
+    }
+
+    /// Checks every rule against each archived page of a crawl.
+    pub fn check(&self, crawl_id: i64, database: &Database) -> Result<AssertionReport, Box<dyn Error>> {
+        let expressions = self
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.validate()?;
+                SelectorExpression::parse(&rule.selector)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let archives = PageArchive::fetch_by_crawl_id(crawl_id, database)?;
+        let pages: Vec<_> = archives
+            .iter()
+            .filter_map(|archive| archive.to_page().ok().map(|page| (archive.url.as_str(), page)))
+            .collect();
+
+        let outcomes = self
+            .rules
+            .iter()
+            .zip(&expressions)
+            .map(|(rule, expression)| {
+                let mut checked = Vec::new();
+                let mut violations = Vec::new();
+                for (url, page) in pages.iter().filter(|(url, _)| rule.applies_to(url)) {
+                    checked.push(url.to_string());
+                    let count = expression.count(page);
+                    if !rule.holds(count) {
+                        violations.push(Violation { url: url.to_string(), count });
+                    }
+                }
+                RuleOutcome { rule: rule.clone(), checked, violations }
+            })
+            .collect();
+
+        Ok(AssertionReport { crawl_id, pages: pages.len(), outcomes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+
+    #[test]
+    fn test_check_rules() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed');",
+            )
+            .unwrap();
+        let pages = [
+            ("https://a.com/", "<h1>Home</h1>"),
+            ("https://a.com/products/1", "<h1>A</h1><h1>B</h1><span class=\"price\">1</span>"),
+            ("https://a.com/products/2", "<h1>C</h1><div class=\"debug-banner\"></div>"),
+        ];
+        for (url, html) in pages {
+            Page::new(url, url, html, Some(1)).unwrap().sync(&mut db).unwrap();
+        }
+
+        let rules = RuleSet::from_yaml(
+            "rules:
+  - name: one h1 per page
+    selector: h1
+    count: 1
+  - selector: .debug-banner
+    max: 0
+  - selector: .price
+    min: 1
+    url_pattern: /products/
+",
+        )
+        .unwrap();
+        assert_eq!(rules.rules[1].label(), "no '.debug-banner'");
+        assert_eq!(rules.rules[2].label(), "at least 1 '.price'");
+
+        let report = rules.check(1, &db).unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.pages, 3);
+        assert_eq!(report.violations(), 3);
+        let failing: Vec<(usize, Vec<&str>)> = report
+            .outcomes
+            .iter()
+            .map(|outcome| (outcome.checked.len(), outcome.violations.iter().map(|v| v.url.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            failing,
+            [
+                (3, vec!["https://a.com/products/1"]),
+                (3, vec!["https://a.com/products/2"]),
+                (2, vec!["https://a.com/products/2"]),
+            ]
+        );
+        assert_eq!(report.outcomes[0].violations[0].count, 2);
+    }
+
+    #[test]
+    fn test_warnings_and_github_annotations() {
+        let rule = |name: &str, selector: &str, severity: Severity| Rule {
+            name: Some(name.to_string()),
+            selector: selector.to_string(),
+            max: Some(0),
+            severity,
+            ..Rule::default()
+        };
+        let report = AssertionReport {
+            crawl_id: 1,
+            pages: 1,
+            outcomes: vec![RuleOutcome {
+                rule: rule("no banners, please: 100%", ".debug-banner", Severity::Warning),
+                checked: vec!["https://a.com/?q=1".to_string()],
+                violations: vec![Violation { url: "https://a.com/?q=1".to_string(), count: 2 }],
+            }],
+        };
+        assert!(report.passed());
+        assert_eq!(
+            report.github_annotations(),
+            "::warning title=no banners%2C please%3A 100%25::https://a.com/?q=1: 2 matches, expected no '.debug-banner'\n"
+        );
+
+        let mut failing = report.clone();
+        failing.outcomes[0].rule.severity = Severity::Error;
+        assert!(!failing.passed());
+        assert!(failing.github_annotations().starts_with("::error title="));
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let outcome = |severity: Severity| RuleOutcome {
+            rule: Rule {
+                selector: "h1".to_string(),
+                count: Some(1),
+                severity,
+                ..Rule::default()
+            },
+            checked: vec!["https://a.com/".to_string(), "https://a.com/?a=1&b=2".to_string()],
+            violations: vec![Violation { url: "https://a.com/?a=1&b=2".to_string(), count: 2 }],
+        };
+        let report = AssertionReport {
+            crawl_id: 4,
+            pages: 2,
+            outcomes: vec![outcome(Severity::Error), outcome(Severity::Warning)],
+        };
+
+        assert_eq!(
+            report.junit_xml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="palimp crawl 4" tests="4" failures="1">
+  <testsuite name="exactly 1 &apos;h1&apos;" tests="2" failures="1">
+    <testcase classname="exactly 1 &apos;h1&apos;" name="https://a.com/"/>
+    <testcase classname="exactly 1 &apos;h1&apos;" name="https://a.com/?a=1&amp;b=2">
+      <failure message="2 matches, expected exactly 1 &apos;h1&apos;"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="exactly 1 &apos;h1&apos;" tests="2" failures="0">
+    <testcase classname="exactly 1 &apos;h1&apos;" name="https://a.com/"/>
+    <testcase classname="exactly 1 &apos;h1&apos;" name="https://a.com/?a=1&amp;b=2">
+      <system-out>warning: 2 matches, expected exactly 1 &apos;h1&apos;</system-out>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1\n").is_err());
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1\n    count: 1\n    max: 2\n").is_err());
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1\n    min: 3\n    max: 2\n").is_err());
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1\n    cuont: 1\n").is_err());
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1\n    count: 1\n    severity: fatal\n").is_err());
+        assert!(RuleSet::from_yaml("rules:\n  - selector: h1 AND\n    count: 1\n").is_err());
+    }
+}
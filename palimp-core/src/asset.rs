@@ -0,0 +1,295 @@
+use crate::database::Database;
+use crate::page::{tag_attribute, tag_name, Page};
+use crate::third_party::is_same_site;
+use rusqlite::{params, Row};
+use std::error::Error;
+use tl::HTMLTag;
+
+/// Non-HTML responses a crawl can archive next to its pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    Css,
+    Js,
+    /// Includes JSON-LD and web app manifests.
+    Json,
+}
+
+impl AssetKind {
+    pub fn all() -> [AssetKind; 3] {
+        [AssetKind::Css, AssetKind::Js, AssetKind::Json]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AssetKind::Css => "css",
+            AssetKind::Js => "js",
+            AssetKind::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "css" => Some(AssetKind::Css),
+            "js" => Some(AssetKind::Js),
+            "json" => Some(AssetKind::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Largest asset archived of each kind, in bytes. Bigger ones are skipped
+/// without being downloaded in full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssetLimits {
+    pub css: u64,
+    pub js: u64,
+    pub json: u64,
+}
+
+impl Default for AssetLimits {
+    fn default() -> Self {
+        Self {
+            css: 1024 * 1024,
+            js: 2 * 1024 * 1024,
+            json: 1024 * 1024,
+        }
+    }
+}
+
+impl AssetLimits {
+    pub fn max_bytes(&self, kind: AssetKind) -> u64 {
+        match kind {
+            AssetKind::Css => self.css,
+            AssetKind::Js => self.js,
+            AssetKind::Json => self.json,
+        }
+    }
+
+    pub fn with_max_bytes(mut self, kind: AssetKind, max_bytes: u64) -> Self {
+        match kind {
+            AssetKind::Css => self.css = max_bytes,
+            AssetKind::Js => self.js = max_bytes,
+            AssetKind::Json => self.json = max_bytes,
+        }
+        self
+    }
+}
+
+/// The kind of a response from its `Content-Type`. Servers often send
+/// assets as `text/plain`, `application/octet-stream` or without a type, so
+/// those bodies are sniffed: JSON if they parse as JSON, otherwise the kind
+/// the page referenced them as. Any other type, e.g. an error page served
+/// as `text/html`, isn't an asset.
+pub fn sniff(content_type: &str, body: &[u8], referenced_as: AssetKind) -> Option<AssetKind> {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "text/css" => Some(AssetKind::Css),
+        "text/javascript" | "application/javascript" | "application/x-javascript" | "application/ecmascript"
+        | "text/ecmascript" => Some(AssetKind::Js),
+        "application/json" | "text/json" => Some(AssetKind::Json),
+        mime if mime.ends_with("+json") => Some(AssetKind::Json),
+        "" | "text/plain" | "application/octet-stream" => {
+            let body = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+            let looks_like_json = body.trim_ascii_start().first().is_some_and(|byte| matches!(byte, b'{' | b'['));
+            if looks_like_json && serde_json::from_slice::<serde_json::Value>(body).is_ok() {
+                Some(AssetKind::Json)
+            } else {
+                Some(referenced_as)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The stylesheets, scripts and JSON documents a page references on its own
+/// site, subdomains included, as absolute URLs without fragments and in
+/// page order: `<link rel="stylesheet">`, `<script src>`, preloads by their
+/// `as`, `<link rel="manifest">` and links typed as JSON, e.g. an API
+/// endpoint the page fetches its data from.
+pub fn references(page: &Page) -> Vec<(String, AssetKind)> {
+    let site_host = reqwest::Url::parse(&page.final_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_default();
+
+    let mut found: Vec<(String, AssetKind)> = Vec::new();
+    for tag in page.dom.nodes().iter().filter_map(|node| node.as_tag()) {
+        let (kind, href) = match tag_name(tag).as_str() {
+            "link" => (link_kind(tag), tag_attribute(tag, "href")),
+            "script" => (Some(script_kind(tag)), tag_attribute(tag, "src")),
+            _ => continue,
+        };
+        let (Some(kind), Some(href)) = (kind, href) else { continue };
+        let Some(url) = page.resolve_url(&href).and_then(|url| reqwest::Url::parse(&url).ok()) else { continue };
+        let same_site = url.host_str().is_some_and(|host| is_same_site(&host.to_ascii_lowercase(), &site_host));
+        if !matches!(url.scheme(), "http" | "https") || !same_site {
+            continue;
+        }
+        let mut url = url;
+        url.set_fragment(None);
+        let url = url.to_string();
+        if !found.iter().any(|(known, _)| *known == url) {
+            found.push((url, kind));
+        }
+    }
+    found
+}
+
+fn script_kind(script: &HTMLTag) -> AssetKind {
+    match tag_attribute(script, "type").map(|kind| kind.trim().to_ascii_lowercase()) {
+        Some(kind) if kind == "application/json" || kind.ends_with("+json") => AssetKind::Json,
+        _ => AssetKind::Js,
+    }
+}
+
+fn link_kind(link: &HTMLTag) -> Option<AssetKind> {
+    let rel = tag_attribute(link, "rel").unwrap_or_default().to_ascii_lowercase();
+    let has_rel = |wanted: &str| rel.split_whitespace().any(|token| token == wanted);
+    let link_type = tag_attribute(link, "type").unwrap_or_default().trim().to_ascii_lowercase();
+
+    if has_rel("stylesheet") {
+        return Some(AssetKind::Css);
+    }
+    if has_rel("manifest") || link_type == "application/json" || link_type.ends_with("+json") {
+        return Some(AssetKind::Json);
+    }
+    if has_rel("preload") || has_rel("prefetch") || has_rel("modulepreload") {
+        return match tag_attribute(link, "as").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "style" => Some(AssetKind::Css),
+            "script" => Some(AssetKind::Js),
+            "fetch" => Some(AssetKind::Json),
+            _ if has_rel("modulepreload") => Some(AssetKind::Js),
+            _ => None,
+        };
+    }
+    None
+}
+
+const COLUMNS: &str = "id, crawl_id, url, final_url, page_url, kind, content_type, content";
+
+/// A stylesheet, script or JSON document archived with a crawl, once per
+/// URL however many pages reference it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asset {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub url: String,
+    pub final_url: String,
+    /// The first page found referencing it.
+    pub page_url: String,
+    /// As sniffed from the response, which may differ from how the page
+    /// referenced it.
+    pub kind: AssetKind,
+    pub content_type: String,
+    pub content: String,
+}
+
+impl Asset {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let kind: String = row.get(5)?;
+        Ok(Asset {
+            id: Some(row.get(0)?),
+            crawl_id: row.get(1)?,
+            url: row.get(2)?,
+            final_url: row.get(3)?,
+            page_url: row.get(4)?,
+            kind: AssetKind::from_name(&kind).unwrap_or(AssetKind::Js),
+            content_type: row.get(6)?,
+            content: row.get(7)?,
+        })
+    }
+
+    /// Size of the archived body in bytes.
+    pub fn size(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Stores the asset unless the crawl already has one at its URL.
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let inserted = database.conn.execute(
+            "INSERT OR IGNORE INTO assets (crawl_id, url, final_url, page_url, kind, content_type, content)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.crawl_id,
+                self.url,
+                self.final_url,
+                self.page_url,
+                self.kind.name(),
+                self.content_type,
+                self.content
+            ],
+        )?;
+        if inserted > 0 {
+            self.id = Some(database.conn.last_insert_rowid());
+        }
+        Ok(())
+    }
+
+    pub fn exists(crawl_id: i64, url: &str, database: &Database) -> Result<bool, Box<dyn Error>> {
+        let count: i64 = database.conn.query_row(
+            "SELECT COUNT(*) FROM assets WHERE crawl_id = ?1 AND url = ?2",
+            params![crawl_id, url],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let sql = format!("SELECT {} FROM assets WHERE crawl_id = ?1 ORDER BY url", COLUMNS);
+        let mut stmt = database.conn.prepare(&sql)?;
+        let assets = stmt
+            .query_map(params![crawl_id], Self::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(assets)
+    }
+
+    pub fn fetch_by_crawl_and_url(crawl_id: i64, url: &str, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let sql = format!("SELECT {} FROM assets WHERE crawl_id = ?1 AND url = ?2", COLUMNS);
+        database
+            .conn
+            .query_row(&sql, params![crawl_id, url], Self::from_row)
+            .map_err(|_| format!("No asset archived at {} in crawl {}", url, crawl_id).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_sniffing() {
+        let page = Page::new(
+            "https://a.com/p",
+            "https://a.com/p",
+            r#"<html><head>
+                 <link rel="stylesheet" href="/site.css#v2"><link rel="stylesheet" href="https://cdn.other.com/x.css">
+                 <link rel="preload" as="fetch" href="/api/product.json"><link rel="preload" as="image" href="/hero.png">
+                 <link rel="manifest" href="/manifest.webmanifest"><link rel="icon" href="/favicon.ico">
+                 <script src="https://static.a.com/app.js"></script><script type="application/ld+json" src="/ld.json"></script>
+               </head><body><script>inline()</script><link rel="stylesheet" href="/site.css"></body></html>"#,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            references(&page),
+            [
+                ("https://a.com/site.css".to_string(), AssetKind::Css),
+                ("https://a.com/api/product.json".to_string(), AssetKind::Json),
+                ("https://a.com/manifest.webmanifest".to_string(), AssetKind::Json),
+                ("https://static.a.com/app.js".to_string(), AssetKind::Js),
+                ("https://a.com/ld.json".to_string(), AssetKind::Json),
+            ]
+        );
+
+        assert_eq!(sniff("text/css; charset=utf-8", b"body{}", AssetKind::Js), Some(AssetKind::Css));
+        assert_eq!(sniff("application/ld+json", b"{}", AssetKind::Js), Some(AssetKind::Json));
+        assert_eq!(sniff("text/plain", b"\xef\xbb\xbf [1, 2]", AssetKind::Js), Some(AssetKind::Json));
+        assert_eq!(sniff("", b"{ not json", AssetKind::Css), Some(AssetKind::Css));
+        assert_eq!(sniff("text/html", b"<h1>Not found</h1>", AssetKind::Css), None);
+
+        let limits = AssetLimits::default().with_max_bytes(AssetKind::Css, 10);
+        assert_eq!(limits.max_bytes(AssetKind::Css), 10);
+        assert_eq!(limits.max_bytes(AssetKind::Js), 2 * 1024 * 1024);
+    }
+}
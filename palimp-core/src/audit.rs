@@ -0,0 +1,114 @@
+pub mod sitemap_images;
+pub mod noindex;
+pub mod security_headers;
+pub mod accessibility;
+pub mod performance;
+pub mod redirects;
+pub mod soft_404;
+
+use crate::finding::Finding;
+use crate::page_archive::PageArchive;
+
+/// Built-in checks that can be run against an archived crawl. Every audit
+/// stores its output as `Finding` rows tagged with the audit name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audit {
+    SitemapImages,
+    Noindex,
+    SecurityHeaders,
+    Accessibility,
+    Performance,
+    Redirects,
+    Soft404,
+}
+
+/// A single problem found on a page by a page-level audit.
+pub struct PageIssue {
+    pub rule: &'static str,
+    pub detail: String,
+}
+
+impl PageIssue {
+    pub fn new(rule: &'static str, detail: &str) -> Self {
+        Self {
+            rule,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+impl Audit {
+    pub fn all() -> Vec<Audit> {
+        vec![
+            Audit::SitemapImages,
+            Audit::Noindex,
+            Audit::SecurityHeaders,
+            Audit::Accessibility,
+            Audit::Performance,
+            Audit::Redirects,
+            Audit::Soft404,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Audit::SitemapImages => "sitemap-images",
+            Audit::Noindex => "noindex",
+            Audit::SecurityHeaders => "security-headers",
+            Audit::Accessibility => "accessibility",
+            Audit::Performance => "performance",
+            Audit::Redirects => "redirects",
+            Audit::Soft404 => "soft-404",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Audit> {
+        Audit::all().into_iter().find(|audit| audit.name() == name)
+    }
+
+    /// Whether a finding of this audit represents a problem, as opposed to
+    /// an observation (performance metrics, referenced sitemap images).
+    pub fn is_failure(&self, rule: &str) -> bool {
+        match self {
+            Audit::SitemapImages => rule != "referenced",
+            Audit::Performance => false,
+            _ => true,
+        }
+    }
+
+    /// Runs a page-level audit over the archived pages of a crawl.
+    pub fn check_pages(&self, crawl_id: i64, pages: &[PageArchive]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        for archive in pages {
+            let page = match archive.to_page() {
+                Ok(page) => page,
+                Err(_) => continue,
+            };
+
+            let issues = match self {
+                Audit::SitemapImages => Vec::new(),
+                Audit::Noindex => noindex::check(&page),
+                Audit::SecurityHeaders => security_headers::check(&page),
+                Audit::Accessibility => accessibility::check(&page),
+                Audit::Performance => performance::check(&page),
+                Audit::Redirects => redirects::check(archive),
+                Audit::Soft404 => soft_404::check(&page, &soft_404::Soft404Heuristics::default()),
+            };
+
+            for issue in issues {
+                findings.push(Finding::new(
+                    None,
+                    crawl_id,
+                    Some(archive.id),
+                    &archive.url,
+                    self.name(),
+                    issue.rule,
+                    &issue.detail,
+                ));
+            }
+        }
+
+        findings
+    }
+}
@@ -0,0 +1,170 @@
+use crate::audit::PageIssue;
+use crate::page::{tag_attribute, tag_name, Page};
+use std::collections::{BTreeMap, HashSet};
+use tl::HTMLTag;
+
+/// Input types that don't need an associated label.
+const UNLABELLED_INPUT_TYPES: [&str; 5] = ["hidden", "submit", "button", "reset", "image"];
+
+/// Mechanical accessibility rules: images without alt text, unlabelled form
+/// controls, a missing `lang` attribute, empty links/buttons and duplicate IDs.
+pub fn check(page: &Page) -> Vec<PageIssue> {
+    let parser = page.dom.parser();
+    let tags: Vec<&HTMLTag> = page.dom.nodes().iter().filter_map(|node| node.as_tag()).collect();
+    let mut issues = Vec::new();
+
+    let has_lang = tags
+        .iter()
+        .find(|tag| tag_name(tag) == "html")
+        .and_then(|html| tag_attribute(html, "lang"))
+        .is_some_and(|lang| !lang.trim().is_empty());
+    if !has_lang {
+        issues.push(PageIssue::new("missing-lang", "<html> element has no lang attribute"));
+    }
+
+    for tag in tags.iter().filter(|tag| tag_name(tag) == "img") {
+        if tag_attribute(tag, "alt").is_none() {
+            let src = tag_attribute(tag, "src").unwrap_or_default();
+            issues.push(PageIssue::new("img-missing-alt", &src));
+        }
+    }
+
+    let labels: Vec<&&HTMLTag> = tags.iter().filter(|tag| tag_name(tag) == "label").collect();
+    let label_targets: HashSet<String> = labels
+        .iter()
+        .filter_map(|label| tag_attribute(label, "for"))
+        .collect();
+    let mut wrapped_controls: Vec<&HTMLTag> = Vec::new();
+    for label in &labels {
+        for control in ["input", "select", "textarea"] {
+            if let Some(nodes) = label.query_selector(parser, control) {
+                wrapped_controls.extend(nodes.filter_map(|h| h.get(parser)).filter_map(|n| n.as_tag()));
+            }
+        }
+    }
+
+    for tag in &tags {
+        let name = tag_name(tag);
+        let is_control = match name.as_str() {
+            "select" | "textarea" => true,
+            "input" => {
+                let input_type = tag_attribute(tag, "type").unwrap_or_default().to_ascii_lowercase();
+                !UNLABELLED_INPUT_TYPES.contains(&input_type.as_str())
+            }
+            _ => false,
+        };
+        if !is_control {
+            continue;
+        }
+
+        let labelled = has_accessible_name(tag)
+            || tag_attribute(tag, "id").is_some_and(|id| label_targets.contains(&id))
+            || wrapped_controls.iter().any(|control| std::ptr::eq(*control, *tag));
+        if !labelled {
+            let identifier = tag_attribute(tag, "name")
+                .or_else(|| tag_attribute(tag, "id"))
+                .unwrap_or_default();
+            issues.push(PageIssue::new("control-missing-label", &format!("<{}> {}", name, identifier)));
+        }
+    }
+
+    for tag in &tags {
+        let name = tag_name(tag);
+        let is_link = name == "a" && tag_attribute(tag, "href").is_some();
+        if !is_link && name != "button" {
+            continue;
+        }
+
+        let has_text = !tag.inner_text(parser).trim().is_empty();
+        let has_image_alt = tag
+            .query_selector(parser, "img")
+            .map(|mut nodes| {
+                nodes.any(|handle| {
+                    handle
+                        .get(parser)
+                        .and_then(|node| node.as_tag())
+                        .and_then(|img| tag_attribute(img, "alt"))
+                        .is_some_and(|alt| !alt.trim().is_empty())
+                })
+            })
+            .unwrap_or(false);
+
+        if !has_text && !has_image_alt && !has_accessible_name(tag) {
+            if is_link {
+                let href = tag_attribute(tag, "href").unwrap_or_default();
+                issues.push(PageIssue::new("empty-link", &href));
+            } else {
+                issues.push(PageIssue::new("empty-button", "<button> has no accessible text"));
+            }
+        }
+    }
+
+    let mut id_counts: BTreeMap<String, usize> = BTreeMap::new();
+    for id in tags.iter().filter_map(|tag| tag_attribute(tag, "id")) {
+        *id_counts.entry(id).or_default() += 1;
+    }
+    for (id, count) in id_counts {
+        if count > 1 {
+            issues.push(PageIssue::new("duplicate-id", &format!("id \"{}\" is used {} times", id, count)));
+        }
+    }
+
+    issues
+}
+
+fn has_accessible_name(tag: &HTMLTag) -> bool {
+    ["aria-label", "aria-labelledby", "title"]
+        .iter()
+        .any(|attribute| tag_attribute(tag, attribute).is_some_and(|value| !value.trim().is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(html: &str) -> Vec<&'static str> {
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+        check(&page).iter().map(|issue| issue.rule).collect()
+    }
+
+    #[test]
+    fn test_reports_accessibility_issues() {
+        let html = r#"
+            <html>
+                <body>
+                    <img src="/a.png">
+                    <img src="/decorative.png" alt="">
+                    <input type="text" name="email">
+                    <input type="hidden" name="token">
+                    <a href="/empty"></a>
+                    <button></button>
+                    <div id="main"></div>
+                    <div id="main"></div>
+                </body>
+            </html>
+        "#;
+
+        assert_eq!(
+            rules(html),
+            vec!["missing-lang", "img-missing-alt", "control-missing-label", "empty-link", "empty-button", "duplicate-id"]
+        );
+    }
+
+    #[test]
+    fn test_accessible_page_passes() {
+        let html = r#"
+            <html lang="en">
+                <body>
+                    <label for="email">Email</label>
+                    <input type="text" id="email">
+                    <label>Name <input type="text" name="name"></label>
+                    <textarea aria-label="Message"></textarea>
+                    <a href="/home"><img src="/logo.png" alt="Home"></a>
+                    <button>Send</button>
+                </body>
+            </html>
+        "#;
+
+        assert!(rules(html).is_empty());
+    }
+}
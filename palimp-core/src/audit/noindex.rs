@@ -0,0 +1,81 @@
+use crate::audit::PageIssue;
+use crate::page::Page;
+
+const ROBOTS_META_NAMES: [&str; 2] = ["robots", "googlebot"];
+
+/// Flags pages whose robots meta tags or `X-Robots-Tag` headers tell
+/// crawlers not to index the page or not to follow its links.
+pub fn check(page: &Page) -> Vec<PageIssue> {
+    let mut issues = Vec::new();
+
+    for (name, value) in &page.headers {
+        if name.eq_ignore_ascii_case("x-robots-tag") {
+            let source = format!("X-Robots-Tag: {}", value);
+            issues.extend(directive_issues(value, &source));
+        }
+    }
+
+    for name in ROBOTS_META_NAMES {
+        for content in page.meta_content(name) {
+            let source = format!("meta {}: {}", name, content.trim());
+            issues.extend(directive_issues(&content, &source));
+        }
+    }
+
+    issues
+}
+
+/// Turns a comma-separated robots directive list into issues.
+pub(crate) fn directive_issues(directives: &str, source: &str) -> Vec<PageIssue> {
+    let directives: Vec<String> = directives
+        .split(',')
+        // Header values may be scoped to a user agent ("googlebot: noindex")
+        .map(|d| d.rsplit(':').next().unwrap_or(d).trim().to_ascii_lowercase())
+        .collect();
+
+    let has = |wanted: &str| directives.iter().any(|d| d == wanted || d == "none");
+
+    let mut issues = Vec::new();
+    if has("noindex") {
+        issues.push(PageIssue::new("noindex", source));
+    }
+    if has("nofollow") {
+        issues.push(PageIssue::new("nofollow", source));
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_robots_meta_directives() {
+        let html = r#"<html><head><meta name="Robots" content="NOINDEX, follow"><meta name="googlebot" content="none"></head></html>"#;
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+
+        let rules: Vec<&str> = check(&page).iter().map(|i| i.rule).collect();
+        assert_eq!(rules, vec!["noindex", "noindex", "nofollow"]);
+    }
+
+    #[test]
+    fn test_detects_x_robots_tag_header() {
+        let html = "<html><head></head></html>";
+        let page = Page::new("http://test.com", "http://test.com", html, None)
+            .unwrap()
+            .with_headers(vec![("X-Robots-Tag".to_string(), "googlebot: noindex".to_string())]);
+
+        let issues = check(&page);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, "noindex");
+        assert_eq!(issues[0].detail, "X-Robots-Tag: googlebot: noindex");
+    }
+
+    #[test]
+    fn test_indexable_page_has_no_issues() {
+        let html = r#"<html><head><meta name="robots" content="index, follow"></head></html>"#;
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+
+        assert!(check(&page).is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::audit::PageIssue;
+use crate::page::{tag_attribute, tag_name, Page};
+use tl::HTMLTag;
+
+/// Static performance overview derived from the archived HTML alone: render
+/// blocking resources in `<head>`, inline style size, script count and
+/// document size. Every page gets one finding per metric.
+pub fn check(page: &Page) -> Vec<PageIssue> {
+    let parser = page.dom.parser();
+    let tags: Vec<&HTMLTag> = page.dom.nodes().iter().filter_map(|node| node.as_tag()).collect();
+
+    let mut render_blocking = 0;
+    if let Some(head) = tags.iter().find(|tag| tag_name(tag) == "head") {
+        if let Some(scripts) = head.query_selector(parser, "script") {
+            render_blocking += scripts
+                .filter_map(|handle| handle.get(parser))
+                .filter_map(|node| node.as_tag())
+                .filter(|script| is_blocking_script(script))
+                .count();
+        }
+        if let Some(links) = head.query_selector(parser, "link") {
+            render_blocking += links
+                .filter_map(|handle| handle.get(parser))
+                .filter_map(|node| node.as_tag())
+                .filter(|link| is_blocking_stylesheet(link))
+                .count();
+        }
+    }
+
+    let inline_style_bytes: usize = tags
+        .iter()
+        .map(|tag| {
+            let element_styles = if tag_name(tag) == "style" {
+                tag.inner_text(parser).len()
+            } else {
+                0
+            };
+            element_styles + tag_attribute(tag, "style").map(|style| style.len()).unwrap_or(0)
+        })
+        .sum();
+
+    let script_tags = tags.iter().filter(|tag| tag_name(tag) == "script").count();
+
+    vec![
+        PageIssue::new("render-blocking-resources", &render_blocking.to_string()),
+        PageIssue::new("inline-style-bytes", &inline_style_bytes.to_string()),
+        PageIssue::new("script-tags", &script_tags.to_string()),
+        PageIssue::new("document-bytes", &page.html_content.len().to_string()),
+    ]
+}
+
+fn is_blocking_script(script: &HTMLTag) -> bool {
+    let is_module = tag_attribute(script, "type").is_some_and(|t| t.trim().eq_ignore_ascii_case("module"));
+
+    tag_attribute(script, "src").is_some()
+        && tag_attribute(script, "async").is_none()
+        && tag_attribute(script, "defer").is_none()
+        && !is_module
+}
+
+fn is_blocking_stylesheet(link: &HTMLTag) -> bool {
+    let is_stylesheet = tag_attribute(link, "rel").is_some_and(|rel| {
+        rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("stylesheet"))
+    });
+    let media = tag_attribute(link, "media").unwrap_or_default().trim().to_ascii_lowercase();
+
+    is_stylesheet && (media.is_empty() || media == "all" || media == "screen")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_performance_metrics() {
+        let html = r#"<html><head><script src="/a.js"></script><script src="/b.js" defer></script><link rel="stylesheet" href="/a.css"><link rel="stylesheet" href="/print.css" media="print"><style>body{margin:0}</style></head><body><div style="color:red"></div><script>init()</script></body></html>"#;
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+
+        let metrics: Vec<(&str, String)> = check(&page).into_iter().map(|i| (i.rule, i.detail)).collect();
+        assert_eq!(
+            metrics,
+            vec![
+                ("render-blocking-resources", "2".to_string()),
+                ("inline-style-bytes", "23".to_string()),
+                ("script-tags", "3".to_string()),
+                ("document-bytes", html.len().to_string()),
+            ]
+        );
+    }
+}
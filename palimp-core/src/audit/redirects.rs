@@ -0,0 +1,51 @@
+use crate::audit::PageIssue;
+use crate::page_archive::PageArchive;
+use crate::redirect::{same_url, RedirectType};
+
+/// Reports sitemap URLs that redirect, either over HTTP or through a meta
+/// refresh recorded at archiving time.
+pub fn check(archive: &PageArchive) -> Vec<PageIssue> {
+    let mut issues = Vec::new();
+
+    if !same_url(&archive.url, &archive.final_url) {
+        issues.push(PageIssue::new(
+            "http-redirect",
+            &format!("Redirects to {}", archive.final_url),
+        ));
+    }
+
+    let meta_refresh = archive.redirect_type.as_deref().and_then(RedirectType::from_name) == Some(RedirectType::MetaRefresh);
+    if meta_refresh && let Some(target) = &archive.redirect_target {
+        issues.push(PageIssue::new(
+            "meta-refresh",
+            &format!("Meta refresh to {}", target),
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(url: &str, final_url: &str, redirect: Option<(&str, &str)>) -> PageArchive {
+        let mut archive = PageArchive::new(1, 1, url, final_url, "");
+        archive.redirect_type = redirect.map(|(kind, _)| kind.to_string());
+        archive.redirect_target = redirect.map(|(_, target)| target.to_string());
+        archive
+    }
+
+    #[test]
+    fn test_reports_http_and_meta_refresh_redirects() {
+        let issues = check(&archive(
+            "http://example.com/old",
+            "https://example.com/new",
+            Some(("meta-refresh", "https://example.com/newest")),
+        ));
+        let rules: Vec<&str> = issues.iter().map(|issue| issue.rule).collect();
+        assert_eq!(rules, vec!["http-redirect", "meta-refresh"]);
+
+        assert!(check(&archive("https://example.com", "https://example.com/", None)).is_empty());
+    }
+}
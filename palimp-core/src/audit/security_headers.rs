@@ -0,0 +1,131 @@
+use crate::audit::PageIssue;
+use crate::page::Page;
+
+/// HSTS max-age below roughly six months is considered weak.
+const MIN_HSTS_MAX_AGE: u64 = 15_552_000;
+
+/// Reports missing or weak security headers (CSP, HSTS,
+/// X-Content-Type-Options and X-Frame-Options). Pages archived before
+/// headers were stored are skipped.
+pub fn check(page: &Page) -> Vec<PageIssue> {
+    if page.headers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut issues = Vec::new();
+    let csp = page.header("content-security-policy");
+
+    match csp {
+        None => issues.push(PageIssue::new("missing-csp", "Content-Security-Policy header is not set")),
+        Some(policy) => {
+            let lowered = policy.to_ascii_lowercase();
+            for weakness in ["'unsafe-inline'", "'unsafe-eval'"] {
+                if lowered.contains(weakness) {
+                    issues.push(PageIssue::new("weak-csp", &format!("CSP allows {}", weakness)));
+                }
+            }
+            if lowered.split([';', ' ']).any(|source| source.trim() == "*") {
+                issues.push(PageIssue::new("weak-csp", "CSP allows wildcard sources"));
+            }
+        }
+    }
+
+    if page.final_url.starts_with("https://") {
+        match page.header("strict-transport-security") {
+            None => issues.push(PageIssue::new("missing-hsts", "Strict-Transport-Security header is not set")),
+            Some(value) => match hsts_max_age(value) {
+                Some(max_age) if max_age >= MIN_HSTS_MAX_AGE => {}
+                _ => issues.push(PageIssue::new(
+                    "weak-hsts",
+                    &format!("HSTS max-age is below {} seconds: {}", MIN_HSTS_MAX_AGE, value),
+                )),
+            },
+        }
+    }
+
+    match page.header("x-content-type-options") {
+        None => issues.push(PageIssue::new(
+            "missing-x-content-type-options",
+            "X-Content-Type-Options header is not set",
+        )),
+        Some(value) if !value.trim().eq_ignore_ascii_case("nosniff") => issues.push(PageIssue::new(
+            "weak-x-content-type-options",
+            &format!("X-Content-Type-Options is not nosniff: {}", value),
+        )),
+        Some(_) => {}
+    }
+
+    // frame-ancestors in the CSP supersedes X-Frame-Options
+    let has_frame_ancestors = csp.is_some_and(|policy| policy.to_ascii_lowercase().contains("frame-ancestors"));
+    match page.header("x-frame-options") {
+        None if !has_frame_ancestors => issues.push(PageIssue::new(
+            "missing-x-frame-options",
+            "X-Frame-Options header is not set",
+        )),
+        Some(value) if !["deny", "sameorigin"].contains(&value.trim().to_ascii_lowercase().as_str()) => {
+            issues.push(PageIssue::new(
+                "weak-x-frame-options",
+                &format!("X-Frame-Options is neither DENY nor SAMEORIGIN: {}", value),
+            ))
+        }
+        _ => {}
+    }
+
+    issues
+}
+
+fn hsts_max_age(value: &str) -> Option<u64> {
+    value
+        .split(';')
+        .filter_map(|directive| directive.trim().split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("max-age"))
+        .and_then(|(_, age)| age.trim().trim_matches('"').parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_with_headers<'a>(url: &str, html: &'a str, headers: &[(&str, &str)]) -> Page<'a> {
+        Page::new(url, url, html, None).unwrap().with_headers(
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_reports_missing_and_weak_headers() {
+        let page = page_with_headers(
+            "https://test.com",
+            "<html></html>",
+            &[
+                ("content-security-policy", "default-src 'self' 'unsafe-inline'"),
+                ("strict-transport-security", "max-age=3600"),
+                ("x-frame-options", "ALLOW-FROM https://other.com"),
+            ],
+        );
+
+        let rules: Vec<&str> = check(&page).iter().map(|i| i.rule).collect();
+        assert_eq!(
+            rules,
+            vec!["weak-csp", "weak-hsts", "missing-x-content-type-options", "weak-x-frame-options"]
+        );
+    }
+
+    #[test]
+    fn test_hardened_page_passes() {
+        let page = page_with_headers(
+            "https://test.com",
+            "<html></html>",
+            &[
+                ("Content-Security-Policy", "default-src 'self'; frame-ancestors 'none'"),
+                ("Strict-Transport-Security", "max-age=31536000; includeSubDomains"),
+                ("X-Content-Type-Options", "nosniff"),
+            ],
+        );
+
+        assert!(check(&page).is_empty());
+    }
+}
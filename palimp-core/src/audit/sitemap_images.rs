@@ -0,0 +1,145 @@
+use crate::database::Database;
+use crate::finding::Finding;
+use crate::http_client::HTTPClient;
+use crate::page_archive::PageArchive;
+use crate::sitemap_image::SitemapImage;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const AUDIT_NAME: &str = "sitemap-images";
+const MAX_CONCURRENT_CHECKS: usize = 5;
+
+/// Compares the images declared in the image sitemap against the `<img>`
+/// tags of the archived pages, optionally probing every declared image
+/// over HTTP to detect dead ones.
+pub async fn run(
+    crawl_id: i64,
+    db: &Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    check_dead: bool,
+) -> Result<Vec<Finding>, Box<dyn Error>> {
+    let (declared, referenced) = {
+        let db_lock = db.lock().await;
+        let declared = SitemapImage::fetch_by_crawl_id(crawl_id, &db_lock)?;
+        let pages = PageArchive::fetch_by_crawl_id(crawl_id, &db_lock)?;
+        (declared, referenced_images(&pages))
+    };
+
+    let dead: HashSet<String> = if check_dead {
+        let unique: HashSet<String> = declared.iter().map(|i| i.image_url.clone()).collect();
+
+        stream::iter(unique)
+            .map(|image_url| {
+                let client = http_client.clone();
+                async move {
+                    let alive = matches!(client.check_url(&image_url).await, Ok(status) if status < 400);
+                    (image_url, alive)
+                }
+            })
+            .buffer_unordered(MAX_CONCURRENT_CHECKS)
+            .filter_map(|(image_url, alive)| async move { (!alive).then_some(image_url) })
+            .collect()
+            .await
+    } else {
+        HashSet::new()
+    };
+
+    Ok(classify(crawl_id, &declared, &referenced, &dead))
+}
+
+/// Maps every archived page URL to its page ID and the absolute URLs of the
+/// images it references.
+fn referenced_images(pages: &[PageArchive]) -> HashMap<String, (i64, HashSet<String>)> {
+    let mut referenced = HashMap::new();
+
+    for archive in pages {
+        if let Ok(page) = archive.to_page() {
+            let mut sources: HashSet<String> = page
+                .attribute_values("img", "src")
+                .iter()
+                .filter_map(|src| page.resolve_url(src))
+                .collect();
+
+            for srcset in page.attribute_values("img", "srcset") {
+                for candidate in srcset.split(',') {
+                    if let Some(src) = candidate.split_whitespace().next()
+                        && let Some(resolved) = page.resolve_url(src)
+                    {
+                        sources.insert(resolved);
+                    }
+                }
+            }
+
+            referenced.insert(archive.url.clone(), (archive.id, sources));
+        }
+    }
+
+    referenced
+}
+
+fn classify(
+    crawl_id: i64,
+    declared: &[SitemapImage],
+    referenced: &HashMap<String, (i64, HashSet<String>)>,
+    dead: &HashSet<String>,
+) -> Vec<Finding> {
+    declared
+        .iter()
+        .map(|image| {
+            let page = referenced.get(&image.page_url);
+
+            let rule = if dead.contains(&image.image_url) {
+                "dead"
+            } else {
+                match page {
+                    Some((_, sources)) if sources.contains(&image.image_url) => "referenced",
+                    Some(_) => "missing",
+                    None => "unverified",
+                }
+            };
+
+            Finding::new(
+                None,
+                crawl_id,
+                page.map(|(page_id, _)| *page_id),
+                &image.page_url,
+                AUDIT_NAME,
+                rule,
+                &image.image_url,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_declared_images() {
+        let declared = vec![
+            SitemapImage::new(None, 1, "https://example.com/", "https://example.com/hero.jpg"),
+            SitemapImage::new(None, 1, "https://example.com/", "https://example.com/gone.jpg"),
+            SitemapImage::new(None, 1, "https://example.com/", "https://example.com/broken.jpg"),
+            SitemapImage::new(None, 1, "https://example.com/about", "https://example.com/team.jpg"),
+        ];
+
+        let mut referenced = HashMap::new();
+        referenced.insert(
+            "https://example.com/".to_string(),
+            (7, HashSet::from(["https://example.com/hero.jpg".to_string()])),
+        );
+
+        let dead = HashSet::from(["https://example.com/broken.jpg".to_string()]);
+
+        let findings = classify(1, &declared, &referenced, &dead);
+        let rules: Vec<&str> = findings.iter().map(|f| f.rule.as_str()).collect();
+
+        assert_eq!(rules, vec!["referenced", "missing", "dead", "unverified"]);
+        assert_eq!(findings[0].page_id, Some(7));
+        assert_eq!(findings[3].page_id, None);
+    }
+}
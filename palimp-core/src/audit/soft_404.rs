@@ -0,0 +1,169 @@
+use crate::audit::{Audit, PageIssue};
+use crate::crawl::Crawl;
+use crate::database::Database;
+use crate::finding::Finding;
+use crate::page::{tag_name, Page};
+use crate::page_archive::PageArchive;
+use crate::site::Site;
+use std::error::Error;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Pages with less visible text than this are considered thin.
+pub const DEFAULT_MIN_TEXT_BYTES: usize = 512;
+
+/// Phrases error pages commonly use, matched case-insensitively.
+pub const DEFAULT_PHRASES: [&str; 10] = [
+    "page not found",
+    "not found",
+    "404",
+    "page introuvable",
+    "seite nicht gefunden",
+    "página no encontrada",
+    "pagina non trovata",
+    "pagina niet gevonden",
+    "no longer available",
+    "does not exist",
+];
+
+/// How pages are recognised as soft 404s. A page is flagged when its title
+/// or main heading contains an error phrase, or when it is thin and its
+/// text contains one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Soft404Heuristics {
+    pub min_text_bytes: usize,
+    pub phrases: Vec<String>,
+}
+
+impl Default for Soft404Heuristics {
+    fn default() -> Self {
+        Self {
+            min_text_bytes: DEFAULT_MIN_TEXT_BYTES,
+            phrases: DEFAULT_PHRASES.iter().map(|phrase| phrase.to_string()).collect(),
+        }
+    }
+}
+
+impl Soft404Heuristics {
+    fn matching_phrase(&self, text: &str) -> Option<&str> {
+        let text = text.to_lowercase();
+        self.phrases
+            .iter()
+            .map(|phrase| phrase.trim())
+            .filter(|phrase| !phrase.is_empty())
+            .find(|phrase| text.contains(&phrase.to_lowercase()))
+    }
+}
+
+/// Runs the audit with the heuristics configured for the crawl's site.
+pub async fn run(crawl_id: i64, db: &Arc<Mutex<Database>>) -> Result<Vec<Finding>, Box<dyn Error>> {
+    let db_lock = db.lock().await;
+    let crawl = Crawl::fetch(crawl_id, &db_lock)?;
+    let heuristics = Site::fetch(crawl.site_id, &db_lock)?.soft_404_heuristics();
+    let pages = PageArchive::fetch_by_crawl_id(crawl_id, &db_lock)?;
+
+    let mut findings = Vec::new();
+    for archive in &pages {
+        let Ok(page) = archive.to_page() else {
+            continue;
+        };
+
+        for issue in check(&page, &heuristics) {
+            findings.push(Finding::new(
+                None,
+                crawl_id,
+                Some(archive.id),
+                &archive.url,
+                Audit::Soft404.name(),
+                issue.rule,
+                &issue.detail,
+            ));
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Flags a successfully served page that looks like an error page.
+pub fn check(page: &Page, heuristics: &Soft404Heuristics) -> Vec<PageIssue> {
+    let text = page.visible_text().join(" ");
+    let thin = text.len() < heuristics.min_text_bytes;
+
+    let mut signals = Vec::new();
+    if let Some(title) = page.title()
+        && let Some(phrase) = heuristics.matching_phrase(&title)
+    {
+        signals.push(format!("title '{}' contains '{}'", title, phrase));
+    }
+    if let Some(heading) = main_heading(page)
+        && let Some(phrase) = heuristics.matching_phrase(&heading)
+    {
+        signals.push(format!("heading '{}' contains '{}'", heading, phrase));
+    }
+    if signals.is_empty()
+        && thin
+        && let Some(phrase) = heuristics.matching_phrase(&text)
+    {
+        signals.push(format!("text contains '{}'", phrase));
+    }
+
+    if signals.is_empty() {
+        return Vec::new();
+    }
+
+    if thin {
+        signals.push(format!("only {} bytes of text", text.len()));
+    }
+    vec![PageIssue::new("soft-404", &signals.join("; "))]
+}
+
+fn main_heading(page: &Page) -> Option<String> {
+    let parser = page.dom.parser();
+
+    page.dom
+        .nodes()
+        .iter()
+        .filter_map(|node| node.as_tag())
+        .find(|tag| tag_name(tag) == "h1")
+        .map(|tag| tag.inner_text(parser).split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(html: &str, heuristics: &Soft404Heuristics) -> Vec<PageIssue> {
+        let page = Page::new("https://a.com/x", "https://a.com/x", html, None).unwrap();
+        check(&page, heuristics)
+    }
+
+    #[test]
+    fn test_flags_error_pages() {
+        let defaults = Soft404Heuristics::default();
+        let long_text = "Lorem ipsum dolor sit amet. ".repeat(40);
+
+        let by_title = issues("<title>Page introuvable</title><p>Retour</p>", &defaults);
+        assert_eq!(by_title.len(), 1);
+        assert_eq!(by_title[0].rule, "soft-404");
+        assert!(by_title[0].detail.starts_with("title 'Page introuvable'"));
+
+        let by_heading = format!("<h1>Sorry, page not found</h1><p>{}</p>", long_text);
+        assert_eq!(issues(&by_heading, &defaults).len(), 1);
+
+        assert_eq!(issues("<p>The item was not found.</p>", &defaults).len(), 1);
+        let mentioned = format!("<h1>Help</h1><p>If a page is not found, {}</p>", long_text);
+        assert!(issues(&mentioned, &defaults).is_empty());
+        assert!(issues("<title>Contact</title><p>Call us.</p>", &defaults).is_empty());
+    }
+
+    #[test]
+    fn test_custom_heuristics() {
+        let custom = Soft404Heuristics {
+            min_text_bytes: 10,
+            phrases: vec!["Oups".to_string()],
+        };
+
+        assert_eq!(issues("<h1>OUPS !</h1>", &custom).len(), 1);
+        assert!(issues("<title>404</title>", &custom).is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::database::Database;
+use rusqlite::params;
+use rusqlite::types::Type;
+use std::error::Error;
+
+/// What an [`AuditEvent`] records: deletions, trash purges and crawl starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    DeleteSite,
+    DeleteCrawl,
+    DeleteQuery,
+    PurgeTrash,
+    /// A new crawl or the resumption of an interrupted one.
+    StartCrawl,
+}
+
+impl AuditAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditAction::DeleteSite => "delete-site",
+            AuditAction::DeleteCrawl => "delete-crawl",
+            AuditAction::DeleteQuery => "delete-query",
+            AuditAction::PurgeTrash => "purge-trash",
+            AuditAction::StartCrawl => "start-crawl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "delete-site" => Some(AuditAction::DeleteSite),
+            "delete-crawl" => Some(AuditAction::DeleteCrawl),
+            "delete-query" => Some(AuditAction::DeleteQuery),
+            "purge-trash" => Some(AuditAction::PurgeTrash),
+            "start-crawl" => Some(AuditAction::StartCrawl),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the audit log: who did what to which row, and when.
+pub struct AuditEvent {
+    pub id: Option<i64>,
+    pub created_at: Option<String>,
+    /// Set with [`crate::Application::with_actor`], e.g. the OS user.
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    /// The site, crawl or query acted on. Crawl starts name their site.
+    pub target_id: Option<i64>,
+    pub detail: String,
+}
+
+impl AuditEvent {
+    pub fn new(actor: Option<&str>, action: AuditAction, target_id: Option<i64>, detail: &str) -> Self {
+        Self {
+            id: None,
+            created_at: None,
+            actor: actor.map(str::to_string),
+            action,
+            target_id,
+            detail: detail.to_string(),
+        }
+    }
+
+    /// Appends the event to the log. Events are never changed afterwards.
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        if self.id.is_some() {
+            return Err("Audit events can't be changed".into());
+        }
+
+        let sql = "INSERT INTO audit_log (created_at, actor, action, target_id, detail)
+                   VALUES (palimp_now(), ?1, ?2, ?3, ?4) RETURNING id, created_at";
+        let (new_id, time): (i64, String) = database.conn.query_row(
+            sql,
+            params![self.actor, self.action.name(), self.target_id, self.detail],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        self.id = Some(new_id);
+        self.created_at = Some(time);
+        Ok(())
+    }
+
+    /// The latest `limit` events, newest first, optionally of one action.
+    pub fn fetch_recent(action: Option<AuditAction>, limit: usize, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, created_at, actor, action, target_id, detail
+             FROM audit_log
+             WHERE ?1 IS NULL OR action = ?1
+             ORDER BY id DESC
+             LIMIT ?2",
+        )?;
+
+        let event_iter = stmt.query_map(params![action.map(|action| action.name()), limit as i64], |row| {
+            let action: String = row.get(3)?;
+            Ok(AuditEvent {
+                id: Some(row.get(0)?),
+                created_at: Some(row.get(1)?),
+                actor: row.get(2)?,
+                action: AuditAction::from_name(&action).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(3, Type::Text, format!("unknown audit action '{}'", action).into())
+                })?,
+                target_id: row.get(4)?,
+                detail: row.get(5)?,
+            })
+        })?;
+
+        let mut events = Vec::new();
+        for event in event_iter {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::database::Database;
+use aws_lc_rs::digest::{digest, SHA256};
+use rusqlite::params;
+use std::error::Error;
+
+/// Hex SHA-256 of `content`, the key page bodies are stored under.
+pub fn content_hash(content: &str) -> String {
+    digest(&SHA256, content.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Stores an HTML body once however many pages share it and returns its
+/// hash. Bodies are removed with the last page referencing them.
+pub fn store(content: &str, db: &Database) -> Result<String, Box<dyn Error>> {
+    let hash = content_hash(content);
+    db.conn.execute(
+        "INSERT OR IGNORE INTO blobs (hash, content) VALUES (?1, ?2)",
+        params![hash, content],
+    )?;
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::Crawl;
+    use crate::page::Page;
+    use crate::page_archive::PageArchive;
+    use crate::site::Site;
+
+    fn blob_count(db: &Database) -> i64 {
+        db.conn.query_row("SELECT COUNT(*) FROM blobs", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_shared_bodies_are_stored_once() {
+        assert_eq!(
+            content_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let mut crawls = Vec::new();
+        for _ in 0..2 {
+            let mut crawl = Crawl::new(None, site.id.unwrap());
+            crawl.sync(&mut db).unwrap();
+            crawls.push(crawl.id.unwrap());
+        }
+
+        let shared = "<p>Same everywhere</p>";
+        for (crawl_id, url) in [(crawls[0], "https://a.com/"), (crawls[0], "https://a.com/copy"), (crawls[1], "https://a.com/")] {
+            Page::new(url, url, shared, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+        }
+        Page::new("https://a.com/new", "https://a.com/new", "<p>New</p>", Some(crawls[1])).unwrap().sync(&mut db).unwrap();
+        assert_eq!(blob_count(&db), 2);
+        assert_eq!(PageArchive::fetch_by_crawl_and_url(crawls[0], "https://a.com/copy", &db).unwrap().html_content, shared);
+
+        // The shared body stays while a page still uses it
+        Crawl::purge(crawls[0], &db).unwrap();
+        assert_eq!(blob_count(&db), 2);
+        Crawl::purge(crawls[1], &db).unwrap();
+        assert_eq!(blob_count(&db), 0);
+    }
+
+    #[test]
+    fn test_inline_bodies_move_to_blobs() {
+        // Pages as archived before the blob store
+        let db = Database::new(":memory:").unwrap();
+        db.conn.execute_batch(
+            "CREATE TABLE pages (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                final_url TEXT NOT NULL,
+                html_content TEXT NOT NULL,
+                headers TEXT NOT NULL DEFAULT '',
+                redirect_type TEXT,
+                redirect_target TEXT,
+                canonical_url TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO pages (crawl_id, url, final_url, html_content) VALUES
+                (1, 'https://a.com/', 'https://a.com/', '<p>Same</p>'),
+                (2, 'https://a.com/', 'https://a.com/', '<p>Same</p>');
+            -- More pages than one migration batch holds
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 600)
+            INSERT INTO pages (crawl_id, url, final_url, html_content)
+                SELECT 3, 'https://a.com/' || i, 'https://a.com/' || i, '<p>' || i || '</p>' FROM n;",
+        ).unwrap();
+        db.seed().unwrap();
+
+        assert_eq!(blob_count(&db), 601);
+        assert_eq!(PageArchive::fetch_by_crawl_and_url(2, "https://a.com/", &db).unwrap().html_content, "<p>Same</p>");
+        assert_eq!(PageArchive::fetch_by_crawl_and_url(3, "https://a.com/600", &db).unwrap().html_content, "<p>600</p>");
+        // Seeding again leaves the migrated archive alone
+        db.seed().unwrap();
+        assert_eq!(blob_count(&db), 601);
+    }
+}
@@ -0,0 +1,302 @@
+//! A synchronous facade over [`crate::Application`] for embedders without a
+//! tokio runtime. Every method blocks the calling thread on a runtime owned
+//! by the facade, so it must not be called from within an async context.
+//!
+//! Enabled by the `blocking` feature.
+
+use crate::alert::Alert;
+use crate::audit::Audit;
+use crate::comparison::SiteComparison;
+use crate::crawl::{Crawl, CrawlOptions, SiteCrawl};
+use crate::crawl_error::CrawlError;
+use crate::database::Database;
+use crate::dashboard::SiteDashboard;
+use crate::diff::ChangedRegion;
+use crate::estimate::CrawlEstimate;
+use crate::export::ExportOptions;
+use crate::finding::Finding;
+#[cfg(feature = "google-sheets")]
+use crate::google_sheets::{SheetTarget, SheetWrite, SheetsClient};
+use crate::http_client::HTTPClient;
+use crate::jobs::{Job, JobKind, JobStatus};
+use crate::monitor::{Monitor, MonitorKind};
+use crate::page_history::PageHistory;
+use crate::preview::SelectorPreview;
+use crate::query::Query;
+use crate::quota::ArchiveUsage;
+use crate::query_history::QueryHistory;
+use crate::result_entry::{ResultEntry, ResultFilter, ResultStatus};
+use crate::search::SearchHit;
+use crate::site::{Site, SiteValidation};
+use crate::sql::SqlRows;
+use crate::storage_report::StorageReport;
+use crate::suite::{PageSummary, Suite, SuiteCheck};
+use crate::third_party::DomainUsage;
+use crate::trash::{PurgeCounts, TrashEntry, TrashKind};
+use crate::audit_log::{AuditAction, AuditEvent};
+use crate::site_config::{ConfigImport, SiteConfig};
+use crate::manifest::{CrawlManifest, ManifestMismatch, QueryReplay};
+use crate::assertion::{AssertionReport, RuleSet};
+use crate::site_rule::{RuleOutcomeRecord, SiteRule};
+use crate::sitemap_change::SitemapChange;
+use crate::link_graph::{LinkGraph, LinkMetric};
+use crate::crawl_file::{CrawlFileCounts, ImportedCrawl};
+use crate::crawl_meta::CrawlMeta;
+use crate::asset::Asset;
+use crate::skipped_url::SkippedUrl;
+use crate::result_match::{MatchChange, ResultMatch};
+use crate::suggestion::SelectorSuggestion;
+use crate::CrawlResult;
+use std::error::Error;
+use std::path::Path;
+use tokio::runtime::Runtime;
+
+/// Declares blocking equivalents of `Application` methods that take no
+/// generic parameters, each running the async method to completion.
+macro_rules! blocking {
+    ($($(#[$meta:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            $(#[$meta])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Box<dyn Error>> {
+                self.runtime.block_on(self.app.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+pub struct Application {
+    app: crate::Application,
+    runtime: Runtime,
+}
+
+impl Application {
+    pub fn new(db_path: &str) -> Result<Self, Box<dyn Error>> {
+        let runtime = Runtime::new()?;
+        let app = {
+            let _guard = runtime.enter();
+            crate::Application::new(db_path)?
+        };
+        Ok(Self { app, runtime })
+    }
+
+    /// Opens the archive with a custom-tuned client, see [`HTTPClient::builder`].
+    pub fn with_http_client(db_path: &str, http_client: HTTPClient) -> Result<Self, Box<dyn Error>> {
+        let runtime = Runtime::new()?;
+        let app = {
+            let _guard = runtime.enter();
+            crate::Application::with_http_client(db_path, http_client)?
+        };
+        Ok(Self { app, runtime })
+    }
+
+    /// Runs on an already opened database, see [`crate::Application::with_database`].
+    pub fn with_database(db: Database, http_client: HTTPClient) -> Result<Self, Box<dyn Error>> {
+        let runtime = Runtime::new()?;
+        let app = {
+            let _guard = runtime.enter();
+            crate::Application::with_database(db, http_client)?
+        };
+        Ok(Self { app, runtime })
+    }
+
+    /// See [`crate::Application::with_actor`].
+    pub fn with_actor(mut self, actor: &str) -> Self {
+        self.app = self.app.with_actor(actor);
+        self
+    }
+
+    /// See [`crate::Application::with_read_only`].
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.app = self.app.with_read_only(read_only);
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.app.is_read_only()
+    }
+
+    /// The async application underneath, sharing the same database.
+    pub fn as_async(&self) -> &crate::Application {
+        &self.app
+    }
+
+    pub fn new_crawl<F>(&self, site_id: i64, max_concurrent: usize, on_update: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.runtime.block_on(self.app.new_crawl(site_id, max_concurrent, on_update))
+    }
+
+    pub fn new_crawl_with_options<F>(&self, site_id: i64, options: CrawlOptions, on_update: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.runtime.block_on(self.app.new_crawl_with_options(site_id, options, on_update))
+    }
+
+    pub fn new_crawl_from_urls<F>(
+        &self,
+        site_id: i64,
+        urls: Vec<String>,
+        options: CrawlOptions,
+        on_update: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.runtime.block_on(self.app.new_crawl_from_urls(site_id, urls, options, on_update))
+    }
+
+    pub fn resume_crawl<F>(&self, crawl_id: i64, options: CrawlOptions, on_update: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.runtime.block_on(self.app.resume_crawl(crawl_id, options, on_update))
+    }
+
+    pub fn new_crawls<F>(
+        &self,
+        tag: Option<&str>,
+        options: CrawlOptions,
+        budget: usize,
+        on_update: F,
+    ) -> Result<Vec<SiteCrawl>, Box<dyn Error>>
+    where
+        F: Fn(i64, CrawlResult) + Send + Sync + 'static,
+    {
+        self.runtime.block_on(self.app.new_crawls(tag, options, budget, on_update))
+    }
+
+    blocking! {
+        fn unlock_secrets(&self, passphrase: &str) -> ();
+        fn set_site_credential(&self, site_id: i64, name: &str, value: &str) -> ();
+        fn list_site_credentials(&self, site_id: i64) -> Vec<String>;
+        fn delete_site_credential(&self, site_id: i64, name: &str) -> ();
+
+        fn new_site(&self, domain: &str, sitemap_url: &str) -> Site;
+        fn new_site_with_validation(&self, domain: &str, sitemap_url: &str, validation: SiteValidation) -> Site;
+        fn update_site(&self, site_id: i64, domain: Option<&str>, sitemap_url: Option<&str>) -> Site;
+        fn set_site_insecure_tls(&self, site_id: i64, insecure_tls: bool) -> ();
+        fn set_site_fetch_override(&self, site_id: i64, fetch_override: Option<&str>) -> ();
+        fn set_site_soft_404_heuristics(&self, site_id: i64, min_text_bytes: Option<usize>, phrases: Option<Vec<String>>) -> ();
+        fn set_site_politeness(&self, site_id: i64, max_requests_per_sec: Option<f64>, min_delay_ms: Option<u64>) -> ();
+        fn list_sites(&self) -> Vec<Site>;
+        fn tag_site(&self, site_id: i64, tag: &str) -> bool;
+        fn untag_site(&self, site_id: i64, tag: &str) -> bool;
+        fn list_site_tags(&self, site_id: i64) -> Vec<String>;
+        fn export_config(&self) -> SiteConfig;
+        fn import_config(&self, config: &SiteConfig) -> ConfigImport;
+        fn delete_site(&self, site_id: i64) -> ();
+        fn site_dashboard(&self, site_id: i64) -> SiteDashboard;
+
+        fn estimate_crawl(&self, site_id: i64) -> CrawlEstimate;
+        fn list_crawl_errors(&self, crawl_id: i64) -> Vec<CrawlError>;
+        fn list_skipped_urls(&self, crawl_id: i64) -> Vec<SkippedUrl>;
+        fn crawl_manifest(&self, crawl_id: i64) -> CrawlManifest;
+        fn verify_manifest(&self, manifest: &CrawlManifest) -> Vec<ManifestMismatch>;
+        fn replay_manifest(&self, manifest: &CrawlManifest) -> Vec<QueryReplay>;
+        fn list_sitemap_changes(&self, crawl_id: i64) -> Vec<SitemapChange>;
+        fn link_graph(&self, crawl_id: i64) -> LinkGraph;
+        fn crawl_meta(&self, crawl_id: i64) -> CrawlMeta;
+        fn cancel_crawl(&self, crawl_id: i64) -> usize;
+        fn export_crawl_db(&self, crawl_id: i64, path: &Path) -> CrawlFileCounts;
+        fn import_crawl_db(&self, path: &Path, site_id: Option<i64>) -> ImportedCrawl;
+        fn list_link_metrics(&self, crawl_id: i64) -> Vec<LinkMetric>;
+        fn compute_link_metrics(&self, crawl_id: i64) -> usize;
+        fn list_crawls(&self) -> Vec<Crawl>;
+        fn delete_crawl(&self, crawl_id: i64) -> ();
+        fn delete_crawls(&self, crawl_ids: &[i64]) -> ();
+        fn import_folder(&self, site_id: i64, dir: &Path) -> (i64, usize);
+        fn import_har(&self, site_id: i64, path: &Path) -> (i64, usize);
+        fn unlock_site_crawls(&self, site_id: i64) -> ();
+        fn pending_urls(&self, crawl_id: i64) -> usize;
+        fn set_crawl_pinned(&self, crawl_id: i64, pinned: bool) -> ();
+        fn set_archive_quota(&self, quota: Option<u64>) -> ();
+        fn archive_usage(&self) -> ArchiveUsage;
+        fn prune_archive(&self) -> Vec<i64>;
+        fn list_trash(&self) -> Vec<TrashEntry>;
+        fn restore(&self, kind: TrashKind, id: i64) -> ();
+        fn purge_trash(&self, older_than: Option<std::time::Duration>) -> PurgeCounts;
+        fn list_audit_log(&self, action: Option<AuditAction>, limit: usize) -> Vec<AuditEvent>;
+
+        fn list_queries(&self) -> Vec<Query>;
+        fn describe_query(&self, query_id: i64, name: Option<&str>, description: Option<&str>) -> Query;
+        fn delete_query(&self, query_id: i64) -> ();
+        fn delete_queries(&self, query_ids: &[i64]) -> ();
+        fn query(&self, crawl_id: i64, selector: &str) -> Vec<ResultEntry>;
+        fn query_with_details(&self, crawl_id: i64, selector: &str, name: Option<&str>, description: Option<&str>) -> Vec<ResultEntry>;
+        fn query_history(&self, site_id: i64, selector: &str) -> QueryHistory;
+        fn compare_sites(&self, site_ids: &[i64], selector: &str) -> Vec<SiteComparison>;
+        fn raw_query(&self, sql: &str) -> SqlRows;
+        fn list_page_urls(&self, crawl_id: i64) -> Vec<String>;
+        fn list_assets(&self, crawl_id: i64) -> Vec<Asset>;
+        fn fetch_asset(&self, crawl_id: i64, url: &str) -> Asset;
+        fn preview_selector(&self, crawl_id: i64, url: &str, selector: &str) -> SelectorPreview;
+        fn suggest_selectors(&self, crawl_id: i64, url: &str, example: &str) -> Vec<SelectorSuggestion>;
+        fn preview(&self, url: &str, selector: &str) -> SelectorPreview;
+
+        fn list_results(&self) -> Vec<ResultEntry>;
+        fn list_results_for_query(&self, query_id: i64) -> Vec<(ResultEntry, String)>;
+        fn search_results(&self, filter: &ResultFilter) -> Vec<(ResultEntry, String)>;
+        fn list_result_matches(&self, result_id: i64) -> Vec<ResultMatch>;
+        fn export_query_results(&self, query_id: i64, options: &ExportOptions, writer: impl std::io::Write) -> usize;
+        fn export_results(&self, crawl_id: i64, selector: &str, options: &ExportOptions, writer: impl std::io::Write) -> usize;
+        #[cfg(feature = "google-sheets")]
+        fn push_query_results_to_sheet(&self, query_id: i64, options: &ExportOptions, sheets: &SheetsClient, target: &SheetTarget, mode: SheetWrite) -> usize;
+        fn delete_result(&self, result_id: i64) -> ();
+        fn delete_results(&self, result_ids: &[i64]) -> ();
+        fn set_result_status(&self, result_ids: &[i64], status: ResultStatus) -> usize;
+        fn set_matching_result_status(&self, filter: &ResultFilter, status: ResultStatus) -> usize;
+
+        fn submit_job(&self, kind: JobKind, priority: i64) -> Job;
+        fn set_job_priority(&self, job_id: i64, priority: i64) -> ();
+        fn list_jobs(&self, limit: usize) -> Vec<Job>;
+        fn fetch_job(&self, job_id: i64) -> Job;
+        fn cancel_job(&self, job_id: i64) -> JobStatus;
+
+        fn run_assertions(&self, crawl_id: i64, rules: &RuleSet) -> AssertionReport;
+        fn run_audit(&self, crawl_id: i64, audit: Audit, check_dead: bool) -> Vec<Finding>;
+        fn list_findings(&self, crawl_id: i64, audit: Option<Audit>) -> Vec<Finding>;
+        #[cfg(feature = "google-sheets")]
+        fn push_findings_to_sheet(&self, crawl_id: i64, audit: Option<Audit>, sheets: &SheetsClient, target: &SheetTarget, mode: SheetWrite) -> usize;
+        fn storage_report(&self, crawl_id: i64, top: usize) -> StorageReport;
+        fn third_party_domains(&self, crawl_id: i64) -> Vec<DomainUsage>;
+        fn diff_page(&self, url: &str, before_crawl_id: i64, after_crawl_id: i64) -> Vec<ChangedRegion>;
+        fn text_diff(&self, url: &str, before_crawl_id: i64, after_crawl_id: i64) -> String;
+        fn match_diff(&self, url: &str, selector: &str, before_crawl_id: i64, after_crawl_id: i64) -> Vec<MatchChange>;
+        fn search_pages(&self, crawl_id: i64, phrase: &str) -> Vec<SearchHit>;
+        fn page_history(&self, site_id: i64, url: &str) -> PageHistory;
+
+        fn list_suites(&self) -> Vec<Suite>;
+        fn new_suite(&self, name: &str, checks: Vec<SuiteCheck>) -> Suite;
+        fn delete_suite(&self, suite_id: i64) -> ();
+        fn run_suite(&self, crawl_id: i64, suite_name: &str) -> Vec<PageSummary>;
+
+        fn new_monitor(&self, site_id: i64, kind: MonitorKind, url_pattern: Option<&str>) -> Monitor;
+        fn list_monitors(&self) -> Vec<Monitor>;
+        fn delete_monitor(&self, monitor_id: i64) -> ();
+        fn add_site_rules(&self, site_id: i64, rules: &RuleSet) -> Vec<SiteRule>;
+        fn list_site_rules(&self) -> Vec<SiteRule>;
+        fn delete_site_rule(&self, rule_id: i64) -> ();
+        fn list_rule_outcomes(&self, crawl_id: i64) -> Vec<RuleOutcomeRecord>;
+        fn list_alerts(&self, crawl_id: Option<i64>) -> Vec<Alert>;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_site_lifecycle() {
+        let app = Application::new(":memory:").unwrap();
+
+        app.new_site_with_validation("example.com", "https://example.com/sitemap.xml", SiteValidation::Format)
+            .unwrap();
+        let sites = app.list_sites().unwrap();
+        assert_eq!(sites.len(), 1);
+
+        app.delete_site(sites[0].id.unwrap()).unwrap();
+        assert!(app.list_sites().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of the current time for timestamps palimp stores, so tests can pin
+/// it. The database reads it through the `palimp_now()` SQL function.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The real time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to.
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+}
+
+impl ManualClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    /// A clock at `seconds` after the Unix epoch.
+    pub fn at_unix(seconds: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Formats a time the way SQLite's `CURRENT_TIMESTAMP` does, e.g.
+/// `2024-01-02 03:04:05` in UTC.
+pub fn sql_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's
+    // `civil_from_days`
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        of_day / 3_600,
+        of_day % 3_600 / 60,
+        of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sql_timestamp() {
+        assert_eq!(sql_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00");
+        // 2024 is a leap year
+        assert_eq!(sql_timestamp(ManualClock::at_unix(1_709_164_800).now()), "2024-02-29 00:00:00");
+        assert_eq!(sql_timestamp(ManualClock::at_unix(1_704_164_645).now()), "2024-01-02 03:04:05");
+
+        let clock = ManualClock::at_unix(1_704_164_645);
+        clock.advance(Duration::from_secs(86_400));
+        assert_eq!(sql_timestamp(clock.now()), "2024-01-03 03:04:05");
+    }
+}
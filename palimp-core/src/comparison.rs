@@ -0,0 +1,87 @@
+use crate::crawl::Crawl;
+use crate::database::Database;
+use crate::query_history::{count_matches, CrawlCount};
+use crate::site::Site;
+use std::error::Error;
+
+/// Matches of a selector in the latest completed crawl of one site.
+pub struct SiteComparison {
+    pub site_id: i64,
+    pub domain: String,
+    /// `None` when the site has no completed crawl yet.
+    pub latest: Option<CrawlCount>,
+}
+
+impl SiteComparison {
+    /// Matches per archived page, to compare sites of different sizes.
+    pub fn matches_per_page(&self) -> f64 {
+        match &self.latest {
+            Some(latest) if latest.pages > 0 => latest.total as f64 / latest.pages as f64,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Runs a selector against the latest completed crawl of each site, in the
+/// order given.
+pub fn compare_sites(site_ids: &[i64], selector: &str, database: &Database) -> Result<Vec<SiteComparison>, Box<dyn Error>> {
+    let mut comparisons = Vec::new();
+
+    for &site_id in site_ids {
+        let site = Site::fetch(site_id, database).map_err(|e| format!("Site {} not found: {}", site_id, e))?;
+        let latest = match Crawl::fetch_latest_completed(site_id, database)? {
+            Some(crawl) => Some(count_matches(&crawl, selector, database)?.0),
+            None => None,
+        };
+
+        comparisons.push(SiteComparison {
+            site_id,
+            domain: site.domain,
+            latest,
+        });
+    }
+
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::CrawlStatus;
+    use crate::page::Page;
+
+    #[test]
+    fn test_compares_latest_completed_crawls() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', ''), (2, 'b.com', ''), (3, 'c.com', '')",
+                [],
+            )
+            .unwrap();
+
+        let crawls = [
+            (1, CrawlStatus::Completed, "<b></b>"),
+            (1, CrawlStatus::Completed, "<b></b><b></b>"),
+            (1, CrawlStatus::Interrupted, "<b></b><b></b><b></b>"),
+            (2, CrawlStatus::Completed, "<i></i>"),
+        ];
+        for (site_id, status, html) in crawls {
+            let mut crawl = Crawl::new(None, site_id);
+            crawl.status = status;
+            crawl.sync(&mut db).unwrap();
+            Page::new("https://x.com/", "https://x.com/", html, crawl.id).unwrap().sync(&mut db).unwrap();
+        }
+
+        let comparisons = compare_sites(&[2, 1, 3], "b", &db).unwrap();
+
+        let totals: Vec<(&str, Option<usize>)> = comparisons
+            .iter()
+            .map(|comparison| (comparison.domain.as_str(), comparison.latest.as_ref().map(|latest| latest.total)))
+            .collect();
+        assert_eq!(totals, vec![("b.com", Some(0)), ("a.com", Some(2)), ("c.com", None)]);
+        assert_eq!(comparisons[1].matches_per_page(), 2.0);
+        assert!(compare_sites(&[9], "b", &db).is_err());
+    }
+}
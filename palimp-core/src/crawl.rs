@@ -1,11 +1,236 @@
+use crate::asset::AssetLimits;
+use crate::crawl_meta::CrawlMeta;
 use crate::database::Database;
-use rusqlite::params;
+use crate::link_crawl::LinkFollowing;
+use crate::sitemap::SitemapLimits;
+use crate::trash::{self, TrashKind};
+use rusqlite::{params, Row};
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Tunables of a single crawl run.
+#[derive(Clone, Debug)]
+pub struct CrawlOptions {
+    /// Pages fetched at once across all hosts.
+    pub max_concurrent: usize,
+    /// Pages fetched at once from any single host, for sitemaps spanning
+    /// several hosts. Defaults to `max_concurrent`.
+    pub max_per_host: Option<usize>,
+    /// Stops the crawl from fetching further pages when triggered.
+    pub stop: StopSignal,
+    /// Page bytes the crawl may download. Once they are used up the crawl
+    /// stops as if interrupted; fetches in flight still finish, so it can
+    /// overshoot by up to `max_concurrent` pages.
+    pub max_bytes: Option<u64>,
+    /// User agents taken in turn for the crawl's requests, e.g. to compare
+    /// what a site serves to browsers and bots. Empty keeps the client's.
+    pub user_agents: Vec<String>,
+    /// How far a sitemap index is followed.
+    pub sitemap_limits: SitemapLimits,
+    /// Stored with the crawl when it's created; a resume keeps the original.
+    pub meta: CrawlMeta,
+    /// Where a new crawl gets its URLs.
+    pub mode: CrawlMode,
+    /// Archives the stylesheets, scripts and JSON documents pages reference,
+    /// up to these sizes. Off when unset.
+    pub assets: Option<AssetLimits>,
+    /// Leaves out the sitemap and link-following URLs a host's robots.txt
+    /// disallows for palimp, recording them as skipped. On by default.
+    pub respect_robots: bool,
+}
+
+impl CrawlOptions {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            max_per_host: None,
+            stop: StopSignal::default(),
+            max_bytes: None,
+            user_agents: Vec::new(),
+            sitemap_limits: SitemapLimits::default(),
+            meta: CrawlMeta::new(),
+            mode: CrawlMode::Sitemap,
+            assets: None,
+            respect_robots: true,
+        }
+    }
+
+    pub fn with_stop_signal(mut self, stop: StopSignal) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_max_per_host(mut self, max_per_host: usize) -> Self {
+        self.max_per_host = Some(max_per_host);
+        self
+    }
+
+    pub fn with_user_agents(mut self, user_agents: Vec<String>) -> Self {
+        self.user_agents = user_agents;
+        self
+    }
+
+    pub fn with_sitemap_limits(mut self, sitemap_limits: SitemapLimits) -> Self {
+        self.sitemap_limits = sitemap_limits;
+        self
+    }
+
+    pub fn with_meta(mut self, meta: CrawlMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: CrawlMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_assets(mut self, limits: AssetLimits) -> Self {
+        self.assets = Some(limits);
+        self
+    }
+
+    /// Turning it off fetches every URL whatever the robots.txt says.
+    pub fn with_respect_robots(mut self, respect_robots: bool) -> Self {
+        self.respect_robots = respect_robots;
+        self
+    }
+
+    /// The user agent of the crawl's `request`th request, if any are set.
+    pub fn user_agent_for(&self, request: usize) -> Option<&str> {
+        if self.user_agents.is_empty() {
+            return None;
+        }
+        Some(self.user_agents[request % self.user_agents.len()].as_str())
+    }
+
+    /// Effective cap for one host, never above the overall concurrency.
+    pub fn per_host_limit(&self) -> usize {
+        match self.max_per_host {
+            Some(per_host) => per_host.min(self.max_concurrent).max(1),
+            None => self.max_concurrent.max(1),
+        }
+    }
+}
+
+/// Where a new crawl gets the URLs it fetches.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum CrawlMode {
+    /// Every URL in the site's sitemap.
+    #[default]
+    Sitemap,
+    /// The pages reached by following links from seed URLs, level by level.
+    /// Only the level being fetched is in the frontier, so a resumed crawl
+    /// finishes that level without following its links.
+    FollowLinks(LinkFollowing),
+}
+
+/// Asks a running crawl to stop, e.g. on Ctrl-C. The crawl issues no new
+/// fetches, lets the ones in flight finish and is marked interrupted, so it
+/// can be resumed from its frontier. [`StopSignal::cancel`] gives it up
+/// instead.
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal {
+    stopped: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops the crawl for good: it is marked cancelled and the URLs it had
+    /// left are dropped, so it can't be resumed.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.stop();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// How one site fared when crawling several at once, see
+/// `Application::new_crawls`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteCrawl {
+    pub site_id: i64,
+    pub domain: String,
+    /// The crawl created for the site, unset if it never got that far.
+    pub crawl_id: Option<i64>,
+    pub pages_succeeded: usize,
+    pub pages_failed: usize,
+    /// Why the crawl failed or wasn't started, e.g. the site was already
+    /// being crawled.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlStatus {
+    /// Fetching pages, or cut short by a crash.
+    Running,
+    Completed,
+    /// Stopped on request with URLs left in its frontier.
+    Interrupted,
+    /// Given up with URLs left, which were dropped; the pages fetched so
+    /// far are kept.
+    Cancelled,
+}
+
+impl CrawlStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrawlStatus::Running => "running",
+            CrawlStatus::Completed => "completed",
+            CrawlStatus::Interrupted => "interrupted",
+            CrawlStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "running" => Some(CrawlStatus::Running),
+            "completed" => Some(CrawlStatus::Completed),
+            "interrupted" => Some(CrawlStatus::Interrupted),
+            "cancelled" => Some(CrawlStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+const COLUMNS: &str = "id, site_id, started_at, status, bytes_fetched, pinned, finished_at,
+    CAST(ROUND((julianday(finished_at) - julianday(started_at)) * 86400) AS INTEGER)";
 
 pub struct Crawl {
     pub id: Option<i64>,
     pub site_id: i64,
     pub started_at: Option<String>,
+    pub status: CrawlStatus,
+    /// Page bytes downloaded so far, counted against `CrawlOptions::max_bytes`.
+    pub bytes_fetched: u64,
+    /// Kept when the archive quota prunes old crawls.
+    pub pinned: bool,
+    /// When the crawl last completed or was interrupted, unset while running.
+    pub finished_at: Option<String>,
+    /// From the start to `finished_at`, including any pause before a resume.
+    pub duration: Option<Duration>,
 }
 
 impl Crawl {
@@ -14,59 +239,103 @@ impl Crawl {
             id,
             site_id,
             started_at: None,
+            status: CrawlStatus::Running,
+            bytes_fetched: 0,
+            pinned: false,
+            finished_at: None,
+            duration: None,
         }
     }
 
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let status: String = row.get(3)?;
+        Ok(Crawl {
+            id: Some(row.get(0)?),
+            site_id: row.get(1)?,
+            started_at: Some(row.get(2)?),
+            status: CrawlStatus::from_name(&status).unwrap_or(CrawlStatus::Completed),
+            bytes_fetched: row.get::<_, i64>(4)? as u64,
+            pinned: row.get(5)?,
+            finished_at: row.get(6)?,
+            duration: row.get::<_, Option<i64>>(7)?.map(|seconds| Duration::from_secs(seconds.max(0) as u64)),
+        })
+    }
+
     pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
         match self.id {
             Some(existing_id) => {
                 database.conn.execute(
-                    "UPDATE crawls SET site_id = ?1 WHERE id = ?2",
-                    params![self.site_id, existing_id],
+                    "UPDATE crawls SET site_id = ?1, status = ?2 WHERE id = ?3",
+                    params![self.site_id, self.status.name(), existing_id],
                 )?;
             }
             None => {
-                let sql = "INSERT INTO crawls (site_id) VALUES (?1) RETURNING id, started_at";
+                // A crawl stored already done, e.g. an import, finishes as it starts
+                let sql = "INSERT INTO crawls (site_id, status, started_at, finished_at)
+                           VALUES (?1, ?2, palimp_now(), CASE WHEN ?2 = 'running' THEN NULL ELSE palimp_now() END)
+                           RETURNING id, started_at, finished_at";
 
-                let (new_id, time): (i64, String) =
-                    database.conn.query_row(sql, params![self.site_id], |row| {
-                        Ok((row.get(0)?, row.get(1)?))
+                let (new_id, time, finished_at): (i64, String, Option<String>) =
+                    database.conn.query_row(sql, params![self.site_id, self.status.name()], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
                     })?;
 
                 self.id = Some(new_id);
                 self.started_at = Some(time);
+                if finished_at.is_some() {
+                    self.duration = Some(Duration::ZERO);
+                }
+                self.finished_at = finished_at;
             }
         }
         Ok(())
     }
 
     pub fn fetch(id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
-        let sql = "SELECT id, site_id, started_at FROM crawls WHERE id = ?1";
+        let sql = format!("SELECT {} FROM crawls WHERE id = ?1 AND deleted_at IS NULL", COLUMNS);
 
         database
             .conn
-            .query_row(sql, params![id], |row| {
-                Ok(Crawl {
-                    id: Some(row.get(0)?),
-                    site_id: row.get(1)?,
-                    started_at: Some(row.get(2)?),
-                })
-            })
+            .query_row(&sql, params![id], Self::from_row)
             .map_err(|e| e.into())
     }
 
+    /// The most recent crawl of the same site that started before `crawl_id`.
+    pub fn fetch_previous(site_id: i64, crawl_id: i64, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let sql = format!(
+            "SELECT {} FROM crawls WHERE site_id = ?1 AND id < ?2 AND deleted_at IS NULL ORDER BY id DESC LIMIT 1",
+            COLUMNS
+        );
+
+        let mut stmt = database.conn.prepare(&sql)?;
+        let mut rows = stmt.query_map(params![site_id, crawl_id], Self::from_row)?;
+
+        match rows.next() {
+            Some(crawl) => Ok(Some(crawl?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The most recent completed crawl of a site.
+    pub fn fetch_latest_completed(site_id: i64, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let sql = format!(
+            "SELECT {} FROM crawls WHERE site_id = ?1 AND status = 'completed' AND deleted_at IS NULL ORDER BY id DESC LIMIT 1",
+            COLUMNS
+        );
+
+        let mut stmt = database.conn.prepare(&sql)?;
+        let mut rows = stmt.query_map(params![site_id], Self::from_row)?;
+
+        match rows.next() {
+            Some(crawl) => Ok(Some(crawl?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
-        let mut stmt = database
-            .conn
-            .prepare("SELECT id, site_id, started_at FROM crawls")?;
+        let mut stmt = database.conn.prepare(&format!("SELECT {} FROM crawls WHERE deleted_at IS NULL", COLUMNS))?;
 
-        let crawl_iter = stmt.query_map([], |row| {
-            Ok(Crawl {
-                id: Some(row.get(0)?),
-                site_id: row.get(1)?,
-                started_at: Some(row.get(2)?),
-            })
-        })?;
+        let crawl_iter = stmt.query_map([], Self::from_row)?;
 
         let mut crawls = Vec::new();
         for crawl in crawl_iter {
@@ -76,7 +345,44 @@ impl Crawl {
         Ok(crawls)
     }
 
+    /// Records a new status for an existing crawl. Completing or interrupting
+    /// it sets its finish time, running it again clears it.
+    pub fn set_status(id: i64, status: CrawlStatus, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "UPDATE crawls SET status = ?1, finished_at = CASE WHEN ?1 = 'running' THEN NULL ELSE palimp_now() END
+             WHERE id = ?2",
+            params![status.name(), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_pinned(id: i64, pinned: bool, database: &Database) -> Result<(), Box<dyn Error>> {
+        let updated = database
+            .conn
+            .execute("UPDATE crawls SET pinned = ?1 WHERE id = ?2", params![pinned, id])?;
+        if updated == 0 {
+            return Err(format!("Crawl {} not found", id).into());
+        }
+        Ok(())
+    }
+
+    /// Adds `bytes` to the crawl's downloaded total.
+    pub fn add_bytes_fetched(id: i64, bytes: u64, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "UPDATE crawls SET bytes_fetched = bytes_fetched + ?1 WHERE id = ?2",
+            params![bytes as i64, id],
+        )?;
+        Ok(())
+    }
+
+    /// Moves the crawl, its queries and their results to the trash, see
+    /// [`trash::delete`].
     pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        trash::delete(TrashKind::Crawl, id, database)
+    }
+
+    /// Removes the crawl and its pages for good, deleted or not.
+    pub fn purge(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
         database
             .conn
             .execute("DELETE FROM crawls WHERE id = ?1", params![id])?;
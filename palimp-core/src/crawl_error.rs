@@ -0,0 +1,130 @@
+use crate::database::Database;
+use crate::http_client::{RedirectFailure, ServerError};
+use rusqlite::params;
+use std::error::Error;
+
+/// What kind of failure kept a URL out of the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlErrorKind {
+    /// Connection problems, error statuses and documents that can't be archived.
+    Fetch,
+    RedirectLoop,
+    TooManyRedirects,
+}
+
+impl CrawlErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrawlErrorKind::Fetch => "fetch",
+            CrawlErrorKind::RedirectLoop => "redirect-loop",
+            CrawlErrorKind::TooManyRedirects => "too-many-redirects",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fetch" => Some(CrawlErrorKind::Fetch),
+            "redirect-loop" => Some(CrawlErrorKind::RedirectLoop),
+            "too-many-redirects" => Some(CrawlErrorKind::TooManyRedirects),
+            _ => None,
+        }
+    }
+
+    pub fn classify(err: &(dyn Error + 'static)) -> Self {
+        match err.downcast_ref::<ServerError>() {
+            Some(ServerError::Redirect { failure: RedirectFailure::Loop(_), .. }) => CrawlErrorKind::RedirectLoop,
+            Some(ServerError::Redirect { failure: RedirectFailure::TooManyHops(_), .. }) => {
+                CrawlErrorKind::TooManyRedirects
+            }
+            _ => CrawlErrorKind::Fetch,
+        }
+    }
+}
+
+/// A URL of a crawl that could not be archived, with the reason.
+pub struct CrawlError {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub url: String,
+    pub kind: CrawlErrorKind,
+    pub message: String,
+}
+
+impl CrawlError {
+    pub fn new(id: Option<i64>, crawl_id: i64, url: &str, kind: CrawlErrorKind, message: &str) -> Self {
+        Self {
+            id,
+            crawl_id,
+            url: url.to_string(),
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE crawl_errors SET url = ?1, kind = ?2, message = ?3 WHERE id = ?4",
+                    params![self.url, self.kind.name(), self.message, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO crawl_errors (crawl_id, url, kind, message) VALUES (?1, ?2, ?3, ?4)",
+                    params![self.crawl_id, self.url, self.kind.name(), self.message],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare("SELECT id, crawl_id, url, kind, message FROM crawl_errors WHERE crawl_id = ?1 ORDER BY id")?;
+
+        let error_iter = stmt.query_map(params![crawl_id], |row| {
+            let kind: String = row.get(3)?;
+            Ok(CrawlError {
+                id: Some(row.get(0)?),
+                crawl_id: row.get(1)?,
+                url: row.get(2)?,
+                kind: CrawlErrorKind::from_name(&kind).unwrap_or(CrawlErrorKind::Fetch),
+                message: row.get(4)?,
+            })
+        })?;
+
+        let mut errors = Vec::new();
+        for error in error_iter {
+            errors.push(error?);
+        }
+
+        Ok(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_redirect_failures() {
+        let chain = vec!["https://a.com/x".to_string(), "https://a.com/y".to_string(), "https://a.com/x".to_string()];
+        let looped: Box<dyn Error> = Box::new(ServerError::Redirect {
+            url: "https://a.com/x".to_string(),
+            failure: RedirectFailure::Loop(chain.clone()),
+        });
+        let too_many: Box<dyn Error> = Box::new(ServerError::Redirect {
+            url: "https://a.com/x".to_string(),
+            failure: RedirectFailure::TooManyHops(chain),
+        });
+        let status: Box<dyn Error> = Box::new(ServerError::Status { url: "https://a.com/x".to_string(), status: 500 });
+
+        assert_eq!(CrawlErrorKind::classify(looped.as_ref()), CrawlErrorKind::RedirectLoop);
+        assert_eq!(CrawlErrorKind::classify(too_many.as_ref()), CrawlErrorKind::TooManyRedirects);
+        assert_eq!(CrawlErrorKind::classify(status.as_ref()), CrawlErrorKind::Fetch);
+        assert_eq!(CrawlErrorKind::from_name("redirect-loop"), Some(CrawlErrorKind::RedirectLoop));
+    }
+}
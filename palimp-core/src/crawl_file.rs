@@ -0,0 +1,386 @@
+//! A single crawl in a standalone SQLite file, with the archive's schema but
+//! only that crawl's site, pages and query results, so one crawl can be
+//! handed to someone without the whole archive, and merged into theirs.
+
+use crate::crawl::Crawl;
+use crate::database::Database;
+use crate::site::Site;
+use rusqlite::{params, params_from_iter};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// What an exported crawl file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CrawlFileCounts {
+    pub pages: usize,
+    pub queries: usize,
+    pub results: usize,
+}
+
+/// A crawl merged into the archive from an exported file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportedCrawl {
+    pub crawl_id: i64,
+    pub site_id: i64,
+    /// Whether no site matched and the exported one was added.
+    pub created_site: bool,
+    pub counts: CrawlFileCounts,
+}
+
+/// Columns never copied on import: new IDs are assigned and change tracking
+/// starts over.
+const FRESH_COLUMNS: [&str; 4] = ["id", "updated_at", "deleted_at", "deleted_batch"];
+
+/// Rows copied for a crawl, by table, in foreign key order. `?1` is the
+/// crawl ID.
+const EXPORTED_ROWS: [(&str, &str); 9] = [
+    ("sites", "id = (SELECT site_id FROM main.crawls WHERE id = ?1)"),
+    ("crawls", "id = ?1"),
+    ("crawl_meta", "crawl_id = ?1"),
+    ("blobs", "hash IN (SELECT blob_hash FROM main.pages WHERE crawl_id = ?1)"),
+    ("pages", "crawl_id = ?1"),
+    ("assets", "crawl_id = ?1"),
+    ("queries", "crawl_id = ?1 AND deleted_at IS NULL"),
+    (
+        "results",
+        "deleted_at IS NULL AND query_id IN (SELECT id FROM main.queries WHERE crawl_id = ?1 AND deleted_at IS NULL)",
+    ),
+    (
+        "result_matches",
+        "result_id IN (SELECT r.id FROM main.results r INNER JOIN main.queries q ON q.id = r.query_id
+         WHERE q.crawl_id = ?1 AND q.deleted_at IS NULL AND r.deleted_at IS NULL)",
+    ),
+];
+
+/// Writes a crawl to a new SQLite file at `path`, which must not exist yet.
+pub fn export(crawl_id: i64, database: &Database, path: &Path) -> Result<CrawlFileCounts, Box<dyn Error>> {
+    Crawl::fetch(crawl_id, database).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    let path_str = path.to_str().ok_or("The export path is not valid UTF-8")?;
+
+    {
+        let export = Database::new(path_str)?;
+        export.seed()?;
+        // A single file to hand over, without a write-ahead log next to it
+        export.conn.query_row("PRAGMA journal_mode = DELETE", [], |row| row.get::<_, String>(0))?;
+    }
+
+    database.conn.execute("ATTACH DATABASE ?1 AS export", params![path_str])?;
+    let copied = copy_crawl(crawl_id, database);
+    database.conn.execute("DETACH DATABASE export", [])?;
+
+    if copied.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    copied
+}
+
+fn copy_crawl(crawl_id: i64, database: &Database) -> Result<CrawlFileCounts, Box<dyn Error>> {
+    database.conn.execute_batch("BEGIN IMMEDIATE")?;
+    let copied = (|| -> Result<CrawlFileCounts, Box<dyn Error>> {
+        let mut counts = CrawlFileCounts::default();
+        for (table, condition) in EXPORTED_ROWS {
+            let columns = export_columns(table, database)?.join(", ");
+            let rows = database.conn.execute(
+                &format!(
+                    "INSERT INTO export.{table} ({columns}) SELECT {columns} FROM main.{table} WHERE {condition}",
+                    table = table,
+                    columns = columns,
+                    condition = condition
+                ),
+                params![crawl_id],
+            )?;
+            match table {
+                "pages" => counts.pages = rows,
+                "queries" => counts.queries = rows,
+                "results" => counts.results = rows,
+                _ => {}
+            }
+        }
+
+        // Keep full-text search working on the exported pages
+        database.conn.execute(
+            "INSERT INTO export.pages_fts (rowid, content)
+             SELECT rowid, content FROM main.pages_fts
+             WHERE rowid IN (SELECT id FROM main.pages WHERE crawl_id = ?1)",
+            params![crawl_id],
+        )?;
+        Ok(counts)
+    })();
+
+    match copied {
+        Ok(counts) => {
+            database.conn.execute_batch("COMMIT")?;
+            Ok(counts)
+        }
+        Err(err) => {
+            let _ = database.conn.execute_batch("ROLLBACK");
+            Err(err)
+        }
+    }
+}
+
+/// Columns of a table in the export, which has the current schema. The
+/// archive may have them in another order after migrations.
+fn export_columns(table: &str, database: &Database) -> Result<Vec<String>, Box<dyn Error>> {
+    table_columns("export", table, database)
+}
+
+fn table_columns(schema: &str, table: &str, database: &Database) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns)
+}
+
+/// Merges the crawl of an exported file into the archive under new IDs. It
+/// joins `site_id` if given, otherwise the site with the exported domain and
+/// sitemap, which is created when there is none.
+pub fn import(path: &Path, site_id: Option<i64>, database: &mut Database) -> Result<ImportedCrawl, Box<dyn Error>> {
+    if !path.is_file() {
+        return Err(format!("{} not found", path.display()).into());
+    }
+    if let Some(site_id) = site_id {
+        Site::fetch(site_id, database).map_err(|e| format!("Site {} not found: {}", site_id, e))?;
+    }
+    let path_str = path.to_str().ok_or("The import path is not valid UTF-8")?;
+
+    database.conn.execute("ATTACH DATABASE ?1 AS import", params![path_str])?;
+    let imported = database.in_transaction(|db| import_crawl(site_id, db));
+    database.conn.execute("DETACH DATABASE import", [])?;
+    imported
+}
+
+fn import_crawl(site_id: Option<i64>, database: &mut Database) -> Result<ImportedCrawl, Box<dyn Error>> {
+    let crawl_ids = ids("SELECT id FROM import.crawls", &[], database)?;
+    let [old_crawl_id] = crawl_ids[..] else {
+        return Err(format!("Expected one crawl in the file, found {}", crawl_ids.len()).into());
+    };
+    let (old_site_id, domain, sitemap_url): (i64, String, String) = database.conn.query_row(
+        "SELECT s.id, s.domain, s.sitemap_url FROM import.sites s
+         INNER JOIN import.crawls c ON c.site_id = s.id WHERE c.id = ?1",
+        params![old_crawl_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let existing = match site_id {
+        Some(site_id) => Some(site_id),
+        None => Site::fetch_by_domain_and_sitemap(&domain, &sitemap_url, database)?.and_then(|site| site.id),
+    };
+    let site_id = match existing {
+        Some(site_id) => site_id,
+        None => copy_row("sites", old_site_id, &[], database)?,
+    };
+    let crawl_id = copy_row("crawls", old_crawl_id, &[("site_id", site_id)], database)?;
+    if !table_columns("import", "crawl_meta", database)?.is_empty() {
+        database.conn.execute(
+            "INSERT INTO main.crawl_meta (crawl_id, key, value) SELECT ?1, key, value FROM import.crawl_meta WHERE crawl_id = ?2",
+            params![crawl_id, old_crawl_id],
+        )?;
+    }
+
+    database.conn.execute(
+        "INSERT OR IGNORE INTO main.blobs (hash, content) SELECT hash, content FROM import.blobs",
+        [],
+    )?;
+
+    let mut pages = HashMap::new();
+    for old_id in ids("SELECT id FROM import.pages WHERE crawl_id = ?1 ORDER BY id", &[old_crawl_id], database)? {
+        let new_id = copy_row("pages", old_id, &[("crawl_id", crawl_id)], database)?;
+        database.conn.execute(
+            "INSERT INTO main.pages_fts (rowid, content) SELECT ?1, content FROM import.pages_fts WHERE rowid = ?2",
+            params![new_id, old_id],
+        )?;
+        pages.insert(old_id, new_id);
+    }
+
+    if !table_columns("import", "assets", database)?.is_empty() {
+        database.conn.execute(
+            "INSERT INTO main.assets (crawl_id, url, final_url, page_url, kind, content_type, content)
+             SELECT ?1, url, final_url, page_url, kind, content_type, content FROM import.assets WHERE crawl_id = ?2",
+            params![crawl_id, old_crawl_id],
+        )?;
+    }
+
+    let mut queries = HashMap::new();
+    for old_id in ids("SELECT id FROM import.queries WHERE crawl_id = ?1 ORDER BY id", &[old_crawl_id], database)? {
+        queries.insert(old_id, copy_row("queries", old_id, &[("crawl_id", crawl_id)], database)?);
+    }
+
+    // Files exported before match positions were kept have no such table
+    let with_matches = !table_columns("import", "result_matches", database)?.is_empty();
+    let mut results = 0;
+    let mut stmt = database.conn.prepare("SELECT id, query_id, page_id FROM import.results ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+    for (old_id, old_query_id, old_page_id) in rows {
+        if let (Some(&query_id), Some(&page_id)) = (queries.get(&old_query_id), pages.get(&old_page_id)) {
+            let new_id = copy_row("results", old_id, &[("query_id", query_id), ("page_id", page_id)], database)?;
+            if with_matches {
+                database.conn.execute(
+                    "INSERT INTO main.result_matches (result_id, position, path, text, hash)
+                     SELECT ?1, position, path, text, hash FROM import.result_matches WHERE result_id = ?2",
+                    params![new_id, old_id],
+                )?;
+            }
+            results += 1;
+        }
+    }
+
+    Ok(ImportedCrawl {
+        crawl_id,
+        site_id,
+        created_site: existing.is_none(),
+        counts: CrawlFileCounts {
+            pages: pages.len(),
+            queries: queries.len(),
+            results,
+        },
+    })
+}
+
+fn ids(sql: &str, parameters: &[i64], database: &Database) -> Result<Vec<i64>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare(sql)?;
+    let ids = stmt
+        .query_map(params_from_iter(parameters), |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ids)
+}
+
+/// Copies a row of `table` from the import into the archive with the
+/// columns in `overrides` replaced, and returns its new ID. Columns only one
+/// side has, from an older or newer version, are left out.
+fn copy_row(table: &str, id: i64, overrides: &[(&str, i64)], database: &Database) -> Result<i64, Box<dyn Error>> {
+    let archive_columns = table_columns("main", table, database)?;
+    let columns: Vec<String> = table_columns("import", table, database)?
+        .into_iter()
+        .filter(|column| archive_columns.contains(column))
+        .filter(|column| !FRESH_COLUMNS.contains(&column.as_str()))
+        .filter(|column| !overrides.iter().any(|(name, _)| name == column))
+        .collect();
+
+    let mut names: Vec<&str> = overrides.iter().map(|(name, _)| *name).collect();
+    names.extend(columns.iter().map(String::as_str));
+    let mut values: Vec<String> = (0..overrides.len()).map(|i| format!("?{}", i + 2)).collect();
+    values.extend(columns.iter().cloned());
+
+    let mut parameters = vec![id];
+    parameters.extend(overrides.iter().map(|(_, value)| *value));
+    database.conn.execute(
+        &format!(
+            "INSERT INTO main.{table} ({names}) SELECT {values} FROM import.{table} WHERE id = ?1",
+            table = table,
+            names = names.join(", "),
+            values = values.join(", ")
+        ),
+        params_from_iter(parameters),
+    )?;
+    Ok(database.conn.last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+    use crate::query::Query;
+    use crate::result_entry::ResultEntry;
+
+    #[test]
+    fn test_export_crawl() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', ''), (2, 'b.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed'), (2, 1, 'completed'), (3, 2, 'completed');",
+            )
+            .unwrap();
+        for crawl_id in 1..=3 {
+            Page::new("https://a.com/", "https://a.com/", "<h1>Shared</h1>", Some(crawl_id))
+                .unwrap()
+                .sync(&mut db)
+                .unwrap();
+        }
+        Page::new("https://a.com/x", "https://a.com/x", "<p>Own body</p>", Some(2))
+            .unwrap()
+            .sync(&mut db)
+            .unwrap();
+        let mut query = Query::new(None, 2, "h1");
+        query.sync(&mut db).unwrap();
+        let page_id: i64 = db
+            .conn
+            .query_row("SELECT id FROM pages WHERE crawl_id = 2 AND url = 'https://a.com/'", [], |row| row.get(0))
+            .unwrap();
+        let mut result = ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 1);
+        result.sync(&mut db).unwrap();
+        let shared = Page::new("https://a.com/", "https://a.com/", "<h1>Shared</h1>", None).unwrap();
+        crate::result_match::record(result.id.unwrap(), &crate::result_match::locate(&shared, "h1"), &db).unwrap();
+        let meta = crate::crawl_meta::CrawlMeta::from([("release".to_string(), "1.4".to_string())]);
+        crate::crawl_meta::record(2, &meta, &db).unwrap();
+        let mut asset = crate::asset::Asset {
+            id: None,
+            crawl_id: 2,
+            url: "https://a.com/site.css".to_string(),
+            final_url: "https://a.com/site.css".to_string(),
+            page_url: "https://a.com/".to_string(),
+            kind: crate::asset::AssetKind::Css,
+            content_type: "text/css".to_string(),
+            content: "h1{color:red}".to_string(),
+        };
+        asset.sync(&mut db).unwrap();
+
+        let path = std::env::temp_dir().join(format!("palimp-export-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let counts = export(2, &db, &path).unwrap();
+        assert_eq!(counts, CrawlFileCounts { pages: 2, queries: 1, results: 1 });
+        assert!(export(2, &db, &path).is_err());
+        assert!(export(99, &db, &path.with_extension("other.db")).is_err());
+
+        let exported = Database::new(path.to_str().unwrap()).unwrap();
+        let count = |sql: &str| exported.conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM sites"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM crawls WHERE id = 2"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM result_matches"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM blobs"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM pages_fts WHERE pages_fts MATCH 'body'"), 1);
+        drop(exported);
+
+        // Into another archive: the site is created, then found again
+        let mut other = Database::new(":memory:").unwrap();
+        other.seed().unwrap();
+        other
+            .conn
+            .execute("INSERT INTO sites (id, domain, sitemap_url) VALUES (7, 'c.com', '')", [])
+            .unwrap();
+        let imported = import(&path, None, &mut other).unwrap();
+        assert!(imported.created_site);
+        assert_eq!(imported.counts, counts);
+        let again = import(&path, None, &mut other).unwrap();
+        assert!(!again.created_site);
+        assert_eq!(again.site_id, imported.site_id);
+        assert_ne!(again.crawl_id, imported.crawl_id);
+        let onto = import(&path, Some(7), &mut other).unwrap();
+        assert_eq!(onto.site_id, 7);
+        assert!(import(&path, Some(99), &mut other).is_err());
+
+        let results = ResultEntry::search(&crate::result_entry::ResultFilter::for_query(3), &other).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "https://a.com/");
+        let page = crate::page_archive::PageArchive::fetch(results[0].0.page_id, &other).unwrap();
+        assert_eq!((page.crawl_id, page.html_content.as_str()), (onto.crawl_id, "<h1>Shared</h1>"));
+        let count = |sql: &str| other.conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM pages_fts WHERE pages_fts MATCH 'body'"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM blobs"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM result_matches WHERE path = 'h1'"), 3);
+        assert_eq!(crate::crawl_meta::fetch(onto.crawl_id, &other).unwrap(), meta);
+        let assets = crate::asset::Asset::fetch_by_crawl_id(onto.crawl_id, &other).unwrap();
+        assert_eq!((assets.len(), assets[0].content.as_str()), (1, "h1{color:red}"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
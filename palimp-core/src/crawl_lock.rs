@@ -0,0 +1,142 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Seconds without a heartbeat after which a lock is considered abandoned,
+/// e.g. by a process that crashed mid-crawl.
+pub const STALE_AFTER_SECS: i64 = 120;
+
+/// How often a running crawl refreshes its lock.
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+
+static NEXT_OWNER: AtomicU64 = AtomicU64::new(1);
+
+/// Advisory lock held by the process crawling a site, so that the GUI, CLI
+/// and daemon sharing a database never crawl the same site at once.
+pub struct CrawlLock {
+    pub site_id: i64,
+    /// Process ID plus a per-process counter, unique among live holders.
+    pub owner: String,
+    pub acquired_at: Option<String>,
+    pub heartbeat_at: Option<String>,
+}
+
+impl CrawlLock {
+    /// Takes the lock for a site, replacing a stale one. Fails when another
+    /// crawl holds it.
+    pub fn acquire(site_id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let owner = format!(
+            "pid {} #{}",
+            std::process::id(),
+            NEXT_OWNER.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let acquired = database.conn.execute(
+            "INSERT INTO crawl_locks (site_id, owner, acquired_at, heartbeat_at) VALUES (?1, ?2, palimp_now(), palimp_now())
+             ON CONFLICT (site_id) DO UPDATE SET
+                owner = excluded.owner,
+                acquired_at = excluded.acquired_at,
+                heartbeat_at = excluded.heartbeat_at
+             WHERE crawl_locks.heartbeat_at < datetime(palimp_now(), ?3)",
+            params![site_id, owner, format!("-{} seconds", STALE_AFTER_SECS)],
+        )?;
+
+        if acquired == 0 {
+            let holder = Self::fetch_by_site(site_id, database)?;
+            return Err(format!(
+                "Site {} is already being crawled ({}, last seen {}). Use 'crawls unlock {}' if that crawl is gone.",
+                site_id,
+                holder.as_ref().map(|lock| lock.owner.as_str()).unwrap_or("unknown"),
+                holder.as_ref().and_then(|lock| lock.heartbeat_at.as_deref()).unwrap_or("never"),
+                site_id
+            )
+            .into());
+        }
+
+        Ok(Self {
+            site_id,
+            owner,
+            acquired_at: None,
+            heartbeat_at: None,
+        })
+    }
+
+    pub fn heartbeat(&self, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "UPDATE crawl_locks SET heartbeat_at = palimp_now() WHERE site_id = ?1 AND owner = ?2",
+            params![self.site_id, self.owner],
+        )?;
+        Ok(())
+    }
+
+    /// Gives the lock up, unless it was taken over in the meantime.
+    pub fn release(&self, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "DELETE FROM crawl_locks WHERE site_id = ?1 AND owner = ?2",
+            params![self.site_id, self.owner],
+        )?;
+        Ok(())
+    }
+
+    pub fn fetch_by_site(site_id: i64, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare("SELECT site_id, owner, acquired_at, heartbeat_at FROM crawl_locks WHERE site_id = ?1")?;
+        let mut rows = stmt.query_map(params![site_id], |row| {
+            Ok(CrawlLock {
+                site_id: row.get(0)?,
+                owner: row.get(1)?,
+                acquired_at: row.get(2)?,
+                heartbeat_at: row.get(3)?,
+            })
+        })?;
+
+        match rows.next() {
+            Some(lock) => Ok(Some(lock?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes a site's lock whoever holds it.
+    pub fn delete(site_id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        database
+            .conn
+            .execute("DELETE FROM crawl_locks WHERE site_id = ?1", params![site_id])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lock_is_exclusive_until_released_or_stale() {
+        let clock = Arc::new(ManualClock::at_unix(1_704_164_645));
+        let db = Database::with_clock(":memory:", clock.clone()).unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute("INSERT INTO sites (id, domain) VALUES (1, 'example.com')", [])
+            .unwrap();
+
+        let lock = CrawlLock::acquire(1, &db).unwrap();
+        assert!(CrawlLock::acquire(1, &db).is_err());
+
+        lock.release(&db).unwrap();
+        let lock = CrawlLock::acquire(1, &db).unwrap();
+
+        // A holder that stopped sending heartbeats is taken over
+        clock.advance(Duration::from_secs(STALE_AFTER_SECS as u64 - 1));
+        assert!(CrawlLock::acquire(1, &db).is_err());
+        clock.advance(Duration::from_secs(2));
+        let taken_over = CrawlLock::acquire(1, &db).unwrap();
+
+        // The previous holder can't release the new holder's lock
+        lock.release(&db).unwrap();
+        assert_eq!(CrawlLock::fetch_by_site(1, &db).unwrap().unwrap().owner, taken_over.owner);
+    }
+}
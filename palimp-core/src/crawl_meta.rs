@@ -0,0 +1,69 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Key/value pairs attached to a crawl when it starts, e.g. the release or
+/// git SHA of the deployed site, to tell which deployment a crawl saw.
+pub type CrawlMeta = BTreeMap<String, String>;
+
+/// Parses a `key=value` pair. The key is trimmed and can't be empty; the
+/// value is kept as given and may itself contain `=`.
+pub fn parse_pair(pair: &str) -> Result<(String, String), Box<dyn Error>> {
+    let (key, value) = pair
+        .split_once('=')
+        .ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("Missing key in '{}'", pair).into());
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+pub fn record(crawl_id: i64, meta: &CrawlMeta, database: &Database) -> Result<(), Box<dyn Error>> {
+    for (key, value) in meta {
+        database.conn.execute(
+            "INSERT OR REPLACE INTO crawl_meta (crawl_id, key, value) VALUES (?1, ?2, ?3)",
+            params![crawl_id, key, value],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn fetch(crawl_id: i64, database: &Database) -> Result<CrawlMeta, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare("SELECT key, value FROM crawl_meta WHERE crawl_id = ?1")?;
+    let meta = stmt
+        .query_map(params![crawl_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<CrawlMeta, _>>()?;
+    Ok(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_record_meta() {
+        assert_eq!(parse_pair(" release =1.4").unwrap(), ("release".to_string(), "1.4".to_string()));
+        assert_eq!(parse_pair("query=a=b").unwrap().1, "a=b");
+        assert!(parse_pair("release").is_err());
+        assert!(parse_pair(" =1.4").is_err());
+
+        let db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed'), (2, 1, 'completed');",
+            )
+            .unwrap();
+        let meta: CrawlMeta = [("sha", "4f2a9c1"), ("release", "1.4")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        record(1, &meta, &db).unwrap();
+
+        assert_eq!(fetch(1, &db).unwrap(), meta);
+        assert!(fetch(2, &db).unwrap().is_empty());
+    }
+}
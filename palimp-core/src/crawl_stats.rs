@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+/// Running totals of a crawl run, sent with every finished page so a
+/// dashboard can show how the site copes with the chosen concurrency. A
+/// resumed crawl starts counting again.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CrawlStats {
+    /// Pages fetched, failed ones included.
+    pub requests: usize,
+    pub failed: usize,
+    /// HTML bytes downloaded.
+    pub bytes: u64,
+    /// Time spent waiting for responses, summed over all requests.
+    pub total_latency: Duration,
+    /// Since the run started fetching.
+    pub elapsed: Duration,
+}
+
+impl CrawlStats {
+    pub fn record(&mut self, bytes: u64, latency: Duration, failed: bool) {
+        self.requests += 1;
+        self.bytes += bytes;
+        self.total_latency += latency;
+        if failed {
+            self.failed += 1;
+        }
+    }
+
+    pub fn requests_per_sec(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Share of requests that failed, from 0 to 1.
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.failed as f64 / self.requests as f64
+    }
+
+    pub fn average_latency(&self) -> Duration {
+        if self.requests == 0 {
+            return Duration::ZERO;
+        }
+        self.total_latency / self.requests as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates() {
+        let mut stats = CrawlStats::default();
+        assert_eq!((stats.requests_per_sec(), stats.error_rate(), stats.average_latency()), (0.0, 0.0, Duration::ZERO));
+
+        stats.record(1000, Duration::from_millis(100), false);
+        stats.record(3000, Duration::from_millis(300), false);
+        stats.record(0, Duration::from_millis(200), true);
+        stats.record(0, Duration::from_millis(200), true);
+        stats.elapsed = Duration::from_secs(2);
+
+        assert_eq!(stats.requests_per_sec(), 2.0);
+        assert_eq!(stats.error_rate(), 0.5);
+        assert_eq!(stats.average_latency(), Duration::from_millis(200));
+        assert_eq!(stats.bytes, 4000);
+    }
+}
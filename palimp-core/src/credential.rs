@@ -0,0 +1,78 @@
+use crate::database::Database;
+use crate::secrets::SecretStore;
+use rusqlite::params;
+use std::error::Error;
+
+/// A request header sent with every fetch for a site (e.g. `Authorization`),
+/// with its value encrypted by a [`SecretStore`].
+pub struct SiteCredential {
+    pub id: Option<i64>,
+    pub site_id: i64,
+    pub name: String,
+    pub secret: Vec<u8>,
+}
+
+impl SiteCredential {
+    pub fn new(id: Option<i64>, site_id: i64, name: &str, value: &str, secrets: &SecretStore) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            id,
+            site_id,
+            name: name.to_string(),
+            secret: secrets.encrypt(value)?,
+        })
+    }
+
+    pub fn value(&self, secrets: &SecretStore) -> Result<String, Box<dyn Error>> {
+        secrets.decrypt(&self.secret)
+    }
+
+    /// Inserts the credential, replacing any existing one with the same name.
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE site_credentials SET name = ?1, secret = ?2 WHERE id = ?3",
+                    params![self.name, self.secret, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT OR REPLACE INTO site_credentials (site_id, name, secret) VALUES (?1, ?2, ?3)",
+                    params![self.site_id, self.name, self.secret],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_site(site_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare("SELECT id, site_id, name, secret FROM site_credentials WHERE site_id = ?1 ORDER BY name")?;
+
+        let credential_iter = stmt.query_map(params![site_id], |row| {
+            Ok(SiteCredential {
+                id: Some(row.get(0)?),
+                site_id: row.get(1)?,
+                name: row.get(2)?,
+                secret: row.get(3)?,
+            })
+        })?;
+
+        let mut credentials = Vec::new();
+        for credential in credential_iter {
+            credentials.push(credential?);
+        }
+
+        Ok(credentials)
+    }
+
+    pub fn delete_by_site_and_name(site_id: i64, name: &str, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "DELETE FROM site_credentials WHERE site_id = ?1 AND name = ?2",
+            params![site_id, name],
+        )?;
+        Ok(())
+    }
+}
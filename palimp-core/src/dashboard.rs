@@ -0,0 +1,122 @@
+use crate::database::Database;
+use crate::site::Site;
+use rusqlite::params;
+use std::error::Error;
+
+/// Number of most recent queries included in a dashboard.
+const RECENT_QUERIES_LIMIT: i64 = 10;
+
+pub struct CrawlStats {
+    pub crawl_id: i64,
+    pub started_at: Option<String>,
+    pub page_count: u32,
+    pub error_count: u32,
+}
+
+impl CrawlStats {
+    /// Share of URLs that failed to be archived, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        let attempted = self.page_count + self.error_count;
+        if attempted == 0 {
+            0.0
+        } else {
+            self.error_count as f64 / attempted as f64
+        }
+    }
+}
+
+pub struct QuerySummary {
+    pub query_id: i64,
+    pub crawl_id: i64,
+    pub selector: String,
+    pub name: Option<String>,
+    pub matching_pages: u32,
+    pub total_count: u32,
+}
+
+impl QuerySummary {
+    /// The name of the query, or its selector when it has none.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.selector)
+    }
+}
+
+/// Aggregated overview of a site, computed in one call.
+pub struct SiteDashboard {
+    pub site: Site,
+    pub latest_crawl: Option<CrawlStats>,
+    /// Stats of every crawl of the site, oldest first.
+    pub crawl_trend: Vec<CrawlStats>,
+    /// Most recent queries first.
+    pub recent_queries: Vec<QuerySummary>,
+}
+
+impl SiteDashboard {
+    pub fn fetch(site_id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let site = Site::fetch(site_id, database)?;
+
+        let mut stmt = database.conn.prepare(
+            "SELECT c.id, c.started_at,
+                    (SELECT COUNT(*) FROM pages p WHERE p.crawl_id = c.id),
+                    (SELECT COUNT(*) FROM crawl_errors e WHERE e.crawl_id = c.id)
+             FROM crawls c
+             WHERE c.site_id = ?1 AND c.deleted_at IS NULL
+             ORDER BY c.id",
+        )?;
+
+        let stats_iter = stmt.query_map(params![site_id], |row| {
+            Ok(CrawlStats {
+                crawl_id: row.get(0)?,
+                started_at: row.get(1)?,
+                page_count: row.get(2)?,
+                error_count: row.get(3)?,
+            })
+        })?;
+
+        let mut crawl_trend = Vec::new();
+        for stats in stats_iter {
+            crawl_trend.push(stats?);
+        }
+
+        let mut stmt = database.conn.prepare(
+            "SELECT q.id, q.crawl_id, q.selector, q.name, COUNT(r.id), COALESCE(SUM(r.count), 0)
+             FROM queries q
+             INNER JOIN crawls c ON c.id = q.crawl_id
+             LEFT JOIN results r ON r.query_id = q.id AND r.deleted_at IS NULL
+             WHERE c.site_id = ?1 AND c.deleted_at IS NULL AND q.deleted_at IS NULL
+             GROUP BY q.id
+             ORDER BY q.id DESC
+             LIMIT ?2",
+        )?;
+
+        let query_iter = stmt.query_map(params![site_id, RECENT_QUERIES_LIMIT], |row| {
+            Ok(QuerySummary {
+                query_id: row.get(0)?,
+                crawl_id: row.get(1)?,
+                selector: row.get(2)?,
+                name: row.get(3)?,
+                matching_pages: row.get(4)?,
+                total_count: row.get(5)?,
+            })
+        })?;
+
+        let mut recent_queries = Vec::new();
+        for summary in query_iter {
+            recent_queries.push(summary?);
+        }
+
+        let latest_crawl = crawl_trend.last().map(|stats| CrawlStats {
+            crawl_id: stats.crawl_id,
+            started_at: stats.started_at.clone(),
+            page_count: stats.page_count,
+            error_count: stats.error_count,
+        });
+
+        Ok(SiteDashboard {
+            site,
+            latest_crawl,
+            crawl_trend,
+            recent_queries,
+        })
+    }
+}
@@ -1,18 +1,55 @@
+use rusqlite::functions::FunctionFlags;
 use rusqlite::{Connection, Result};
+use crate::blob;
+use crate::clock::{sql_timestamp, Clock, SystemClock};
+use crate::page::Page;
 use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
 
+/// How long a statement waits for another process to release the database
+/// before failing with `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pages whose bodies `move_bodies_to_blobs` holds in memory at a time.
+const BODY_MIGRATION_BATCH: i64 = 500;
+
+/// Tables whose rows record when they last changed and can be soft deleted.
+pub(crate) const TRACKED_TABLES: [&str; 4] = ["sites", "crawls", "queries", "results"];
+
+/// The palimp database. Several processes (GUI, CLI, daemon) may open the
+/// same file: it runs in WAL mode so readers never block the writer, writes
+/// wait up to [`BUSY_TIMEOUT`] for a competing writer, and crawls take an
+/// advisory per-site lock (see `crawl_lock`).
+///
+/// Timestamps are written with the `palimp_now()` SQL function, which reads
+/// the [`Clock`] the database was opened with.
 pub struct Database {
     pub conn: Connection,
 }
 
 impl Database {
     pub fn new(path: &str) -> Result<Database, Box<dyn Error>> {
+        Self::with_clock(path, Arc::new(SystemClock))
+    }
+
+    /// Opens a database whose timestamps come from `clock`, e.g. a
+    /// [`ManualClock`](crate::clock::ManualClock) in tests.
+    pub fn with_clock(path: &str, clock: Arc<dyn Clock>) -> Result<Database, Box<dyn Error>> {
         let conn = Connection::open(path)?;
+        conn.create_scalar_function("palimp_now", 0, FunctionFlags::SQLITE_UTF8, move |_| {
+            Ok(sql_timestamp(clock.now()))
+        })?;
 
         // Enable foreign key constraints
         conn.execute("PRAGMA foreign_keys = ON;", [])?;
 
-        Ok(Database { conn: conn })
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // In-memory databases report "memory" and stay as they are
+        conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))?;
+        conn.execute("PRAGMA synchronous = NORMAL", [])?;
+
+        Ok(Database { conn })
     }
 
     pub fn seed(&self) -> Result<(), Box<dyn Error>> {
@@ -20,7 +57,16 @@ impl Database {
             "CREATE TABLE IF NOT EXISTS sites (
                 id INTEGER PRIMARY KEY,
                 domain TEXT NOT NULL,
-                sitemap_url TEXT
+                sitemap_url TEXT,
+                insecure_tls INTEGER NOT NULL DEFAULT 0,
+                fetch_override TEXT,
+                soft_404_min_text_bytes INTEGER,
+                soft_404_phrases TEXT,
+                max_requests_per_sec REAL,
+                min_request_delay_ms INTEGER,
+                updated_at DATETIME,
+                deleted_at DATETIME,
+                deleted_batch INTEGER
             )",
             [],
         )?;
@@ -30,18 +76,42 @@ impl Database {
                 id INTEGER PRIMARY KEY,
                 site_id INTEGER NOT NULL,
                 started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                status TEXT NOT NULL DEFAULT 'completed',
+                bytes_fetched INTEGER NOT NULL DEFAULT 0,
+                pinned INTEGER NOT NULL DEFAULT 0,
+                finished_at DATETIME,
+                updated_at DATETIME,
+                deleted_at DATETIME,
+                deleted_batch INTEGER,
                 FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
             )",
             [],
         )?;
 
+        // HTML bodies keyed by content hash, shared by every page with that body
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS blobs (
+                hash TEXT PRIMARY KEY,
+                content TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS pages (
                 id INTEGER PRIMARY KEY,
                 crawl_id INTEGER NOT NULL,
                 url TEXT NOT NULL,
                 final_url TEXT NOT NULL,
-                html_content TEXT NOT NULL,
+                blob_hash TEXT REFERENCES blobs (hash),
+                headers TEXT NOT NULL DEFAULT '',
+                redirect_type TEXT,
+                redirect_target TEXT,
+                canonical_url TEXT,
+                user_agent TEXT,
+                sitemap_lastmod TEXT,
+                sitemap_changefreq TEXT,
+                sitemap_priority REAL,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
             )",
@@ -53,6 +123,11 @@ impl Database {
                 id INTEGER PRIMARY KEY,
                 crawl_id INTEGER NOT NULL,
                 selector TEXT NOT NULL,
+                name TEXT,
+                description TEXT,
+                updated_at DATETIME,
+                deleted_at DATETIME,
+                deleted_batch INTEGER,
                 FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
             )",
             [],
@@ -61,14 +136,733 @@ impl Database {
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS results (
                 id INTEGER PRIMARY KEY,
+                query_id INTEGER REFERENCES queries (id) ON DELETE CASCADE,
                 page_id INTEGER NOT NULL,
                 selector TEXT NOT NULL,
                 count INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'new',
+                updated_at DATETIME,
+                deleted_at DATETIME,
+                deleted_batch INTEGER,
+                FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Where each element a single-selector query matched sits on the page
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS result_matches (
+                result_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                text TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                PRIMARY KEY (result_id, position),
+                FOREIGN KEY (result_id) REFERENCES results (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS secret_keys (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                salt BLOB NOT NULL,
+                verifier BLOB NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS site_credentials (
+                id INTEGER PRIMARY KEY,
+                site_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                secret BLOB NOT NULL,
+                UNIQUE (site_id, name),
+                FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS crawl_errors (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                kind TEXT NOT NULL DEFAULT 'fetch',
+                message TEXT NOT NULL,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL,
+                site_id INTEGER,
+                crawl_id INTEGER,
+                selector TEXT,
+                max_concurrent INTEGER,
+                status TEXT NOT NULL DEFAULT 'queued',
+                progress_done INTEGER NOT NULL DEFAULT 0,
+                progress_total INTEGER NOT NULL DEFAULT 0,
+                message TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                started_at DATETIME,
+                finished_at DATETIME,
+                cancel_requested INTEGER NOT NULL DEFAULT 0,
+                priority INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS crawl_locks (
+                site_id INTEGER PRIMARY KEY,
+                owner TEXT NOT NULL,
+                acquired_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                heartbeat_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS crawl_frontier (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS crawl_frontier_crawl_url ON crawl_frontier (crawl_id, url)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sitemap_images (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                page_url TEXT NOT NULL,
+                image_url TEXT NOT NULL,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS findings (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                page_id INTEGER,
+                url TEXT NOT NULL,
+                audit TEXT NOT NULL,
+                rule TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE,
                 FOREIGN KEY (page_id) REFERENCES pages (id) ON DELETE CASCADE
             )",
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS monitors (
+                id INTEGER PRIMARY KEY,
+                site_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                selector TEXT,
+                threshold REAL,
+                url_pattern TEXT,
+                FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS site_rules (
+                id INTEGER PRIMARY KEY,
+                site_id INTEGER NOT NULL,
+                name TEXT,
+                selector TEXT NOT NULL,
+                count INTEGER,
+                min INTEGER,
+                max INTEGER,
+                url_pattern TEXT,
+                severity TEXT NOT NULL DEFAULT 'error',
+                FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS site_tags (
+                site_id INTEGER NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (site_id, tag),
+                FOREIGN KEY (site_id) REFERENCES sites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // How each site rule fared on each crawl of its site
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS rule_outcomes (
+                id INTEGER PRIMARY KEY,
+                rule_id INTEGER NOT NULL,
+                crawl_id INTEGER NOT NULL,
+                checked INTEGER NOT NULL,
+                violations INTEGER NOT NULL,
+                created_at DATETIME NOT NULL,
+                UNIQUE (rule_id, crawl_id),
+                FOREIGN KEY (rule_id) REFERENCES site_rules (id) ON DELETE CASCADE,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // The URLs each crawl's sitemap listed, and what changed since the
+        // site's previous crawl
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sitemap_urls (
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                lastmod TEXT,
+                changefreq TEXT,
+                priority REAL,
+                PRIMARY KEY (crawl_id, url),
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sitemap_changes (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                previous_crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Deployment details and the like, given when a crawl is started
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS crawl_meta (
+                crawl_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (crawl_id, key),
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Stylesheets, scripts and JSON documents referenced by a crawl's pages
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS assets (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                final_url TEXT NOT NULL,
+                page_url TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                content_type TEXT NOT NULL,
+                content TEXT NOT NULL,
+                UNIQUE (crawl_id, url),
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // URLs a crawl left out without requesting them
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS skipped_urls (
+                id INTEGER PRIMARY KEY,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                UNIQUE (crawl_id, url),
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Inlinks, outlinks and centrality of each page within its crawl
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS link_metrics (
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                inlinks INTEGER NOT NULL,
+                outlinks INTEGER NOT NULL,
+                score REAL NOT NULL,
+                PRIMARY KEY (crawl_id, url),
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Raised by a monitor, a site rule that started failing or the crawl
+        // itself
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS alerts (
+                id INTEGER PRIMARY KEY,
+                monitor_id INTEGER,
+                rule_id INTEGER,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                message TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (monitor_id) REFERENCES monitors (id) ON DELETE CASCADE,
+                FOREIGN KEY (rule_id) REFERENCES site_rules (id) ON DELETE CASCADE,
+                FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS suites (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS suite_checks (
+                id INTEGER PRIMARY KEY,
+                suite_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL,
+                FOREIGN KEY (suite_id) REFERENCES suites (id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Application-wide preferences, e.g. the archive quota
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Deletions and crawl starts, appended by `Application`
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY,
+                created_at DATETIME NOT NULL,
+                actor TEXT,
+                action TEXT NOT NULL,
+                target_id INTEGER,
+                detail TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Full-text index over the visible text of archived pages
+        self.conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS pages_fts USING fts5(content)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS pages_fts_delete AFTER DELETE ON pages BEGIN
+                DELETE FROM pages_fts WHERE rowid = old.id;
+            END",
+            [],
+        )?;
+
+        // Views are rebuilt at the end, so an old definition never holds
+        // back a migration of the tables below it
+        self.drop_views()?;
+
+        // Columns added after the initial schema
+        self.add_column_if_missing("pages", "headers", "TEXT NOT NULL DEFAULT ''")?;
+        self.add_column_if_missing("sites", "insecure_tls", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("sites", "fetch_override", "TEXT")?;
+        self.add_column_if_missing("sites", "soft_404_min_text_bytes", "INTEGER")?;
+        self.add_column_if_missing("sites", "soft_404_phrases", "TEXT")?;
+        self.add_column_if_missing("sites", "max_requests_per_sec", "REAL")?;
+        self.add_column_if_missing("sites", "min_request_delay_ms", "INTEGER")?;
+        self.add_column_if_missing("pages", "redirect_type", "TEXT")?;
+        self.add_column_if_missing("pages", "redirect_target", "TEXT")?;
+        self.add_column_if_missing("jobs", "cancel_requested", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("jobs", "priority", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("crawls", "status", "TEXT NOT NULL DEFAULT 'completed'")?;
+        self.add_column_if_missing("crawl_errors", "kind", "TEXT NOT NULL DEFAULT 'fetch'")?;
+        self.add_column_if_missing("crawls", "bytes_fetched", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("crawls", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        if self.add_column_if_missing("crawls", "finished_at", "DATETIME")? {
+            // Crawls done before the upgrade finished at their last page, as
+            // close as the archive can tell
+            self.conn.execute(
+                "UPDATE crawls SET finished_at = COALESCE(
+                     (SELECT MAX(created_at) FROM pages WHERE crawl_id = crawls.id), started_at)
+                 WHERE status != 'running'",
+                [],
+            )?;
+        }
+        self.add_column_if_missing("queries", "name", "TEXT")?;
+        self.add_column_if_missing("queries", "description", "TEXT")?;
+        self.add_column_if_missing("results", "status", "TEXT NOT NULL DEFAULT 'new'")?;
+        self.add_column_if_missing("pages", "user_agent", "TEXT")?;
+        self.add_column_if_missing("pages", "sitemap_lastmod", "TEXT")?;
+        self.add_column_if_missing("pages", "sitemap_changefreq", "TEXT")?;
+        self.add_column_if_missing("pages", "sitemap_priority", "REAL")?;
+        self.add_column_if_missing("sitemap_urls", "lastmod", "TEXT")?;
+        self.add_column_if_missing("sitemap_urls", "changefreq", "TEXT")?;
+        self.add_column_if_missing("sitemap_urls", "priority", "REAL")?;
+        if !self.has_column("alerts", "rule_id")? {
+            self.rebuild_alerts()?;
+        }
+        if self.add_column_if_missing("pages", "canonical_url", "TEXT")? {
+            self.backfill_canonical_urls()?;
+        }
+        if self.add_column_if_missing("pages", "blob_hash", "TEXT REFERENCES blobs (hash)")? {
+            self.move_bodies_to_blobs()?;
+        }
+
+        // Change tracking and soft deletes. Rows archived before get the
+        // time of the upgrade, crawls their start
+        for table in TRACKED_TABLES {
+            if self.add_column_if_missing(table, "updated_at", "DATETIME")? {
+                let since = if table == "crawls" { "started_at" } else { "palimp_now()" };
+                self.conn.execute(&format!("UPDATE {} SET updated_at = {}", table, since), [])?;
+            }
+            self.add_column_if_missing(table, "deleted_at", "DATETIME")?;
+            // Rows deleted before batches were kept count as deleted together
+            // when they were deleted in the same second
+            if self.add_column_if_missing(table, "deleted_batch", "INTEGER")? {
+                self.conn.execute(
+                    &format!(
+                        "UPDATE {} SET deleted_batch = CAST(strftime('%s', deleted_at) AS INTEGER)
+                         WHERE deleted_at IS NOT NULL",
+                        table
+                    ),
+                    [],
+                )?;
+            }
+            self.conn.execute_batch(&format!(
+                "CREATE TRIGGER IF NOT EXISTS {table}_insert_touch AFTER INSERT ON {table} BEGIN
+                    UPDATE {table} SET updated_at = palimp_now() WHERE id = new.id;
+                END;
+                CREATE TRIGGER IF NOT EXISTS {table}_update_touch AFTER UPDATE ON {table}
+                WHEN new.updated_at IS old.updated_at BEGIN
+                    UPDATE {table} SET updated_at = palimp_now() WHERE id = new.id;
+                END;",
+                table = table
+            ))?;
+        }
+
+        // A site is identified by its domain and sitemap, deleted ones aside
+        if !self.has_index("sites_domain_sitemap")? {
+            self.merge_duplicate_sites()?;
+        }
+
+        // Results used to be tied to their query by crawl and selector only.
+        // Link each to the latest matching query; the rest belong to deleted
+        // queries and go
+        if self.add_column_if_missing("results", "query_id", "INTEGER REFERENCES queries (id) ON DELETE CASCADE")? {
+            self.conn.execute_batch(
+                "UPDATE results SET query_id = (
+                     SELECT MAX(q.id) FROM queries q INNER JOIN pages p ON p.crawl_id = q.crawl_id
+                     WHERE p.id = results.page_id AND q.selector = results.selector
+                 );
+                 DELETE FROM results WHERE query_id IS NULL;",
+            )?;
+        }
+        self.conn.execute("CREATE INDEX IF NOT EXISTS results_query_id ON results (query_id)", [])?;
+
+        self.conn.execute("CREATE INDEX IF NOT EXISTS pages_blob_hash ON pages (blob_hash)", [])?;
+        self.conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS pages_blob_delete AFTER DELETE ON pages BEGIN
+                DELETE FROM blobs WHERE hash = old.blob_hash
+                    AND NOT EXISTS (SELECT 1 FROM pages WHERE blob_hash = old.blob_hash);
+            END",
+            [],
+        )?;
+
+        self.create_views()?;
+
+        Ok(())
+    }
+
+    /// Read-only entry points for raw SQL and external reporting tools.
+    fn create_views(&self) -> Result<(), Box<dyn Error>> {
+        // Deleted rows are left out. The most recent crawl of every site,
+        // whatever its status
+        self.conn.execute(
+            "CREATE VIEW latest_crawls AS
+             SELECT s.id AS site_id, s.domain, c.id AS crawl_id, c.started_at, c.status, c.bytes_fetched,
+                    (SELECT COUNT(*) FROM pages p WHERE p.crawl_id = c.id) AS pages,
+                    (SELECT COUNT(*) FROM crawl_errors e WHERE e.crawl_id = c.id) AS errors
+             FROM sites s
+             INNER JOIN crawls c ON c.id = (SELECT MAX(id) FROM crawls WHERE site_id = s.id AND deleted_at IS NULL)
+             WHERE s.deleted_at IS NULL",
+            [],
+        )?;
+
+        // Pages matched and matches of every stored query
+        self.conn.execute(
+            "CREATE VIEW query_totals AS
+             SELECT q.id AS query_id, c.site_id, q.crawl_id, q.selector, q.name,
+                    COUNT(r.id) AS matching_pages, COALESCE(SUM(r.count), 0) AS matches
+             FROM queries q
+             INNER JOIN crawls c ON c.id = q.crawl_id
+             LEFT JOIN results r ON r.query_id = q.id AND r.deleted_at IS NULL
+             WHERE q.deleted_at IS NULL AND c.deleted_at IS NULL
+             GROUP BY q.id",
+            [],
+        )?;
+
+        // The last archived version of every URL of a site
+        self.conn.execute(
+            "CREATE VIEW latest_pages AS
+             SELECT site_id, url, final_url, page_id, crawl_id, archived_at, blob_hash,
+                    LENGTH(CAST(b.content AS BLOB)) AS bytes, redirect_type, redirect_target, canonical_url
+             FROM (
+                 SELECT c.site_id, p.url, p.final_url, p.id AS page_id, p.crawl_id, p.created_at AS archived_at,
+                        p.blob_hash, p.redirect_type, p.redirect_target, p.canonical_url,
+                        ROW_NUMBER() OVER (PARTITION BY c.site_id, p.url ORDER BY p.id DESC) AS version
+                 FROM pages p
+                 INNER JOIN crawls c ON c.id = p.crawl_id
+                 WHERE c.deleted_at IS NULL
+             )
+             INNER JOIN blobs b ON b.hash = blob_hash
+             WHERE version = 1",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    fn drop_views(&self) -> Result<(), Box<dyn Error>> {
+        self.conn.execute_batch(
+            "DROP VIEW IF EXISTS latest_crawls;
+             DROP VIEW IF EXISTS query_totals;
+             DROP VIEW IF EXISTS latest_pages;",
+        )?;
+        Ok(())
+    }
+
+    pub fn setting(&self, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+        let mut rows = stmt.query_map([key], |row| row.get(0))?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Stores a setting, or removes it when `value` is `None`.
+    pub fn set_setting(&self, key: &str, value: Option<&str>) -> Result<(), Box<dyn Error>> {
+        match value {
+            Some(value) => self.conn.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                [key, value],
+            )?,
+            None => self.conn.execute("DELETE FROM settings WHERE key = ?1", [key])?,
+        };
+        Ok(())
+    }
+
+    /// Runs `f` in a transaction, committing when it succeeds and rolling
+    /// back every write it made when it fails. The write lock is taken up
+    /// front so a concurrent writer can't make the transaction fail halfway.
+    pub fn in_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Database) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(err) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(err)
+            }
+        }
+    }
+
+    /// Adds a column to an existing table unless it is already there, so
+    /// databases created by older versions pick up new columns. Returns
+    /// whether the column was added.
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<bool, Box<dyn Error>> {
+        let exists = self.has_column(table, column)?;
+
+        if !exists {
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
+
+        Ok(!exists)
+    }
+
+    /// Moves the bodies of pages archived inline into the blob store, one
+    /// copy per distinct body, and drops the old column. Bodies are read a
+    /// batch at a time so a large archive never sits in memory at once.
+    fn move_bodies_to_blobs(&self) -> Result<(), Box<dyn Error>> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let moved = (|| -> Result<(), Box<dyn Error>> {
+            let mut last_id = 0;
+            loop {
+                let pages = {
+                    let mut stmt = self
+                        .conn
+                        .prepare("SELECT id, html_content FROM pages WHERE id > ?1 ORDER BY id LIMIT ?2")?;
+                    stmt.query_map(rusqlite::params![last_id, BODY_MIGRATION_BATCH], |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?
+                };
+                let Some(&(last, _)) = pages.last() else { break };
+                for (id, html) in pages {
+                    let hash = blob::store(&html, self)?;
+                    self.conn.execute("UPDATE pages SET blob_hash = ?1 WHERE id = ?2", rusqlite::params![hash, id])?;
+                }
+                last_id = last;
+            }
+            self.conn.execute("ALTER TABLE pages DROP COLUMN html_content", [])?;
+            Ok(())
+        })();
+
+        match moved {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(err) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(err)
+            }
+        }
+    }
+
+    fn has_column(&self, table: &str, column: &str) -> Result<bool, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|name| name.ok())
+            .any(|name| name == column);
+        Ok(exists)
+    }
+
+    fn has_index(&self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1",
+            [name],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Folds sites created more than once into the first of them, crawls,
+    /// monitors and jobs included, then makes domain and sitemap unique.
+    fn merge_duplicate_sites(&self) -> Result<(), Box<dyn Error>> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let merged = self.conn.execute_batch(
+            "CREATE TEMP TABLE site_merges AS
+                 SELECT s.id AS duplicate,
+                        (SELECT MIN(k.id) FROM sites k
+                         WHERE k.domain = s.domain AND k.sitemap_url = s.sitemap_url AND k.deleted_at IS NULL) AS keeper
+                 FROM sites s WHERE s.deleted_at IS NULL;
+             DELETE FROM site_merges WHERE duplicate = keeper;
+             UPDATE crawls SET site_id = (SELECT keeper FROM site_merges WHERE duplicate = crawls.site_id)
+             WHERE site_id IN (SELECT duplicate FROM site_merges);
+             UPDATE monitors SET site_id = (SELECT keeper FROM site_merges WHERE duplicate = monitors.site_id)
+             WHERE site_id IN (SELECT duplicate FROM site_merges);
+             UPDATE jobs SET site_id = (SELECT keeper FROM site_merges WHERE duplicate = jobs.site_id)
+             WHERE site_id IN (SELECT duplicate FROM site_merges);
+             UPDATE OR IGNORE site_credentials
+             SET site_id = (SELECT keeper FROM site_merges WHERE duplicate = site_credentials.site_id)
+             WHERE site_id IN (SELECT duplicate FROM site_merges);
+             DELETE FROM sites WHERE id IN (SELECT duplicate FROM site_merges);
+             DROP TABLE site_merges;
+             CREATE UNIQUE INDEX sites_domain_sitemap ON sites (domain, sitemap_url) WHERE deleted_at IS NULL;",
+        );
+
+        match merged {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(err) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Alerts used to come from monitors only. SQLite can't drop the NOT NULL
+    /// of `monitor_id`, so the table is copied into one that also takes
+    /// alerts of site rules.
+    fn rebuild_alerts(&self) -> Result<(), Box<dyn Error>> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let rebuilt = self.conn.execute_batch(
+            "CREATE TABLE alerts_rebuilt (
+                 id INTEGER PRIMARY KEY,
+                 monitor_id INTEGER,
+                 rule_id INTEGER,
+                 crawl_id INTEGER NOT NULL,
+                 url TEXT NOT NULL,
+                 message TEXT NOT NULL,
+                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                 FOREIGN KEY (monitor_id) REFERENCES monitors (id) ON DELETE CASCADE,
+                 FOREIGN KEY (rule_id) REFERENCES site_rules (id) ON DELETE CASCADE,
+                 FOREIGN KEY (crawl_id) REFERENCES crawls (id) ON DELETE CASCADE
+             );
+             INSERT INTO alerts_rebuilt (id, monitor_id, crawl_id, url, message, created_at)
+                 SELECT id, monitor_id, crawl_id, url, message, created_at FROM alerts;
+             DROP TABLE alerts;
+             ALTER TABLE alerts_rebuilt RENAME TO alerts;",
+        );
+
+        match rebuilt {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(err) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Extracts canonical links of pages archived before the column existed.
+    fn backfill_canonical_urls(&self) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare("SELECT id, url, final_url, html_content FROM pages")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+
+        for row in rows {
+            let (id, url, final_url, html) = row?;
+            let canonical = Page::new(&url, &final_url, &html, None)
+                .ok()
+                .and_then(|page| page.canonical_url());
+            if let Some(canonical) = canonical {
+                self.conn.execute(
+                    "UPDATE pages SET canonical_url = ?1 WHERE id = ?2",
+                    rusqlite::params![canonical, id],
+                )?;
+            }
+        }
+
         Ok(())
     }
 }
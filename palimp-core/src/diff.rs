@@ -0,0 +1,308 @@
+use crate::page::{tag_name, Page};
+use tl::{Node, NodeHandle, Parser};
+
+/// Above this many LCS table cells the changed middle section is reported as
+/// a single replacement instead of being diffed line by line.
+const MAX_LCS_CELLS: usize = 25_000_000;
+
+/// Elements whose content is not part of the page structure worth diffing.
+const SKIPPED_ELEMENTS: [&str; 3] = ["script", "style", "noscript"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffOp<T> {
+    Equal(T),
+    Removed(T),
+    Added(T),
+}
+
+/// One line of a linearized DOM: the element path it belongs to and either
+/// the element's opening tag or a text node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomLine {
+    pub path: String,
+    pub content: String,
+}
+
+/// A contiguous block of changes between two versions of a page.
+#[derive(Debug)]
+pub struct ChangedRegion {
+    pub path: String,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+/// Computes a minimal line diff (longest common subsequence) of two sequences.
+pub fn diff<T: PartialEq + Clone>(before: &[T], after: &[T]) -> Vec<DiffOp<T>> {
+    let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &before[prefix..before.len() - suffix];
+    let b = &after[prefix..after.len() - suffix];
+
+    let mut ops: Vec<DiffOp<T>> = before[..prefix].iter().cloned().map(DiffOp::Equal).collect();
+
+    if a.len() * b.len() > MAX_LCS_CELLS {
+        ops.extend(a.iter().cloned().map(DiffOp::Removed));
+        ops.extend(b.iter().cloned().map(DiffOp::Added));
+    } else {
+        let (n, m) = (a.len(), b.len());
+        let width = m + 1;
+        let mut table = vec![0u32; (n + 1) * width];
+
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                table[i * width + j] = if a[i] == b[j] {
+                    table[(i + 1) * width + j + 1] + 1
+                } else {
+                    table[(i + 1) * width + j].max(table[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if a[i] == b[j] {
+                ops.push(DiffOp::Equal(a[i].clone()));
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+                ops.push(DiffOp::Removed(a[i].clone()));
+                i += 1;
+            } else {
+                ops.push(DiffOp::Added(b[j].clone()));
+                j += 1;
+            }
+        }
+        ops.extend(a[i..].iter().cloned().map(DiffOp::Removed));
+        ops.extend(b[j..].iter().cloned().map(DiffOp::Added));
+    }
+
+    ops.extend(before[before.len() - suffix..].iter().cloned().map(DiffOp::Equal));
+    ops
+}
+
+/// Linearizes the DOM of a page into element and text lines, each tagged
+/// with its element path (e.g. `html > body > div#main > p`).
+pub fn dom_outline(page: &Page) -> Vec<DomLine> {
+    let parser = page.dom.parser();
+    let mut lines = Vec::new();
+
+    for handle in page.dom.children() {
+        walk(*handle, parser, "", &mut lines);
+    }
+
+    lines
+}
+
+fn walk(handle: NodeHandle, parser: &Parser, path: &str, lines: &mut Vec<DomLine>) {
+    match handle.get(parser) {
+        Some(Node::Tag(tag)) => {
+            let name = tag_name(tag);
+            if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                return;
+            }
+
+            let mut segment = name.clone();
+            if let Some(id) = tag.attributes().id() {
+                segment.push('#');
+                segment.push_str(&id.as_utf8_str());
+            }
+            let path = if path.is_empty() {
+                segment
+            } else {
+                format!("{} > {}", path, segment)
+            };
+
+            let mut attributes: Vec<String> = tag
+                .attributes()
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => format!("{}=\"{}\"", key, value),
+                    None => key.to_string(),
+                })
+                .collect();
+            attributes.sort();
+            let opening = if attributes.is_empty() {
+                format!("<{}>", name)
+            } else {
+                format!("<{} {}>", name, attributes.join(" "))
+            };
+
+            lines.push(DomLine {
+                path: path.clone(),
+                content: opening,
+            });
+
+            for child in tag.children().top().iter() {
+                walk(*child, parser, &path, lines);
+            }
+        }
+        Some(Node::Raw(text)) => {
+            let text = text.as_utf8_str().split_whitespace().collect::<Vec<_>>().join(" ");
+            if !text.is_empty() {
+                lines.push(DomLine {
+                    path: path.to_string(),
+                    content: text,
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Groups consecutive non-equal operations into changed regions.
+pub fn changed_regions(ops: &[DiffOp<DomLine>]) -> Vec<ChangedRegion> {
+    let mut regions = Vec::new();
+    let mut current: Option<ChangedRegion> = None;
+
+    for op in ops {
+        match op {
+            DiffOp::Equal(_) => {
+                if let Some(region) = current.take() {
+                    regions.push(region);
+                }
+            }
+            DiffOp::Removed(line) | DiffOp::Added(line) => {
+                let region = current.get_or_insert_with(|| ChangedRegion {
+                    path: line.path.clone(),
+                    removed: Vec::new(),
+                    added: Vec::new(),
+                });
+                if matches!(op, DiffOp::Removed(_)) {
+                    region.removed.push(line.content.clone());
+                } else {
+                    region.added.push(line.content.clone());
+                }
+            }
+        }
+    }
+
+    if let Some(region) = current {
+        regions.push(region);
+    }
+
+    regions
+}
+
+/// Renders a unified diff (`---`/`+++` headers and `@@` hunks with
+/// `context` lines around each change). Returns an empty string when both
+/// sides are identical.
+pub fn unified_diff(
+    before: &[String],
+    after: &[String],
+    before_label: &str,
+    after_label: &str,
+    context: usize,
+) -> String {
+    let ops = diff(before, after);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for index in changes {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end.max(last.1),
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    // Number of old/new lines preceding each operation
+    let mut old_pos = vec![0; ops.len() + 1];
+    let mut new_pos = vec![0; ops.len() + 1];
+    for (i, op) in ops.iter().enumerate() {
+        old_pos[i + 1] = old_pos[i] + usize::from(!matches!(op, DiffOp::Added(_)));
+        new_pos[i + 1] = new_pos[i] + usize::from(!matches!(op, DiffOp::Removed(_)));
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", before_label, after_label);
+
+    for (start, end) in hunks {
+        let old_count = old_pos[end] - old_pos[start];
+        let new_count = new_pos[end] - new_pos[start];
+        let old_start = if old_count == 0 { old_pos[start] } else { old_pos[start] + 1 };
+        let new_start = if new_count == 0 { new_pos[start] } else { new_pos[start] + 1 };
+
+        output.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_count, new_start, new_count));
+
+        for op in &ops[start..end] {
+            let (marker, line) = match op {
+                DiffOp::Equal(line) => (' ', line),
+                DiffOp::Removed(line) => ('-', line),
+                DiffOp::Added(line) => ('+', line),
+            };
+            output.push(marker);
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+/// Structural diff of two versions of a page.
+pub fn dom_diff(before: &Page, after: &Page) -> Vec<ChangedRegion> {
+    changed_regions(&diff(&dom_outline(before), &dom_outline(after)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff() {
+        let before = vec!["a", "b", "c", "d"];
+        let after = vec!["a", "c", "x", "d"];
+
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                DiffOp::Equal("a"),
+                DiffOp::Removed("b"),
+                DiffOp::Equal("c"),
+                DiffOp::Added("x"),
+                DiffOp::Equal("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let before: Vec<String> = ["a", "b", "c", "d", "e", "f", "g"].iter().map(|s| s.to_string()).collect();
+        let after: Vec<String> = ["a", "b", "c", "D", "e", "f", "g"].iter().map(|s| s.to_string()).collect();
+
+        let output = unified_diff(&before, &after, "crawl 1", "crawl 2", 1);
+
+        assert_eq!(output, "--- crawl 1\n+++ crawl 2\n@@ -3,3 +3,3 @@\n c\n-d\n+D\n e\n");
+        assert_eq!(unified_diff(&before, &before, "a", "b", 3), "");
+    }
+
+    #[test]
+    fn test_dom_diff_reports_changed_regions() {
+        let before_html = r#"<html><body><div id="main"><h1>Prices</h1><p class="price">10 EUR</p></div></body></html>"#;
+        let after_html = r#"<html><body><div id="main"><h1>Prices</h1><p class="price">12 EUR</p><p>New</p></div></body></html>"#;
+        let before = Page::new("http://test.com", "http://test.com", before_html, None).unwrap();
+        let after = Page::new("http://test.com", "http://test.com", after_html, None).unwrap();
+
+        let regions = dom_diff(&before, &after);
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].path, "html > body > div#main > p");
+        assert_eq!(regions[0].removed, vec!["10 EUR"]);
+        assert_eq!(regions[0].added, vec!["12 EUR", "<p>", "New"]);
+    }
+}
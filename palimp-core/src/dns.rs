@@ -0,0 +1,107 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+type LookupCache = Arc<Mutex<HashMap<String, Vec<SocketAddr>>>>;
+
+/// Which IP versions outbound connections may use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    #[default]
+    Any,
+    PreferIpv4,
+    PreferIpv6,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl AddressFamily {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(AddressFamily::Any),
+            "prefer-ipv4" => Some(AddressFamily::PreferIpv4),
+            "prefer-ipv6" => Some(AddressFamily::PreferIpv6),
+            "ipv4" => Some(AddressFamily::Ipv4Only),
+            "ipv6" => Some(AddressFamily::Ipv6Only),
+            _ => None,
+        }
+    }
+
+    /// Filters and orders resolved addresses. The connector tries the family
+    /// of the first address before falling back to the other one.
+    pub fn apply(&self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self {
+            AddressFamily::Any => {}
+            AddressFamily::PreferIpv4 => addrs.sort_by_key(|addr| !addr.is_ipv4()),
+            AddressFamily::PreferIpv6 => addrs.sort_by_key(|addr| !addr.is_ipv6()),
+            AddressFamily::Ipv4Only => addrs.retain(|addr| addr.is_ipv4()),
+            AddressFamily::Ipv6Only => addrs.retain(|addr| addr.is_ipv6()),
+        }
+        addrs
+    }
+}
+
+/// System resolver with an optional lookup cache kept for the lifetime of
+/// the client (so large crawls don't resolve each connection again) and an
+/// address family policy.
+#[derive(Default)]
+pub struct Resolver {
+    cache: Option<LookupCache>,
+    family: AddressFamily,
+}
+
+impl Resolver {
+    pub fn new(cache: bool, family: AddressFamily) -> Self {
+        Self {
+            cache: cache.then(|| Arc::new(Mutex::new(HashMap::new()))),
+            family,
+        }
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let family = self.family;
+
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.lock().unwrap().get(&host).cloned());
+        if let Some(addrs) = cached {
+            return Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) });
+        }
+
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addrs = family.apply(resolved);
+            if addrs.is_empty() {
+                return Err(format!("No {:?} address found for {}", family, host).into());
+            }
+
+            if let Some(cache) = cache {
+                cache.lock().unwrap().insert(host, addrs.clone());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_family_orders_and_filters() {
+        let v4: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let v6: SocketAddr = "[::1]:0".parse().unwrap();
+
+        assert_eq!(AddressFamily::PreferIpv4.apply(vec![v6, v4]), vec![v4, v6]);
+        assert_eq!(AddressFamily::PreferIpv6.apply(vec![v4, v6]), vec![v6, v4]);
+        assert_eq!(AddressFamily::Ipv4Only.apply(vec![v6, v4]), vec![v4]);
+        assert_eq!(AddressFamily::Ipv6Only.apply(vec![v4]), Vec::<SocketAddr>::new());
+        assert_eq!(AddressFamily::Any.apply(vec![v6, v4]), vec![v6, v4]);
+    }
+}
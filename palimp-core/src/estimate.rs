@@ -0,0 +1,177 @@
+use crate::crawl::CrawlOptions;
+use crate::sitemap::{SitemapReader, SitemapStream};
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+/// Page size assumed for a site that was never crawled.
+pub const DEFAULT_PAGE_BYTES: u64 = 100 * 1024;
+
+/// What a sitemap crawl of a site would cost, worked out from its sitemap
+/// before any page is fetched. [`CrawlEstimate::duration`] projects it for
+/// a given concurrency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrawlEstimate {
+    /// URLs the crawl would fetch.
+    pub urls: usize,
+    /// Sitemap entries that are not http(s) URLs. They are recorded as
+    /// failures without a request.
+    pub invalid_urls: usize,
+    /// URLs listed more than once, each fetched again.
+    pub duplicate_urls: usize,
+    pub hosts: usize,
+    /// URLs on the host with the most of them, which bounds the duration
+    /// when pages are capped per host.
+    pub largest_host_urls: usize,
+    pub sitemap_bytes: u64,
+    /// Sitemaps fetched, more than one behind a sitemap index.
+    pub sitemap_requests: usize,
+    /// Average page size, from the site's last crawl when there is one.
+    pub page_bytes: u64,
+    /// The crawl `page_bytes` was measured on.
+    pub measured_crawl_id: Option<i64>,
+    /// Time one page is expected to take, measured on the sitemap request.
+    pub page_latency: Duration,
+}
+
+impl CrawlEstimate {
+    /// Counts the URLs of `sitemap_content`; page sizes and latency are
+    /// filled in by the caller.
+    pub fn from_sitemap(sitemap_content: &str) -> Result<Self, Box<dyn Error>> {
+        let mut tally = UrlTally::default();
+        for entry in SitemapReader::from_content(sitemap_content) {
+            tally.add(entry?.loc);
+        }
+        Ok(tally.estimate(sitemap_content.len() as u64, 1))
+    }
+
+    /// Counts the URLs of a sitemap, or of every sitemap reached from a
+    /// sitemap index, as they are read.
+    pub async fn from_stream(mut sitemap: SitemapStream<'_>) -> Result<Self, Box<dyn Error>> {
+        let mut tally = UrlTally::default();
+        while let Some(entry) = sitemap.next().await? {
+            tally.add(entry.loc);
+        }
+        Ok(tally.estimate(sitemap.bytes, sitemap.requests))
+    }
+
+    /// Requests the crawl would send: the sitemaps and every valid URL.
+    pub fn requests(&self) -> usize {
+        self.urls + self.sitemap_requests
+    }
+
+    /// Projected crawl time with `options`, limited either by the overall
+    /// concurrency or by the busiest host's cap.
+    pub fn duration(&self, options: &CrawlOptions) -> Duration {
+        let overall = self.urls.div_ceil(options.max_concurrent.max(1));
+        let busiest_host = self.largest_host_urls.div_ceil(options.per_host_limit());
+        self.page_latency * overall.max(busiest_host) as u32
+    }
+
+    /// Projected bytes downloaded, sitemap included.
+    pub fn bandwidth(&self) -> u64 {
+        self.sitemap_bytes + self.urls as u64 * self.page_bytes
+    }
+}
+
+/// The sitemap URLs counted so far.
+#[derive(Default)]
+struct UrlTally {
+    seen: HashMap<String, usize>,
+    per_host: HashMap<String, usize>,
+    urls: usize,
+    invalid_urls: usize,
+}
+
+impl UrlTally {
+    fn add(&mut self, loc: String) {
+        let host = reqwest::Url::parse(loc.trim())
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .and_then(|url| url.host_str().map(str::to_string));
+        let Some(host) = host else {
+            self.invalid_urls += 1;
+            return;
+        };
+
+        *self.seen.entry(loc).or_insert(0) += 1;
+        *self.per_host.entry(host).or_default() += 1;
+        self.urls += 1;
+    }
+
+    fn estimate(self, sitemap_bytes: u64, sitemap_requests: usize) -> CrawlEstimate {
+        CrawlEstimate {
+            urls: self.urls,
+            invalid_urls: self.invalid_urls,
+            duplicate_urls: self.seen.values().map(|count| count - 1).sum(),
+            hosts: self.per_host.len(),
+            largest_host_urls: self.per_host.values().copied().max().unwrap_or(0),
+            sitemap_bytes,
+            sitemap_requests,
+            page_bytes: DEFAULT_PAGE_BYTES,
+            measured_crawl_id: None,
+            page_latency: Duration::ZERO,
+        }
+    }
+}
+
+/// Formats a byte count for display, e.g. `1.5 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formats a duration for display, e.g. `1h 02m` or `45s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..60 => format!("{}s", seconds.max(1)),
+        60..3_600 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3_600, seconds % 3_600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_from_sitemap() {
+        let sitemap = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://a.com/</loc></url>
+                <url><loc>https://a.com/pricing</loc></url>
+                <url><loc>https://a.com/pricing</loc></url>
+                <url><loc>https://cdn.a.com/docs</loc></url>
+                <url><loc>mailto:team@a.com</loc></url>
+            </urlset>"#;
+        let mut estimate = CrawlEstimate::from_sitemap(sitemap).unwrap();
+        assert_eq!(estimate.urls, 4);
+        assert_eq!(estimate.invalid_urls, 1);
+        assert_eq!(estimate.duplicate_urls, 1);
+        assert_eq!(estimate.hosts, 2);
+        assert_eq!(estimate.largest_host_urls, 3);
+        assert_eq!(estimate.requests(), 5);
+
+        estimate.page_latency = Duration::from_millis(500);
+        estimate.page_bytes = 1_000;
+        // Four at once would take one round, but a.com only takes two at a time
+        let options = CrawlOptions::new(4).with_max_per_host(2);
+        assert_eq!(estimate.duration(&options), Duration::from_secs(1));
+        assert_eq!(estimate.duration(&CrawlOptions::new(1)), Duration::from_secs(2));
+        assert_eq!(estimate.bandwidth(), sitemap.len() as u64 + 4_000);
+
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1_572_864), "1.5 MB");
+        assert_eq!(format_duration(Duration::from_secs(3_720)), "1h 02m");
+    }
+}
@@ -0,0 +1,343 @@
+use crate::crawl::Crawl;
+use crate::database::Database;
+use crate::finding::Finding;
+use crate::page_archive::PageArchive;
+use crate::preview::{collapse_whitespace, shorten};
+use crate::query::Query;
+use crate::result_entry::{ResultEntry, ResultFilter};
+use crate::site::Site;
+use std::error::Error;
+use std::io::Write;
+
+/// Environment variable with the default columns, e.g. `url,count,text`.
+pub const COLUMNS_ENV: &str = "PALIMP_EXPORT_COLUMNS";
+
+/// Environment variable with the default delimiter, e.g. `;` or `tab`.
+pub const DELIMITER_ENV: &str = "PALIMP_EXPORT_DELIMITER";
+
+/// Longest snippet written, in characters.
+const SNIPPET_MAX_CHARS: usize = 200;
+
+/// A column of a query results export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportColumn {
+    Id,
+    Site,
+    CrawlStartedAt,
+    Selector,
+    Url,
+    FinalUrl,
+    Count,
+    /// HTML of the first match, shortened.
+    Snippet,
+    /// Text of every match, separated by ` | `.
+    Text,
+    /// `<lastmod>` of the page's sitemap entry.
+    Lastmod,
+    Changefreq,
+    Priority,
+    /// Review status of the result, e.g. `confirmed` or `ignored`.
+    Status,
+}
+
+impl ExportColumn {
+    pub const ALL: [ExportColumn; 13] = [
+        ExportColumn::Id,
+        ExportColumn::Site,
+        ExportColumn::CrawlStartedAt,
+        ExportColumn::Selector,
+        ExportColumn::Url,
+        ExportColumn::FinalUrl,
+        ExportColumn::Count,
+        ExportColumn::Snippet,
+        ExportColumn::Text,
+        ExportColumn::Lastmod,
+        ExportColumn::Changefreq,
+        ExportColumn::Priority,
+        ExportColumn::Status,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportColumn::Id => "id",
+            ExportColumn::Site => "site",
+            ExportColumn::CrawlStartedAt => "crawl-started-at",
+            ExportColumn::Selector => "selector",
+            ExportColumn::Url => "url",
+            ExportColumn::FinalUrl => "final-url",
+            ExportColumn::Count => "count",
+            ExportColumn::Snippet => "snippet",
+            ExportColumn::Text => "text",
+            ExportColumn::Lastmod => "lastmod",
+            ExportColumn::Changefreq => "changefreq",
+            ExportColumn::Priority => "priority",
+            ExportColumn::Status => "status",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|column| column.name() == name)
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            ExportColumn::Id => "ID",
+            ExportColumn::Site => "Site",
+            ExportColumn::CrawlStartedAt => "Crawl Started At",
+            ExportColumn::Selector => "Selector",
+            ExportColumn::Url => "Page URL",
+            ExportColumn::FinalUrl => "Final URL",
+            ExportColumn::Count => "Count",
+            ExportColumn::Snippet => "Snippet",
+            ExportColumn::Text => "Text",
+            ExportColumn::Lastmod => "Last Modified",
+            ExportColumn::Changefreq => "Change Frequency",
+            ExportColumn::Priority => "Priority",
+            ExportColumn::Status => "Status",
+        }
+    }
+}
+
+/// Columns and delimiter of a query results export. The default matches the
+/// original ID, URL and count export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub columns: Vec<ExportColumn>,
+    pub delimiter: u8,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            columns: vec![ExportColumn::Id, ExportColumn::Url, ExportColumn::Count],
+            delimiter: b',',
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Defaults overridden by the `PALIMP_EXPORT_*` environment variables.
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let mut options = Self::default();
+        if let Ok(columns) = std::env::var(COLUMNS_ENV) {
+            options.columns = parse_columns(&columns)?;
+        }
+        if let Ok(delimiter) = std::env::var(DELIMITER_ENV) {
+            options.delimiter = parse_delimiter(&delimiter)?;
+        }
+        Ok(options)
+    }
+
+    fn needs_page(&self) -> bool {
+        self.columns
+            .iter()
+            .any(|column| matches!(column, ExportColumn::FinalUrl | ExportColumn::Snippet | ExportColumn::Text))
+    }
+}
+
+/// Parses a comma-separated list of column names.
+pub fn parse_columns(list: &str) -> Result<Vec<ExportColumn>, Box<dyn Error>> {
+    let columns = list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            ExportColumn::from_name(name).ok_or_else(|| {
+                let known: Vec<&str> = ExportColumn::ALL.iter().map(|column| column.name()).collect();
+                format!("Unknown column '{}'. Available: {}", name, known.join(", "))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if columns.is_empty() {
+        return Err("No export columns given".into());
+    }
+    Ok(columns)
+}
+
+/// Parses a single-byte delimiter; `tab` and `\t` stand for a tab.
+pub fn parse_delimiter(value: &str) -> Result<u8, Box<dyn Error>> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if value.len() == 1 => Ok(value.as_bytes()[0]),
+        _ => Err(format!("Delimiter must be a single character or 'tab', got '{}'", value).into()),
+    }
+}
+
+/// Writes the stored results of a query as CSV and returns the number of rows
+/// written.
+pub fn write_results(
+    query_id: i64,
+    options: &ExportOptions,
+    database: &Database,
+    writer: impl Write,
+) -> Result<usize, Box<dyn Error>> {
+    let table = result_table(query_id, options, database)?;
+
+    let mut wtr = csv::WriterBuilder::new().delimiter(options.delimiter).from_writer(writer);
+    for record in &table {
+        wtr.write_record(record)?;
+    }
+
+    wtr.flush()?;
+    Ok(table.len() - 1)
+}
+
+/// The stored results of a query as rows of the chosen columns, the header
+/// row first.
+pub fn result_table(query_id: i64, options: &ExportOptions, database: &Database) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let query = Query::fetch(query_id, database)?;
+    let selector = query.selector.as_str();
+    let crawl = Crawl::fetch(query.crawl_id, database)?;
+    let site = Site::fetch(crawl.site_id, database)?;
+    let results = ResultEntry::search(&ResultFilter::for_query(query_id), database)?;
+
+    let mut table = vec![options.columns.iter().map(|column| column.header().to_string()).collect()];
+    for (result, _) in &results {
+        let archive = PageArchive::fetch(result.page_id, database)?;
+        let matches = if options.needs_page() {
+            Matches::extract(&archive, selector)
+        } else {
+            Matches::default()
+        };
+
+        let record: Vec<String> = options
+            .columns
+            .iter()
+            .map(|column| match column {
+                ExportColumn::Id => result.id.unwrap_or(0).to_string(),
+                ExportColumn::Site => site.domain.clone(),
+                ExportColumn::CrawlStartedAt => crawl.started_at.clone().unwrap_or_default(),
+                ExportColumn::Selector => selector.to_string(),
+                ExportColumn::Url => archive.url.clone(),
+                ExportColumn::FinalUrl => archive.final_url.clone(),
+                ExportColumn::Count => result.count.to_string(),
+                ExportColumn::Snippet => matches.snippet.clone(),
+                ExportColumn::Text => matches.text.clone(),
+                ExportColumn::Lastmod => archive.sitemap_lastmod.clone().unwrap_or_default(),
+                ExportColumn::Changefreq => archive.sitemap_changefreq.clone().unwrap_or_default(),
+                ExportColumn::Priority => archive.sitemap_priority.map(|priority| priority.to_string()).unwrap_or_default(),
+                ExportColumn::Status => result.status.name().to_string(),
+            })
+            .collect();
+        table.push(record);
+    }
+    Ok(table)
+}
+
+/// Audit findings as rows, the header row first.
+pub fn finding_table(findings: &[Finding]) -> Vec<Vec<String>> {
+    let mut table = vec![["ID", "Page URL", "Audit", "Rule", "Detail"].map(String::from).to_vec()];
+    for finding in findings {
+        table.push(vec![
+            finding.id.unwrap_or(0).to_string(),
+            finding.url.clone(),
+            finding.audit.clone(),
+            finding.rule.clone(),
+            finding.detail.clone(),
+        ]);
+    }
+    table
+}
+
+#[derive(Default)]
+struct Matches {
+    snippet: String,
+    text: String,
+}
+
+impl Matches {
+    fn extract(archive: &PageArchive, selector: &str) -> Self {
+        let Ok(page) = archive.to_page() else {
+            return Self::default();
+        };
+        let parser = page.dom.parser();
+        let Some(nodes) = page.dom.query_selector(selector) else {
+            return Self::default();
+        };
+
+        let nodes: Vec<_> = nodes.filter_map(|handle| handle.get(parser)).collect();
+
+        let snippet = nodes.first().map(|node| collapse_whitespace(&node.outer_html(parser))).unwrap_or_default();
+        let snippet = shorten(snippet, SNIPPET_MAX_CHARS);
+
+        let text = nodes
+            .iter()
+            .map(|node| collapse_whitespace(&node.inner_text(parser)))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        Self { snippet, text }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+    use crate::sitemap::SitemapUrl;
+    use crate::sitemap_change::record_sitemap_url;
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            parse_columns("url, count,text").unwrap(),
+            vec![ExportColumn::Url, ExportColumn::Count, ExportColumn::Text]
+        );
+        assert!(parse_columns("url,price").is_err());
+        assert!(parse_columns(" , ").is_err());
+        assert_eq!(parse_delimiter(";").unwrap(), b';');
+        assert_eq!(parse_delimiter("tab").unwrap(), b'\t');
+        assert!(parse_delimiter("::").is_err());
+    }
+
+    #[test]
+    fn test_write_results_with_columns() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute("INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '')", [])
+            .unwrap();
+        let mut crawl = Crawl::new(None, 1);
+        crawl.sync(&mut db).unwrap();
+        let entry = SitemapUrl {
+            loc: "https://a.com/p".to_string(),
+            lastmod: Some("2024-05-01".to_string()),
+            priority: Some(0.8),
+            ..SitemapUrl::default()
+        };
+        record_sitemap_url(crawl.id.unwrap(), &entry, &db).unwrap();
+        Page::new(
+            "https://a.com/p",
+            "https://a.com/p/",
+            "<ul><li class=\"x\">One</li><li class=\"x\"> Two\n apples </li></ul>",
+            crawl.id,
+        )
+        .unwrap()
+        .sync(&mut db)
+        .unwrap();
+        let page_id = PageArchive::fetch_by_crawl_id(crawl.id.unwrap(), &db).unwrap()[0].id;
+        let mut query = Query::new(None, crawl.id.unwrap(), ".x");
+        query.sync(&mut db).unwrap();
+        ResultEntry::new(None, query.id.unwrap(), page_id, ".x", 2).sync(&mut db).unwrap();
+
+        let options = ExportOptions {
+            columns: parse_columns("site,final-url,count,snippet,text,lastmod,priority,status").unwrap(),
+            delimiter: b';',
+        };
+        let mut output = Vec::new();
+        let rows = write_results(query.id.unwrap(), &options, &db, &mut output).unwrap();
+
+        assert_eq!(rows, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Site;Final URL;Count;Snippet;Text;Last Modified;Priority;Status\n\
+             a.com;https://a.com/p/;2;\"<li class=\"\"x\"\">One</li>\";One | Two apples;2024-05-01;0.8;new\n"
+        );
+
+        let since = |date: &str| ResultFilter { modified_since: Some(date.to_string()), ..ResultFilter::default() };
+        assert_eq!(ResultEntry::search(&since("2024-04-30"), &db).unwrap().len(), 1);
+        assert!(ResultEntry::search(&since("2024-05-02"), &db).unwrap().is_empty());
+    }
+}
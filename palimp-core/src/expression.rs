@@ -0,0 +1,429 @@
+use crate::page::Page;
+use std::error::Error;
+
+/// Comparison of a selector's match count with a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    const ALL: [(&'static str, Comparison); 6] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("!=", Comparison::NotEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ];
+
+    fn holds(&self, count: usize, threshold: usize) -> bool {
+        match self {
+            Comparison::Greater => count > threshold,
+            Comparison::GreaterOrEqual => count >= threshold,
+            Comparison::Less => count < threshold,
+            Comparison::LessOrEqual => count <= threshold,
+            Comparison::Equal => count == threshold,
+            Comparison::NotEqual => count != threshold,
+        }
+    }
+}
+
+/// A test on the response headers a page was archived with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderCondition {
+    /// `header:etag`
+    Present,
+    /// `header:cache-control missing`
+    Missing,
+    /// `header:content-language=en`, ignoring case.
+    Equals(String),
+    /// `header:x-robots-tag~=noindex`, ignoring case.
+    Contains(String),
+}
+
+impl HeaderCondition {
+    fn holds(&self, values: &[&str]) -> bool {
+        match self {
+            HeaderCondition::Present => !values.is_empty(),
+            HeaderCondition::Missing => values.is_empty(),
+            HeaderCondition::Equals(expected) => values.iter().any(|value| value.trim().eq_ignore_ascii_case(expected)),
+            HeaderCondition::Contains(part) => {
+                let part = part.to_lowercase();
+                values.iter().any(|value| value.to_lowercase().contains(&part))
+            }
+        }
+    }
+}
+
+/// What a query matches on a page: a CSS selector, or selectors combined
+/// with `AND`, `OR`, `NOT`, parentheses and count thresholds, e.g.
+/// `.hero AND NOT link[rel=canonical]` or `count(.a) > 3 OR h1`.
+///
+/// `header:<name>` tests a response header instead of the DOM, e.g.
+/// `header:x-robots-tag~=noindex` or `h1 AND header:cache-control missing`.
+/// The selector engine has no pseudo-classes, so this never shadows a
+/// selector on `<header>` elements.
+///
+/// Keywords are upper case and separated by spaces, so anything without
+/// them parses as a single selector and queries behave as before.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectorExpression {
+    /// Matches pages where the selector finds at least one element.
+    Selector(String),
+    Count(String, Comparison, usize),
+    Header(String, HeaderCondition),
+    Not(Box<SelectorExpression>),
+    And(Box<SelectorExpression>, Box<SelectorExpression>),
+    Or(Box<SelectorExpression>, Box<SelectorExpression>),
+}
+
+impl SelectorExpression {
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parser = ExpressionParser { input: input.trim(), pos: 0 };
+        if parser.input.is_empty() {
+            return Err("Empty selector".into());
+        }
+        let expression = parser.or()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return Err(format!("Unexpected '{}' in '{}'", &parser.input[parser.pos..], input).into());
+        }
+        Ok(expression)
+    }
+
+    /// Whether the expression is more than a single selector.
+    pub fn is_compound(&self) -> bool {
+        !matches!(self, SelectorExpression::Selector(_))
+    }
+
+    pub fn matches(&self, page: &Page) -> bool {
+        match self {
+            SelectorExpression::Selector(selector) => count(page, selector) > 0,
+            SelectorExpression::Count(selector, comparison, threshold) => {
+                comparison.holds(count(page, selector), *threshold)
+            }
+            SelectorExpression::Header(name, condition) => {
+                let values: Vec<&str> = page
+                    .headers
+                    .iter()
+                    .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str())
+                    .collect();
+                condition.holds(&values)
+            }
+            SelectorExpression::Not(inner) => !inner.matches(page),
+            SelectorExpression::And(left, right) => left.matches(page) && right.matches(page),
+            SelectorExpression::Or(left, right) => left.matches(page) || right.matches(page),
+        }
+    }
+
+    /// The count stored for a page: the number of elements for a single
+    /// selector, and 1 or 0 for whether a compound expression matches.
+    pub fn count(&self, page: &Page) -> usize {
+        match self {
+            SelectorExpression::Selector(selector) => count(page, selector),
+            expression => expression.matches(page) as usize,
+        }
+    }
+}
+
+/// Match count of `selector` on a page, read as a [`SelectorExpression`].
+/// An expression that doesn't parse counts as no match.
+pub fn count_matches(page: &Page, selector: &str) -> usize {
+    SelectorExpression::parse(selector).map(|expression| expression.count(page)).unwrap_or(0)
+}
+
+fn count(page: &Page, selector: &str) -> usize {
+    page.dom.query_selector(selector).map(|nodes| nodes.count()).unwrap_or(0)
+}
+
+struct ExpressionParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `keyword` when it follows as a whole word.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let whole_word = rest.strip_prefix(keyword).is_some_and(|after| {
+            after.is_empty() || after.starts_with(char::is_whitespace) || (keyword == "NOT" && after.starts_with('('))
+        });
+        if whole_word {
+            self.pos += keyword.len();
+        }
+        whole_word
+    }
+
+    fn or(&mut self) -> Result<SelectorExpression, Box<dyn Error>> {
+        let mut left = self.and()?;
+        while self.keyword("OR") {
+            left = SelectorExpression::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<SelectorExpression, Box<dyn Error>> {
+        let mut left = self.unary()?;
+        while self.keyword("AND") {
+            left = SelectorExpression::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<SelectorExpression, Box<dyn Error>> {
+        if self.keyword("NOT") {
+            return Ok(SelectorExpression::Not(Box::new(self.unary()?)));
+        }
+        self.skip_whitespace();
+
+        if self.rest().starts_with('(') {
+            self.pos += 1;
+            let inner = self.or()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with(')') {
+                return Err(format!("Missing ')' in '{}'", self.input).into());
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+
+        if self.rest().starts_with("count(") {
+            self.pos += "count(".len();
+            let selector = self.selector(true)?;
+            if !self.rest().starts_with(')') {
+                return Err(format!("Missing ')' after count({}", selector).into());
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let (symbol, comparison) = Comparison::ALL
+                .into_iter()
+                .find(|(symbol, _)| self.rest().starts_with(symbol))
+                .ok_or_else(|| format!("Expected a comparison after count({})", selector))?;
+            self.pos += symbol.len();
+            self.skip_whitespace();
+            let digits: String = self.rest().chars().take_while(char::is_ascii_digit).collect();
+            let threshold = digits
+                .parse::<usize>()
+                .map_err(|_| format!("Expected a number after count({}) {}", selector, symbol))?;
+            self.pos += digits.len();
+            return Ok(SelectorExpression::Count(selector, comparison, threshold));
+        }
+
+        if self.rest().starts_with("header:") {
+            self.pos += "header:".len();
+            return self.header();
+        }
+
+        Ok(SelectorExpression::Selector(self.selector(false)?))
+    }
+
+    /// Reads the header name and condition after `header:`.
+    fn header(&mut self) -> Result<SelectorExpression, Box<dyn Error>> {
+        let name: String = self
+            .rest()
+            .chars()
+            .take_while(|c| !c.is_whitespace() && !"=~!()".contains(*c))
+            .collect();
+        if name.is_empty() {
+            return Err(format!("Missing header name in '{}'", self.input).into());
+        }
+        self.pos += name.len();
+
+        let condition = if self.rest().starts_with("~=") {
+            self.pos += 2;
+            HeaderCondition::Contains(self.header_value(&name)?)
+        } else if self.rest().starts_with('=') {
+            self.pos += 1;
+            HeaderCondition::Equals(self.header_value(&name)?)
+        } else {
+            let start = self.pos;
+            self.skip_whitespace();
+            let missing = self.rest().strip_prefix("missing").is_some_and(|after| {
+                after.is_empty() || after.starts_with(char::is_whitespace) || after.starts_with(')')
+            });
+            if missing {
+                self.pos += "missing".len();
+                HeaderCondition::Missing
+            } else {
+                self.pos = start;
+                HeaderCondition::Present
+            }
+        };
+
+        Ok(SelectorExpression::Header(name.to_ascii_lowercase(), condition))
+    }
+
+    /// Reads a quoted header value, or one up to whitespace or `)`.
+    fn header_value(&mut self, name: &str) -> Result<String, Box<dyn Error>> {
+        let rest = self.rest();
+        let (value, consumed) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = rest[1..]
+                    .find(quote)
+                    .ok_or_else(|| format!("Unterminated value for header:{}", name))?;
+                (&rest[1..end + 1], end + 2)
+            }
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || c == ')').unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        if value.is_empty() {
+            return Err(format!("Missing value for header:{}", name).into());
+        }
+        let value = value.to_string();
+        self.pos += consumed;
+        Ok(value)
+    }
+
+    /// Reads a CSS selector up to an unbalanced `)` or, unless inside
+    /// `count()`, a keyword. Brackets, parentheses and quotes may contain
+    /// anything.
+    fn selector(&mut self, in_count: bool) -> Result<String, Box<dyn Error>> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        let mut quote: Option<char> = None;
+        let mut end = self.input.len();
+
+        for (offset, c) in self.rest().char_indices() {
+            let at = start + offset;
+            if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                }
+                continue;
+            }
+            match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '(' => depth += 1,
+                ']' if depth > 0 => depth -= 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' => {
+                    end = at;
+                    break;
+                }
+                c if c.is_whitespace() && depth == 0 && !in_count => {
+                    let after = self.input[at..].trim_start();
+                    let keyword = ["AND", "OR"].iter().any(|keyword| {
+                        after.strip_prefix(keyword).is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    });
+                    if keyword {
+                        end = at;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let selector = self.input[start..end].trim();
+        if selector.is_empty() {
+            return Err(format!("Missing selector in '{}'", self.input).into());
+        }
+        self.pos = end;
+        Ok(selector.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector(s: &str) -> Box<SelectorExpression> {
+        Box::new(SelectorExpression::Selector(s.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(SelectorExpression::parse("div.item > a[title='A AND B']").unwrap(), *selector("div.item > a[title='A AND B']"));
+        assert!(!SelectorExpression::parse("a:not(.x)").unwrap().is_compound());
+
+        assert_eq!(
+            SelectorExpression::parse(".hero AND NOT link[rel=canonical]").unwrap(),
+            SelectorExpression::And(selector(".hero"), Box::new(SelectorExpression::Not(selector("link[rel=canonical]"))))
+        );
+        // AND binds tighter than OR
+        assert_eq!(
+            SelectorExpression::parse("h1 OR .a AND .b").unwrap(),
+            SelectorExpression::Or(selector("h1"), Box::new(SelectorExpression::And(selector(".a"), selector(".b"))))
+        );
+        assert_eq!(
+            SelectorExpression::parse("(h1 OR .a) AND count(div > p) >= 2").unwrap(),
+            SelectorExpression::And(
+                Box::new(SelectorExpression::Or(selector("h1"), selector(".a"))),
+                Box::new(SelectorExpression::Count("div > p".to_string(), Comparison::GreaterOrEqual, 2))
+            )
+        );
+
+        assert!(SelectorExpression::parse("count(.a) >").is_err());
+        assert!(SelectorExpression::parse(".a AND").is_err());
+        assert!(SelectorExpression::parse("(.a OR .b").is_err());
+        assert!(SelectorExpression::parse("").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let html = r#"<div class="hero"><a class="a">1</a><a class="a">2</a><a class="a">3</a><a class="a">4</a></div>"#;
+        let page = Page::new("https://a.com/", "https://a.com/", html, None).unwrap();
+
+        let matches = |input: &str| SelectorExpression::parse(input).unwrap().matches(&page);
+        assert!(matches(".hero AND NOT link[rel=canonical]"));
+        assert!(!matches(".hero AND link[rel=canonical]"));
+        assert!(matches("count(.a) > 3"));
+        assert!(!matches("count(.a) != 4"));
+        assert!(matches("NOT(h1) AND (count(.a) = 4 OR h1)"));
+
+        assert_eq!(count_matches(&page, ".a"), 4);
+        assert_eq!(count_matches(&page, "count(.a) > 3"), 1);
+        assert_eq!(count_matches(&page, "count(.a) >"), 0);
+    }
+
+    #[test]
+    fn test_header_conditions() {
+        assert_eq!(
+            SelectorExpression::parse("header:X-Robots-Tag~=noindex").unwrap(),
+            SelectorExpression::Header("x-robots-tag".to_string(), HeaderCondition::Contains("noindex".to_string()))
+        );
+        assert_eq!(
+            SelectorExpression::parse("h1 AND header:cache-control missing").unwrap(),
+            SelectorExpression::And(
+                selector("h1"),
+                Box::new(SelectorExpression::Header("cache-control".to_string(), HeaderCondition::Missing))
+            )
+        );
+        assert!(SelectorExpression::parse("header:").is_err());
+        assert!(SelectorExpression::parse("header:vary=").is_err());
+        assert!(SelectorExpression::parse("header:vary='accept").is_err());
+
+        let page = Page::new("https://a.com/", "https://a.com/", "<h1>A</h1>", None)
+            .unwrap()
+            .with_headers(vec![
+                ("x-robots-tag".to_string(), "googlebot: NOINDEX".to_string()),
+                ("Content-Language".to_string(), "en".to_string()),
+            ]);
+        let matches = |input: &str| SelectorExpression::parse(input).unwrap().matches(&page);
+        assert!(matches("header:x-robots-tag~=noindex"));
+        assert!(matches("header:content-language='EN'"));
+        assert!(!matches("header:content-language=de"));
+        assert!(matches("header:cache-control missing AND header:content-language"));
+        assert!(matches("NOT (header:etag) OR h2"));
+        assert_eq!(count_matches(&page, "header:x-robots-tag"), 1);
+    }
+}
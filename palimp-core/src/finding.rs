@@ -0,0 +1,95 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+/// A single issue (or observation) reported by an audit for a URL of a crawl.
+pub struct Finding {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub page_id: Option<i64>,
+    pub url: String,
+    pub audit: String,
+    pub rule: String,
+    pub detail: String,
+}
+
+impl Finding {
+    pub fn new(
+        id: Option<i64>,
+        crawl_id: i64,
+        page_id: Option<i64>,
+        url: &str,
+        audit: &str,
+        rule: &str,
+        detail: &str,
+    ) -> Self {
+        Self {
+            id,
+            crawl_id,
+            page_id,
+            url: url.to_string(),
+            audit: audit.to_string(),
+            rule: rule.to_string(),
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE findings SET rule = ?1, detail = ?2 WHERE id = ?3",
+                    params![self.rule, self.detail, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO findings (crawl_id, page_id, url, audit, rule, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![self.crawl_id, self.page_id, self.url, self.audit, self.rule, self.detail],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl(
+        crawl_id: i64,
+        audit: Option<&str>,
+        database: &Database,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, crawl_id, page_id, url, audit, rule, detail
+             FROM findings
+             WHERE crawl_id = ?1 AND (?2 IS NULL OR audit = ?2)
+             ORDER BY id",
+        )?;
+
+        let finding_iter = stmt.query_map(params![crawl_id, audit], |row| {
+            Ok(Finding {
+                id: Some(row.get(0)?),
+                crawl_id: row.get(1)?,
+                page_id: row.get(2)?,
+                url: row.get(3)?,
+                audit: row.get(4)?,
+                rule: row.get(5)?,
+                detail: row.get(6)?,
+            })
+        })?;
+
+        let mut findings = Vec::new();
+        for finding in finding_iter {
+            findings.push(finding?);
+        }
+
+        Ok(findings)
+    }
+
+    pub fn delete_by_crawl_and_audit(crawl_id: i64, audit: &str, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "DELETE FROM findings WHERE crawl_id = ?1 AND audit = ?2",
+            params![crawl_id, audit],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+/// A URL of a crawl that has not been fetched yet. A crawl queues every
+/// sitemap URL up front and removes each one once it was archived or failed,
+/// so whatever is left after a crash or shutdown can be resumed.
+pub struct FrontierUrl {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub url: String,
+}
+
+impl FrontierUrl {
+    pub fn new(id: Option<i64>, crawl_id: i64, url: &str) -> Self {
+        Self {
+            id,
+            crawl_id,
+            url: url.to_string(),
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE crawl_frontier SET url = ?1 WHERE id = ?2",
+                    params![self.url, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO crawl_frontier (crawl_id, url) VALUES (?1, ?2)",
+                    params![self.crawl_id, self.url],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare("SELECT id, crawl_id, url FROM crawl_frontier WHERE crawl_id = ?1 ORDER BY id")?;
+
+        let url_iter = stmt.query_map(params![crawl_id], |row| {
+            Ok(FrontierUrl {
+                id: Some(row.get(0)?),
+                crawl_id: row.get(1)?,
+                url: row.get(2)?,
+            })
+        })?;
+
+        let mut urls = Vec::new();
+        for url in url_iter {
+            urls.push(url?);
+        }
+
+        Ok(urls)
+    }
+
+    /// Up to `limit` URLs of a crawl queued after `after_id`, in queue
+    /// order, to page through a frontier too large to load at once.
+    pub fn fetch_after(
+        crawl_id: i64,
+        after_id: i64,
+        limit: usize,
+        database: &Database,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, crawl_id, url FROM crawl_frontier WHERE crawl_id = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+        )?;
+
+        let url_iter = stmt.query_map(params![crawl_id, after_id, limit as i64], |row| {
+            Ok(FrontierUrl {
+                id: Some(row.get(0)?),
+                crawl_id: row.get(1)?,
+                url: row.get(2)?,
+            })
+        })?;
+
+        let mut urls = Vec::new();
+        for url in url_iter {
+            urls.push(url?);
+        }
+
+        Ok(urls)
+    }
+
+    /// URLs left to fetch, none once the crawl is deleted.
+    pub fn count_by_crawl_id(crawl_id: i64, database: &Database) -> Result<usize, Box<dyn Error>> {
+        let count: i64 = database.conn.query_row(
+            "SELECT COUNT(*) FROM crawl_frontier f
+             INNER JOIN crawls c ON c.id = f.crawl_id
+             WHERE f.crawl_id = ?1 AND c.deleted_at IS NULL",
+            params![crawl_id],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Drops every URL a crawl has left, returning how many there were.
+    pub fn clear(crawl_id: i64, database: &Database) -> Result<usize, Box<dyn Error>> {
+        let cleared = database
+            .conn
+            .execute("DELETE FROM crawl_frontier WHERE crawl_id = ?1", params![crawl_id])?;
+        Ok(cleared)
+    }
+
+    /// Marks a URL as done. Duplicate sitemap entries are queued once each
+    /// and taken off in queue order.
+    pub fn complete(crawl_id: i64, url: &str, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "DELETE FROM crawl_frontier WHERE id =
+                (SELECT id FROM crawl_frontier WHERE crawl_id = ?1 AND url = ?2 ORDER BY id LIMIT 1)",
+            params![crawl_id, url],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::http_client::HtmlResponse;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Directory to cache fetched pages in, e.g. while developing against one site.
+pub const CACHE_DIR_ENV: &str = "PALIMP_HTTP_CACHE";
+
+/// Seconds a cached page is served without asking the server at all.
+pub const CACHE_MAX_AGE_ENV: &str = "PALIMP_HTTP_CACHE_MAX_AGE";
+
+/// On-disk cache of HTML responses, one file per URL. Entries keep the
+/// response validators so stale ones are revalidated with a conditional
+/// request instead of downloaded again.
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    max_age: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub final_url: String,
+    pub html: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl CachedResponse {
+    pub fn etag(&self) -> Option<&str> {
+        self.header("etag")
+    }
+
+    pub fn last_modified(&self) -> Option<&str> {
+        self.header("last-modified")
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn into_response(self) -> HtmlResponse {
+        HtmlResponse {
+            final_url: self.final_url,
+            html: self.html,
+            headers: self.headers,
+        }
+    }
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), max_age: None }
+    }
+
+    /// Serves entries younger than `max_age` without a request.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The cache configured by the `PALIMP_HTTP_CACHE*` environment
+    /// variables, if any.
+    pub fn from_env() -> Result<Option<Self>, Box<dyn Error>> {
+        let Ok(dir) = std::env::var(CACHE_DIR_ENV) else {
+            return Ok(None);
+        };
+        let mut cache = Self::new(dir);
+        if let Ok(seconds) = std::env::var(CACHE_MAX_AGE_ENV) {
+            let seconds = seconds
+                .parse::<u64>()
+                .map_err(|_| format!("{} must be a number of seconds, got '{}'", CACHE_MAX_AGE_ENV, seconds))?;
+            cache = cache.max_age(Duration::from_secs(seconds));
+        }
+        Ok(Some(cache))
+    }
+
+    /// The cached response for `url` and whether it is still fresh enough
+    /// to use without revalidating.
+    pub fn load(&self, url: &str) -> Option<(CachedResponse, bool)> {
+        let path = self.path(url);
+        let content = std::fs::read_to_string(&path).ok()?;
+        let entry: CachedResponse = serde_json::from_str(&content).ok()?;
+        // Guards against the unlikely hash collision
+        if entry.url != url {
+            return None;
+        }
+
+        let fresh = self.max_age.is_some_and(|max_age| {
+            std::fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age < max_age)
+        });
+        Some((entry, fresh))
+    }
+
+    pub fn store(&self, url: &str, response: &HtmlResponse) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = CachedResponse {
+            url: url.to_string(),
+            final_url: response.final_url.clone(),
+            html: response.html.clone(),
+            headers: response.headers.clone(),
+        };
+        std::fs::write(self.path(url), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    /// Marks an entry as just revalidated, restarting its max age.
+    pub fn touch(&self, url: &str) -> Result<(), Box<dyn Error>> {
+        let file = std::fs::File::options().append(true).open(self.path(url))?;
+        file.set_modified(SystemTime::now())?;
+        Ok(())
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(url.as_bytes())))
+    }
+}
+
+/// FNV-1a, a hash that stays the same across builds, unlike `DefaultHasher`,
+/// so cache file names survive a toolchain update.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("palimp-http-cache-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let url = "https://a.com/pricing";
+        assert!(cache.load(url).is_none());
+
+        let response = HtmlResponse {
+            final_url: "https://a.com/pricing/".to_string(),
+            html: "<h1>Pricing</h1>".to_string(),
+            headers: vec![("ETag".to_string(), "\"v1\"".to_string())],
+        };
+        cache.store(url, &response).unwrap();
+
+        let (entry, fresh) = cache.load(url).unwrap();
+        assert_eq!(entry.etag(), Some("\"v1\""));
+        assert_eq!(entry.last_modified(), None);
+        assert!(!fresh);
+        assert!(cache.load("https://a.com/").is_none());
+
+        let (entry, fresh) = HttpCache::new(&dir).max_age(Duration::from_secs(60)).load(url).unwrap();
+        assert!(fresh);
+        assert_eq!(entry.into_response().html, "<h1>Pricing</h1>");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
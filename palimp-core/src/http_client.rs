@@ -1,35 +1,334 @@
+use crate::dns::{AddressFamily, Resolver};
+use crate::http_cache::HttpCache;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
 
 const PAGE_SIZE_LIMIT_MB: u64 = 10;
+/// Sent unless a request asks for another user agent.
+pub const DEFAULT_USER_AGENT: &str = "PalimpCralwer/0.1";
+/// Redirects followed for one request before giving up.
+const MAX_REDIRECT_HOPS: usize = 10;
+
+/// Why a redirect chain was abandoned. Carries the chain, starting with the
+/// requested URL.
+#[derive(Debug, Clone)]
+pub enum RedirectFailure {
+    Loop(Vec<String>),
+    TooManyHops(Vec<String>),
+}
+
+impl fmt::Display for RedirectFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectFailure::Loop(chain) => write!(f, "redirect loop: {}", chain.join(" -> ")),
+            RedirectFailure::TooManyHops(chain) => {
+                write!(f, "more than {} redirects: {}", chain.len() - 2, chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl Error for RedirectFailure {}
+
+fn redirect_action(attempt: reqwest::redirect::Attempt, max_hops: usize) -> reqwest::redirect::Action {
+    let looped = attempt.previous().contains(attempt.url());
+    let exceeded = attempt.previous().len() > max_hops;
+    if !looped && !exceeded {
+        return attempt.follow();
+    }
+
+    let chain: Vec<String> = attempt
+        .previous()
+        .iter()
+        .chain(std::iter::once(attempt.url()))
+        .map(|url| url.to_string())
+        .collect();
+
+    if looped {
+        attempt.error(RedirectFailure::Loop(chain))
+    } else {
+        attempt.error(RedirectFailure::TooManyHops(chain))
+    }
+}
+
+/// The server could not be reached or answered with an error status, as
+/// opposed to serving a document palimp declines to archive.
+#[derive(Debug)]
+pub enum ServerError {
+    Connection { url: String, message: String },
+    Status { url: String, status: u16 },
+    Redirect { url: String, failure: RedirectFailure },
+}
+
+impl ServerError {
+    /// Whether the failure suggests the server is overloaded.
+    pub fn is_overload(&self) -> bool {
+        match self {
+            ServerError::Connection { .. } => true,
+            ServerError::Status { status, .. } => *status == 429 || *status >= 500,
+            ServerError::Redirect { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Connection { url, message } => {
+                write!(f, "HTTP client could not connect with {}:\n{}", url, message)
+            }
+            ServerError::Status { url, status } => {
+                write!(f, "Server returned an error for {}: {}", url, status)
+            }
+            ServerError::Redirect { url, failure } => {
+                write!(f, "Gave up following redirects for {}, {}", url, failure)
+            }
+        }
+    }
+}
+
+impl Error for ServerError {}
+
+pub struct HtmlResponse {
+    pub final_url: String,
+    pub html: String,
+    pub headers: Vec<(String, String)>,
+}
+
+pub struct AssetResponse {
+    pub final_url: String,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
 
 #[derive(Clone)]
 pub struct HTTPClient {
     client: reqwest::Client,
+    settings: HTTPClientBuilder,
 }
 
-impl HTTPClient {
-    pub fn new() -> Result<HTTPClient, Box<dyn Error>> {
-        let client = reqwest::Client::builder()
-            .user_agent("PalimpCralwer/0.1")
-            .timeout(std::time::Duration::from_secs(30))
-            .connect_timeout(std::time::Duration::from_secs(10))
+/// Connection tuning for an [`HTTPClient`].
+#[derive(Clone, Default)]
+pub struct HTTPClientBuilder {
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    dns_cache: bool,
+    address_family: AddressFamily,
+    accept_invalid_certs: bool,
+    max_redirects: Option<usize>,
+    default_headers: Vec<(String, String)>,
+    dns_overrides: HashMap<String, Vec<SocketAddr>>,
+    cache: Option<HttpCache>,
+}
+
+impl HTTPClientBuilder {
+    /// How long an idle pooled connection is kept open.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum number of idle connections kept per host.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Caches DNS lookups for the lifetime of the client.
+    pub fn dns_cache(mut self, enabled: bool) -> Self {
+        self.dns_cache = enabled;
+        self
+    }
+
+    /// Prefers or restricts the IP version used for outbound connections,
+    /// e.g. for staging hosts that only resolve over IPv4.
+    pub fn address_family(mut self, family: AddressFamily) -> Self {
+        self.address_family = family;
+        self
+    }
+
+    /// Skips TLS certificate verification. Only meant for staging servers
+    /// with self-signed certificates.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Redirects followed for one request before it fails as a redirect
+    /// error (10 by default).
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = Some(max);
+        self
+    }
+
+    /// Sends a header with every request. The value is marked sensitive so
+    /// it never shows up in debug output.
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.default_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Resolves `host` to `ip` instead of asking DNS, e.g. to point a
+    /// production domain at a staging server.
+    pub fn dns_override(mut self, host: &str, ip: IpAddr) -> Self {
+        // Port 0 keeps the scheme's default port
+        self.dns_overrides
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .push(SocketAddr::new(ip, 0));
+        self
+    }
+
+    /// Keeps fetched pages on disk and revalidates them on the next fetch,
+    /// so repeated development runs against one site skip most downloads.
+    pub fn cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn build(self) -> Result<HTTPClient, Box<dyn Error>> {
+        let max_hops = self.max_redirects.unwrap_or(MAX_REDIRECT_HOPS);
+        let mut builder = reqwest::Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| redirect_action(attempt, max_hops)));
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        if self.dns_cache || self.address_family != AddressFamily::Any {
+            builder = builder.dns_resolver(Resolver::new(self.dns_cache, self.address_family));
+        }
+
+        // Binding to an unspecified local address of one family keeps
+        // overridden and literal IP hosts on that family too
+        match self.address_family {
+            AddressFamily::Ipv4Only => builder = builder.local_address(IpAddr::from(Ipv4Addr::UNSPECIFIED)),
+            AddressFamily::Ipv6Only => builder = builder.local_address(IpAddr::from(Ipv6Addr::UNSPECIFIED)),
+            _ => {}
+        }
+
+        for (host, addrs) in &self.dns_overrides {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+
+        if self.accept_invalid_certs {
+            builder = builder.tls_danger_accept_invalid_certs(true);
+        }
+
+        if !self.default_headers.is_empty() {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for (name, value) in &self.default_headers {
+                let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| format!("Invalid header name {}: {}", name, err))?;
+                let mut header_value = reqwest::header::HeaderValue::from_str(value)
+                    .map_err(|err| format!("Invalid value for header {}: {}", name, err))?;
+                header_value.set_sensitive(true);
+                headers.insert(header_name, header_value);
+            }
+            builder = builder.default_headers(headers);
+        }
+
+        let client = builder
             .build()
             .map_err(|err| format!("Failed to initialize HTTP request client (reqwest):\n{}", err))?;
 
-        Ok(HTTPClient { client })
+        Ok(HTTPClient { client, settings: self })
+    }
+}
+
+impl HTTPClient {
+    pub fn new() -> Result<HTTPClient, Box<dyn Error>> {
+        HTTPClient::builder().build()
+    }
+
+    pub fn builder() -> HTTPClientBuilder {
+        HTTPClientBuilder::default()
     }
 
-    pub async fn get_html(&self, url: &str) -> Result<(String, String), Box<dyn Error>> {
+    /// A default client, caching pages on disk when the `PALIMP_HTTP_CACHE*`
+    /// environment variables ask for it.
+    pub fn from_env() -> Result<HTTPClient, Box<dyn Error>> {
+        let mut builder = HTTPClient::builder();
+        if let Some(cache) = HttpCache::from_env()? {
+            builder = builder.cache(cache);
+        }
+        builder.build()
+    }
+
+    /// The `User-Agent` sent by requests that don't set their own: a default
+    /// header's if one is configured, otherwise `DEFAULT_USER_AGENT`.
+    pub fn user_agent(&self) -> &str {
+        self.settings
+            .default_headers
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+            .map_or(DEFAULT_USER_AGENT, |(_, value)| value.as_str())
+    }
+
+    /// The settings this client was built with, to derive a dedicated client
+    /// with some of them changed.
+    pub fn settings(&self) -> HTTPClientBuilder {
+        self.settings.clone()
+    }
+
+    pub async fn get_html(&self, url: &str) -> Result<HtmlResponse, Box<dyn Error>> {
+        self.get_html_as(url, None).await
+    }
+
+    /// Like `get_html`, sending `user_agent` instead of the client's. A
+    /// cached page is served whichever agent it was fetched with.
+    pub async fn get_html_as(&self, url: &str, user_agent: Option<&str>) -> Result<HtmlResponse, Box<dyn Error>> {
         let url = url.trim();
 
-        let response = self.client
-            .get(url)
+        let cached = self.settings.cache.as_ref().and_then(|cache| cache.load(url));
+        if let Some((entry, true)) = cached {
+            return Ok(entry.into_response());
+        }
+        let cached = cached.map(|(entry, _)| entry);
+
+        let mut request = self.client.get(url);
+        if let Some(user_agent) = user_agent {
+            request = request.header(reqwest::header::USER_AGENT, user_agent);
+        }
+        if let Some(entry) = &cached {
+            if let Some(etag) = entry.etag() {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = entry.last_modified() {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request
             .send()
             .await
-            .map_err(|err| format!("HTTP client could not connect with {}:\n{}", url, err))?;
+            .map_err(|err| server_error(url, err))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED
+            && let Some(entry) = cached
+        {
+            if let Some(cache) = &self.settings.cache
+                && let Err(e) = cache.touch(url)
+            {
+                eprintln!("Could not refresh the cached copy of {}: {}", url, e);
+            }
+            return Ok(entry.into_response());
+        }
 
         if !response.status().is_success() {
-            return Err(format!("Server returned an error for {}: {}", url, response.status()).into());
+            return Err(ServerError::Status { url: url.to_string(), status: response.status().as_u16() }.into());
         }
 
         if let Some(len) = response.content_length() {
@@ -55,17 +354,78 @@ impl HTTPClient {
 
         let final_url = response.url().as_str().to_string();
 
+        let headers = response.headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+
         let response_text = response.text()
             .await
             .map_err(|err| format!("Could not read response text for {}: {}", url, err))?;
 
-        Ok((final_url, response_text))
+        let response = HtmlResponse {
+            final_url,
+            html: response_text,
+            headers,
+        };
+
+        if let Some(cache) = &self.settings.cache
+            && let Err(e) = cache.store(url, &response)
+        {
+            eprintln!("Could not cache {}: {}", url, e);
+        }
+
+        Ok(response)
     }
 
-    pub async fn get_sitemap(&self, url: &str) -> Result<String, Box<dyn Error>> {
+    /// Fetches a stylesheet, script or other non-HTML resource, giving up
+    /// as soon as it turns out larger than `max_bytes`. Not cached.
+    pub async fn get_asset(&self, url: &str, max_bytes: u64) -> Result<AssetResponse, Box<dyn Error>> {
         let url = url.trim();
+        let too_large = || format!("Larger than the {} byte limit: {}", max_bytes, url);
 
-        let response = self.client
+        let mut response = self.client
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| server_error(url, err))?;
+
+        if !response.status().is_success() {
+            return Err(ServerError::Status { url: url.to_string(), status: response.status().as_u16() }.into());
+        }
+        if response.content_length().is_some_and(|len| len > max_bytes) {
+            return Err(too_large().into());
+        }
+
+        let final_url = response.url().as_str().to_string();
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+
+        // The length may be missing or wrong, so the body is counted as it comes
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|err| format!("Could not read response body for {}: {}", url, err))?
+        {
+            if (body.len() + chunk.len()) as u64 > max_bytes {
+                return Err(too_large().into());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(AssetResponse { final_url, content_type, body })
+    }
+
+    /// Downloads a sitemap into `body` as it arrives, so a large one is
+    /// never held in memory. Returns the bytes written.
+    pub async fn get_sitemap(&self, url: &str, body: &mut impl Write) -> Result<u64, Box<dyn Error>> {
+        let url = url.trim();
+
+        let mut response = self.client
             .get(url)
             .header("Accept", "application/xml, text/xml, */*")
             .send()
@@ -80,8 +440,64 @@ impl HTTPClient {
             return Err(format!("Document type is not XML for: {}", url).into());
         }
 
-        let body = response.text().await?;
+        let mut bytes = 0;
+        while let Some(chunk) = response.chunk().await? {
+            body.write_all(&chunk)?;
+            bytes += chunk.len() as u64;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Fetches a robots.txt. `None` when the server has none, i.e. answers
+    /// with a 4xx status, which allows everything; 5xx statuses are errors.
+    pub async fn get_robots_txt(&self, url: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let url = url.trim();
+
+        let response = self.client
+            .get(url)
+            .send()
+            .await
+            .map_err(|err| server_error(url, err))?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(ServerError::Status { url: url.to_string(), status: status.as_u16() }.into());
+        }
+
+        let body = response.text()
+            .await
+            .map_err(|err| format!("Could not read response text for {}: {}", url, err))?;
+        Ok(Some(body))
+    }
+
+    /// Issues a HEAD request and returns the response status code.
+    pub async fn check_url(&self, url: &str) -> Result<u16, Box<dyn Error>> {
+        let url = url.trim();
+
+        let response = self.client
+            .head(url)
+            .send()
+            .await
+            .map_err(|err| format!("HTTP client could not connect with {}:\n{}", url, err))?;
+
+        Ok(response.status().as_u16())
+    }
+}
+
+/// Maps a failed request to a `ServerError`, keeping redirect failures
+/// raised by the redirect policy apart from connection problems.
+fn server_error(url: &str, err: reqwest::Error) -> ServerError {
+    let failure = err
+        .source()
+        .and_then(|source| source.downcast_ref::<RedirectFailure>())
+        .cloned();
 
-        Ok(body)
+    match failure {
+        Some(failure) => ServerError::Redirect { url: url.to_string(), failure },
+        None => ServerError::Connection { url: url.to_string(), message: err.to_string() },
     }
 }
@@ -0,0 +1,230 @@
+use crate::crawl::{Crawl, CrawlStatus};
+use crate::database::Database;
+use crate::page::Page;
+use base64::Engine;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A page captured outside of a crawl, e.g. in a static export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPage {
+    pub url: String,
+    pub html: String,
+    /// Response headers, when the capture recorded them.
+    pub headers: Vec<(String, String)>,
+}
+
+/// Reads every `.html`/`.htm` file below `dir`, in path order. Each file is
+/// given the URL it would be served at under `base_url`, with `index.html`
+/// standing for its directory.
+pub fn read_folder(dir: &Path, base_url: &str) -> Result<Vec<ImportedPage>, Box<dyn Error>> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()).into());
+    }
+
+    let mut files = Vec::new();
+    collect_html_files(dir, &mut files)?;
+    files.sort();
+
+    let base_url = base_url.trim_end_matches('/');
+    let mut pages = Vec::new();
+    for file in files {
+        let relative = file.strip_prefix(dir)?;
+        let html = String::from_utf8_lossy(&fs::read(&file)?).into_owned();
+        pages.push(ImportedPage {
+            url: format!("{}/{}", base_url, url_path(relative)),
+            html,
+            headers: Vec::new(),
+        });
+    }
+
+    Ok(pages)
+}
+
+fn collect_html_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_html_files(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// URL path of a file relative to the export root, e.g. `blog/index.html`
+/// becomes `blog/`.
+fn url_path(relative: &Path) -> String {
+    let segments: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().replace(' ', "%20"))
+        .collect();
+
+    match segments.split_last() {
+        Some((file, directories)) if file.eq_ignore_ascii_case("index.html") || file.eq_ignore_ascii_case("index.htm") => {
+            directories.iter().map(|directory| format!("{}/", directory)).collect()
+        }
+        _ => segments.join("/"),
+    }
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    #[serde(default)]
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+    response: HarResponse,
+}
+
+#[derive(Deserialize)]
+struct HarRequest {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    content: HarContent,
+}
+
+#[derive(Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct HarContent {
+    #[serde(default, rename = "mimeType")]
+    mime_type: String,
+    text: Option<String>,
+    encoding: Option<String>,
+}
+
+/// Reads the HTML documents of a HAR file exported from a browser's network
+/// panel. Redirects, errors and other resources are skipped; when a URL was
+/// captured more than once, the last response wins.
+pub fn read_har(content: &str) -> Result<Vec<ImportedPage>, Box<dyn Error>> {
+    let har: Har = serde_json::from_str(content).map_err(|e| format!("Invalid HAR file: {}", e))?;
+
+    let mut pages: Vec<ImportedPage> = Vec::new();
+    for entry in har.log.entries {
+        let response = entry.response;
+        if !(200..300).contains(&response.status) || !response.content.mime_type.contains("html") {
+            continue;
+        }
+        let Some(text) = response.content.text else {
+            continue;
+        };
+
+        let html = match response.content.encoding.as_deref() {
+            Some("base64") => {
+                let bytes = base64::engine::general_purpose::STANDARD.decode(text.trim())?;
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            _ => text,
+        };
+        let page = ImportedPage {
+            url: entry.request.url,
+            html,
+            headers: response.headers.into_iter().map(|header| (header.name, header.value)).collect(),
+        };
+
+        match pages.iter_mut().find(|existing| existing.url == page.url) {
+            Some(existing) => *existing = page,
+            None => pages.push(page),
+        }
+    }
+
+    Ok(pages)
+}
+
+/// Stores imported pages as a new, completed crawl of a site and returns the
+/// crawl ID.
+pub fn store(site_id: i64, pages: &[ImportedPage], db: &mut Database) -> Result<i64, Box<dyn Error>> {
+    db.in_transaction(|db| {
+        let mut crawl = Crawl::new(None, site_id);
+        crawl.status = CrawlStatus::Completed;
+        crawl.sync(db)?;
+        let crawl_id = crawl.id.ok_or("Failed to get crawl ID after sync")?;
+
+        for imported in pages {
+            Page::new(&imported.url, &imported.url, &imported.html, Some(crawl_id))?
+                .with_headers(imported.headers.clone())
+                .sync(db)?;
+        }
+
+        Ok(crawl_id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_folder() {
+        let dir = std::env::temp_dir().join(format!("palimp-import-{}", std::process::id()));
+        fs::create_dir_all(dir.join("blog/first post")).unwrap();
+        fs::write(dir.join("index.html"), "<h1>Home</h1>").unwrap();
+        fs::write(dir.join("about.htm"), "<h1>About</h1>").unwrap();
+        fs::write(dir.join("blog/first post/index.html"), "<h1>First</h1>").unwrap();
+        fs::write(dir.join("style.css"), "h1 {}").unwrap();
+
+        let pages = read_folder(&dir, "https://example.com/").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let urls: Vec<&str> = pages.iter().map(|page| page.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/about.htm",
+                "https://example.com/blog/first%20post/",
+                "https://example.com/",
+            ]
+        );
+        assert_eq!(pages[2].html, "<h1>Home</h1>");
+    }
+
+    #[test]
+    fn test_read_har() {
+        let har = r#"{"log": {"version": "1.2", "entries": [
+            {"request": {"method": "GET", "url": "https://a.com/account"},
+             "response": {"status": 200, "headers": [{"name": "Content-Type", "value": "text/html"}],
+                          "content": {"mimeType": "text/html; charset=utf-8", "text": "<h1>Old</h1>"}}},
+            {"request": {"method": "GET", "url": "https://a.com/app.js"},
+             "response": {"status": 200, "headers": [],
+                          "content": {"mimeType": "application/javascript", "text": "run()"}}},
+            {"request": {"method": "GET", "url": "https://a.com/login"},
+             "response": {"status": 302, "headers": [], "content": {"mimeType": "text/html", "text": ""}}},
+            {"request": {"method": "GET", "url": "https://a.com/account"},
+             "response": {"status": 200, "headers": [],
+                          "content": {"mimeType": "text/html", "text": "PGgxPk5ldzwvaDE+", "encoding": "base64"}}}
+        ]}}"#;
+
+        let pages = read_har(har).unwrap();
+
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].url, "https://a.com/account");
+        assert_eq!(pages[0].html, "<h1>New</h1>");
+        assert!(read_har("{}").is_err());
+    }
+}
@@ -0,0 +1,625 @@
+use crate::crawl::{CrawlOptions, StopSignal};
+use crate::database::Database;
+use crate::{Application, CrawlResult};
+use futures::stream::{FuturesUnordered, StreamExt};
+use rusqlite::{params, Row};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default limit on the sum of `max_concurrent` over all running crawl jobs.
+pub const DEFAULT_CONCURRENCY_BUDGET: usize = 10;
+
+/// Work a job performs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobKind {
+    Crawl { site_id: i64, max_concurrent: usize },
+    Query { crawl_id: i64, selector: String },
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::Crawl { .. } => "crawl",
+            JobKind::Query { .. } => "query",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            JobKind::Crawl { site_id, max_concurrent } => {
+                format!("crawl site {} (concurrency {})", site_id, max_concurrent)
+            }
+            JobKind::Query { crawl_id, selector } => format!("query '{}' on crawl {}", selector, crawl_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "succeeded" => Some(JobStatus::Succeeded),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+const COLUMNS: &str = "id, kind, site_id, crawl_id, selector, max_concurrent, status, progress_done, progress_total,
+                       message, created_at, started_at, finished_at, cancel_requested, priority";
+
+/// A long operation submitted by a frontend and executed in the background
+/// by a [`Worker`]. Status and progress live in the database so any process
+/// can poll them.
+pub struct Job {
+    pub id: Option<i64>,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub progress_done: usize,
+    pub progress_total: usize,
+    /// Outcome summary, or the error of a failed job.
+    pub message: Option<String>,
+    pub created_at: Option<String>,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// Set by [`Job::request_cancel`] while the job runs.
+    pub cancel_requested: bool,
+    /// Queued jobs with a higher priority are claimed first.
+    pub priority: i64,
+}
+
+impl Job {
+    pub fn new(id: Option<i64>, kind: JobKind) -> Self {
+        Self {
+            id,
+            kind,
+            status: JobStatus::Queued,
+            progress_done: 0,
+            progress_total: 0,
+            message: None,
+            created_at: None,
+            started_at: None,
+            finished_at: None,
+            cancel_requested: false,
+            priority: 0,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i64) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn kind_columns(&self) -> (&'static str, Option<i64>, Option<i64>, Option<String>, Option<i64>) {
+        match &self.kind {
+            JobKind::Crawl { site_id, max_concurrent } => {
+                ("crawl", Some(*site_id), None, None, Some(*max_concurrent as i64))
+            }
+            JobKind::Query { crawl_id, selector } => ("query", None, Some(*crawl_id), Some(selector.clone()), None),
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let kind: String = row.get(1)?;
+        let site_id: Option<i64> = row.get(2)?;
+        let crawl_id: Option<i64> = row.get(3)?;
+        let selector: Option<String> = row.get(4)?;
+        let max_concurrent: Option<i64> = row.get(5)?;
+        let status: String = row.get(6)?;
+
+        let kind = match kind.as_str() {
+            "query" => JobKind::Query {
+                crawl_id: crawl_id.unwrap_or_default(),
+                selector: selector.unwrap_or_default(),
+            },
+            _ => JobKind::Crawl {
+                site_id: site_id.unwrap_or_default(),
+                max_concurrent: max_concurrent.unwrap_or(5) as usize,
+            },
+        };
+
+        Ok(Job {
+            id: Some(row.get(0)?),
+            kind,
+            status: JobStatus::from_name(&status).unwrap_or(JobStatus::Failed),
+            progress_done: row.get::<_, i64>(7)? as usize,
+            progress_total: row.get::<_, i64>(8)? as usize,
+            message: row.get(9)?,
+            created_at: row.get(10)?,
+            started_at: row.get(11)?,
+            finished_at: row.get(12)?,
+            cancel_requested: row.get(13)?,
+            priority: row.get(14)?,
+        })
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let (kind, site_id, crawl_id, selector, max_concurrent) = self.kind_columns();
+
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE jobs SET kind = ?1, site_id = ?2, crawl_id = ?3, selector = ?4, max_concurrent = ?5,
+                     status = ?6, progress_done = ?7, progress_total = ?8, message = ?9, priority = ?10 WHERE id = ?11",
+                    params![
+                        kind,
+                        site_id,
+                        crawl_id,
+                        selector,
+                        max_concurrent,
+                        self.status.name(),
+                        self.progress_done as i64,
+                        self.progress_total as i64,
+                        self.message,
+                        self.priority,
+                        existing_id
+                    ],
+                )?;
+            }
+            None => {
+                let sql = "INSERT INTO jobs (kind, site_id, crawl_id, selector, max_concurrent, status, priority, created_at)
+                           VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, palimp_now()) RETURNING id, created_at";
+
+                let (new_id, time): (i64, String) = database.conn.query_row(
+                    sql,
+                    params![kind, site_id, crawl_id, selector, max_concurrent, self.status.name(), self.priority],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+
+                self.id = Some(new_id);
+                self.created_at = Some(time);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch(id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let sql = format!("SELECT {} FROM jobs WHERE id = ?1", COLUMNS);
+
+        database
+            .conn
+            .query_row(&sql, params![id], Self::from_row)
+            .map_err(|e| format!("Job {} not found: {}", id, e).into())
+    }
+
+    /// The most recent jobs first.
+    pub fn fetch_recent(limit: usize, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare(&format!("SELECT {} FROM jobs ORDER BY id DESC LIMIT ?1", COLUMNS))?;
+
+        let job_iter = stmt.query_map(params![limit as i64], Self::from_row)?;
+
+        let mut jobs = Vec::new();
+        for job in job_iter {
+            jobs.push(job?);
+        }
+
+        Ok(jobs)
+    }
+
+    /// Marks the next queued job as running and returns it: the highest
+    /// priority first, then the oldest. The claim is a single statement, so
+    /// concurrent workers never pick the same job.
+    ///
+    /// A crawl job is only claimed while its site isn't being crawled by
+    /// another job and its `max_concurrent` fits in what running crawls leave
+    /// of `budget`. A crawl larger than the whole budget runs alone. Smaller
+    /// crawls may start ahead of a higher priority one that doesn't fit yet.
+    pub fn claim_next(budget: usize, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "UPDATE jobs SET status = 'running', started_at = palimp_now()
+             WHERE id = (
+                SELECT queued.id FROM jobs AS queued
+                WHERE queued.status = 'queued'
+                  AND (
+                    queued.kind != 'crawl'
+                    OR (
+                        NOT EXISTS (
+                            SELECT 1 FROM jobs AS running
+                            WHERE running.status = 'running' AND running.kind = 'crawl'
+                              AND running.site_id = queued.site_id
+                        )
+                        AND (
+                            (SELECT COALESCE(SUM(max_concurrent), 0) FROM jobs
+                             WHERE status = 'running' AND kind = 'crawl') + queued.max_concurrent <= ?1
+                            OR NOT EXISTS (SELECT 1 FROM jobs WHERE status = 'running' AND kind = 'crawl')
+                        )
+                    )
+                  )
+                ORDER BY queued.priority DESC, queued.id
+                LIMIT 1
+             )
+             RETURNING id",
+        )?;
+        let mut rows = stmt.query_map(params![budget as i64], |row| row.get::<_, i64>(0))?;
+
+        match rows.next() {
+            Some(id) => Ok(Some(Self::fetch(id?, database)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_progress(id: i64, done: usize, total: usize, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "UPDATE jobs SET progress_done = ?1, progress_total = ?2 WHERE id = ?3",
+            params![done as i64, total as i64, id],
+        )?;
+        Ok(())
+    }
+
+    /// Changes the priority of a job that hasn't started yet.
+    pub fn set_priority(id: i64, priority: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        let updated = database.conn.execute(
+            "UPDATE jobs SET priority = ?1 WHERE id = ?2 AND status = 'queued'",
+            params![priority, id],
+        )?;
+        if updated == 0 {
+            let job = Self::fetch(id, database)?;
+            return Err(format!("Job {} is already {}", id, job.status.name()).into());
+        }
+        Ok(())
+    }
+
+    /// Cancels a queued job outright; a running one is flagged for its worker
+    /// to stop. Returns the job's status after the request.
+    pub fn request_cancel(id: i64, database: &Database) -> Result<JobStatus, Box<dyn Error>> {
+        let cancelled = database.conn.execute(
+            "UPDATE jobs SET status = 'cancelled', finished_at = palimp_now() WHERE id = ?1 AND status = 'queued'",
+            params![id],
+        )?;
+        if cancelled > 0 {
+            return Ok(JobStatus::Cancelled);
+        }
+
+        let flagged = database.conn.execute(
+            "UPDATE jobs SET cancel_requested = 1 WHERE id = ?1 AND status = 'running'",
+            params![id],
+        )?;
+        if flagged > 0 {
+            return Ok(JobStatus::Running);
+        }
+
+        let job = Self::fetch(id, database)?;
+        Err(format!("Job {} already {}", id, job.status.name()).into())
+    }
+
+    pub fn is_cancel_requested(id: i64, database: &Database) -> Result<bool, Box<dyn Error>> {
+        let requested = database.conn.query_row(
+            "SELECT cancel_requested FROM jobs WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(requested)
+    }
+
+    /// Records the final status of a job with its outcome summary or error.
+    pub fn finish(id: i64, status: JobStatus, message: &str, database: &Database) -> Result<(), Box<dyn Error>> {
+        database.conn.execute(
+            "UPDATE jobs SET status = ?1, message = ?2, finished_at = palimp_now() WHERE id = ?3",
+            params![status.name(), message, id],
+        )?;
+        Ok(())
+    }
+}
+
+/// Claims queued jobs and runs them, e.g. in `palimp daemon` or on a
+/// frontend's background thread. Jobs run concurrently as long as crawls fit
+/// in the concurrency budget. Progress is written every second, which is
+/// also when cancel requests are picked up.
+pub struct Worker<'a> {
+    app: &'a Application,
+    budget: usize,
+}
+
+impl<'a> Worker<'a> {
+    pub fn new(app: &'a Application) -> Self {
+        Self {
+            app,
+            budget: DEFAULT_CONCURRENCY_BUDGET,
+        }
+    }
+
+    /// Limits the sum of `max_concurrent` over crawl jobs running at once,
+    /// counting those of other workers sharing the database.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+
+    /// Runs jobs until `stop` fires, checking for new ones every
+    /// `poll_interval`. `on_event` receives each job as it starts and ends.
+    /// Running jobs are stopped and awaited before returning.
+    pub async fn run<F>(&self, poll_interval: Duration, stop: &StopSignal, on_event: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(&Job),
+    {
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            while !stop.is_stopped()
+                && let Some(job) = self.claim().await?
+            {
+                on_event(&job);
+                running.push(self.process(job, stop, &on_event));
+            }
+
+            if stop.is_stopped() && running.is_empty() {
+                break;
+            }
+
+            // Wake up when a job finishes, to claim what it leaves room for,
+            // or after a short step so a stop request is noticed quickly
+            let step = poll_interval.min(Duration::from_millis(100));
+            let mut waited = Duration::ZERO;
+            while waited < poll_interval && !stop.is_stopped() {
+                if running.is_empty() {
+                    tokio::time::sleep(step).await;
+                } else {
+                    tokio::select! {
+                        finished = running.next() => {
+                            if let Some(finished) = finished {
+                                finished?;
+                            }
+                            break;
+                        }
+                        _ = tokio::time::sleep(step) => {}
+                    }
+                }
+                waited += step;
+            }
+
+            if stop.is_stopped() {
+                while let Some(finished) = running.next().await {
+                    finished?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Claims and runs the next queued job that fits in the budget,
+    /// returning it in its final state, or `None` when nothing can start.
+    pub async fn run_next<F>(&self, stop: &StopSignal, on_event: F) -> Result<Option<Job>, Box<dyn Error>>
+    where
+        F: Fn(&Job),
+    {
+        let Some(job) = self.claim().await? else {
+            return Ok(None);
+        };
+
+        on_event(&job);
+        self.process(job, stop, &on_event).await.map(Some)
+    }
+
+    async fn claim(&self) -> Result<Option<Job>, Box<dyn Error>> {
+        // Jobs crawl, so a read-only archive has none to run
+        self.app.check_writable()?;
+        let db = self.app.db.lock().await;
+        Job::claim_next(self.budget, &db)
+    }
+
+    async fn process<F>(&self, job: Job, stop: &StopSignal, on_event: &F) -> Result<Job, Box<dyn Error>>
+    where
+        F: Fn(&Job),
+    {
+        let job_id = job.id.ok_or("Job without an ID")?;
+        let outcome = self.execute(job_id, &job.kind, stop).await;
+
+        let db = self.app.db.lock().await;
+        let cancelled = Job::is_cancel_requested(job_id, &db)?;
+        match outcome {
+            Ok(summary) => Job::finish(job_id, JobStatus::Succeeded, &summary, &db)?,
+            Err(error) if cancelled => Job::finish(job_id, JobStatus::Cancelled, &error.to_string(), &db)?,
+            Err(error) => Job::finish(job_id, JobStatus::Failed, &error.to_string(), &db)?,
+        }
+
+        let job = Job::fetch(job_id, &db)?;
+        drop(db);
+        on_event(&job);
+        Ok(job)
+    }
+
+    async fn execute(&self, job_id: i64, kind: &JobKind, stop: &StopSignal) -> Result<String, Box<dyn Error>> {
+        match kind {
+            JobKind::Crawl { site_id, max_concurrent } => self.crawl(job_id, *site_id, *max_concurrent, stop).await,
+            JobKind::Query { crawl_id, selector } => {
+                let results = self.app.query(*crawl_id, selector).await?;
+                let db = self.app.db.lock().await;
+                Job::set_progress(job_id, 1, 1, &db)?;
+                Ok(format!("{} matching pages", results.len()))
+            }
+        }
+    }
+
+    async fn crawl(
+        &self,
+        job_id: i64,
+        site_id: i64,
+        max_concurrent: usize,
+        stop: &StopSignal,
+    ) -> Result<String, Box<dyn Error>> {
+        let total = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicUsize::new(0));
+        let alerts = Arc::new(AtomicUsize::new(0));
+        let interrupted = Arc::new(std::sync::Mutex::new(None));
+
+        // Stopping the worker or cancelling the job both stop the crawl
+        let job_stop = StopSignal::new();
+        let options = CrawlOptions::new(max_concurrent).with_stop_signal(job_stop.clone());
+        let crawl = {
+            let (total, done, failed, alerts, interrupted) = (
+                Arc::clone(&total),
+                Arc::clone(&done),
+                Arc::clone(&failed),
+                Arc::clone(&alerts),
+                Arc::clone(&interrupted),
+            );
+            self.app.new_crawl_with_options(site_id, options, move |result| match result {
+                CrawlResult::CrawlStarted(pages) => total.store(pages, Ordering::Relaxed),
+                CrawlResult::PageSucceeded(_) => {
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+                CrawlResult::PageFailed(_, _) => {
+                    done.fetch_add(1, Ordering::Relaxed);
+                    failed.fetch_add(1, Ordering::Relaxed);
+                }
+                CrawlResult::Interrupted(crawl_id, _) => {
+                    if let Ok(mut interrupted) = interrupted.lock() {
+                        *interrupted = Some(crawl_id);
+                    }
+                }
+                CrawlResult::AlertRaised(_, _) => {
+                    alerts.fetch_add(1, Ordering::Relaxed);
+                }
+                CrawlResult::Warning(_) | CrawlResult::Cancelled(_, _) | CrawlResult::Stats(_) => {}
+            })
+        };
+        tokio::pin!(crawl);
+
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        let result = loop {
+            tokio::select! {
+                result = &mut crawl => break result,
+                _ = ticker.tick() => {
+                    let db = self.app.db.lock().await;
+                    Job::set_progress(job_id, done.load(Ordering::Relaxed), total.load(Ordering::Relaxed), &db)?;
+                    if stop.is_stopped() || Job::is_cancel_requested(job_id, &db)? {
+                        job_stop.stop();
+                    }
+                }
+            }
+        };
+        result?;
+
+        let (done, total, failed) =
+            (done.load(Ordering::Relaxed), total.load(Ordering::Relaxed), failed.load(Ordering::Relaxed));
+        {
+            let db = self.app.db.lock().await;
+            Job::set_progress(job_id, done, total, &db)?;
+        }
+
+        let interrupted = interrupted.lock().ok().and_then(|interrupted| *interrupted);
+        match interrupted {
+            Some(crawl_id) => Err(format!("Interrupted, resume with 'crawls resume {}'", crawl_id).into()),
+            None => match alerts.load(Ordering::Relaxed) {
+                0 => Ok(format!("{} pages crawled, {} failed", done, failed)),
+                alerts => Ok(format!("{} pages crawled, {} failed, {} alerts raised", done, failed, alerts)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_are_claimed_once_in_order() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+
+        let mut crawl = Job::new(None, JobKind::Crawl { site_id: 1, max_concurrent: 3 });
+        crawl.sync(&mut db).unwrap();
+        let mut query = Job::new(None, JobKind::Query { crawl_id: 2, selector: "h1".to_string() });
+        query.sync(&mut db).unwrap();
+
+        let first = Job::claim_next(DEFAULT_CONCURRENCY_BUDGET, &db).unwrap().unwrap();
+        assert_eq!(first.kind, JobKind::Crawl { site_id: 1, max_concurrent: 3 });
+        assert_eq!(first.status, JobStatus::Running);
+
+        let second = Job::claim_next(DEFAULT_CONCURRENCY_BUDGET, &db).unwrap().unwrap();
+        assert_eq!(second.kind, JobKind::Query { crawl_id: 2, selector: "h1".to_string() });
+        assert!(Job::claim_next(DEFAULT_CONCURRENCY_BUDGET, &db).unwrap().is_none());
+
+        Job::finish(second.id.unwrap(), JobStatus::Failed, "boom", &db).unwrap();
+        let failed = Job::fetch(second.id.unwrap(), &db).unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(failed.message.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_cancel_requests() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+
+        let mut queued = Job::new(None, JobKind::Crawl { site_id: 1, max_concurrent: 1 });
+        queued.sync(&mut db).unwrap();
+        let mut running = Job::new(None, JobKind::Crawl { site_id: 2, max_concurrent: 1 });
+        running.sync(&mut db).unwrap();
+        let (queued_id, running_id) = (queued.id.unwrap(), running.id.unwrap());
+        db.conn
+            .execute("UPDATE jobs SET status = 'running' WHERE id = ?1", params![running_id])
+            .unwrap();
+
+        assert_eq!(Job::request_cancel(queued_id, &db).unwrap(), JobStatus::Cancelled);
+        assert!(Job::claim_next(DEFAULT_CONCURRENCY_BUDGET, &db).unwrap().is_none());
+
+        assert_eq!(Job::request_cancel(running_id, &db).unwrap(), JobStatus::Running);
+        assert!(Job::is_cancel_requested(running_id, &db).unwrap());
+
+        assert!(Job::request_cancel(queued_id, &db).is_err());
+    }
+
+    #[test]
+    fn test_claims_follow_priority_and_budget() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+
+        fn submit(db: &mut Database, kind: JobKind, priority: i64) -> i64 {
+            let mut job = Job::new(None, kind).with_priority(priority);
+            job.sync(db).unwrap();
+            job.id.unwrap()
+        }
+        let low = submit(&mut db, JobKind::Crawl { site_id: 1, max_concurrent: 4 }, 0);
+        let same_site = submit(&mut db, JobKind::Crawl { site_id: 2, max_concurrent: 2 }, 1);
+        let high = submit(&mut db, JobKind::Crawl { site_id: 2, max_concurrent: 4 }, 5);
+        let query = submit(&mut db, JobKind::Query { crawl_id: 1, selector: "h1".to_string() }, -1);
+
+        let claim = |db: &Database| Job::claim_next(6, db).unwrap().map(|job| job.id.unwrap());
+        assert_eq!(claim(&db), Some(high));
+        // Site 2 is busy, and site 1 needs more than the 2 left in the budget
+        assert_eq!(claim(&db), Some(query));
+        assert_eq!(claim(&db), None);
+
+        Job::finish(high, JobStatus::Succeeded, "", &db).unwrap();
+        assert_eq!(claim(&db), Some(same_site));
+        assert_eq!(claim(&db), Some(low));
+
+        // A crawl larger than the budget still runs when nothing else does
+        let huge = submit(&mut db, JobKind::Crawl { site_id: 3, max_concurrent: 20 }, 0);
+        assert_eq!(claim(&db), None);
+        Job::finish(same_site, JobStatus::Succeeded, "", &db).unwrap();
+        Job::finish(low, JobStatus::Succeeded, "", &db).unwrap();
+        assert_eq!(claim(&db), Some(huge));
+    }
+}
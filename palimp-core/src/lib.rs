@@ -1,48 +1,380 @@
 pub mod http_client;
+pub mod http_cache;
+pub mod clock;
 pub mod page;
 pub mod page_archive;
+pub mod blob;
 pub mod sitemap;
 pub mod crawl;
 pub mod site;
 pub mod database;
 pub mod result_entry;
 pub mod query;
+pub mod sitemap_image;
+pub mod finding;
+pub mod audit;
+pub mod third_party;
+pub mod diff;
+pub mod monitor;
+pub mod alert;
+pub mod search;
+pub mod suite;
+pub mod template;
+pub mod crawl_error;
+pub mod dashboard;
+pub mod throttle;
+pub mod dns;
+pub mod secrets;
+pub mod credential;
+pub mod redirect;
+pub mod frontier;
+pub mod crawl_lock;
+pub mod jobs;
+pub mod import;
+pub mod query_history;
+pub mod comparison;
+pub mod export;
+pub mod url_list;
+pub mod preview;
+pub mod estimate;
+pub mod quota;
+pub mod storage_report;
+pub mod page_history;
+pub mod expression;
+pub mod sql;
+pub mod trash;
+pub mod audit_log;
+pub mod site_config;
+pub mod manifest;
+pub mod assertion;
+pub mod site_rule;
+pub mod sitemap_change;
+pub mod link_graph;
+pub mod link_crawl;
+pub mod crawl_file;
+pub mod crawl_meta;
+pub mod site_tag;
+pub mod result_match;
+pub mod suggestion;
+pub mod asset;
+pub mod crawl_stats;
+pub mod robots;
+pub mod skipped_url;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "google-sheets")]
+pub mod google_sheets;
 
-use http_client::HTTPClient;
+use http_client::{HTTPClient, HTTPClientBuilder};
 use page::Page;
-use sitemap::Sitemap;
+use sitemap::{SitemapLimits, SitemapStream};
 use std::error::Error;
+use std::fmt;
 use database::Database;
-use site::Site;
-use crawl::Crawl;
+use site::{FetchOverride, Site, SiteValidation};
+use crawl::{Crawl, CrawlMode, CrawlOptions, CrawlStatus, SiteCrawl};
 use query::Query;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use tokio::sync::Mutex;
+use futures::future;
 use futures::stream::{self, StreamExt};
 use page_archive::PageArchive;
-use result_entry::ResultEntry;
-use rusqlite::params;
+use result_entry::{ResultEntry, ResultFilter, ResultStatus};
+use sitemap_image::SitemapImage;
+use finding::Finding;
+use audit::Audit;
+use third_party::DomainUsage;
+use diff::ChangedRegion;
+use monitor::{Monitor, MonitorKind};
+use alert::Alert;
+use search::SearchHit;
+use suite::{PageSummary, Suite, SuiteCheck};
+use std::collections::{BTreeMap, HashSet};
+use crawl_error::{CrawlError, CrawlErrorKind};
+use dashboard::SiteDashboard;
+use throttle::{HostLimiters, Permit, RequestPacer};
+use secrets::SecretStore;
+use credential::SiteCredential;
+use frontier::FrontierUrl;
+use crawl_lock::{CrawlLock, HEARTBEAT_INTERVAL_SECS};
+use std::future::Future;
+use std::time::Duration;
+use jobs::{Job, JobKind, JobStatus};
+use std::path::Path;
+use query_history::QueryHistory;
+use comparison::SiteComparison;
+use export::ExportOptions;
+use preview::SelectorPreview;
+use estimate::CrawlEstimate;
+use quota::ArchiveUsage;
+use storage_report::StorageReport;
+use page_history::PageHistory;
+use expression::SelectorExpression;
+use sql::SqlRows;
+use trash::{PurgeCounts, TrashEntry, TrashKind};
+use audit_log::{AuditAction, AuditEvent};
+use site_config::{ConfigImport, SiteConfig};
+use manifest::{CrawlManifest, ManifestMismatch, QueryReplay};
+use assertion::{AssertionReport, RuleSet};
+use site_rule::{RuleOutcomeRecord, SiteRule};
+use sitemap_change::SitemapChange;
+use link_graph::{LinkGraph, LinkMetric};
+use link_crawl::LinkFollowing;
+use crawl_file::{CrawlFileCounts, ImportedCrawl};
+use crawl_meta::CrawlMeta;
+use result_match::{MatchChange, ResultMatch};
+use suggestion::SelectorSuggestion;
+use asset::{Asset, AssetLimits};
+use crawl_stats::CrawlStats;
+use robots::RobotsTxt;
+use skipped_url::SkippedUrl;
+#[cfg(feature = "google-sheets")]
+use google_sheets::{SheetTarget, SheetWrite, SheetsClient};
+
+/// Returned by methods that would crawl into or change an archive opened
+/// with [`Application::with_read_only`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOnlyError;
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the archive is open read-only")
+    }
+}
+
+impl Error for ReadOnlyError {}
 
 pub struct Application {
     pub db: Arc<Mutex<Database>>,
     pub http_client: HTTPClient,
+    /// Set once the secrets passphrase has been given, see `unlock_secrets`.
+    secrets: Mutex<Option<Arc<SecretStore>>>,
+    read_only: bool,
+    /// Who the audit log credits with deletions and crawl starts.
+    actor: Option<String>,
 }
 
 impl Application {
     pub fn new(db_path: &str) -> Result<Self, Box<dyn Error>> {
-        let db = Database::new(db_path)?;
+        Self::with_http_client(db_path, HTTPClient::new()?)
+    }
+
+    /// Opens the archive with a custom-tuned client, see [`HTTPClient::builder`].
+    pub fn with_http_client(db_path: &str, http_client: HTTPClient) -> Result<Self, Box<dyn Error>> {
+        Self::with_database(Database::new(db_path)?, http_client)
+    }
+
+    /// Runs on an already opened database, e.g. an in-memory one a test
+    /// seeded or opened with a fake clock. The schema is created if missing.
+    pub fn with_database(db: Database, http_client: HTTPClient) -> Result<Self, Box<dyn Error>> {
         db.seed()?;
-        let http_client = HTTPClient::new()?;
 
         Ok(Self {
             db: Arc::new(Mutex::new(db)),
             http_client,
+            secrets: Mutex::new(None),
+            read_only: false,
+            actor: None,
         })
     }
 
-    pub async fn new_site(&self, domain: &str, sitemap_url: &str) -> Result<(), Box<dyn Error>> {
+    /// Names who is using the archive in the audit log, e.g. the OS user.
+    pub fn with_actor(mut self, actor: &str) -> Self {
+        self.actor = Some(actor.to_string());
+        self
+    }
+
+    /// Lets the archive be listed, queried, exported and its pages viewed,
+    /// while crawls, imports, deletions and settings changes fail with a
+    /// [`ReadOnlyError`]. Queries still store their results.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), Box<dyn Error>> {
+        if self.read_only {
+            return Err(ReadOnlyError.into());
+        }
+        Ok(())
+    }
+
+    /// Unlocks encrypted site credentials for this session. The first call on
+    /// an archive sets its passphrase.
+    pub async fn unlock_secrets(&self, passphrase: &str) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let salt = {
+            let db = self.db.lock().await;
+            SecretStore::salt(&db)?
+        };
+        // Deriving takes a while, hold neither the database nor a runtime thread
+        let passphrase = passphrase.to_string();
+        let (store, salt) =
+            tokio::task::spawn_blocking(move || SecretStore::derive(&passphrase, &salt).map(|store| (store, salt)))
+                .await??;
+        let store = {
+            let db = self.db.lock().await;
+            store.verify(&salt, &db)?
+        };
+        *self.secrets.lock().await = Some(Arc::new(store));
+        Ok(())
+    }
+
+    async fn unlocked_secrets(&self) -> Result<Arc<SecretStore>, Box<dyn Error>> {
+        self.secrets
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| "Secrets are locked, provide the secrets passphrase first".into())
+    }
+
+    /// Stores a header sent with every request for the site, encrypted at rest.
+    pub async fn set_site_credential(&self, site_id: i64, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let secrets = self.unlocked_secrets().await?;
+        let mut db = self.db.lock().await;
+        let mut credential = SiteCredential::new(None, site_id, &name.to_ascii_lowercase(), value, &secrets)?;
+        credential.sync(&mut db)
+    }
+
+    /// Names of the site's stored credentials; values stay encrypted.
+    pub async fn list_site_credentials(&self, site_id: i64) -> Result<Vec<String>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let credentials = SiteCredential::fetch_by_site(site_id, &db)?;
+        Ok(credentials.into_iter().map(|credential| credential.name).collect())
+    }
+
+    pub async fn delete_site_credential(&self, site_id: i64, name: &str) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        SiteCredential::delete_by_site_and_name(site_id, &name.to_ascii_lowercase(), &db)
+    }
+
+    /// Stores a new site once its domain and sitemap URL are well-formed and
+    /// its sitemap can be fetched and parsed. A site with the same domain and
+    /// sitemap is returned as it is instead of being created again.
+    pub async fn new_site(&self, domain: &str, sitemap_url: &str) -> Result<Site, Box<dyn Error>> {
+        self.new_site_with_validation(domain, sitemap_url, SiteValidation::Full).await
+    }
+
+    /// Stores a new site after the checks `validation` asks for, or returns
+    /// the existing one. A refused site fails with a
+    /// [`site::SiteValidationError`].
+    pub async fn new_site_with_validation(
+        &self,
+        domain: &str,
+        sitemap_url: &str,
+        validation: SiteValidation,
+    ) -> Result<Site, Box<dyn Error>> {
+        self.check_writable()?;
+        let site = Site::new(None, domain.trim(), sitemap_url.trim());
+        {
+            let db = self.db.lock().await;
+            if let Some(existing) = Site::fetch_by_domain_and_sitemap(&site.domain, &site.sitemap_url, &db)? {
+                return Ok(existing);
+            }
+        }
+        if validation != SiteValidation::Skip {
+            site.validate_format()?;
+        }
+        if validation == SiteValidation::Full {
+            site.verify_sitemap(&self.http_client).await?;
+        }
+
         let mut db = self.db.lock().await;
-        new_site(domain, sitemap_url, &mut db).await
+        new_site(site, &mut db).await
+    }
+
+    /// Changes a site's domain, sitemap URL or both, keeping its crawls. The
+    /// new values must be well-formed and not belong to another site.
+    pub async fn update_site(
+        &self,
+        site_id: i64,
+        domain: Option<&str>,
+        sitemap_url: Option<&str>,
+    ) -> Result<Site, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut site = Site::fetch(site_id, &db)?;
+        if let Some(domain) = domain {
+            site.domain = domain.trim().to_string();
+        }
+        if let Some(sitemap_url) = sitemap_url {
+            site.sitemap_url = sitemap_url.trim().to_string();
+        }
+        site.validate_format()?;
+
+        if let Some(other) = Site::fetch_by_domain_and_sitemap(&site.domain, &site.sitemap_url, &db)?
+            && other.id != site.id
+        {
+            return Err(format!("Site {} already has this domain and sitemap", other.id.unwrap_or(0)).into());
+        }
+        site.sync(&mut db)?;
+        Ok(site)
+    }
+
+    /// Toggles accepting invalid TLS certificates when crawling a site.
+    pub async fn set_site_insecure_tls(&self, site_id: i64, insecure_tls: bool) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut site = Site::fetch(site_id, &db)?;
+        site.insecure_tls = insecure_tls;
+        site.sync(&mut db)
+    }
+
+    /// Sets or clears the IP address or base URL a site's pages are fetched
+    /// from, see [`FetchOverride`].
+    pub async fn set_site_fetch_override(&self, site_id: i64, fetch_override: Option<&str>) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        if let Some(value) = fetch_override {
+            FetchOverride::parse(value)?;
+        }
+
+        let mut db = self.db.lock().await;
+        let mut site = Site::fetch(site_id, &db)?;
+        site.fetch_override = fetch_override.map(|value| value.trim().to_string());
+        site.sync(&mut db)
+    }
+
+    /// Overrides the soft 404 audit's thin page threshold and error phrases
+    /// for a site. `None` restores the default.
+    pub async fn set_site_soft_404_heuristics(
+        &self,
+        site_id: i64,
+        min_text_bytes: Option<usize>,
+        phrases: Option<Vec<String>>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut site = Site::fetch(site_id, &db)?;
+        site.soft_404_min_text_bytes = min_text_bytes.map(|bytes| bytes as i64);
+        site.soft_404_phrases = phrases.map(|phrases| phrases.join("\n"));
+        site.sync(&mut db)
+    }
+
+    /// Limits the requests a crawl of the site starts per second and sets a
+    /// minimum delay between them, however many pages are fetched at once.
+    /// The stricter of the two applies; `None` lifts it.
+    pub async fn set_site_politeness(
+        &self,
+        site_id: i64,
+        max_requests_per_sec: Option<f64>,
+        min_delay_ms: Option<u64>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut site = Site::fetch(site_id, &db)?;
+        site.max_requests_per_sec = max_requests_per_sec;
+        site.min_request_delay_ms = min_delay_ms.map(|ms| ms.min(i64::MAX as u64) as i64);
+        site.validate_politeness()?;
+        site.sync(&mut db)
     }
 
     pub async fn list_sites(&self) -> Result<Vec<Site>, Box<dyn Error>> {
@@ -50,9 +382,91 @@ impl Application {
         list_sites(&db).await
     }
 
+    /// Tags a site, e.g. with the client it belongs to, to crawl its sites
+    /// together. Returns false if it already had the tag.
+    pub async fn tag_site(&self, site_id: i64, tag: &str) -> Result<bool, Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        Site::fetch(site_id, &db)?;
+        site_tag::add(site_id, tag, &db)
+    }
+
+    /// Returns false if the site didn't have the tag.
+    pub async fn untag_site(&self, site_id: i64, tag: &str) -> Result<bool, Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        site_tag::remove(site_id, tag, &db)
+    }
+
+    pub async fn list_site_tags(&self, site_id: i64) -> Result<Vec<String>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        site_tag::fetch_by_site(site_id, &db)
+    }
+
+    /// The sites with their settings and monitors, and the user-defined
+    /// suites, to reproduce the setup elsewhere. See [`SiteConfig`].
+    pub async fn export_config(&self) -> Result<SiteConfig, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        SiteConfig::build(&db)
+    }
+
+    /// Creates or updates what a config describes, see [`SiteConfig::apply`].
+    pub async fn import_config(&self, config: &SiteConfig) -> Result<ConfigImport, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        config.apply(&mut db)
+    }
+
+    /// Moves the site and its crawls to the trash, from where
+    /// [`Self::restore`] brings them back until they are purged.
     pub async fn delete_site(&self, site_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| delete_site(site_id, self.actor.as_deref(), db))
+    }
+
+    /// Latest crawl stats, per-crawl trend and recent query summaries of a site.
+    pub async fn site_dashboard(&self, site_id: i64) -> Result<SiteDashboard, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        SiteDashboard::fetch(site_id, &db)
+    }
+
+    /// URLs of a crawl that could not be archived, with their error kind.
+    pub async fn list_crawl_errors(&self, crawl_id: i64) -> Result<Vec<CrawlError>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        CrawlError::fetch_by_crawl_id(crawl_id, &db)
+    }
+
+    /// URLs a crawl left out without requesting them, e.g. those disallowed
+    /// by robots.txt, with the reason.
+    pub async fn list_skipped_urls(&self, crawl_id: i64) -> Result<Vec<SkippedUrl>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        SkippedUrl::fetch_by_crawl_id(crawl_id, &db)
+    }
+
+    /// Describes a crawl for documentation and later integrity checks.
+    pub async fn crawl_manifest(&self, crawl_id: i64) -> Result<CrawlManifest, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        CrawlManifest::build(crawl_id, &db)
+    }
+
+    /// What changed in the archive since the manifest was written.
+    pub async fn verify_manifest(&self, manifest: &CrawlManifest) -> Result<Vec<ManifestMismatch>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        manifest.verify(&db)
+    }
+
+    /// Re-runs the queries recorded in a manifest and compares their results
+    /// with the recorded ones. Nothing is saved, so it works read-only.
+    pub async fn replay_manifest(&self, manifest: &CrawlManifest) -> Result<Vec<QueryReplay>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        manifest.replay(&db)
+    }
+
+    /// URLs that entered or left the sitemap since the site's previous crawl.
+    pub async fn list_sitemap_changes(&self, crawl_id: i64) -> Result<Vec<SitemapChange>, Box<dyn Error>> {
         let db = self.db.lock().await;
-        delete_site(site_id, &db).await
+        SitemapChange::fetch_by_crawl_id(crawl_id, &db)
     }
 
     pub async fn list_crawls(&self) -> Result<Vec<Crawl>, Box<dyn Error>> {
@@ -61,8 +475,81 @@ impl Application {
     }
 
     pub async fn delete_crawl(&self, crawl_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| delete_crawl(crawl_id, self.actor.as_deref(), db))
+    }
+
+    /// Moves several crawls to the trash in one transaction: either all of
+    /// them go or none.
+    pub async fn delete_crawls(&self, crawl_ids: &[i64]) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| crawl_ids.iter().try_for_each(|&id| delete_crawl(id, self.actor.as_deref(), db)))
+    }
+
+    /// Deleted sites, crawls, queries and results that can be restored.
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        trash::list(&db)
+    }
+
+    /// Brings back a deleted row along with everything deleted with it.
+    pub async fn restore(&self, kind: TrashKind, id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        trash::restore(kind, id, &db)
+    }
+
+    /// Removes deleted rows for good, or only those deleted at least
+    /// `older_than` ago.
+    pub async fn purge_trash(&self, older_than: Option<Duration>) -> Result<PurgeCounts, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let counts = trash::purge(older_than, &mut db)?;
+        let mut detail = format!(
+            "{} sites, {} crawls, {} queries, {} results",
+            counts.sites, counts.crawls, counts.queries, counts.results
+        );
+        if let Some(older_than) = older_than {
+            detail.push_str(&format!(" deleted over {}s ago", older_than.as_secs()));
+        }
+        AuditEvent::new(self.actor.as_deref(), AuditAction::PurgeTrash, None, &detail).sync(&mut db)?;
+        Ok(counts)
+    }
+
+    /// The latest `limit` deletions and crawl starts, newest first.
+    pub async fn list_audit_log(&self, action: Option<AuditAction>, limit: usize) -> Result<Vec<AuditEvent>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        AuditEvent::fetch_recent(action, limit, &db)
+    }
+
+    /// Keeps a crawl from being pruned by the archive quota.
+    pub async fn set_crawl_pinned(&self, crawl_id: i64, pinned: bool) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
         let db = self.db.lock().await;
-        delete_crawl(crawl_id, &db).await
+        Crawl::set_pinned(crawl_id, pinned, &db)
+    }
+
+    /// Caps the size of archived pages; `None` lifts the cap. Crawls over it
+    /// are pruned once the next crawl completes, or by [`Self::prune_archive`].
+    pub async fn set_archive_quota(&self, quota: Option<u64>) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        quota::set_quota(quota, &db)
+    }
+
+    pub async fn archive_usage(&self) -> Result<ArchiveUsage, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        ArchiveUsage::fetch(&db)
+    }
+
+    /// Prunes the oldest unpinned crawls until the archive fits its quota,
+    /// returning their IDs. See [`quota::prune`].
+    pub async fn prune_archive(&self) -> Result<Vec<i64>, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        quota::prune(&mut db)
     }
 
     pub async fn list_queries(&self) -> Result<Vec<Query>, Box<dyn Error>> {
@@ -70,9 +557,38 @@ impl Application {
         list_queries(&db).await
     }
 
+    /// Renames a stored query and sets its description. Blank values clear
+    /// them, `None` leaves them unchanged.
+    pub async fn describe_query(
+        &self,
+        query_id: i64,
+        name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<Query, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut query = Query::fetch(query_id, &db)?;
+        if let Some(name) = name {
+            query.name = non_blank(Some(name)).map(str::to_string);
+        }
+        if let Some(description) = description {
+            query.description = non_blank(Some(description)).map(str::to_string);
+        }
+        query.sync(&mut db)?;
+        Ok(query)
+    }
+
     pub async fn delete_query(&self, query_id: i64) -> Result<(), Box<dyn Error>> {
-        let db = self.db.lock().await;
-        delete_query(query_id, &db).await
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| delete_query(query_id, self.actor.as_deref(), db))
+    }
+
+    /// Moves several queries to the trash in one transaction.
+    pub async fn delete_queries(&self, query_ids: &[i64]) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| query_ids.iter().try_for_each(|&id| delete_query(id, self.actor.as_deref(), db)))
     }
 
     pub async fn list_results(&self) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
@@ -82,80 +598,814 @@ impl Application {
     
     pub async fn list_results_for_query(&self, query_id: i64) -> Result<Vec<(ResultEntry, String)>, Box<dyn Error>> {
         let db = self.db.lock().await;
-        
-        let query = Query::fetch(query_id, &db)?;
-        
-        let results = ResultEntry::fetch_by_crawl_and_selector(query.crawl_id, &query.selector, &db)?;
-        
-        let mut enriched_results = Vec::new();
-        for res in results { 
-             let page_url: String = db.conn.query_row(
-                "SELECT url FROM pages WHERE id = ?1",
-                params![res.page_id],
-                |row| row.get(0)
-             )?;
-             
-             enriched_results.push((res, page_url));
+        Query::fetch(query_id, &db)?;
+        ResultEntry::search(&ResultFilter::for_query(query_id), &db)
+    }
+
+    /// Stored results matching `filter`, each with the URL of its page.
+    pub async fn search_results(&self, filter: &ResultFilter) -> Result<Vec<(ResultEntry, String)>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        ResultEntry::search(filter, &db)
+    }
+
+    /// Where on its page each element of a result was matched.
+    pub async fn list_result_matches(&self, result_id: i64) -> Result<Vec<ResultMatch>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        ResultEntry::fetch(result_id, &db).map_err(|e| format!("Result {} not found: {}", result_id, e))?;
+        ResultMatch::fetch_by_result_id(result_id, &db)
+    }
+
+    /// Writes the results of a query as CSV, returning the number of rows.
+    pub async fn export_query_results(
+        &self,
+        query_id: i64,
+        options: &ExportOptions,
+        writer: impl std::io::Write,
+    ) -> Result<usize, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        export::write_results(query_id, options, &db, writer)
+    }
+
+    /// Writes the results of the latest query of a selector on a crawl as
+    /// CSV, returning the number of rows.
+    pub async fn export_results(
+        &self,
+        crawl_id: i64,
+        selector: &str,
+        options: &ExportOptions,
+        writer: impl std::io::Write,
+    ) -> Result<usize, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let query = Query::fetch_latest(crawl_id, selector, &db)?
+            .ok_or_else(|| format!("No query of '{}' on crawl {}", selector, crawl_id))?;
+        export::write_results(query.id.ok_or("Query without an ID")?, options, &db, writer)
+    }
+
+    /// Writes the results of a query to a Google Sheet, returning the number
+    /// of rows.
+    #[cfg(feature = "google-sheets")]
+    pub async fn push_query_results_to_sheet(
+        &self,
+        query_id: i64,
+        options: &ExportOptions,
+        sheets: &SheetsClient,
+        target: &SheetTarget,
+        mode: SheetWrite,
+    ) -> Result<usize, Box<dyn Error>> {
+        let table = {
+            let db = self.db.lock().await;
+            export::result_table(query_id, options, &db)?
+        };
+        sheets.write(target, &table, mode).await
+    }
+
+    pub async fn delete_result(&self, result_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        delete_result(result_id, &db).await
+    }
+
+    /// Moves several results to the trash in one transaction.
+    pub async fn delete_results(&self, result_ids: &[i64]) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| result_ids.iter().try_for_each(|&id| ResultEntry::delete(id, db)))
+    }
+
+    /// Marks results as new, reviewed or ignored, returning how many were
+    /// found.
+    pub async fn set_result_status(&self, result_ids: &[i64], status: ResultStatus) -> Result<usize, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| ResultEntry::set_status(result_ids, status, db))
+    }
+
+    /// Marks every result matching `filter`, e.g. all new results of a query,
+    /// returning how many were updated.
+    pub async fn set_matching_result_status(
+        &self,
+        filter: &ResultFilter,
+        status: ResultStatus,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        db.in_transaction(|db| {
+            let ids: Vec<i64> = ResultEntry::search(filter, db)?
+                .into_iter()
+                .filter_map(|(entry, _)| entry.id)
+                .collect();
+            ResultEntry::set_status(&ids, status, db)
+        })
+    }
+
+    /// Fetches the site's sitemap and projects what crawling it would cost,
+    /// without fetching any page. See [`CrawlEstimate`].
+    pub async fn estimate_crawl(&self, site_id: i64) -> Result<CrawlEstimate, Box<dyn Error>> {
+        let secrets = self.secrets.lock().await.clone();
+        estimate_crawl(site_id, &self.db, &self.http_client, secrets).await
+    }
+
+    pub async fn new_crawl<F>(&self, site_id: i64, max_concurrent: usize, on_update: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.new_crawl_with_options(site_id, CrawlOptions::new(max_concurrent), on_update).await
+    }
+
+    /// Crawls the site's sitemap, or follows links from seed URLs when the
+    /// options' [`CrawlMode`] says so.
+    pub async fn new_crawl_with_options<F>(
+        &self,
+        site_id: i64,
+        options: CrawlOptions,
+        on_update: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.check_writable()?;
+        let secrets = self.secrets.lock().await.clone();
+        match &options.mode {
+            CrawlMode::Sitemap => {
+                let crawl = new_crawl(site_id, self.db.clone(), &self.http_client, secrets, &options, on_update);
+                let started = AuditEvent::new(self.actor.as_deref(), AuditAction::StartCrawl, Some(site_id), "sitemap");
+                with_crawl_lock(site_id, &self.db, started, crawl).await
+            }
+            CrawlMode::FollowLinks(following) => {
+                let crawl = new_link_crawl(site_id, following, self.db.clone(), &self.http_client, secrets, &options, on_update);
+                let detail = format!("links, {} levels deep", following.max_depth);
+                let started = AuditEvent::new(self.actor.as_deref(), AuditAction::StartCrawl, Some(site_id), &detail);
+                with_crawl_lock(site_id, &self.db, started, crawl).await
+            }
+        }
+    }
+
+    /// Crawls every site, or those with `tag`, several at a time: the sum of
+    /// `max_concurrent` over the crawls running at once stays within
+    /// `budget`, though one crawl always runs. Updates come with the ID of
+    /// the site they're about. A site whose crawl fails doesn't stop the
+    /// others; once the options' stop signal fires, sites not yet started
+    /// are skipped.
+    pub async fn new_crawls<F>(
+        &self,
+        tag: Option<&str>,
+        options: CrawlOptions,
+        budget: usize,
+        on_update: F,
+    ) -> Result<Vec<SiteCrawl>, Box<dyn Error>>
+    where
+        F: Fn(i64, CrawlResult) + Send + Sync + 'static,
+    {
+        self.check_writable()?;
+        if let CrawlMode::FollowLinks(following) = &options.mode
+            && !following.seeds.is_empty()
+        {
+            return Err("Seed URLs belong to one site, they can't be used when crawling several".into());
+        }
+        let sites = {
+            let db = self.db.lock().await;
+            match tag {
+                Some(tag) => {
+                    let ids = site_tag::site_ids(tag, &db)?;
+                    ids.into_iter().map(|id| Site::fetch(id, &db)).collect::<Result<Vec<_>, _>>()?
+                }
+                None => list_sites(&db).await?,
+            }
+        };
+
+        let slots = (budget / options.max_concurrent.max(1)).max(1);
+        let on_update = Arc::new(on_update);
+        let mut crawls = stream::iter(sites)
+            .map(|site| self.crawl_one_of_many(site, options.clone(), Arc::clone(&on_update)))
+            .buffer_unordered(slots)
+            .collect::<Vec<SiteCrawl>>()
+            .await;
+        crawls.sort_by_key(|crawl| crawl.site_id);
+        Ok(crawls)
+    }
+
+    async fn crawl_one_of_many<F>(&self, site: Site, options: CrawlOptions, on_update: Arc<F>) -> SiteCrawl
+    where
+        F: Fn(i64, CrawlResult) + Send + Sync + 'static,
+    {
+        let site_id = site.id.unwrap_or(0);
+        let mut summary = SiteCrawl {
+            site_id,
+            domain: site.domain,
+            crawl_id: None,
+            pages_succeeded: 0,
+            pages_failed: 0,
+            error: None,
+        };
+        if options.stop.is_stopped() {
+            summary.error = Some("Not started, the crawls were stopped".to_string());
+            return summary;
+        }
+
+        // The crawl is only known by its ID once it's been created
+        let latest_id = |db: &Database| {
+            Crawl::fetch_previous(site_id, i64::MAX, db).ok().flatten().and_then(|crawl| crawl.id)
+        };
+        let before = {
+            let db = self.db.lock().await;
+            latest_id(&db)
+        };
+        let succeeded = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicUsize::new(0));
+        let result = {
+            let succeeded = Arc::clone(&succeeded);
+            let failed = Arc::clone(&failed);
+            self.new_crawl_with_options(site_id, options, move |update| {
+                match &update {
+                    CrawlResult::PageSucceeded(_) => succeeded.fetch_add(1, Ordering::Relaxed),
+                    CrawlResult::PageFailed(_, _) => failed.fetch_add(1, Ordering::Relaxed),
+                    _ => 0,
+                };
+                on_update(site_id, update);
+            })
+            .await
+        };
+
+        let after = {
+            let db = self.db.lock().await;
+            latest_id(&db)
+        };
+        if after != before {
+            summary.crawl_id = after;
+        }
+        summary.pages_succeeded = succeeded.load(Ordering::Relaxed);
+        summary.pages_failed = failed.load(Ordering::Relaxed);
+        summary.error = result.err().map(|e| e.to_string());
+        summary
+    }
+
+    /// Crawls a list of URLs instead of the site's sitemap, e.g. a handful
+    /// of pages for an ad-hoc audit. See [`url_list::parse`].
+    pub async fn new_crawl_from_urls<F>(
+        &self,
+        site_id: i64,
+        urls: Vec<String>,
+        options: CrawlOptions,
+        on_update: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.check_writable()?;
+        let secrets = self.secrets.lock().await.clone();
+        let detail = format!("{} listed URLs", urls.len());
+        let crawl = new_crawl_from_urls(site_id, urls, self.db.clone(), &self.http_client, secrets, &options, on_update);
+        let started = AuditEvent::new(self.actor.as_deref(), AuditAction::StartCrawl, Some(site_id), &detail);
+        with_crawl_lock(site_id, &self.db, started, crawl).await
+    }
+
+    /// Continues a crawl that was cut short, fetching the URLs still in its
+    /// frontier. An unfinished crawl with an empty frontier, e.g. from before
+    /// frontiers were kept, re-reads the sitemap and fetches the URLs it has
+    /// no page or error for.
+    pub async fn resume_crawl<F>(&self, crawl_id: i64, options: CrawlOptions, on_update: F) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static,
+    {
+        self.check_writable()?;
+        let site_id = {
+            let db = self.db.lock().await;
+            Crawl::fetch(crawl_id, &db)?.site_id
+        };
+        let secrets = self.secrets.lock().await.clone();
+        let crawl = resume_crawl(crawl_id, self.db.clone(), &self.http_client, secrets, &options, on_update);
+        let detail = format!("resume of crawl {}", crawl_id);
+        let started = AuditEvent::new(self.actor.as_deref(), AuditAction::StartCrawl, Some(site_id), &detail);
+        with_crawl_lock(site_id, &self.db, started, crawl).await
+    }
+
+    /// The key/value pairs a crawl was started with.
+    pub async fn crawl_meta(&self, crawl_id: i64) -> Result<CrawlMeta, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        crawl_meta::fetch(crawl_id, &db)
+    }
+
+    /// Writes a crawl with its site, pages and query results to a new
+    /// SQLite file, to share it without the rest of the archive.
+    pub async fn export_crawl_db(&self, crawl_id: i64, path: &Path) -> Result<CrawlFileCounts, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        crawl_file::export(crawl_id, &db, path)
+    }
+
+    /// Merges a crawl exported with `export_crawl_db` into the archive under
+    /// new IDs, attached to `site_id` or to the site it was exported from.
+    pub async fn import_crawl_db(&self, path: &Path, site_id: Option<i64>) -> Result<ImportedCrawl, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        crawl_file::import(path, site_id, &mut db)
+    }
+
+    /// Stores the HTML files of a local folder, e.g. a static export, as a
+    /// new crawl of a site. Returns the crawl ID and the number of pages.
+    pub async fn import_folder(&self, site_id: i64, dir: &Path) -> Result<(i64, usize), Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let site = Site::fetch(site_id, &db)?;
+        let pages = import::read_folder(dir, &site.base_url())?;
+        if pages.is_empty() {
+            return Err(format!("No HTML files found in {}", dir.display()).into());
+        }
+
+        let crawl_id = import::store(site_id, &pages, &mut db)?;
+        Ok((crawl_id, pages.len()))
+    }
+
+    /// Stores the HTML documents captured in a browser-exported HAR file as a
+    /// new crawl of a site. Returns the crawl ID and the number of pages.
+    pub async fn import_har(&self, site_id: i64, path: &Path) -> Result<(i64, usize), Box<dyn Error>> {
+        self.check_writable()?;
+        let content = std::fs::read_to_string(path)?;
+        let pages = import::read_har(&content)?;
+        if pages.is_empty() {
+            return Err(format!("No HTML documents found in {}", path.display()).into());
         }
+
+        let mut db = self.db.lock().await;
+        Site::fetch(site_id, &db)?;
+        let crawl_id = import::store(site_id, &pages, &mut db)?;
+        Ok((crawl_id, pages.len()))
+    }
+
+    /// Removes a site's crawl lock, for when the process holding it is gone
+    /// but the lock is not stale yet.
+    pub async fn unlock_site_crawls(&self, site_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        CrawlLock::delete(site_id, &db)
+    }
+
+    /// Gives up an interrupted crawl, or one cut short by a crash: the URLs
+    /// it had left are dropped and it is marked cancelled. Returns how many
+    /// were dropped. A crawl still running is stopped with
+    /// [`StopSignal::cancel`](crawl::StopSignal::cancel) instead.
+    pub async fn cancel_crawl(&self, crawl_id: i64) -> Result<usize, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let crawl = Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        if matches!(crawl.status, CrawlStatus::Completed | CrawlStatus::Cancelled) {
+            return Err(format!("Crawl {} is already {}", crawl_id, crawl.status.name()).into());
+        }
+
+        // Fails while a crawl of the site is running
+        let lock = CrawlLock::acquire(crawl.site_id, &db)?;
+        let dropped = db.in_transaction(|db| {
+            let dropped = FrontierUrl::clear(crawl_id, db)?;
+            Crawl::set_status(crawl_id, CrawlStatus::Cancelled, db)?;
+            Ok(dropped)
+        });
+        lock.release(&db)?;
+        dropped
+    }
+
+    /// Number of URLs a crawl has yet to fetch.
+    pub async fn pending_urls(&self, crawl_id: i64) -> Result<usize, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        FrontierUrl::count_by_crawl_id(crawl_id, &db)
+    }
+
+    /// Queues a job for a [`jobs::Worker`] to run. Higher priorities run first.
+    pub async fn submit_job(&self, kind: JobKind, priority: i64) -> Result<Job, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut job = Job::new(None, kind).with_priority(priority);
+        job.sync(&mut db)?;
+        Ok(job)
+    }
+
+    pub async fn set_job_priority(&self, job_id: i64, priority: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        Job::set_priority(job_id, priority, &db)
+    }
+
+    pub async fn list_jobs(&self, limit: usize) -> Result<Vec<Job>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Job::fetch_recent(limit, &db)
+    }
+
+    pub async fn fetch_job(&self, job_id: i64) -> Result<Job, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Job::fetch(job_id, &db)
+    }
+
+    /// Cancels a queued job, or asks the worker running it to stop.
+    pub async fn cancel_job(&self, job_id: i64) -> Result<JobStatus, Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        Job::request_cancel(job_id, &db)
+    }
+
+    /// URLs archived in a crawl, e.g. to pick a sample page from.
+    pub async fn list_page_urls(&self, crawl_id: i64) -> Result<Vec<String>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        PageArchive::fetch_urls_by_crawl_id(crawl_id, &db)
+    }
+
+    /// Stylesheets, scripts and JSON documents archived with a crawl started
+    /// with [`CrawlOptions::with_assets`], by URL.
+    pub async fn list_assets(&self, crawl_id: i64) -> Result<Vec<Asset>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        Asset::fetch_by_crawl_id(crawl_id, &db)
+    }
+
+    pub async fn fetch_asset(&self, crawl_id: i64, url: &str) -> Result<Asset, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Asset::fetch_by_crawl_and_url(crawl_id, url, &db)
+    }
+
+    /// Evaluates a selector against one archived page of a crawl without
+    /// storing anything, so a selector can be tried out before running it.
+    pub async fn preview_selector(&self, crawl_id: i64, url: &str, selector: &str) -> Result<SelectorPreview, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let archive = PageArchive::fetch_by_crawl_and_url(crawl_id, url, &db)?;
+        SelectorPreview::build(&archive.to_page()?, selector)
+    }
+
+    /// Selectors matching only the element with `example` in its text on an
+    /// archived page, each with the number of other pages of the crawl it
+    /// matches.
+    pub async fn suggest_selectors(&self, crawl_id: i64, url: &str, example: &str) -> Result<Vec<SelectorSuggestion>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        suggestion::suggest(crawl_id, url, example, &db)
+    }
+
+    /// Fetches one URL on demand and evaluates a selector against it, without
+    /// creating a crawl or archiving the page.
+    pub async fn preview(&self, url: &str, selector: &str) -> Result<SelectorPreview, Box<dyn Error>> {
+        SelectorPreview::fetch(&self.http_client, url, selector).await
+    }
+
+    /// Runs a selector, or a [`SelectorExpression`] combining several, on
+    /// every page of a crawl and stores the pages it matches.
+    pub async fn query(&self, crawl_id: i64, selector: &str) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
+        self.query_with_details(crawl_id, selector, None, None).await
+    }
+
+    /// Like [`Application::query`], saving the query under a name and
+    /// description so lists and reports don't show the bare selector.
+    pub async fn query_with_details(
+        &self,
+        crawl_id: i64,
+        selector: &str,
+        name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
+        SelectorExpression::parse(selector)?;
+        let mut db = self.db.lock().await;
         
-        Ok(enriched_results)
+        // Save the query definition
+        let mut q = Query::new(None, crawl_id, selector)
+            .with_name(non_blank(name))
+            .with_description(non_blank(description));
+        q.sync(&mut db)?;
+
+        query(q.id.ok_or("Query without an ID")?, crawl_id, selector, &mut db).await
+    }
+
+    /// Counts of a selector across every crawl of a site, oldest first.
+    pub async fn query_history(&self, site_id: i64, selector: &str) -> Result<QueryHistory, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Site::fetch(site_id, &db)?;
+        QueryHistory::build(site_id, selector, &db)
+    }
+
+    /// Runs a selector against the latest completed crawl of each site, or
+    /// of every site when `site_ids` is empty.
+    pub async fn compare_sites(&self, site_ids: &[i64], selector: &str) -> Result<Vec<SiteComparison>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let site_ids = if site_ids.is_empty() {
+            Site::fetch_all(&db)?.into_iter().filter_map(|site| site.id).collect()
+        } else {
+            site_ids.to_vec()
+        };
+        comparison::compare_sites(&site_ids, selector, &db)
+    }
+
+    /// Runs a read-only SQL statement against the archive.
+    pub async fn raw_query(&self, sql: &str) -> Result<SqlRows, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        sql::run(sql, &db)
+    }
+
+    /// Checks baseline rules against every page of a crawl; see
+    /// [`RuleSet::check`].
+    pub async fn run_assertions(&self, crawl_id: i64, rules: &RuleSet) -> Result<AssertionReport, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        rules.check(crawl_id, &db)
+    }
+
+    /// The links between the archived pages of a crawl.
+    pub async fn link_graph(&self, crawl_id: i64) -> Result<LinkGraph, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        LinkGraph::build(crawl_id, &db)
+    }
+
+    /// Inlinks, outlinks and centrality of a crawl's pages, best linked
+    /// first. Stored when a crawl completes; see `compute_link_metrics` for
+    /// imported crawls.
+    pub async fn list_link_metrics(&self, crawl_id: i64) -> Result<Vec<LinkMetric>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        LinkMetric::fetch_by_crawl_id(crawl_id, &db)
+    }
+
+    /// Computes and stores the link metrics of a crawl, returning the number
+    /// of pages.
+    pub async fn compute_link_metrics(&self, crawl_id: i64) -> Result<usize, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        Crawl::fetch(crawl_id, &db).map_err(|e| format!("Crawl {} not found: {}", crawl_id, e))?;
+        link_graph::record_metrics(crawl_id, &mut db)
+    }
+
+    pub async fn run_audit(&self, crawl_id: i64, audit: Audit, check_dead: bool) -> Result<Vec<Finding>, Box<dyn Error>> {
+        run_audit(crawl_id, audit, self.db.clone(), &self.http_client, check_dead).await
+    }
+
+    /// Raw and stored size of a crawl's pages, the bytes taken by bodies
+    /// repeated within the crawl or since the previous one, and the `top`
+    /// largest pages.
+    pub async fn storage_report(&self, crawl_id: i64, top: usize) -> Result<StorageReport, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let crawl = Crawl::fetch(crawl_id, &db)?;
+        let pages = PageArchive::fetch_by_crawl_id(crawl_id, &db)?;
+        let previous = match Crawl::fetch_previous(crawl.site_id, crawl_id, &db)? {
+            Some(previous) => PageArchive::fetch_by_crawl_id(previous.id.ok_or("Crawl without an ID")?, &db)?,
+            None => Vec::new(),
+        };
+        Ok(StorageReport::build(crawl_id, &pages, &previous, top))
+    }
+
+    pub async fn third_party_domains(&self, crawl_id: i64) -> Result<Vec<DomainUsage>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let crawl = Crawl::fetch(crawl_id, &db)?;
+        let site = Site::fetch(crawl.site_id, &db)?;
+        let pages = PageArchive::fetch_by_crawl_id(crawl_id, &db)?;
+
+        Ok(third_party::aggregate(&site.domain, &pages))
+    }
+
+    /// Structural diff of a URL between two crawls.
+    pub async fn diff_page(&self, url: &str, before_crawl_id: i64, after_crawl_id: i64) -> Result<Vec<ChangedRegion>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let before = PageArchive::fetch_by_crawl_and_url(before_crawl_id, url, &db)?;
+        let after = PageArchive::fetch_by_crawl_and_url(after_crawl_id, url, &db)?;
+
+        Ok(diff::dom_diff(&before.to_page()?, &after.to_page()?))
+    }
+
+    /// Unified diff of the visible text of a URL between two crawls.
+    pub async fn text_diff(&self, url: &str, before_crawl_id: i64, after_crawl_id: i64) -> Result<String, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let before = PageArchive::fetch_by_crawl_and_url(before_crawl_id, url, &db)?;
+        let after = PageArchive::fetch_by_crawl_and_url(after_crawl_id, url, &db)?;
+
+        Ok(diff::unified_diff(
+            &before.to_page()?.visible_text(),
+            &after.to_page()?.visible_text(),
+            &format!("{} (crawl {})", url, before_crawl_id),
+            &format!("{} (crawl {})", url, after_crawl_id),
+            3,
+        ))
+    }
+
+    /// How the matches of a selector on a URL moved, changed, disappeared or
+    /// appeared between two crawls, each of which has run the query.
+    pub async fn match_diff(
+        &self,
+        url: &str,
+        selector: &str,
+        before_crawl_id: i64,
+        after_crawl_id: i64,
+    ) -> Result<Vec<MatchChange>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let before = result_match::for_page(before_crawl_id, selector, url, &db)?;
+        let after = result_match::for_page(after_crawl_id, selector, url, &db)?;
+
+        Ok(result_match::diff(&before, &after))
+    }
+
+    /// Full-text search for a phrase within the pages of a crawl. Pages
+    /// archived before the index existed are indexed first, which an
+    /// archive open read-only refuses.
+    pub async fn search_pages(&self, crawl_id: i64, phrase: &str) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        if search::has_unindexed_pages(crawl_id, &db)? {
+            self.check_writable()?;
+            search::index_crawl(crawl_id, &db)?;
+        }
+        search::search(crawl_id, phrase, &db)
+    }
+
+    /// Every archived version of a URL across the crawls of a site, oldest
+    /// first, with failed fetches included.
+    pub async fn page_history(&self, site_id: i64, url: &str) -> Result<PageHistory, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Site::fetch(site_id, &db)?;
+        PageHistory::build(site_id, url, &db)
+    }
+
+    /// Built-in suites followed by user-defined ones.
+    pub async fn list_suites(&self) -> Result<Vec<Suite>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        let mut suites = Suite::built_in();
+        suites.extend(Suite::fetch_all(&db)?);
+        Ok(suites)
+    }
+
+    pub async fn new_suite(&self, name: &str, checks: Vec<SuiteCheck>) -> Result<Suite, Box<dyn Error>> {
+        self.check_writable()?;
+        if Suite::built_in().iter().any(|suite| suite.name == name) {
+            return Err(format!("'{}' is the name of a built-in suite", name).into());
+        }
+
+        let mut db = self.db.lock().await;
+        let mut suite = Suite::new(None, name, checks);
+        suite.sync(&mut db)?;
+        Ok(suite)
+    }
+
+    pub async fn delete_suite(&self, suite_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        Suite::delete(suite_id, &db)
+    }
+
+    /// Runs every check of a suite against a crawl and returns a combined
+    /// pass/fail summary per page.
+    pub async fn run_suite(&self, crawl_id: i64, suite_name: &str) -> Result<Vec<PageSummary>, Box<dyn Error>> {
+        let suite = self
+            .list_suites()
+            .await?
+            .into_iter()
+            .find(|suite| suite.name == suite_name)
+            .ok_or_else(|| format!("Unknown suite '{}'", suite_name))?;
+
+        let mut failures: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for audit in suite.audits() {
+            for finding in self.run_audit(crawl_id, audit, false).await? {
+                if audit.is_failure(&finding.rule) {
+                    failures
+                        .entry(finding.url)
+                        .or_default()
+                        .push(format!("{}/{}: {}", finding.audit, finding.rule, finding.detail));
+                }
+            }
+        }
+
+        let db = self.db.lock().await;
+        for archive in PageArchive::fetch_by_crawl_id(crawl_id, &db)? {
+            let page_failures = match archive.to_page() {
+                Ok(page) => suite.check_selectors(&page),
+                Err(e) => vec![format!("page could not be parsed: {}", e)],
+            };
+            failures.entry(archive.url.clone()).or_default().extend(page_failures);
+        }
+
+        Ok(failures
+            .into_iter()
+            .map(|(url, failures)| PageSummary { url, failures })
+            .collect())
+    }
+
+    pub async fn new_monitor(&self, site_id: i64, kind: MonitorKind, url_pattern: Option<&str>) -> Result<Monitor, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        let mut monitor = Monitor::new(None, site_id, kind, url_pattern);
+        monitor.sync(&mut db)?;
+        Ok(monitor)
+    }
+
+    pub async fn list_monitors(&self) -> Result<Vec<Monitor>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Monitor::fetch_all(&db)
+    }
+
+    pub async fn delete_monitor(&self, monitor_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
+        let db = self.db.lock().await;
+        Monitor::delete(monitor_id, &db)
+    }
+
+    /// Attaches rules to a site, checked after each of its crawls from now
+    /// on. Alerts are raised when a rule starts failing.
+    pub async fn add_site_rules(&self, site_id: i64, rules: &RuleSet) -> Result<Vec<SiteRule>, Box<dyn Error>> {
+        self.check_writable()?;
+        let mut db = self.db.lock().await;
+        Site::fetch(site_id, &db).map_err(|e| format!("Site {} not found: {}", site_id, e))?;
+        db.in_transaction(|db| {
+            let mut added = Vec::new();
+            for rule in &rules.rules {
+                let mut site_rule = SiteRule::new(None, site_id, rule.clone());
+                site_rule.sync(db)?;
+                added.push(site_rule);
+            }
+            Ok(added)
+        })
+    }
+
+    pub async fn list_site_rules(&self) -> Result<Vec<SiteRule>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        SiteRule::fetch_all(&db)
     }
 
-    pub async fn delete_result(&self, result_id: i64) -> Result<(), Box<dyn Error>> {
+    pub async fn delete_site_rule(&self, rule_id: i64) -> Result<(), Box<dyn Error>> {
+        self.check_writable()?;
         let db = self.db.lock().await;
-        delete_result(result_id, &db).await
+        SiteRule::delete(rule_id, &db)
     }
 
-    pub async fn new_crawl<F>(&self, site_id: i64, max_concurrent: usize, on_update: F) -> Result<(), Box<dyn Error>>
-    where
-        F: Fn(CrawlResult) + Send + Sync + 'static,
-    {
-        new_crawl(site_id, self.db.clone(), &self.http_client, max_concurrent, on_update).await
+    /// How each site rule fared on a crawl.
+    pub async fn list_rule_outcomes(&self, crawl_id: i64) -> Result<Vec<RuleOutcomeRecord>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        RuleOutcomeRecord::fetch_by_crawl_id(crawl_id, &db)
     }
 
-    pub async fn query(&self, crawl_id: i64, selector: &str) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
-        let mut db = self.db.lock().await;
-        
-        // Save the query definition
-        let mut q = Query::new(None, crawl_id, selector);
-        q.sync(&mut db)?;
+    pub async fn list_alerts(&self, crawl_id: Option<i64>) -> Result<Vec<Alert>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Alert::fetch_all(crawl_id, &db)
+    }
+
+    pub async fn list_findings(&self, crawl_id: i64, audit: Option<Audit>) -> Result<Vec<Finding>, Box<dyn Error>> {
+        let db = self.db.lock().await;
+        Finding::fetch_by_crawl(crawl_id, audit.map(|a| a.name()), &db)
+    }
 
-        query(crawl_id, selector, &mut db).await
+    /// Writes the findings of a crawl, optionally of one audit, to a Google
+    /// Sheet, returning the number of rows.
+    #[cfg(feature = "google-sheets")]
+    pub async fn push_findings_to_sheet(
+        &self,
+        crawl_id: i64,
+        audit: Option<Audit>,
+        sheets: &SheetsClient,
+        target: &SheetTarget,
+        mode: SheetWrite,
+    ) -> Result<usize, Box<dyn Error>> {
+        let findings = self.list_findings(crawl_id, audit).await?;
+        sheets.write(target, &export::finding_table(&findings), mode).await
     }
 }
 
 
-async fn new_site(domain: &str, sitemap_url: &str, mut db: &mut Database) -> Result<(), Box<dyn Error>> {
-    let mut site = Site::new(None, domain, sitemap_url);
+async fn new_site(mut site: Site, db: &mut Database) -> Result<Site, Box<dyn Error>> {
+    // Another process may have created it while the sitemap was checked
+    if let Some(existing) = Site::fetch_by_domain_and_sitemap(&site.domain, &site.sitemap_url, db)? {
+        return Ok(existing);
+    }
 
-    site.sync(&mut db)
+    site.sync(db)
         .map_err(|err| format!("Could not create site in the database: {}", err))?;
 
-    Ok(())
+    Ok(site)
 }
 
 async fn list_sites(db: &Database) -> Result<Vec<Site>, Box<dyn Error>> {
     Site::fetch_all(db)
 }
 
-async fn delete_site(site_id: i64, db: &Database) -> Result<(), Box<dyn Error>> {
-    Site::delete(site_id, db)
+/// Moves a site to the trash and records it in the audit log.
+fn delete_site(site_id: i64, actor: Option<&str>, db: &mut Database) -> Result<(), Box<dyn Error>> {
+    let site = Site::fetch(site_id, db)?;
+    Site::delete(site_id, db)?;
+    let detail = format!("{} ({})", site.domain, site.sitemap_url);
+    AuditEvent::new(actor, AuditAction::DeleteSite, Some(site_id), &detail).sync(db)
 }
 
 async fn list_crawls(db: &Database) -> Result<Vec<Crawl>, Box<dyn Error>> {
     Crawl::fetch_all(db)
 }
 
-async fn delete_crawl(crawl_id: i64, db: &Database) -> Result<(), Box<dyn Error>> {
-    Crawl::delete(crawl_id, db)
+fn delete_crawl(crawl_id: i64, actor: Option<&str>, db: &mut Database) -> Result<(), Box<dyn Error>> {
+    let crawl = Crawl::fetch(crawl_id, db)?;
+    Crawl::delete(crawl_id, db)?;
+    let detail = format!("site {}, started {}", crawl.site_id, crawl.started_at.unwrap_or_default());
+    AuditEvent::new(actor, AuditAction::DeleteCrawl, Some(crawl_id), &detail).sync(db)
+}
+
+/// Trimmed `value`, `None` when blank.
+fn non_blank(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
 }
 
 async fn list_queries(db: &Database) -> Result<Vec<Query>, Box<dyn Error>> {
     Query::fetch_all(db)
 }
 
-async fn delete_query(query_id: i64, db: &Database) -> Result<(), Box<dyn Error>> {
-    Query::delete(query_id, db)
+fn delete_query(query_id: i64, actor: Option<&str>, db: &mut Database) -> Result<(), Box<dyn Error>> {
+    let query = Query::fetch(query_id, db)?;
+    Query::delete(query_id, db)?;
+    let detail = format!("'{}' on crawl {}", query.label(), query.crawl_id);
+    AuditEvent::new(actor, AuditAction::DeleteQuery, Some(query_id), &detail).sync(db)
 }
 
 async fn list_results(db: &Database) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
@@ -170,97 +1420,998 @@ pub enum CrawlResult {
     CrawlStarted(usize),  // Total number of pages to crawl
     PageSucceeded(String),
     PageFailed(String, String),
+    AlertRaised(String, String),  // URL, alert message
+    Warning(String),
+    Interrupted(i64, usize),  // Crawl ID, URLs left to fetch
+    Cancelled(i64, usize),  // Crawl ID, URLs dropped
+    Stats(CrawlStats),  // Totals so far, after each page
 }
 
 async fn new_crawl<F>(
     site_id: i64, 
     db: Arc<Mutex<Database>>,
     http_client: &HTTPClient, 
-    max_concurrent: usize,
+    secrets: Option<Arc<SecretStore>>,
+    options: &CrawlOptions,
     on_update: F
 ) -> Result<(), Box<dyn Error>> 
 where 
     F: Fn(CrawlResult) + Send + Sync + 'static 
 {
-    let site = {
+    let (site, credentials) = {
+        let db_lock = db.lock().await;
+        let site = Site::fetch(site_id, &db_lock)
+            .map_err(|e| format!("DB Error: {}", e))?;
+        let credentials = SiteCredential::fetch_by_site(site_id, &db_lock)?;
+        (site, credentials)
+    };
+
+    let site_settings = site_client_settings(&site, &credentials, http_client, secrets, &on_update)?;
+
+    // The sitemap always comes from production, only pages are redirected
+    let sitemap_client = match &site_settings {
+        Some(settings) => settings.clone().build()?,
+        None => http_client.clone(),
+    };
+    let sitemap = SitemapStream::open(&sitemap_client, site.sitemap_url.as_str(), &options.sitemap_limits).await?;
+    let robots = robots_for(options, &sitemap_client);
+
+    let (crawl_id, total_pages, hosts) =
+        enqueue_sitemap(site_id, sitemap, robots, &sitemap_client, options, &db, &on_update).await?;
+    warn_skipped(crawl_id, &db, &on_update).await?;
+
+    let page_client = page_client(&site, site_settings, sitemap_client, hosts, &on_update)?;
+
+    on_update(CrawlResult::CrawlStarted(total_pages));
+
+    run_crawl(&site, crawl_id, db, &page_client, options, on_update).await
+}
+
+async fn estimate_crawl(
+    site_id: i64,
+    db: &Mutex<Database>,
+    http_client: &HTTPClient,
+    secrets: Option<Arc<SecretStore>>,
+) -> Result<CrawlEstimate, Box<dyn Error>> {
+    let (site, credentials) = {
+        let db_lock = db.lock().await;
+        let site = Site::fetch(site_id, &db_lock)?;
+        let credentials = SiteCredential::fetch_by_site(site_id, &db_lock)?;
+        (site, credentials)
+    };
+
+    // Warnings are for crawls, the estimate only needs the client
+    let sitemap_client = match site_client_settings(&site, &credentials, http_client, secrets, &|_| {})? {
+        Some(settings) => settings.build()?,
+        None => http_client.clone(),
+    };
+    let started = std::time::Instant::now();
+    let sitemap = SitemapStream::open(&sitemap_client, site.sitemap_url.as_str(), &SitemapLimits::default()).await?;
+
+    let mut estimate = CrawlEstimate::from_stream(sitemap).await?;
+    estimate.page_latency = started.elapsed();
+    if let Some((crawl_id, page_bytes)) = PageArchive::average_size_by_site(site_id, &*db.lock().await)? {
+        estimate.measured_crawl_id = Some(crawl_id);
+        estimate.page_bytes = page_bytes;
+    }
+
+    Ok(estimate)
+}
+
+async fn new_crawl_from_urls<F>(
+    site_id: i64,
+    urls: Vec<String>,
+    db: Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    secrets: Option<Arc<SecretStore>>,
+    options: &CrawlOptions,
+    on_update: F
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult) + Send + Sync + 'static
+{
+    if urls.is_empty() {
+        return Err("No URLs to crawl".into());
+    }
+
+    let (site, credentials) = {
         let db_lock = db.lock().await;
-        Site::fetch(site_id, &*db_lock)
-            .map_err(|e| format!("DB Error: {}", e))?
+        let site = Site::fetch(site_id, &db_lock)?;
+        let credentials = SiteCredential::fetch_by_site(site_id, &db_lock)?;
+        (site, credentials)
     };
 
-    let sitemap_content = http_client.get_sitemap(site.sitemap_url.as_str()).await?;
-    let sitemap = Sitemap::new(sitemap_content.as_str())?;
+    let site_settings = site_client_settings(&site, &credentials, http_client, secrets, &on_update)?;
+    let client = match &site_settings {
+        Some(settings) => settings.clone().build()?,
+        None => http_client.clone(),
+    };
 
-    // Create and sync the crawl first to generate its ID
     let crawl_id = {
         let mut db_lock = db.lock().await;
-        let mut crawl = Crawl::new(None, site_id);
-        crawl.sync(&mut *db_lock)?;
-        crawl.id.ok_or("Failed to get crawl ID after sync")?
+        db_lock.in_transaction(|db| enqueue_urls(site_id, &urls, &options.meta, db))?
     };
 
-    let on_update = Arc::new(on_update);
-    
-    // Notify about total page count before starting
-    let total_pages = sitemap.urlset.urls.len();
+    let hosts = urls.iter().filter_map(|url| url_host(url)).collect();
+    let page_client = page_client(&site, site_settings, client, hosts, &on_update)?;
+
+    on_update(CrawlResult::CrawlStarted(urls.len()));
+
+    run_crawl(&site, crawl_id, db, &page_client, options, on_update).await
+}
+
+/// Fetches the URLs a crawl left in its frontier, e.g. after a crash.
+async fn resume_crawl<F>(
+    crawl_id: i64,
+    db: Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    secrets: Option<Arc<SecretStore>>,
+    options: &CrawlOptions,
+    on_update: F
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult) + Send + Sync + 'static
+{
+    let (site, credentials, status, pending) = {
+        let db_lock = db.lock().await;
+        let crawl = Crawl::fetch(crawl_id, &db_lock)?;
+        let site = Site::fetch(crawl.site_id, &db_lock)?;
+        let credentials = SiteCredential::fetch_by_site(crawl.site_id, &db_lock)?;
+        let pending = FrontierUrl::fetch_by_crawl_id(crawl_id, &db_lock)?;
+        (site, credentials, crawl.status, pending)
+    };
+
+    // A crawl from before frontiers were kept, or whose process died after
+    // its last fetch, is unfinished with nothing queued: the sitemap tells
+    // what it's missing
+    let rebuild = pending.is_empty() && matches!(status, CrawlStatus::Running | CrawlStatus::Interrupted);
+    if pending.is_empty() && !rebuild {
+        return Err(format!("Crawl {} has no pending URLs to resume", crawl_id).into());
+    }
+
+    let site_settings = site_client_settings(&site, &credentials, http_client, secrets, &on_update)?;
+    let client = match &site_settings {
+        Some(settings) => settings.clone().build()?,
+        None => http_client.clone(),
+    };
+    let (total_pages, hosts) = if rebuild {
+        let queued = enqueue_missing_urls(&site, crawl_id, &db, &client, options, &on_update).await?;
+        warn_skipped(crawl_id, &db, &on_update).await?;
+        queued
+    } else {
+        (pending.len(), pending.iter().filter_map(|pending| url_host(&pending.url)).collect())
+    };
+    {
+        let db_lock = db.lock().await;
+        Crawl::set_status(crawl_id, CrawlStatus::Running, &db_lock)?;
+    }
+
+    let page_client = page_client(&site, site_settings, client, hosts, &on_update)?;
+
     on_update(CrawlResult::CrawlStarted(total_pages));
 
-    stream::iter(sitemap.urlset.urls)
-        .for_each_concurrent(max_concurrent, |url_entry| {
-            let url = url_entry.loc;
-            let client = http_client.clone();
-            let db_clone = Arc::clone(&db);
-            let on_update_clone = Arc::clone(&on_update);
-            let crawl_id = crawl_id; // Capture crawl_id for the async block
+    run_crawl(&site, crawl_id, db, &page_client, options, on_update).await
+}
+
+/// Re-reads the site's sitemap and queues the URLs the crawl has neither
+/// archived, recorded an error for nor skipped, so they're all that's
+/// fetched. Returns the number of URLs queued and their hosts.
+async fn enqueue_missing_urls<F>(
+    site: &Site,
+    crawl_id: i64,
+    db: &Mutex<Database>,
+    sitemap_client: &HTTPClient,
+    options: &CrawlOptions,
+    on_update: &F,
+) -> Result<(usize, HashSet<String>), Box<dyn Error>>
+where
+    F: Fn(CrawlResult)
+{
+    let mut sitemap = SitemapStream::open(sitemap_client, site.sitemap_url.as_str(), &options.sitemap_limits).await?;
+
+    let mut done: HashSet<String> = {
+        let db_lock = db.lock().await;
+        let mut done: HashSet<String> = PageArchive::fetch_urls_by_crawl_id(crawl_id, &db_lock)?.into_iter().collect();
+        done.extend(CrawlError::fetch_by_crawl_id(crawl_id, &db_lock)?.into_iter().map(|error| error.url));
+        done.extend(SkippedUrl::fetch_by_crawl_id(crawl_id, &db_lock)?.into_iter().map(|skipped| skipped.url));
+        done
+    };
+
+    // Queued so that if this run is cut short too, it resumes from the
+    // frontier. A sitemap that breaks off leaves the frontier empty again
+    let mut robots = robots_for(options, sitemap_client);
+    let mut total_pages = 0;
+    let mut hosts = HashSet::new();
+    loop {
+        let batch = match sitemap.next_batch(SITEMAP_BATCH).await {
+            Ok(batch) if batch.is_empty() => break,
+            Ok(batch) => batch,
+            Err(err) => {
+                FrontierUrl::clear(crawl_id, &*db.lock().await)?;
+                return Err(err);
+            }
+        };
+        let missing: Vec<String> = batch
+            .into_iter()
+            .map(|entry| entry.loc)
+            .filter(|loc| done.insert(loc.clone()))
+            .collect();
+        robots = read_robots(robots, missing.iter().cloned(), sitemap_client, on_update).await;
+
+        let queued = db.lock().await.in_transaction(|db| queue_allowed(crawl_id, missing, robots.as_ref(), db))?;
+        total_pages += queued.len();
+        hosts.extend(queued.iter().filter_map(|url| url_host(url)));
+    }
+    warn_sitemap_limits(&sitemap, options, on_update);
+
+    Ok((total_pages, hosts))
+}
 
-            async move {
-                let result = process_single_page(&url, crawl_id, db_clone, client).await;
+/// Runs `crawl` holding the site's crawl lock, refreshing the lock while the
+/// crawl runs and releasing it however the crawl ends.
+async fn with_crawl_lock<T>(
+    site_id: i64,
+    db: &Arc<Mutex<Database>>,
+    mut started: AuditEvent,
+    crawl: impl Future<Output = Result<T, Box<dyn Error>>>,
+) -> Result<T, Box<dyn Error>> {
+    // The crawl start is only logged once the lock is held
+    let lock = {
+        let mut db_lock = db.lock().await;
+        Arc::new(db_lock.in_transaction(|db| {
+            let lock = CrawlLock::acquire(site_id, db)?;
+            started.sync(db)?;
+            Ok(lock)
+        })?)
+    };
 
-                match result {
-                    Ok(_) => on_update_clone(CrawlResult::PageSucceeded(url)),
-                    Err(e) => on_update_clone(CrawlResult::PageFailed(url, e.to_string())),
+    let heartbeat = {
+        let db = Arc::clone(db);
+        let lock = Arc::clone(&lock);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                let db_lock = db.lock().await;
+                if let Err(e) = lock.heartbeat(&db_lock) {
+                    eprintln!("Could not refresh the crawl lock of site {}: {}", lock.site_id, e);
                 }
             }
         })
-    .await;
+    };
+
+    let result = crawl.await;
+    heartbeat.abort();
+
+    let db_lock = db.lock().await;
+    lock.release(&db_lock)?;
+    result
+}
+
+/// Client settings a site needs beyond the application's client: relaxed
+/// TLS and credential headers. `None` when the shared client will do.
+fn site_client_settings<F>(
+    site: &Site,
+    credentials: &[SiteCredential],
+    http_client: &HTTPClient,
+    secrets: Option<Arc<SecretStore>>,
+    on_update: &F,
+) -> Result<Option<HTTPClientBuilder>, Box<dyn Error>>
+where
+    F: Fn(CrawlResult),
+{
+    let mut site_settings = None;
+    if site.insecure_tls {
+        on_update(CrawlResult::Warning(format!(
+            "TLS certificate verification is DISABLED for {}. Responses could be intercepted or forged.",
+            site.domain
+        )));
+        site_settings = Some(http_client.settings().danger_accept_invalid_certs(true));
+    }
+
+    if !credentials.is_empty() {
+        let secrets = secrets.ok_or("This site has stored credentials, unlock secrets before crawling it")?;
+        let mut settings = site_settings.unwrap_or_else(|| http_client.settings());
+        for credential in credentials {
+            settings = settings.default_header(&credential.name, &credential.value(&secrets)?);
+        }
+        site_settings = Some(settings);
+    }
+
+    Ok(site_settings)
+}
+
+/// The client pages are fetched with, honouring the site's fetch override.
+/// `hosts` are the hosts of the URLs to crawl.
+fn page_client<F>(
+    site: &Site,
+    site_settings: Option<HTTPClientBuilder>,
+    client: HTTPClient,
+    hosts: HashSet<String>,
+    on_update: &F,
+) -> Result<HTTPClient, Box<dyn Error>>
+where
+    F: Fn(CrawlResult),
+{
+    match site.fetch_override()? {
+        Some(FetchOverride::Address(ip)) => {
+            let mut settings = site_settings.unwrap_or_else(|| client.settings());
+            for host in hosts {
+                settings = settings.dns_override(&host, ip);
+            }
+            on_update(CrawlResult::Warning(format!("Fetching pages of {} from {}", site.domain, ip)));
+            settings.build()
+        }
+        Some(FetchOverride::BaseUrl(base)) => {
+            on_update(CrawlResult::Warning(format!("Fetching pages of {} from {}", site.domain, base)));
+            Ok(client)
+        }
+        None => Ok(client),
+    }
+}
+
+/// Sitemap entries queued per transaction, their robots.txt read first.
+const SITEMAP_BATCH: usize = 1_000;
+
+/// Creates a crawl and queues the sitemap URLs in its frontier as they are
+/// read, along with the declared sitemap images. URLs `robots` disallows
+/// are recorded as skipped instead. Returns the crawl ID, the number of
+/// URLs queued and their hosts. A sitemap that breaks off leaves no crawl.
+async fn enqueue_sitemap<F>(
+    site_id: i64,
+    mut sitemap: SitemapStream<'_>,
+    mut robots: Option<RobotsTxt>,
+    sitemap_client: &HTTPClient,
+    options: &CrawlOptions,
+    db: &Mutex<Database>,
+    on_update: &F,
+) -> Result<(i64, usize, HashSet<String>), Box<dyn Error>>
+where
+    F: Fn(CrawlResult)
+{
+    let crawl_id = db.lock().await.in_transaction(|db| enqueue_urls(site_id, &[], &options.meta, db))?;
+
+    let mut total_pages = 0;
+    let mut hosts = HashSet::new();
+    loop {
+        let batch = match sitemap.next_batch(SITEMAP_BATCH).await {
+            Ok(batch) if batch.is_empty() => break,
+            Ok(batch) => batch,
+            Err(err) => {
+                Crawl::purge(crawl_id, &*db.lock().await)?;
+                return Err(err);
+            }
+        };
+        robots = read_robots(robots, batch.iter().map(|entry| entry.loc.clone()), sitemap_client, on_update).await;
+
+        let mut db_lock = db.lock().await;
+        db_lock.in_transaction(|db| {
+            for entry in &batch {
+                sitemap_change::record_sitemap_url(crawl_id, entry, db)?;
+                if let Some(pattern) = robots.as_ref().and_then(|robots| robots.disallowing(&entry.loc)) {
+                    SkippedUrl::new(None, crawl_id, &entry.loc, &robots_reason(pattern)).sync(db)?;
+                    continue;
+                }
+                FrontierUrl::new(None, crawl_id, &entry.loc).sync(db)?;
+
+                // Keep the image sitemap declarations for the image inventory audit
+                for image in &entry.images {
+                    SitemapImage::new(None, crawl_id, &entry.loc, &image.loc).sync(db)?;
+                }
+
+                total_pages += 1;
+                hosts.extend(url_host(&entry.loc));
+            }
+            Ok(())
+        })?;
+    }
+    warn_sitemap_limits(&sitemap, options, on_update);
+
+    Ok((crawl_id, total_pages, hosts))
+}
+
+/// Says which child sitemaps were left out and whether the URL cap cut the
+/// sitemap short.
+fn warn_sitemap_limits<F>(sitemap: &SitemapStream<'_>, options: &CrawlOptions, on_update: &F)
+where
+    F: Fn(CrawlResult)
+{
+    for (url, reason) in &sitemap.skipped {
+        on_update(CrawlResult::Warning(format!("Skipped sitemap {}: {}", url, reason)));
+    }
+    if let Some(max_urls) = options.sitemap_limits.max_urls
+        && sitemap.capped
+    {
+        on_update(CrawlResult::Warning(format!("Crawling only the first {} sitemap URLs", max_urls)));
+    }
+}
+
+/// Creates a crawl with `urls` in its frontier and returns the crawl ID.
+fn enqueue_urls(site_id: i64, urls: &[String], meta: &CrawlMeta, db: &mut Database) -> Result<i64, Box<dyn Error>> {
+    let mut crawl = Crawl::new(None, site_id);
+    crawl.sync(db)?;
+    let crawl_id = crawl.id.ok_or("Failed to get crawl ID after sync")?;
+    crawl_meta::record(crawl_id, meta, db)?;
+
+    for url in urls {
+        FrontierUrl::new(None, crawl_id, url).sync(db)?;
+    }
+
+    Ok(crawl_id)
+}
+
+/// Adds the robots.txt of the hosts among `urls` that `robots` doesn't have
+/// yet, warning about those it can't read. `None` is a crawl ignoring them.
+async fn read_robots<F>(
+    robots: Option<RobotsTxt>,
+    urls: impl Iterator<Item = String>,
+    client: &HTTPClient,
+    on_update: &F,
+) -> Option<RobotsTxt>
+where
+    F: Fn(CrawlResult)
+{
+    let mut robots = robots?;
+    robots.fetch_for(client, urls).await;
+    for (url, reason) in std::mem::take(&mut robots.unreadable) {
+        on_update(CrawlResult::Warning(format!("Could not read {}, crawling without its rules: {}", url, reason)));
+    }
+    Some(robots)
+}
+
+/// Empty robots.txt rules for the user agents a crawl sends, rotated ones
+/// or the client's own, or `None` if it ignores robots.txt.
+fn robots_for(options: &CrawlOptions, client: &HTTPClient) -> Option<RobotsTxt> {
+    options.respect_robots.then(|| {
+        let user_agents = if options.user_agents.is_empty() {
+            vec![client.user_agent().to_string()]
+        } else {
+            options.user_agents.clone()
+        };
+        RobotsTxt::new(user_agents)
+    })
+}
+
+fn robots_reason(pattern: &str) -> String {
+    format!("Disallowed by robots.txt: {}", pattern)
+}
+
+/// Queues the URLs `robots` allows in the crawl's frontier, records the
+/// others as skipped and returns the queued ones.
+fn queue_allowed(
+    crawl_id: i64,
+    urls: Vec<String>,
+    robots: Option<&RobotsTxt>,
+    db: &mut Database,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut allowed = Vec::new();
+    for url in urls {
+        match robots.and_then(|robots| robots.disallowing(&url)) {
+            Some(pattern) => SkippedUrl::new(None, crawl_id, &url, &robots_reason(pattern)).sync(db)?,
+            None => {
+                FrontierUrl::new(None, crawl_id, &url).sync(db)?;
+                allowed.push(url);
+            }
+        }
+    }
+    Ok(allowed)
+}
 
+/// Says how many URLs the crawl has left out so far, if any.
+async fn warn_skipped<F>(crawl_id: i64, db: &Mutex<Database>, on_update: &F) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult)
+{
+    let skipped = SkippedUrl::fetch_by_crawl_id(crawl_id, &*db.lock().await)?.len();
+    if skipped > 0 {
+        on_update(CrawlResult::Warning(format!("Left out {} URLs disallowed by robots.txt", skipped)));
+    }
     Ok(())
 }
 
-async fn process_single_page(
-    url: &str, 
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url.trim()).ok()?.host_str().map(str::to_string)
+}
+
+/// Frontier URLs loaded at a time while a crawl runs.
+const FRONTIER_PAGE: usize = 1_000;
+
+/// Fetches and archives the URLs in the crawl's frontier, a page of them at
+/// a time, taking each off once it is done, then evaluates the site's
+/// monitors. When the stop signal fires or the bandwidth budget runs out,
+/// fetches in flight finish and the crawl is marked interrupted.
+async fn run_crawl<F>(
+    site: &Site,
     crawl_id: i64,
-    db: Arc<Mutex<Database>>, 
-    client: HTTPClient
-) -> Result<(), Box<dyn Error>> {
-    let (final_url, html) = client.get_html(url).await?;
-    let page = Page::new(url, final_url.as_str(), html.as_str(), Some(crawl_id))?;
+    db: Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    options: &CrawlOptions,
+    on_update: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult) + Send + Sync + 'static
+{
+    let on_update = Arc::new(on_update);
+    let fetch = PageFetch::new(site, crawl_id, options, &db).await?;
+    let mut after_id = 0;
+    while !options.stop.is_stopped() {
+        let page = FrontierUrl::fetch_after(crawl_id, after_id, FRONTIER_PAGE, &*db.lock().await)?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id.ok_or("Frontier URL without an ID")?;
+        let urls = page.into_iter().map(|queued| queued.url);
+        fetch.run(crawl_id, urls, &db, http_client, options, &on_update).await?;
+    }
+    finish_crawl(site, crawl_id, &db, options, on_update.as_ref()).await
+}
+
+/// Crawls the pages reached by following links from the seeds, one level of
+/// link hops at a time, within the site and its depth and page limits.
+async fn new_link_crawl<F>(
+    site_id: i64,
+    following: &LinkFollowing,
+    db: Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    secrets: Option<Arc<SecretStore>>,
+    options: &CrawlOptions,
+    on_update: F
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult) + Send + Sync + 'static
+{
+    let (site, credentials) = {
+        let db_lock = db.lock().await;
+        let site = Site::fetch(site_id, &db_lock)?;
+        let credentials = SiteCredential::fetch_by_site(site_id, &db_lock)?;
+        (site, credentials)
+    };
+    let site_host = url_host(&site.base_url()).ok_or_else(|| format!("No host in the domain of site {}", site_id))?;
+    let mut level = match following.seeds.is_empty() {
+        true => vec![format!("{}/", site.base_url())],
+        false => following.seeds.clone(),
+    };
+    // At least the first seed is fetched, whatever the limit
+    let max_pages = following.max_pages.max(1);
+    level.truncate(max_pages);
+
+    let site_settings = site_client_settings(&site, &credentials, http_client, secrets, &on_update)?;
+    let client = match &site_settings {
+        Some(settings) => settings.clone().build()?,
+        None => http_client.clone(),
+    };
+
+    let robots = robots_for(options, &client);
+    let mut robots = read_robots(robots, level.iter().cloned(), &client, &on_update).await;
+    let (crawl_id, mut level) = {
+        let mut db_lock = db.lock().await;
+        db_lock.in_transaction(|db| {
+            let crawl_id = enqueue_urls(site_id, &[], &options.meta, db)?;
+            Ok((crawl_id, queue_allowed(crawl_id, level, robots.as_ref(), db)?))
+        })?
+    };
+
+    let hosts = level.iter().filter_map(|url| url_host(url)).chain([site_host.clone()]).collect();
+    let page_client = page_client(&site, site_settings, client.clone(), hosts, &on_update)?;
+
+    on_update(CrawlResult::CrawlStarted(level.len()));
+
+    let on_update = Arc::new(on_update);
+    let fetch = PageFetch::new(&site, crawl_id, options, &db).await?;
+    let mut seen: HashSet<String> = level.iter().cloned().collect();
+    let mut queued = level.len();
+    for depth in 0..=following.max_depth {
+        fetch.run(crawl_id, level.iter().cloned(), &db, &page_client, options, &on_update).await?;
+        if options.stop.is_stopped() || depth == following.max_depth {
+            break;
+        }
+
+        let mut next = Vec::new();
+        {
+            let db_lock = db.lock().await;
+            for url in &level {
+                let Ok(archive) = PageArchive::fetch_by_crawl_and_url(crawl_id, url, &db_lock) else { continue };
+                // A page reached again under the URL it redirected to isn't fetched twice
+                seen.insert(archive.final_url.clone());
+                let Ok(page) = archive.to_page() else { continue };
+                next.extend(link_crawl::new_links(&page, &site_host, &mut seen));
+            }
+        }
+        if queued + next.len() > max_pages {
+            next.truncate(max_pages.saturating_sub(queued));
+            on_update(CrawlResult::Warning(format!(
+                "Crawling only the first {} pages reached by links",
+                max_pages
+            )));
+        }
+        if next.is_empty() {
+            break;
+        }
+
+        robots = read_robots(robots, next.iter().cloned(), &client, on_update.as_ref()).await;
+        let next = {
+            let mut db_lock = db.lock().await;
+            db_lock.in_transaction(|db| queue_allowed(crawl_id, next, robots.as_ref(), db))?
+        };
+        queued += next.len();
+        level = next;
+    }
+
+    warn_skipped(crawl_id, &db, on_update.as_ref()).await?;
+    finish_crawl(&site, crawl_id, &db, options, on_update.as_ref()).await
+}
+
+/// What the batches of pages fetched for one crawl share: the per-host
+/// limits, the bytes counted against the budget and the user agent turns.
+struct PageFetch {
+    fetch_override: Option<FetchOverride>,
+    host_limiters: HostLimiters,
+    /// Set when the site asks for politeness, for pages and assets alike.
+    pacer: Option<RequestPacer>,
+    bytes_fetched: Arc<AtomicU64>,
+    requests: AtomicUsize,
+    /// Asset URLs taken by a page of this run, so each is fetched once.
+    assets_seen: std::sync::Mutex<HashSet<String>>,
+    stats: std::sync::Mutex<CrawlStats>,
+    started: std::time::Instant,
+}
+
+impl PageFetch {
+    async fn new(site: &Site, crawl_id: i64, options: &CrawlOptions, db: &Mutex<Database>) -> Result<Self, Box<dyn Error>> {
+        // A resumed crawl keeps counting against its budget where it left off
+        let bytes_fetched = Crawl::fetch(crawl_id, &*db.lock().await)?.bytes_fetched;
+
+        Ok(Self {
+            fetch_override: site.fetch_override()?,
+            // Each host starts at its cap and backs off when it struggles
+            host_limiters: HostLimiters::new(options.per_host_limit()),
+            pacer: site.request_pacer()?,
+            bytes_fetched: Arc::new(AtomicU64::new(bytes_fetched)),
+            // Rotated user agents go round in the order requests are made
+            requests: AtomicUsize::new(0),
+            assets_seen: std::sync::Mutex::new(HashSet::new()),
+            stats: std::sync::Mutex::new(CrawlStats::default()),
+            started: std::time::Instant::now(),
+        })
+    }
+
+    /// A slot in the host's limit, once the site's pacing allows another
+    /// request.
+    async fn acquire(&self, url: &str) -> Permit {
+        let mut permit = self.host_limiters.for_url(url).acquire().await;
+        if let Some(pacer) = &self.pacer {
+            permit.pace(pacer).await;
+        }
+        permit
+    }
+
+    /// Fetches and archives `urls`, taking each off the crawl's frontier
+    /// once it is done, until they run out or the crawl is stopped.
+    async fn run<F>(
+        &self,
+        crawl_id: i64,
+        urls: impl Iterator<Item = String>,
+        db: &Arc<Mutex<Database>>,
+        http_client: &HTTPClient,
+        options: &CrawlOptions,
+        on_update: &Arc<F>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: Fn(CrawlResult) + Send + Sync + 'static
+    {
+        let fetch_override = &self.fetch_override;
+        let stop = options.stop.clone();
+        stream::iter(urls)
+            .take_while(move |_| future::ready(!stop.is_stopped()))
+            .for_each_concurrent(options.max_concurrent, |url| {
+                let client = http_client.clone();
+                let db_clone = Arc::clone(db);
+                let on_update_clone = Arc::clone(on_update);
+                let fetch_url = match fetch_override {
+                    Some(fetch_override) => fetch_override.fetch_url(&url),
+                    None => url.clone(),
+                };
+                let bytes_fetched = Arc::clone(&self.bytes_fetched);
+                let user_agent = options.user_agent_for(self.requests.fetch_add(1, Ordering::Relaxed)).map(str::to_string);
+
+                async move {
+                    let permit = self.acquire(&fetch_url).await;
+                    let (result, latency) = process_single_page(
+                        &url,
+                        &fetch_url,
+                        crawl_id,
+                        Arc::clone(&db_clone),
+                        client.clone(),
+                        permit,
+                        user_agent,
+                    )
+                    .await;
+                    if result.is_ok()
+                        && let Some(limits) = &options.assets
+                    {
+                        self.archive_assets(&url, crawl_id, limits, &db_clone, &client, on_update_clone.as_ref()).await;
+                    }
+
+                    let bytes = *result.as_ref().unwrap_or(&0);
+                    let failure = result.err().map(|e| (CrawlErrorKind::classify(e.as_ref()), e.to_string()));
+                    {
+                        let mut db_lock = db_clone.lock().await;
+                        if bytes > 0 {
+                            if let Err(e) = Crawl::add_bytes_fetched(crawl_id, bytes, &db_lock) {
+                                eprintln!("Could not record the bytes fetched for {}: {}", url, e);
+                            }
+                            let total = bytes_fetched.fetch_add(bytes, Ordering::SeqCst) + bytes;
+                            // Only the page crossing the budget stops the crawl and says so
+                            if let Some(max_bytes) = options.max_bytes
+                                && total >= max_bytes
+                                && total - bytes < max_bytes
+                            {
+                                options.stop.stop();
+                                on_update_clone(CrawlResult::Warning(format!(
+                                    "Bandwidth budget of {} bytes used up after {} bytes, stopping the crawl",
+                                    max_bytes, total
+                                )));
+                            }
+                        }
+                        if let Some((kind, message)) = &failure {
+                            let mut crawl_error = CrawlError::new(None, crawl_id, &url, *kind, message);
+                            if let Err(e) = crawl_error.sync(&mut db_lock) {
+                                eprintln!("Could not record crawl error for {}: {}", url, e);
+                            }
+                        }
+                        if let Err(e) = FrontierUrl::complete(crawl_id, &url, &db_lock) {
+                            eprintln!("Could not update the crawl frontier for {}: {}", url, e);
+                        }
+                    }
+
+                    let stats = {
+                        let mut stats = self.stats.lock().unwrap();
+                        stats.record(bytes, latency, failure.is_some());
+                        stats.elapsed = self.started.elapsed();
+                        *stats
+                    };
+                    match failure {
+                        None => on_update_clone(CrawlResult::PageSucceeded(url)),
+                        Some((_, message)) => on_update_clone(CrawlResult::PageFailed(url, message)),
+                    }
+                    on_update_clone(CrawlResult::Stats(stats));
+                }
+            })
+        .await;
+
+        Ok(())
+    }
 
+    /// Archives the assets a freshly archived page references that the
+    /// crawl doesn't have yet. Those that can't be fetched, aren't of an
+    /// asset type or are over their limit are skipped with a warning.
+    async fn archive_assets<F>(
+        &self,
+        page_url: &str,
+        crawl_id: i64,
+        limits: &AssetLimits,
+        db: &Mutex<Database>,
+        http_client: &HTTPClient,
+        on_update: &F,
+    ) where
+        F: Fn(CrawlResult)
     {
+        let references = {
+            let db_lock = db.lock().await;
+            let Ok(archive) = PageArchive::fetch_by_crawl_and_url(crawl_id, page_url, &db_lock) else { return };
+            let Ok(page) = archive.to_page() else { return };
+            asset::references(&page)
+        };
+
+        for (url, referenced_as) in references {
+            // Taken before fetching, so pages fetched at once don't both fetch it
+            if !self.assets_seen.lock().unwrap().insert(url.clone()) {
+                continue;
+            }
+            // Archived before a resume
+            if Asset::exists(crawl_id, &url, &*db.lock().await).unwrap_or(false) {
+                continue;
+            }
+
+            let response = {
+                let permit = self.acquire(&url).await;
+                let response = http_client.get_asset(&url, limits.max_bytes(referenced_as)).await;
+                permit.record(&response);
+                response.map_err(|e| e.to_string())
+            };
+            let asset = response.and_then(|response| {
+                let kind = asset::sniff(&response.content_type, &response.body, referenced_as)
+                    .ok_or_else(|| format!("served as {}, not as an asset", response.content_type))?;
+                if response.body.len() as u64 > limits.max_bytes(kind) {
+                    return Err(format!("larger than the {} byte limit for {} files", limits.max_bytes(kind), kind.name()));
+                }
+                Ok(Asset {
+                    id: None,
+                    crawl_id,
+                    url: url.clone(),
+                    final_url: response.final_url,
+                    page_url: page_url.to_string(),
+                    kind,
+                    content_type: response.content_type,
+                    content: String::from_utf8_lossy(&response.body).into_owned(),
+                })
+            });
+
+            match asset {
+                Ok(mut asset) => {
+                    let mut db_lock = db.lock().await;
+                    if let Err(e) = asset.sync(&mut db_lock) {
+                        eprintln!("Could not archive the asset {}: {}", url, e);
+                    }
+                }
+                Err(message) => on_update(CrawlResult::Warning(format!("Skipped asset {}: {}", url, message))),
+            }
+        }
+    }
+}
+
+/// Marks the crawl cancelled or interrupted if it was stopped with URLs
+/// left, otherwise completed, then evaluates the site's monitors and rules
+/// and prunes the archive to its quota.
+async fn finish_crawl<F>(
+    site: &Site,
+    crawl_id: i64,
+    db: &Mutex<Database>,
+    options: &CrawlOptions,
+    on_update: &F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn(CrawlResult)
+{
+    let site_id = site.id.ok_or("Site without an ID")?;
+
+    let alerts = {
+        let mut db_lock = db.lock().await;
+        let pending = FrontierUrl::count_by_crawl_id(crawl_id, &db_lock)?;
+        if options.stop.is_cancelled() && pending > 0 {
+            FrontierUrl::clear(crawl_id, &db_lock)?;
+            Crawl::set_status(crawl_id, CrawlStatus::Cancelled, &db_lock)?;
+            drop(db_lock);
+            on_update(CrawlResult::Cancelled(crawl_id, pending));
+            return Ok(());
+        }
+        if options.stop.is_stopped() && pending > 0 {
+            Crawl::set_status(crawl_id, CrawlStatus::Interrupted, &db_lock)?;
+            drop(db_lock);
+            on_update(CrawlResult::Interrupted(crawl_id, pending));
+            return Ok(());
+        }
+
+        Crawl::set_status(crawl_id, CrawlStatus::Completed, &db_lock)?;
+        let mut alerts = evaluate_monitors(site_id, crawl_id, &mut db_lock)?;
+        alerts.extend(site_rule::evaluate(site_id, crawl_id, &mut db_lock)?);
+        alerts.extend(sitemap_change::evaluate(site_id, crawl_id, &mut db_lock)?);
+        link_graph::record_metrics(crawl_id, &mut db_lock)?;
+        alerts
+    };
+    for alert in alerts {
+        on_update(CrawlResult::AlertRaised(alert.url, alert.message));
+    }
+
+    // The new crawl may have pushed the archive over its quota
+    let pruned = {
         let mut db_lock = db.lock().await;
-        page.sync(&mut *db_lock)?;
+        quota::prune(&mut db_lock)?
+    };
+    if !pruned.is_empty() {
+        let ids: Vec<String> = pruned.iter().map(i64::to_string).collect();
+        on_update(CrawlResult::Warning(format!(
+            "Archive over its quota, pruned old crawl(s) {}",
+            ids.join(", ")
+        )));
     }
 
     Ok(())
 }
 
-async fn query(crawl_id: i64, selector: &str, mut db: &mut Database) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
-    let pages_archive = PageArchive::fetch_by_crawl_id(crawl_id, &db)?;
+/// Evaluates the site's monitors against the previous crawl and stores the
+/// raised alerts.
+fn evaluate_monitors(site_id: i64, crawl_id: i64, db: &mut Database) -> Result<Vec<Alert>, Box<dyn Error>> {
+    let monitors = Monitor::fetch_by_site(site_id, db)?;
+    if monitors.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let previous_id = match Crawl::fetch_previous(site_id, crawl_id, db)?.and_then(|c| c.id) {
+        Some(id) => id,
+        None => return Ok(Vec::new()),
+    };
+
+    let previous = PageArchive::fetch_by_crawl_id(previous_id, db)?;
+    let current = PageArchive::fetch_by_crawl_id(crawl_id, db)?;
+
+    let mut alerts = Vec::new();
+    for monitor in monitors {
+        let monitor_id = monitor.id.ok_or("Monitor without an ID")?;
+        for (url, message) in monitor.evaluate(&previous, &current) {
+            let mut alert = Alert::new(None, monitor_id, crawl_id, &url, &message);
+            alert.sync(db)?;
+            alerts.push(alert);
+        }
+    }
+
+    Ok(alerts)
+}
+
+async fn process_single_page(
+    url: &str, 
+    fetch_url: &str,
+    crawl_id: i64,
+    db: Arc<Mutex<Database>>, 
+    client: HTTPClient,
+    permit: Permit,
+    user_agent: Option<String>,
+) -> (Result<u64, Box<dyn Error>>, Duration) {
+    let (response, latency) = {
+        let requested = std::time::Instant::now();
+        let response = client.get_html_as(fetch_url, user_agent.as_deref()).await;
+        let latency = requested.elapsed();
+        permit.record(&response);
+        (response, latency)
+    };
+
+    let archived = async {
+        let response = response?;
+        let page = Page::new(url, response.final_url.as_str(), response.html.as_str(), Some(crawl_id))?
+            .with_headers(response.headers)
+            .with_user_agent(user_agent);
+
+        let mut db_lock = db.lock().await;
+        page.sync(&mut db_lock)?;
+        Ok(response.html.len() as u64)
+    };
+    (archived.await, latency)
+}
+
+async fn query(query_id: i64, crawl_id: i64, selector: &str, db: &mut Database) -> Result<Vec<ResultEntry>, Box<dyn Error>> {
+    let expression = SelectorExpression::parse(selector)?;
+    let pages_archive = PageArchive::fetch_by_crawl_id(crawl_id, db)?;
 
     let mut all_results: Vec<ResultEntry> = Vec::new();
 
     for archive in pages_archive {
         if let Ok(page) = archive.to_page() {
-            if let Some(nodes) = page.dom.query_selector(selector) {
-                let count_u32 = nodes.count() as u32;
-                if count_u32 > 0 {
-                    let mut result_entry = ResultEntry::new(None, archive.id, selector, count_u32);
-                    let _ = result_entry.sync(&mut db);
-                    all_results.push(result_entry);
+            let count_u32 = expression.count(&page) as u32;
+            if count_u32 > 0 {
+                let mut result_entry = ResultEntry::new(None, query_id, archive.id, selector, count_u32);
+                let _ = result_entry.sync(db);
+                if let (Some(result_id), SelectorExpression::Selector(css)) = (result_entry.id, &expression) {
+                    result_match::record(result_id, &result_match::locate(&page, css), db)?;
                 }
+                all_results.push(result_entry);
             }
         }
     }
 
     Ok(all_results)
 }
+
+async fn run_audit(
+    crawl_id: i64,
+    audit: Audit,
+    db: Arc<Mutex<Database>>,
+    http_client: &HTTPClient,
+    check_dead: bool,
+) -> Result<Vec<Finding>, Box<dyn Error>> {
+    let mut findings = match audit {
+        Audit::SitemapImages => audit::sitemap_images::run(crawl_id, &db, http_client, check_dead).await?,
+        Audit::Soft404 => audit::soft_404::run(crawl_id, &db).await?,
+        page_audit => {
+            let db_lock = db.lock().await;
+            let pages = PageArchive::fetch_by_crawl_id(crawl_id, &db_lock)?;
+            page_audit.check_pages(crawl_id, &pages)
+        }
+    };
+
+    // Re-running an audit replaces its previous findings for the crawl
+    let mut db_lock = db.lock().await;
+    Finding::delete_by_crawl_and_audit(crawl_id, audit.name(), &db_lock)?;
+    for finding in findings.iter_mut() {
+        finding.sync(&mut db_lock)?;
+    }
+
+    Ok(findings)
+}
@@ -0,0 +1,73 @@
+use crate::page::Page;
+use crate::third_party::is_same_site;
+use std::collections::HashSet;
+
+/// Links followed from a seed page by default: seeds are depth 0.
+pub const DEFAULT_LINK_DEPTH: usize = 3;
+
+/// Pages a link-following crawl fetches by default, seeds included.
+pub const DEFAULT_LINK_PAGES: usize = 500;
+
+/// A crawl that starts from seed URLs and follows the links of each page it
+/// fetches, for sites without a sitemap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkFollowing {
+    /// Where the crawl starts; the site's root when empty.
+    pub seeds: Vec<String>,
+    /// Link hops followed from a seed; 0 fetches just the seeds.
+    pub max_depth: usize,
+    /// Pages fetched in all; links found past it are left out. The first
+    /// seed is fetched even when it's 0.
+    pub max_pages: usize,
+}
+
+impl Default for LinkFollowing {
+    fn default() -> Self {
+        Self {
+            seeds: Vec::new(),
+            max_depth: DEFAULT_LINK_DEPTH,
+            max_pages: DEFAULT_LINK_PAGES,
+        }
+    }
+}
+
+/// The HTTP(S) links of a page on the site at `site_host`, subdomains
+/// included, without fragments and in page order. Links already in `seen`
+/// are left out and the rest are added to it.
+pub fn new_links(page: &Page, site_host: &str, seen: &mut HashSet<String>) -> Vec<String> {
+    page.links()
+        .into_iter()
+        .filter(|link| {
+            let Ok(url) = reqwest::Url::parse(link) else { return false };
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some_and(|host| is_same_site(&host.to_ascii_lowercase(), site_host))
+        })
+        .filter(|link| seen.insert(link.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_links_stay_on_site() {
+        let page = Page::new(
+            "https://a.com/blog",
+            "https://a.com/blog/",
+            r##"<a href="post-1#comments">1</a> <a href="/blog/post-1">1 again</a> <a href="https://www.a.com/about">About</a>
+                <a href="https://docs.a.com/">Docs</a> <a href="https://b.com/">B</a> <a href="mailto:hi@a.com">Mail</a>
+                <a href="/">Home</a>"##,
+            None,
+        )
+        .unwrap();
+
+        let mut seen = HashSet::from(["https://a.com/".to_string()]);
+        assert_eq!(
+            new_links(&page, "a.com", &mut seen),
+            ["https://a.com/blog/post-1", "https://www.a.com/about", "https://docs.a.com/"]
+        );
+        assert!(new_links(&page, "a.com", &mut seen).is_empty());
+        assert_eq!(seen.len(), 4);
+    }
+}
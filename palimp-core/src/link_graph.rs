@@ -0,0 +1,302 @@
+use crate::assertion::escape_xml;
+use crate::database::Database;
+use crate::page_archive::PageArchive;
+use rusqlite::params;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::io::Write;
+
+/// Share of a page's score passed on through its links, as in PageRank.
+const DAMPING: f64 = 0.85;
+
+/// Iterations after which scores are used even if they still move.
+const MAX_ITERATIONS: usize = 100;
+
+/// A file format the link graph can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz.
+    Dot,
+    /// Gephi, yEd and most graph tools.
+    GraphMl,
+    /// A `Source,Target` edge list.
+    Csv,
+}
+
+impl GraphFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+            GraphFormat::Csv => "csv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dot" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "csv" => Some(GraphFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// The internal links of a crawl: a node per archived page and an edge per
+/// page linking to another archived page. Links to pages outside the crawl,
+/// repeated links and links of a page to itself are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkGraph {
+    /// Page URLs, in crawl order.
+    pub nodes: Vec<String>,
+    /// Indexes into `nodes` of the linking and the linked page, sorted.
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl LinkGraph {
+    pub fn build(crawl_id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let mut archives = PageArchive::fetch_by_crawl_id(crawl_id, database)?;
+        archives.sort_by_key(|archive| archive.id);
+
+        // A link may point at the requested URL of a page or where it ended up
+        let mut index = HashMap::new();
+        for (node, archive) in archives.iter().enumerate() {
+            index.entry(archive.url.clone()).or_insert(node);
+            index.entry(archive.final_url.clone()).or_insert(node);
+        }
+
+        let mut edges = BTreeSet::new();
+        for (source, archive) in archives.iter().enumerate() {
+            let Ok(page) = archive.to_page() else { continue };
+            for link in page.links() {
+                if let Some(&target) = index.get(&link)
+                    && target != source
+                {
+                    edges.insert((source, target));
+                }
+            }
+        }
+
+        Ok(LinkGraph {
+            nodes: archives.into_iter().map(|archive| archive.url).collect(),
+            edges: edges.into_iter().collect(),
+        })
+    }
+
+    /// Inlinks, outlinks and a PageRank-style score of every page. Scores
+    /// are scaled so that an average page has 1.0: a page well below that is
+    /// hard to reach by following links.
+    pub fn metrics(&self, crawl_id: i64) -> Vec<LinkMetric> {
+        let count = self.nodes.len();
+        let mut inlinks = vec![0; count];
+        let mut outlinks = vec![0; count];
+        for &(source, target) in &self.edges {
+            outlinks[source] += 1;
+            inlinks[target] += 1;
+        }
+
+        let mut scores = vec![1.0 / count as f64; count];
+        for _ in 0..MAX_ITERATIONS {
+            // Pages without links spread their score over every page
+            let dangling: f64 = (0..count).filter(|&node| outlinks[node] == 0).map(|node| scores[node]).sum();
+            let base = (1.0 - DAMPING + DAMPING * dangling) / count as f64;
+            let mut next = vec![base; count];
+            for &(source, target) in &self.edges {
+                next[target] += DAMPING * scores[source] / outlinks[source] as f64;
+            }
+
+            let change: f64 = scores.iter().zip(&next).map(|(old, new)| (old - new).abs()).sum();
+            scores = next;
+            if change < 1e-9 {
+                break;
+            }
+        }
+
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(node, url)| LinkMetric {
+                crawl_id,
+                url: url.clone(),
+                inlinks: inlinks[node],
+                outlinks: outlinks[node],
+                score: scores[node] * count as f64,
+            })
+            .collect()
+    }
+
+    pub fn write(&self, format: GraphFormat, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        match format {
+            GraphFormat::Dot => self.write_dot(writer),
+            GraphFormat::GraphMl => self.write_graphml(writer),
+            GraphFormat::Csv => self.write_csv(writer),
+        }
+    }
+
+    fn write_dot(&self, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(writer, "digraph links {{")?;
+        for url in &self.nodes {
+            writeln!(writer, "  {};", quote_dot(url))?;
+        }
+        for &(source, target) in &self.edges {
+            writeln!(writer, "  {} -> {};", quote_dot(&self.nodes[source]), quote_dot(&self.nodes[target]))?;
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    fn write_graphml(&self, mut writer: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(writer, r#"  <key id="url" for="node" attr.name="url" attr.type="string"/>"#)?;
+        writeln!(writer, r#"  <graph id="links" edgedefault="directed">"#)?;
+        for (node, url) in self.nodes.iter().enumerate() {
+            writeln!(writer, r#"    <node id="n{}"><data key="url">{}</data></node>"#, node, escape_xml(url))?;
+        }
+        for (edge, &(source, target)) in self.edges.iter().enumerate() {
+            writeln!(writer, r#"    <edge id="e{}" source="n{}" target="n{}"/>"#, edge, source, target)?;
+        }
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+        Ok(())
+    }
+
+    fn write_csv(&self, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["Source", "Target"])?;
+        for &(source, target) in &self.edges {
+            wtr.write_record([&self.nodes[source], &self.nodes[target]])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// How well a page of a crawl is linked from the rest of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkMetric {
+    pub crawl_id: i64,
+    pub url: String,
+    /// Archived pages linking to this one.
+    pub inlinks: usize,
+    /// Archived pages this one links to.
+    pub outlinks: usize,
+    /// Centrality relative to an average page of the crawl, which has 1.0.
+    pub score: f64,
+}
+
+impl LinkMetric {
+    /// Metrics of a crawl's pages, best linked first.
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT crawl_id, url, inlinks, outlinks, score FROM link_metrics
+             WHERE crawl_id = ?1 ORDER BY score DESC, url",
+        )?;
+
+        let metric_iter = stmt.query_map(params![crawl_id], |row| {
+            Ok(LinkMetric {
+                crawl_id: row.get(0)?,
+                url: row.get(1)?,
+                inlinks: row.get::<_, i64>(2)? as usize,
+                outlinks: row.get::<_, i64>(3)? as usize,
+                score: row.get(4)?,
+            })
+        })?;
+
+        let mut metrics = Vec::new();
+        for metric in metric_iter {
+            metrics.push(metric?);
+        }
+
+        Ok(metrics)
+    }
+}
+
+/// Builds the link graph of a crawl and stores the metrics of its pages,
+/// replacing earlier ones. Returns the number of pages.
+pub fn record_metrics(crawl_id: i64, database: &mut Database) -> Result<usize, Box<dyn Error>> {
+    let metrics = LinkGraph::build(crawl_id, database)?.metrics(crawl_id);
+
+    database.in_transaction(|db| {
+        db.conn.execute("DELETE FROM link_metrics WHERE crawl_id = ?1", params![crawl_id])?;
+        for metric in &metrics {
+            db.conn.execute(
+                "INSERT INTO link_metrics (crawl_id, url, inlinks, outlinks, score) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![crawl_id, metric.url, metric.inlinks as i64, metric.outlinks as i64, metric.score],
+            )?;
+        }
+        Ok(())
+    })?;
+
+    Ok(metrics.len())
+}
+
+fn quote_dot(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+
+    #[test]
+    fn test_build_and_write_link_graph() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed');",
+            )
+            .unwrap();
+        let pages = [
+            ("https://a.com/", "https://a.com/", r##"<a href="/b">B</a><a href="/b#top">B</a><a href="/">Home</a>"##),
+            ("https://a.com/b", "https://a.com/b/", r#"<a href="../c">C</a><a href="https://other.com/">Other</a>"#),
+            ("https://a.com/c", "https://a.com/c", r#"<a href="/b/">B</a>"#),
+        ];
+        for (url, final_url, html) in pages {
+            Page::new(url, final_url, html, Some(1)).unwrap().sync(&mut db).unwrap();
+        }
+
+        let graph = LinkGraph::build(1, &db).unwrap();
+        assert_eq!(graph.nodes, ["https://a.com/", "https://a.com/b", "https://a.com/c"]);
+        assert_eq!(graph.edges, [(0, 1), (1, 2), (2, 1)]);
+
+        let mut csv = Vec::new();
+        graph.write(GraphFormat::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "Source,Target\nhttps://a.com/,https://a.com/b\nhttps://a.com/b,https://a.com/c\nhttps://a.com/c,https://a.com/b\n"
+        );
+
+        let mut dot = Vec::new();
+        graph.write(GraphFormat::Dot, &mut dot).unwrap();
+        assert!(String::from_utf8(dot).unwrap().contains("  \"https://a.com/\" -> \"https://a.com/b\";\n"));
+
+        let mut graphml = Vec::new();
+        graph.write(GraphFormat::GraphMl, &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert!(graphml.contains(r#"<node id="n2"><data key="url">https://a.com/c</data></node>"#));
+        assert!(graphml.contains(r#"<edge id="e2" source="n2" target="n1"/>"#));
+    }
+
+    #[test]
+    fn test_link_metrics() {
+        // The home page and two pages linking each other, and an orphan
+        let graph = LinkGraph {
+            nodes: vec!["/".to_string(), "/a".to_string(), "/b".to_string(), "/orphan".to_string()],
+            edges: vec![(0, 1), (0, 2), (1, 0), (2, 0), (3, 0)],
+        };
+        let metrics = graph.metrics(1);
+
+        let counts: Vec<(usize, usize)> = metrics.iter().map(|metric| (metric.inlinks, metric.outlinks)).collect();
+        assert_eq!(counts, [(3, 2), (1, 1), (1, 1), (0, 1)]);
+        let total: f64 = metrics.iter().map(|metric| metric.score).sum();
+        assert!((total - 4.0).abs() < 1e-6);
+        assert!(metrics[0].score > metrics[1].score);
+        assert!((metrics[1].score - metrics[2].score).abs() < 1e-9);
+        assert!(metrics[3].score < metrics[1].score);
+        assert!((metrics[3].score - 0.15).abs() < 1e-6);
+    }
+}
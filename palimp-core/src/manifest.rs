@@ -0,0 +1,435 @@
+use crate::blob::content_hash;
+use crate::crawl::Crawl;
+use crate::crawl_meta::{self, CrawlMeta};
+use crate::crawl_error::CrawlError;
+use crate::database::Database;
+use crate::expression::SelectorExpression;
+use crate::page_archive::PageArchive;
+use crate::query::Query;
+use crate::result_entry::{ResultEntry, ResultFilter};
+use crate::site::Site;
+use crate::site_config::SiteEntry;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+
+/// Version of the manifest layout, bumped when a field changes meaning.
+pub const MANIFEST_FORMAT: u32 = 1;
+
+/// A record of one crawl: the site setup, every archived URL with the
+/// SHA-256 of its body, the URLs that failed, totals and the saved queries
+/// with their results. Written as JSON to document a crawl, checked against
+/// the archive with [`CrawlManifest::verify`] and its queries re-run with
+/// [`CrawlManifest::replay`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrawlManifest {
+    pub format: u32,
+    pub crawl_id: i64,
+    pub status: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// What the crawl was started with, e.g. the release it checked.
+    #[serde(default, skip_serializing_if = "CrawlMeta::is_empty")]
+    pub meta: CrawlMeta,
+    /// The site's settings and monitors when the manifest was written;
+    /// crawls don't keep a copy of their own.
+    pub site: SiteEntry,
+    pub stats: ManifestStats,
+    pub pages: Vec<ManifestPage>,
+    pub errors: Vec<ManifestError>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<ManifestQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestStats {
+    pub pages: usize,
+    pub errors: usize,
+    /// Bytes downloaded while crawling, see [`Crawl::bytes_fetched`].
+    pub bytes_fetched: u64,
+    /// Bytes of the archived bodies, before deduplication.
+    pub stored_bytes: u64,
+    pub duration_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPage {
+    pub url: String,
+    pub final_url: String,
+    pub archived_at: String,
+    /// Hex SHA-256 of the body.
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestError {
+    pub url: String,
+    pub kind: String,
+    pub message: String,
+}
+
+/// A saved query of the crawl and the pages it matched when the manifest was
+/// written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestQuery {
+    pub selector: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub results: Vec<RecordedResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResult {
+    pub url: String,
+    pub count: u32,
+}
+
+/// The outcome of re-running one [`ManifestQuery`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryReplay {
+    pub selector: String,
+    pub name: Option<String>,
+    /// Pages whose match count differs from the recorded one.
+    pub differences: Vec<ReplayDifference>,
+}
+
+impl QueryReplay {
+    /// The query's name, or its selector when it has none.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.selector)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// A page matched a different number of times than recorded; 0 means it
+/// didn't match at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDifference {
+    pub url: String,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// A difference between a manifest and the crawl it describes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManifestMismatch {
+    /// Listed in the manifest but no longer archived.
+    Missing { url: String },
+    /// Archived with a body that no longer hashes as recorded.
+    Changed { url: String, expected: String, actual: String },
+    /// Archived in the crawl but not listed in the manifest.
+    Unlisted { url: String },
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestMismatch::Missing { url } => write!(f, "{}: missing from the archive", url),
+            ManifestMismatch::Changed { url, expected, actual } => {
+                write!(f, "{}: body hashes to {} instead of {}", url, actual, expected)
+            }
+            ManifestMismatch::Unlisted { url } => write!(f, "{}: archived but not in the manifest", url),
+        }
+    }
+}
+
+impl CrawlManifest {
+    /// Describes a crawl as it is archived now. Bodies are hashed afresh
+    /// rather than trusting the stored keys.
+    pub fn build(crawl_id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let crawl = Crawl::fetch(crawl_id, database)?;
+        let site = SiteEntry::from_site(Site::fetch(crawl.site_id, database)?, database)?;
+
+        let mut stmt = database.conn.prepare(
+            "SELECT p.url, p.final_url, p.created_at, b.content
+             FROM pages p
+             LEFT JOIN blobs b ON b.hash = p.blob_hash
+             WHERE p.crawl_id = ?1
+             ORDER BY p.id",
+        )?;
+        let page_iter = stmt.query_map(params![crawl_id], |row| {
+            let content: Option<String> = row.get(3)?;
+            let content = content.unwrap_or_default();
+            Ok(ManifestPage {
+                url: row.get(0)?,
+                final_url: row.get(1)?,
+                archived_at: row.get(2)?,
+                sha256: content_hash(&content),
+                bytes: content.len() as u64,
+            })
+        })?;
+        let mut pages = Vec::new();
+        for page in page_iter {
+            pages.push(page?);
+        }
+
+        let errors: Vec<ManifestError> = CrawlError::fetch_by_crawl_id(crawl_id, database)?
+            .into_iter()
+            .map(|error| ManifestError {
+                url: error.url,
+                kind: error.kind.name().to_string(),
+                message: error.message,
+            })
+            .collect();
+
+        let mut queries = Vec::new();
+        for query in Query::fetch_by_crawl_id(crawl_id, database)? {
+            let query_id = query.id.ok_or("Query without an ID")?;
+            let results = ResultEntry::search(&ResultFilter::for_query(query_id), database)?
+                .into_iter()
+                .map(|(entry, url)| RecordedResult { url, count: entry.count })
+                .collect();
+            queries.push(ManifestQuery {
+                selector: query.selector,
+                name: query.name,
+                description: query.description,
+                results,
+            });
+        }
+
+        let stats = ManifestStats {
+            pages: pages.len(),
+            errors: errors.len(),
+            bytes_fetched: crawl.bytes_fetched,
+            stored_bytes: pages.iter().map(|page| page.bytes).sum(),
+            duration_seconds: crawl.duration.map(|duration| duration.as_secs()),
+        };
+
+        Ok(CrawlManifest {
+            format: MANIFEST_FORMAT,
+            crawl_id,
+            status: crawl.status.name().to_string(),
+            started_at: crawl.started_at,
+            finished_at: crawl.finished_at,
+            meta: crawl_meta::fetch(crawl_id, database)?,
+            site,
+            stats,
+            pages,
+            errors,
+            queries,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let manifest: CrawlManifest = serde_json::from_str(json)?;
+        if manifest.format > MANIFEST_FORMAT {
+            return Err(format!("Manifest format {} is newer than this version of palimp", manifest.format).into());
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Compares the manifest with the archived crawl, returning what
+    /// differs. Empty when the crawl is intact.
+    pub fn verify(&self, database: &Database) -> Result<Vec<ManifestMismatch>, Box<dyn Error>> {
+        self.check_crawl(database)?;
+        let current = CrawlManifest::build(self.crawl_id, database)?;
+
+        let archived: HashMap<&str, &str> = current
+            .pages
+            .iter()
+            .map(|page| (page.url.as_str(), page.sha256.as_str()))
+            .collect();
+        let mut mismatches = Vec::new();
+        for page in &self.pages {
+            match archived.get(page.url.as_str()) {
+                None => mismatches.push(ManifestMismatch::Missing { url: page.url.clone() }),
+                Some(&actual) if actual != page.sha256 => mismatches.push(ManifestMismatch::Changed {
+                    url: page.url.clone(),
+                    expected: page.sha256.clone(),
+                    actual: actual.to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        let listed: HashMap<&str, ()> = self.pages.iter().map(|page| (page.url.as_str(), ())).collect();
+        for page in &current.pages {
+            if !listed.contains_key(page.url.as_str()) {
+                mismatches.push(ManifestMismatch::Unlisted { url: page.url.clone() });
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Re-runs the recorded queries on the archived crawl and compares each
+    /// page's match count with the recorded one, without saving anything.
+    pub fn replay(&self, database: &Database) -> Result<Vec<QueryReplay>, Box<dyn Error>> {
+        self.check_crawl(database)?;
+        let archives = PageArchive::fetch_by_crawl_id(self.crawl_id, database)?;
+        let pages: Vec<_> = archives
+            .iter()
+            .filter_map(|archive| archive.to_page().ok().map(|page| (archive.url.as_str(), page)))
+            .collect();
+
+        let mut replays = Vec::new();
+        for query in &self.queries {
+            let expression = SelectorExpression::parse(&query.selector)?;
+            let actual: BTreeMap<&str, u32> = pages
+                .iter()
+                .map(|(url, page)| (*url, expression.count(page) as u32))
+                .filter(|(_, count)| *count > 0)
+                .collect();
+            let expected: BTreeMap<&str, u32> =
+                query.results.iter().map(|result| (result.url.as_str(), result.count)).collect();
+
+            let urls: BTreeSet<&str> = expected.keys().chain(actual.keys()).copied().collect();
+            let differences = urls
+                .into_iter()
+                .filter_map(|url| {
+                    let expected = expected.get(url).copied().unwrap_or(0);
+                    let actual = actual.get(url).copied().unwrap_or(0);
+                    (expected != actual).then(|| ReplayDifference { url: url.to_string(), expected, actual })
+                })
+                .collect();
+
+            replays.push(QueryReplay {
+                selector: query.selector.clone(),
+                name: query.name.clone(),
+                differences,
+            });
+        }
+
+        Ok(replays)
+    }
+
+    /// Fails when the archive's crawl with this ID isn't the one described,
+    /// e.g. when checking a manifest against another archive.
+    fn check_crawl(&self, database: &Database) -> Result<(), Box<dyn Error>> {
+        let crawl = Crawl::fetch(self.crawl_id, database)?;
+        let site = Site::fetch(crawl.site_id, database)?;
+        if site.domain != self.site.domain || crawl.started_at != self.started_at {
+            return Err(format!(
+                "Crawl {} of this archive is not the one the manifest describes ({} started {})",
+                self.crawl_id,
+                self.site.domain,
+                self.started_at.as_deref().unwrap_or("at an unknown time")
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl_error::CrawlErrorKind;
+    use crate::page::Page;
+
+    #[test]
+    fn test_build_and_verify() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let mut crawl = Crawl::new(None, site.id.unwrap());
+        crawl.sync(&mut db).unwrap();
+        let crawl_id = crawl.id.unwrap();
+        for (url, html) in [("https://a.com/", "<h1>Home</h1>"), ("https://a.com/about", "<h1>About</h1>")] {
+            Page::new(url, url, html, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+        }
+        CrawlError::new(None, crawl_id, "https://a.com/gone", CrawlErrorKind::Fetch, "404").sync(&mut db).unwrap();
+        let meta = CrawlMeta::from([("sha".to_string(), "4f2a9c1".to_string())]);
+        crawl_meta::record(crawl_id, &meta, &db).unwrap();
+
+        let manifest = CrawlManifest::build(crawl_id, &db).unwrap();
+        assert_eq!(manifest.site.domain, "a.com");
+        assert_eq!(manifest.meta, meta);
+        assert!(manifest.to_json().unwrap().contains(r#""sha": "4f2a9c1""#));
+        assert_eq!(manifest.pages[0].sha256, content_hash("<h1>Home</h1>"));
+        assert_eq!(manifest.stats.pages, 2);
+        assert_eq!(manifest.stats.errors, 1);
+        assert_eq!(manifest.stats.stored_bytes, 27);
+        assert_eq!(manifest.errors[0].url, "https://a.com/gone");
+        assert_eq!(CrawlManifest::from_json(&manifest.to_json().unwrap()).unwrap(), manifest);
+        assert!(manifest.verify(&db).unwrap().is_empty());
+
+        // Tamper with one body, drop a page and add another
+        db.conn
+            .execute(
+                "UPDATE blobs SET content = '<h1>Changed</h1>' WHERE hash = ?1",
+                params![content_hash("<h1>About</h1>")],
+            )
+            .unwrap();
+        db.conn.execute("DELETE FROM pages WHERE url = 'https://a.com/'", []).unwrap();
+        Page::new("https://a.com/new", "https://a.com/new", "<p>New</p>", Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+
+        assert_eq!(
+            manifest.verify(&db).unwrap(),
+            [
+                ManifestMismatch::Missing { url: "https://a.com/".to_string() },
+                ManifestMismatch::Changed {
+                    url: "https://a.com/about".to_string(),
+                    expected: content_hash("<h1>About</h1>"),
+                    actual: content_hash("<h1>Changed</h1>"),
+                },
+                ManifestMismatch::Unlisted { url: "https://a.com/new".to_string() },
+            ]
+        );
+
+        let mut other = manifest.clone();
+        other.site.domain = "b.com".to_string();
+        assert!(other.verify(&db).is_err());
+        other.format = MANIFEST_FORMAT + 1;
+        assert!(CrawlManifest::from_json(&other.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_replay_queries() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let mut crawl = Crawl::new(None, site.id.unwrap());
+        crawl.sync(&mut db).unwrap();
+        let crawl_id = crawl.id.unwrap();
+        for (url, html) in [("https://a.com/", "<h1>A</h1><h1>B</h1>"), ("https://a.com/about", "<p>About</p>")] {
+            Page::new(url, url, html, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+        }
+        let mut query = Query::new(None, crawl_id, "h1").with_name(Some("Headings"));
+        query.sync(&mut db).unwrap();
+        let page_id: i64 = db.conn.query_row("SELECT id FROM pages WHERE url = 'https://a.com/'", [], |row| row.get(0)).unwrap();
+        ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 2).sync(&mut db).unwrap();
+
+        let manifest = CrawlManifest::build(crawl_id, &db).unwrap();
+        assert_eq!(
+            manifest.queries,
+            [ManifestQuery {
+                selector: "h1".to_string(),
+                name: Some("Headings".to_string()),
+                description: None,
+                results: vec![RecordedResult { url: "https://a.com/".to_string(), count: 2 }],
+            }]
+        );
+        let replays = manifest.replay(&db).unwrap();
+        assert_eq!(replays.len(), 1);
+        assert!(replays[0].passed());
+        assert_eq!(replays[0].label(), "Headings");
+
+        // A recorded output that the archive doesn't reproduce
+        let mut edited = manifest.clone();
+        edited.queries[0].results = vec![RecordedResult { url: "https://a.com/about".to_string(), count: 1 }];
+        assert_eq!(
+            edited.replay(&db).unwrap()[0].differences,
+            [
+                ReplayDifference { url: "https://a.com/".to_string(), expected: 0, actual: 2 },
+                ReplayDifference { url: "https://a.com/about".to_string(), expected: 1, actual: 0 },
+            ]
+        );
+        let results: i64 = db.conn.query_row("SELECT COUNT(*) FROM results", [], |row| row.get(0)).unwrap();
+        assert_eq!(results, 1);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::database::Database;
+use crate::diff::{diff, DiffOp};
+use crate::page_archive::PageArchive;
+use rusqlite::params;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// What a monitor watches for between two consecutive crawls of a site.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonitorKind {
+    /// Alert when the number of elements matching the selector changes.
+    SelectorCount(String),
+    /// Alert when the visible text changes by more than this percentage.
+    TextChange(f64),
+}
+
+/// A change monitoring rule, evaluated after each crawl of its site against
+/// the previous crawl.
+pub struct Monitor {
+    pub id: Option<i64>,
+    pub site_id: i64,
+    pub kind: MonitorKind,
+    /// Only URLs containing this string are compared.
+    pub url_pattern: Option<String>,
+}
+
+impl Monitor {
+    pub fn new(id: Option<i64>, site_id: i64, kind: MonitorKind, url_pattern: Option<&str>) -> Self {
+        Self {
+            id,
+            site_id,
+            kind,
+            url_pattern: url_pattern.map(|p| p.to_string()),
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let (kind, selector, threshold) = self.kind_columns();
+
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE monitors SET kind = ?1, selector = ?2, threshold = ?3, url_pattern = ?4 WHERE id = ?5",
+                    params![kind, selector, threshold, self.url_pattern, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO monitors (site_id, kind, selector, threshold, url_pattern) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![self.site_id, kind, selector, threshold, self.url_pattern],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::fetch_where("1 = 1", params![], database)
+    }
+
+    pub fn fetch_by_site(site_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::fetch_where("site_id = ?1", params![site_id], database)
+    }
+
+    fn fetch_where(
+        condition: &str,
+        parameters: &[&dyn rusqlite::ToSql],
+        database: &Database,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(&format!(
+            "SELECT id, site_id, kind, selector, threshold, url_pattern FROM monitors WHERE {}",
+            condition
+        ))?;
+
+        let monitor_iter = stmt.query_map(parameters, |row| {
+            let kind: String = row.get(2)?;
+            let selector: Option<String> = row.get(3)?;
+            let threshold: Option<f64> = row.get(4)?;
+
+            let kind = match kind.as_str() {
+                "text-change" => MonitorKind::TextChange(threshold.unwrap_or(0.0)),
+                _ => MonitorKind::SelectorCount(selector.unwrap_or_default()),
+            };
+
+            Ok(Monitor {
+                id: Some(row.get(0)?),
+                site_id: row.get(1)?,
+                kind,
+                url_pattern: row.get(5)?,
+            })
+        })?;
+
+        let mut monitors = Vec::new();
+        for monitor in monitor_iter {
+            monitors.push(monitor?);
+        }
+
+        Ok(monitors)
+    }
+
+    pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        database
+            .conn
+            .execute("DELETE FROM monitors WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn kind_columns(&self) -> (&'static str, Option<String>, Option<f64>) {
+        match &self.kind {
+            MonitorKind::SelectorCount(selector) => ("selector-count", Some(selector.clone()), None),
+            MonitorKind::TextChange(threshold) => ("text-change", None, Some(*threshold)),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let scope = match &self.url_pattern {
+            Some(pattern) => format!(" on URLs containing '{}'", pattern),
+            None => String::new(),
+        };
+
+        match &self.kind {
+            MonitorKind::SelectorCount(selector) => format!("count of '{}' changes{}", selector, scope),
+            MonitorKind::TextChange(threshold) => format!("text changes more than {}%{}", threshold, scope),
+        }
+    }
+
+    /// Compares the pages both crawls have in common and returns `(url,
+    /// message)` pairs for every page that triggers the monitor.
+    pub fn evaluate(&self, previous: &[PageArchive], current: &[PageArchive]) -> Vec<(String, String)> {
+        let previous_by_url: HashMap<&str, &PageArchive> =
+            previous.iter().map(|archive| (archive.url.as_str(), archive)).collect();
+
+        let mut triggered = Vec::new();
+
+        for archive in current {
+            if let Some(pattern) = &self.url_pattern
+                && !archive.url.contains(pattern.as_str())
+            {
+                continue;
+            }
+
+            let before_archive = match previous_by_url.get(archive.url.as_str()) {
+                Some(before) => before,
+                None => continue,
+            };
+
+            let (before, after) = match (before_archive.to_page(), archive.to_page()) {
+                (Ok(before), Ok(after)) => (before, after),
+                _ => continue,
+            };
+
+            match &self.kind {
+                MonitorKind::SelectorCount(selector) => {
+                    let count = |page: &crate::page::Page| {
+                        page.dom.query_selector(selector).map(|nodes| nodes.count()).unwrap_or(0)
+                    };
+                    let (old_count, new_count) = (count(&before), count(&after));
+                    if old_count != new_count {
+                        triggered.push((
+                            archive.url.clone(),
+                            format!("count of '{}' changed from {} to {}", selector, old_count, new_count),
+                        ));
+                    }
+                }
+                MonitorKind::TextChange(threshold) => {
+                    let change = text_change_percent(&before.visible_text(), &after.visible_text());
+                    if change > *threshold {
+                        triggered.push((
+                            archive.url.clone(),
+                            format!("visible text changed by {:.1}% (threshold {}%)", change, threshold),
+                        ));
+                    }
+                }
+            }
+        }
+
+        triggered
+    }
+}
+
+/// Share of text lines added or removed between two versions, in percent.
+pub fn text_change_percent(before: &[String], after: &[String]) -> f64 {
+    let total = before.len() + after.len();
+    if total == 0 {
+        return 0.0;
+    }
+
+    let changed = diff(before, after)
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Equal(_)))
+        .count();
+
+    changed as f64 * 100.0 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(url: &str, html: &str) -> PageArchive {
+        PageArchive::new(0, 0, url, url, html)
+    }
+
+    #[test]
+    fn test_selector_count_monitor() {
+        let monitor = Monitor::new(None, 1, MonitorKind::SelectorCount(".price".to_string()), Some("/product/"));
+        let previous = vec![
+            archive("https://shop.com/product/1", r#"<p class="price">1</p>"#),
+            archive("https://shop.com/about", r#"<p class="price">1</p>"#),
+        ];
+        let current = vec![
+            archive("https://shop.com/product/1", r#"<p class="price">1</p><p class="price">2</p>"#),
+            archive("https://shop.com/about", "<p>none</p>"),
+        ];
+
+        let triggered = monitor.evaluate(&previous, &current);
+
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].0, "https://shop.com/product/1");
+        assert_eq!(triggered[0].1, "count of '.price' changed from 1 to 2");
+    }
+
+    #[test]
+    fn test_text_change_monitor() {
+        let monitor = Monitor::new(None, 1, MonitorKind::TextChange(10.0), None);
+        let previous = vec![archive("https://a.com/", "<p>one</p><p>two</p><p>three</p><p>four</p>")];
+        let unchanged = vec![archive("https://a.com/", "<p>one</p><p>two</p><p>three</p><p>four</p>")];
+        let changed = vec![archive("https://a.com/", "<p>one</p><p>2</p><p>three</p><p>four</p>")];
+
+        assert!(monitor.evaluate(&previous, &unchanged).is_empty());
+        assert_eq!(monitor.evaluate(&previous, &changed).len(), 1);
+    }
+}
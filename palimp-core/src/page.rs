@@ -1,7 +1,13 @@
+use crate::blob;
 use crate::database::Database;
+use crate::redirect;
+use crate::search;
 use rusqlite::params;
 use std::error::Error;
-use tl::VDom;
+use tl::{Node, NodeHandle, Parser, VDom};
+
+/// Elements whose text never shows up on the rendered page.
+const NON_VISIBLE_ELEMENTS: [&str; 5] = ["head", "script", "style", "noscript", "template"];
 
 pub struct Page<'a> {
     pub dom: VDom<'a>,
@@ -9,6 +15,9 @@ pub struct Page<'a> {
     pub final_url: String,
     pub html_content: &'a str,
     pub crawl_id: Option<i64>,
+    pub headers: Vec<(String, String)>,
+    /// Set when the crawl rotated user agents.
+    pub user_agent: Option<String>,
 }
 
 impl<'a> Page<'a> {
@@ -21,26 +30,216 @@ impl<'a> Page<'a> {
         let dom = tl::parse(page_content, tl::ParserOptions::default())?;
 
         Ok(Page {
-            dom: dom,
+            dom,
             url: url.to_string(),
             final_url: final_url.to_string(),
             html_content: page_content,
-            crawl_id: crawl_id,
+            crawl_id,
+            headers: Vec::new(),
+            user_agent: None,
         })
     }
 
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Returns the value of a response header (case-insensitive name lookup).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
     pub fn sync(&self, database: &mut Database) -> Result<(), Box<dyn Error>> {
         let cid = self
             .crawl_id
             .ok_or("Cannot sync a page without a crawl_id")?;
 
+        let redirect = redirect::detect(self);
+        let blob_hash = blob::store(self.html_content, database)?;
+
+        // The sitemap's freshness hints for the URL, if it came from one
         database.conn.execute(
-            "INSERT INTO pages (crawl_id, url, final_url, html_content) VALUES (?1, ?2, ?3, ?4)",
-            params![cid, self.url, self.final_url, self.html_content],
+            "INSERT INTO pages (crawl_id, url, final_url, blob_hash, headers, redirect_type, redirect_target, canonical_url, user_agent,
+                                sitemap_lastmod, sitemap_changefreq, sitemap_priority, created_at)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, s.lastmod, s.changefreq, s.priority, palimp_now()
+             FROM (SELECT 1) LEFT JOIN sitemap_urls s ON s.crawl_id = ?1 AND s.url = ?2",
+            params![
+                cid,
+                self.url,
+                self.final_url,
+                blob_hash,
+                encode_headers(&self.headers),
+                redirect.as_ref().map(|(kind, _)| kind.name()),
+                redirect.as_ref().map(|(_, target)| target.as_str()),
+                self.canonical_url(),
+                self.user_agent,
+            ],
         )?;
 
+        let page_id = database.conn.last_insert_rowid();
+        search::index_page(page_id, &self.visible_text().join("\n"), database)?;
+
         Ok(())
     }
+
+    /// Returns the values of `attribute` on every element matching `selector`.
+    pub fn attribute_values(&self, selector: &str, attribute: &str) -> Vec<String> {
+        let parser = self.dom.parser();
+
+        match self.dom.query_selector(selector) {
+            Some(nodes) => nodes
+                .filter_map(|handle| handle.get(parser))
+                .filter_map(|node| node.as_tag())
+                .filter_map(|tag| tag_attribute(tag, attribute))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the `content` of every `<meta>` tag whose `name` matches
+    /// (case-insensitively).
+    pub fn meta_content(&self, name: &str) -> Vec<String> {
+        self.meta_content_where("name", name)
+    }
+
+    /// Returns the `content` of every `<meta>` tag whose `http-equiv`
+    /// matches (case-insensitively).
+    pub fn meta_http_equiv(&self, name: &str) -> Vec<String> {
+        self.meta_content_where("http-equiv", name)
+    }
+
+    fn meta_content_where(&self, attribute: &str, value: &str) -> Vec<String> {
+        let parser = self.dom.parser();
+
+        match self.dom.query_selector("meta") {
+            Some(nodes) => nodes
+                .filter_map(|handle| handle.get(parser))
+                .filter_map(|node| node.as_tag())
+                .filter(|tag| tag_attribute(tag, attribute).is_some_and(|v| v.trim().eq_ignore_ascii_case(value)))
+                .filter_map(|tag| tag_attribute(tag, "content"))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The page's `<link rel="canonical">` target, resolved to an absolute URL.
+    pub fn canonical_url(&self) -> Option<String> {
+        let parser = self.dom.parser();
+
+        self.dom
+            .query_selector("link")?
+            .filter_map(|handle| handle.get(parser))
+            .filter_map(|node| node.as_tag())
+            .filter(|tag| {
+                tag_attribute(tag, "rel").is_some_and(|rel| {
+                    rel.split_whitespace().any(|token| token.eq_ignore_ascii_case("canonical"))
+                })
+            })
+            .find_map(|tag| tag_attribute(tag, "href"))
+            .and_then(|href| self.resolve_url(&href))
+    }
+
+    /// Targets of the page's `<a href>` links, resolved to absolute URLs
+    /// without their fragments, in document order.
+    pub fn links(&self) -> Vec<String> {
+        self.attribute_values("a[href]", "href")
+            .iter()
+            .filter_map(|href| {
+                let mut url = reqwest::Url::parse(&self.final_url).ok()?.join(href.trim()).ok()?;
+                url.set_fragment(None);
+                Some(url.to_string())
+            })
+            .collect()
+    }
+
+    /// Whitespace-normalized text of the page's first `<title>`.
+    pub fn title(&self) -> Option<String> {
+        let parser = self.dom.parser();
+
+        self.dom
+            .query_selector("title")?
+            .filter_map(|handle| handle.get(parser))
+            .map(|node| node.inner_text(parser).split_whitespace().collect::<Vec<_>>().join(" "))
+            .find(|title| !title.is_empty())
+    }
+
+    /// Extracts the visible text of the page, one whitespace-normalized line
+    /// per text node.
+    pub fn visible_text(&self) -> Vec<String> {
+        let parser = self.dom.parser();
+        let mut lines = Vec::new();
+
+        for handle in self.dom.children() {
+            collect_text(*handle, parser, &mut lines);
+        }
+
+        lines
+    }
+
+    /// Resolves a (possibly relative) reference against the page's final URL.
+    pub fn resolve_url(&self, href: &str) -> Option<String> {
+        let base = reqwest::Url::parse(&self.final_url).ok()?;
+        base.join(href.trim()).ok().map(|url| url.to_string())
+    }
+}
+
+/// Serializes headers as `name: value` lines for storage.
+pub fn encode_headers(headers: &[(String, String)]) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value.replace('\n', " ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn decode_headers(encoded: &str) -> Vec<(String, String)> {
+    encoded
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn collect_text(handle: NodeHandle, parser: &Parser, lines: &mut Vec<String>) {
+    match handle.get(parser) {
+        Some(Node::Tag(tag)) => {
+            if NON_VISIBLE_ELEMENTS.contains(&tag_name(tag).as_str()) {
+                return;
+            }
+            for child in tag.children().top().iter() {
+                collect_text(*child, parser, lines);
+            }
+        }
+        Some(Node::Raw(text)) => {
+            let text = text.as_utf8_str().split_whitespace().collect::<Vec<_>>().join(" ");
+            if !text.is_empty() {
+                lines.push(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns an attribute's value; valueless (boolean) attributes yield an
+/// empty string.
+pub(crate) fn tag_attribute(tag: &tl::HTMLTag, attribute: &str) -> Option<String> {
+    tag.attributes()
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(attribute))
+        .map(|(_, value)| value.map(|v| v.into_owned()).unwrap_or_default())
+}
+
+pub(crate) fn tag_name(tag: &tl::HTMLTag) -> String {
+    tag.name().as_utf8_str().to_ascii_lowercase()
 }
 
 #[cfg(test)]
@@ -96,4 +295,36 @@ mod tests {
             assert_eq!(nodes.count(), 0);
         }
     }
+
+    #[test]
+    fn test_page_visible_text() {
+        let html = r#"<html><head><title>Title</title></head><body><h1>Hello</h1><script>var x;</script><p>  Some
+            text </p></body></html>"#;
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+
+        assert_eq!(page.visible_text(), vec!["Hello", "Some text"]);
+    }
+
+    #[test]
+    fn test_page_attribute_values_and_resolution() {
+        let html = r#"<html><body><img src="/a.png"><img src="https://cdn.test.com/b.png"><img></body></html>"#;
+        let page = Page::new("http://test.com/blog/", "http://test.com/blog/", html, None)
+            .expect("Failed to create page");
+
+        let sources = page.attribute_values("img", "src");
+        assert_eq!(sources, vec!["/a.png", "https://cdn.test.com/b.png"]);
+        assert_eq!(page.resolve_url(&sources[0]).as_deref(), Some("http://test.com/a.png"));
+        assert_eq!(page.resolve_url("c.png").as_deref(), Some("http://test.com/blog/c.png"));
+    }
+
+    #[test]
+    fn test_page_canonical_url() {
+        let html = r#"<html><head><link rel="stylesheet" href="/a.css"><link rel="Canonical" href="/pricing"></head></html>"#;
+        let page = Page::new("http://test.com/pricing?ref=ad", "http://test.com/pricing?ref=ad", html, None)
+            .expect("Failed to create page");
+        assert_eq!(page.canonical_url(), Some("http://test.com/pricing".to_string()));
+
+        let page = Page::new("http://test.com", "http://test.com", "<p>None</p>", None).unwrap();
+        assert_eq!(page.canonical_url(), None);
+    }
 }
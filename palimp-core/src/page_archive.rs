@@ -1,7 +1,13 @@
 use std::error::Error;
-use rusqlite::params;
+use rusqlite::{params, Row};
 use crate::database::Database;
-use crate::page::Page;
+use crate::page::{decode_headers, Page};
+
+const COLUMNS: &str = "p.id, p.url, p.final_url, b.content, p.crawl_id, p.headers, p.redirect_type, p.redirect_target, p.canonical_url, p.user_agent, \
+                       p.sitemap_lastmod, p.sitemap_changefreq, p.sitemap_priority";
+
+/// Pages with their bodies from the blob store.
+const PAGES: &str = "pages p INNER JOIN blobs b ON b.hash = p.blob_hash";
 
 pub struct PageArchive {
     pub id: i64,
@@ -9,37 +15,70 @@ pub struct PageArchive {
     pub final_url: String,
     pub html_content: String,
     pub crawl_id: i64,
+    pub headers: Vec<(String, String)>,
+    /// Name of a `RedirectType` detected when the page was archived.
+    pub redirect_type: Option<String>,
+    pub redirect_target: Option<String>,
+    /// Resolved `<link rel="canonical">` target, extracted at crawl time.
+    pub canonical_url: Option<String>,
+    /// User agent the page was fetched with, when the crawl rotated them.
+    pub user_agent: Option<String>,
+    /// `<lastmod>`, `<changefreq>` and `<priority>` of the sitemap entry the
+    /// page was crawled from.
+    pub sitemap_lastmod: Option<String>,
+    pub sitemap_changefreq: Option<String>,
+    pub sitemap_priority: Option<f64>,
 }
 
 impl PageArchive {
+    pub fn new(id: i64, crawl_id: i64, url: &str, final_url: &str, html_content: &str) -> Self {
+        PageArchive {
+            id,
+            url: url.to_string(),
+            final_url: final_url.to_string(),
+            html_content: html_content.to_string(),
+            crawl_id,
+            headers: Vec::new(),
+            redirect_type: None,
+            redirect_target: None,
+            canonical_url: None,
+            user_agent: None,
+            sitemap_lastmod: None,
+            sitemap_changefreq: None,
+            sitemap_priority: None,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(PageArchive {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            final_url: row.get(2)?,
+            html_content: row.get(3)?,
+            crawl_id: row.get(4)?,
+            headers: decode_headers(&row.get::<_, String>(5)?),
+            redirect_type: row.get(6)?,
+            redirect_target: row.get(7)?,
+            canonical_url: row.get(8)?,
+            user_agent: row.get(9)?,
+            sitemap_lastmod: row.get(10)?,
+            sitemap_changefreq: row.get(11)?,
+            sitemap_priority: row.get(12)?,
+        })
+    }
+
     pub fn fetch(id: i64, db: &Database) -> Result<Self, Box<dyn Error>> {
-        let sql = "SELECT id, url, final_url, html_content, crawl_id FROM pages WHERE id = ?1";
+        let sql = format!("SELECT {} FROM {} WHERE p.id = ?1", COLUMNS, PAGES);
         
-        db.conn.query_row(sql, params![id], |row| {
-            Ok(PageArchive {
-                id: row.get(0)?,
-                url: row.get(1)?,
-                final_url: row.get(2)?,
-                html_content: row.get(3)?,
-                crawl_id: row.get(4)?,
-            })
-        }).map_err(|e| e.into())
+        db.conn.query_row(&sql, params![id], Self::from_row).map_err(|e| e.into())
     }
 
     pub fn fetch_by_crawl_id(crawl_id: i64, db: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
-        let sql = "SELECT id, url, final_url, html_content, crawl_id FROM pages WHERE crawl_id = ?1";
+        let sql = format!("SELECT {} FROM {} WHERE p.crawl_id = ?1", COLUMNS, PAGES);
 
-        let mut stmt = db.conn.prepare(sql)?;
+        let mut stmt = db.conn.prepare(&sql)?;
 
-        let rows = stmt.query_map([crawl_id], |row| {
-            Ok(PageArchive {
-                id: row.get(0)?,
-                url: row.get(1)?,
-                final_url: row.get(2)?,
-                html_content: row.get(3)?,
-                crawl_id: row.get(4)?,
-            })
-        })?;
+        let rows = stmt.query_map([crawl_id], Self::from_row)?;
 
         let mut results = Vec::new();
         for row_result in rows {
@@ -49,12 +88,48 @@ impl PageArchive {
         Ok(results)
     }
 
+    /// URLs archived in a crawl, in crawl order, without loading the pages.
+    pub fn fetch_urls_by_crawl_id(crawl_id: i64, db: &Database) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = db.conn.prepare("SELECT url FROM pages WHERE crawl_id = ?1 ORDER BY id")?;
+        let urls = stmt.query_map([crawl_id], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+        Ok(urls)
+    }
+
+    /// Average HTML size in bytes of the site's most recent crawl with
+    /// archived pages, with that crawl's ID.
+    pub fn average_size_by_site(site_id: i64, db: &Database) -> Result<Option<(i64, u64)>, Box<dyn Error>> {
+        let mut stmt = db.conn.prepare(
+            "SELECT c.id, AVG(LENGTH(CAST(b.content AS BLOB))) FROM pages p
+             JOIN blobs b ON b.hash = p.blob_hash
+             JOIN crawls c ON c.id = p.crawl_id
+             WHERE c.site_id = ?1 AND c.deleted_at IS NULL
+             GROUP BY c.id ORDER BY c.id DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map([site_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)))?;
+        Ok(rows.next().transpose()?.map(|(crawl_id, bytes)| (crawl_id, bytes.round() as u64)))
+    }
+
+    /// Fetches the archived version of a URL (matched against either the
+    /// sitemap URL or the final URL) within a crawl.
+    pub fn fetch_by_crawl_and_url(crawl_id: i64, url: &str, db: &Database) -> Result<Self, Box<dyn Error>> {
+        let sql = format!(
+            "SELECT {} FROM {}
+             WHERE p.crawl_id = ?1 AND (p.url = ?2 OR p.final_url = ?2)
+             ORDER BY p.id LIMIT 1",
+            COLUMNS, PAGES
+        );
+
+        db.conn
+            .query_row(&sql, params![crawl_id, url], Self::from_row)
+            .map_err(|e| format!("URL {} was not archived in crawl {}: {}", url, crawl_id, e).into())
+    }
+
     pub fn to_page(&self) -> Result<Page<'_>, Box<dyn Error>> {
         Page::new(
             &self.url, 
             &self.final_url, 
             &self.html_content, 
             Some(self.crawl_id)
-        )
+        ).map(|page| page.with_headers(self.headers.clone()).with_user_agent(self.user_agent.clone()))
     }
 }
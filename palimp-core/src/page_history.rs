@@ -0,0 +1,186 @@
+use crate::database::Database;
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// How a crawl got on with a URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionStatus {
+    Archived,
+    /// Archived, but the page only points elsewhere.
+    Redirect,
+    /// Recorded as a crawl error, so there is no body.
+    Failed,
+}
+
+impl VersionStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VersionStatus::Archived => "archived",
+            VersionStatus::Redirect => "redirect",
+            VersionStatus::Failed => "failed",
+        }
+    }
+}
+
+/// A URL as one crawl saw it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageVersion {
+    pub crawl_id: i64,
+    pub started_at: Option<String>,
+    /// When the page was stored, `None` for failures.
+    pub archived_at: Option<String>,
+    pub status: VersionStatus,
+    /// Content hash of the body, the key into the blob store.
+    pub hash: Option<String>,
+    pub bytes: u64,
+    /// Whether the body differs from the last archived version before it.
+    /// The first archived version counts as changed.
+    pub changed: bool,
+    /// Redirect target or error message.
+    pub detail: Option<String>,
+}
+
+/// Every version of a URL across the crawls of a site, oldest first, with
+/// at most one version per crawl.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageHistory {
+    pub site_id: i64,
+    pub url: String,
+    pub versions: Vec<PageVersion>,
+}
+
+impl PageHistory {
+    /// Reads the history from the archive without loading any bodies. A
+    /// page matches by its sitemap URL or the URL it ended up at.
+    pub fn build(site_id: i64, url: &str, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let mut by_crawl: BTreeMap<i64, PageVersion> = BTreeMap::new();
+
+        let mut stmt = database.conn.prepare(
+            "SELECT c.id, c.started_at, p.created_at, p.blob_hash, LENGTH(CAST(b.content AS BLOB)),
+                    p.redirect_type, p.redirect_target
+             FROM pages p
+             INNER JOIN crawls c ON c.id = p.crawl_id
+             INNER JOIN blobs b ON b.hash = p.blob_hash
+             WHERE c.site_id = ?1 AND c.deleted_at IS NULL AND (p.url = ?2 OR p.final_url = ?2)
+             ORDER BY p.id",
+        )?;
+        let pages = stmt.query_map(params![site_id, url], |row| {
+            let redirect_type: Option<String> = row.get(5)?;
+            Ok(PageVersion {
+                crawl_id: row.get(0)?,
+                started_at: row.get(1)?,
+                archived_at: row.get(2)?,
+                status: if redirect_type.is_some() { VersionStatus::Redirect } else { VersionStatus::Archived },
+                hash: row.get(3)?,
+                bytes: row.get::<_, i64>(4)? as u64,
+                changed: false,
+                detail: row.get(6)?,
+            })
+        })?;
+        for version in pages {
+            let version = version?;
+            by_crawl.entry(version.crawl_id).or_insert(version);
+        }
+
+        let mut stmt = database.conn.prepare(
+            "SELECT c.id, c.started_at, e.message
+             FROM crawl_errors e
+             INNER JOIN crawls c ON c.id = e.crawl_id
+             WHERE c.site_id = ?1 AND c.deleted_at IS NULL AND e.url = ?2
+             ORDER BY e.id",
+        )?;
+        let failures = stmt.query_map(params![site_id, url], |row| {
+            Ok(PageVersion {
+                crawl_id: row.get(0)?,
+                started_at: row.get(1)?,
+                archived_at: None,
+                status: VersionStatus::Failed,
+                hash: None,
+                bytes: 0,
+                changed: false,
+                detail: row.get(2)?,
+            })
+        })?;
+        for version in failures {
+            let version = version?;
+            by_crawl.entry(version.crawl_id).or_insert(version);
+        }
+
+        let mut versions: Vec<PageVersion> = by_crawl.into_values().collect();
+        let mut last_hash: Option<&str> = None;
+        for version in &mut versions {
+            if let Some(hash) = version.hash.as_deref() {
+                version.changed = last_hash != Some(hash);
+                last_hash = Some(hash);
+            }
+        }
+
+        Ok(PageHistory { site_id, url: url.to_string(), versions })
+    }
+
+    /// Versions whose body differs from the one before.
+    pub fn changes(&self) -> usize {
+        self.versions.iter().filter(|version| version.changed).count()
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::content_hash;
+    use crate::crawl::Crawl;
+    use crate::crawl_error::{CrawlError, CrawlErrorKind};
+    use crate::page::Page;
+    use crate::site::Site;
+
+    #[test]
+    fn test_history_across_crawls() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let site_id = site.id.unwrap();
+        let mut other = Site::new(None, "b.com", "https://b.com/sitemap.xml");
+        other.sync(&mut db).unwrap();
+
+        let url = "https://a.com/pricing";
+        let mut crawl_ids = Vec::new();
+        for _ in 0..4 {
+            let mut crawl = Crawl::new(None, site_id);
+            crawl.sync(&mut db).unwrap();
+            crawl_ids.push(crawl.id.unwrap());
+        }
+        let mut other_crawl = Crawl::new(None, other.id.unwrap());
+        other_crawl.sync(&mut db).unwrap();
+
+        Page::new(url, url, "<p>$10</p>", Some(crawl_ids[0])).unwrap().sync(&mut db).unwrap();
+        Page::new(url, url, "<p>$10</p>", Some(crawl_ids[1])).unwrap().sync(&mut db).unwrap();
+        CrawlError::new(None, crawl_ids[2], url, CrawlErrorKind::Fetch, "HTTP 503").sync(&mut db).unwrap();
+        Page::new(url, url, "<p>$12</p>", Some(crawl_ids[3])).unwrap().sync(&mut db).unwrap();
+        Page::new(url, url, "<p>other site</p>", other_crawl.id).unwrap().sync(&mut db).unwrap();
+
+        let history = PageHistory::build(site_id, url, &db).unwrap();
+        let statuses: Vec<VersionStatus> = history.versions.iter().map(|version| version.status).collect();
+        assert_eq!(
+            statuses,
+            vec![VersionStatus::Archived, VersionStatus::Archived, VersionStatus::Failed, VersionStatus::Archived]
+        );
+        assert_eq!(history.versions[0].hash, Some(content_hash("<p>$10</p>")));
+        assert_eq!(history.versions[0].bytes, 10);
+        assert!(history.versions[0].archived_at.is_some());
+        assert_eq!(history.versions[2].detail.as_deref(), Some("HTTP 503"));
+        // Unchanged, then the failure is skipped when comparing
+        let changed: Vec<bool> = history.versions.iter().map(|version| version.changed).collect();
+        assert_eq!(changed, vec![true, false, false, true]);
+        assert_eq!(history.changes(), 2);
+
+        assert!(PageHistory::build(site_id, "https://a.com/missing", &db).unwrap().versions.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::http_client::HTTPClient;
+use crate::page::Page;
+use std::error::Error;
+
+/// Longest match snippet shown, in characters.
+const SNIPPET_MAX_CHARS: usize = 200;
+
+/// An element a selector matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchPreview {
+    /// Outer HTML, whitespace collapsed and shortened.
+    pub html: String,
+    pub text: String,
+}
+
+/// What a selector matches on one page, to try a selector out before
+/// running it against a whole crawl.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelectorPreview {
+    pub url: String,
+    pub matches: Vec<MatchPreview>,
+}
+
+impl SelectorPreview {
+    pub fn build(page: &Page, selector: &str) -> Result<Self, Box<dyn Error>> {
+        let parser = page.dom.parser();
+        let nodes = page
+            .dom
+            .query_selector(selector)
+            .ok_or_else(|| format!("Invalid selector '{}'", selector))?;
+
+        let matches = nodes
+            .filter_map(|handle| handle.get(parser))
+            .map(|node| MatchPreview {
+                html: shorten(collapse_whitespace(&node.outer_html(parser)), SNIPPET_MAX_CHARS),
+                text: collapse_whitespace(&node.inner_text(parser)),
+            })
+            .collect();
+
+        Ok(Self {
+            url: page.url.clone(),
+            matches,
+        })
+    }
+
+    /// Fetches one URL and evaluates a selector against it. Nothing is
+    /// archived, so no database is needed.
+    pub async fn fetch(client: &HTTPClient, url: &str, selector: &str) -> Result<Self, Box<dyn Error>> {
+        let response = client.get_html(url).await?;
+        let page = Page::new(url, &response.final_url, &response.html, None)?;
+        Self::build(&page, selector)
+    }
+
+    pub fn count(&self) -> usize {
+        self.matches.len()
+    }
+}
+
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cuts `text` to `max_chars` characters, marking the cut with an ellipsis.
+pub(crate) fn shorten(text: String, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_matches() {
+        let html = "<ul><li class=\"price\">€ 10</li><li class=\"price\">\n  € 12 </li><li>Free</li></ul>";
+        let page = Page::new("https://a.com/", "https://a.com/", html, None).unwrap();
+
+        let preview = SelectorPreview::build(&page, ".price").unwrap();
+
+        assert_eq!(preview.count(), 2);
+        assert_eq!(preview.matches[1].text, "€ 12");
+        assert_eq!(preview.matches[0].html, "<li class=\"price\">€ 10</li>");
+        assert_eq!(SelectorPreview::build(&page, "table").unwrap().count(), 0);
+        assert_eq!(shorten("abcdef".to_string(), 3), "abc…");
+    }
+}
@@ -1,34 +1,67 @@
 use crate::database::Database;
-use rusqlite::params;
+use crate::trash::{self, TrashKind};
+use rusqlite::{params, OptionalExtension, Row};
 use std::error::Error;
 
 pub struct Query {
     pub id: Option<i64>,
     pub crawl_id: i64,
     pub selector: String,
+    /// Short label of a saved query, such as "Missing canonical check".
+    pub name: Option<String>,
+    pub description: Option<String>,
 }
 
+const COLUMNS: &str = "id, crawl_id, selector, name, description";
+
 impl Query {
     pub fn new(id: Option<i64>, crawl_id: i64, selector: &str) -> Self {
         Self {
             id,
             crawl_id,
             selector: selector.to_string(),
+            name: None,
+            description: None,
         }
     }
 
+    pub fn with_name(mut self, name: Option<&str>) -> Self {
+        self.name = name.map(str::to_string);
+        self
+    }
+
+    pub fn with_description(mut self, description: Option<&str>) -> Self {
+        self.description = description.map(str::to_string);
+        self
+    }
+
+    /// The name of the query, or its selector when it has none.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.selector)
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Query {
+            id: Some(row.get(0)?),
+            crawl_id: row.get(1)?,
+            selector: row.get(2)?,
+            name: row.get(3)?,
+            description: row.get(4)?,
+        })
+    }
+
     pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
         match self.id {
             Some(existing_id) => {
                 database.conn.execute(
-                    "UPDATE queries SET crawl_id = ?1, selector = ?2 WHERE id = ?3",
-                    params![self.crawl_id, self.selector, existing_id],
+                    "UPDATE queries SET crawl_id = ?1, selector = ?2, name = ?3, description = ?4 WHERE id = ?5",
+                    params![self.crawl_id, self.selector, self.name, self.description, existing_id],
                 )?;
             }
             None => {
                 database.conn.execute(
-                    "INSERT INTO queries (crawl_id, selector) VALUES (?1, ?2)",
-                    params![self.crawl_id, self.selector],
+                    "INSERT INTO queries (crawl_id, selector, name, description) VALUES (?1, ?2, ?3, ?4)",
+                    params![self.crawl_id, self.selector, self.name, self.description],
                 )?;
                 self.id = Some(database.conn.last_insert_rowid());
             }
@@ -37,32 +70,53 @@ impl Query {
     }
 
     pub fn fetch(id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
-        let sql = "SELECT id, crawl_id, selector FROM queries WHERE id = ?1";
+        let sql = format!("SELECT {} FROM queries WHERE id = ?1 AND deleted_at IS NULL", COLUMNS);
 
         database
             .conn
-            .query_row(sql, params![id], |row| {
-                Ok(Query {
-                    id: Some(row.get(0)?),
-                    crawl_id: row.get(1)?,
-                    selector: row.get(2)?,
-                })
-            })
+            .query_row(&sql, params![id], Query::from_row)
             .map_err(|e| e.into())
     }
 
+    /// The most recent query of `selector` on a crawl.
+    pub fn fetch_latest(crawl_id: i64, selector: &str, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let sql = format!(
+            "SELECT {} FROM queries
+             WHERE crawl_id = ?1 AND selector = ?2 AND deleted_at IS NULL
+             ORDER BY id DESC LIMIT 1",
+            COLUMNS
+        );
+
+        let query = database
+            .conn
+            .query_row(&sql, params![crawl_id, selector], Query::from_row)
+            .optional()?;
+        Ok(query)
+    }
+
+    /// The saved queries of a crawl, oldest first.
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(&format!(
+            "SELECT {} FROM queries WHERE crawl_id = ?1 AND deleted_at IS NULL ORDER BY id",
+            COLUMNS
+        ))?;
+
+        let query_iter = stmt.query_map(params![crawl_id], Query::from_row)?;
+
+        let mut queries = Vec::new();
+        for q in query_iter {
+            queries.push(q?);
+        }
+
+        Ok(queries)
+    }
+
     pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
         let mut stmt = database
             .conn
-            .prepare("SELECT id, crawl_id, selector FROM queries")?;
+            .prepare(&format!("SELECT {} FROM queries WHERE deleted_at IS NULL", COLUMNS))?;
 
-        let query_iter = stmt.query_map([], |row| {
-            Ok(Query {
-                id: Some(row.get(0)?),
-                crawl_id: row.get(1)?,
-                selector: row.get(2)?,
-            })
-        })?;
+        let query_iter = stmt.query_map([], Query::from_row)?;
 
         let mut queries = Vec::new();
         for q in query_iter {
@@ -73,9 +127,6 @@ impl Query {
     }
 
     pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
-        database
-            .conn
-            .execute("DELETE FROM queries WHERE id = ?1", params![id])?;
-        Ok(())
+        trash::delete(TrashKind::Query, id, database)
     }
 }
@@ -0,0 +1,152 @@
+use crate::crawl::Crawl;
+use crate::database::Database;
+use crate::expression;
+use crate::page_archive::PageArchive;
+use serde::Serialize;
+use std::error::Error;
+
+/// Matches of a selector in one crawl.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrawlCount {
+    pub crawl_id: i64,
+    pub started_at: Option<String>,
+    /// Pages archived in the crawl.
+    pub pages: usize,
+    /// Pages with at least one match.
+    pub matching_pages: usize,
+    /// Matches over all pages.
+    pub total: usize,
+}
+
+/// Matches of a selector on one page of a crawl. Pages without matches are
+/// left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UrlCount {
+    pub crawl_id: i64,
+    pub started_at: Option<String>,
+    pub url: String,
+    pub count: usize,
+}
+
+/// How often a selector matched across the crawls of a site, oldest crawl
+/// first, in long format for plotting.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryHistory {
+    pub site_id: i64,
+    pub selector: String,
+    pub crawls: Vec<CrawlCount>,
+    pub urls: Vec<UrlCount>,
+}
+
+impl QueryHistory {
+    /// Evaluates the selector against the archived pages of every crawl of
+    /// the site. Nothing is stored, so the history covers crawls the query
+    /// never ran on.
+    pub fn build(site_id: i64, selector: &str, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let mut crawls: Vec<Crawl> = Crawl::fetch_all(database)?
+            .into_iter()
+            .filter(|crawl| crawl.site_id == site_id)
+            .collect();
+        crawls.sort_by_key(|crawl| crawl.id);
+
+        let mut history = QueryHistory {
+            site_id,
+            selector: selector.to_string(),
+            crawls: Vec::new(),
+            urls: Vec::new(),
+        };
+
+        for crawl in crawls {
+            let (crawl_count, urls) = count_matches(&crawl, selector, database)?;
+            history.crawls.push(crawl_count);
+            history.urls.extend(urls);
+        }
+
+        Ok(history)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Counts the matches of a selector in the archived pages of a crawl, in
+/// total and for each page with matches.
+pub(crate) fn count_matches(
+    crawl: &Crawl,
+    selector: &str,
+    database: &Database,
+) -> Result<(CrawlCount, Vec<UrlCount>), Box<dyn Error>> {
+    let crawl_id = crawl.id.ok_or("Crawl without an ID")?;
+    let archives = PageArchive::fetch_by_crawl_id(crawl_id, database)?;
+
+    let mut crawl_count = CrawlCount {
+        crawl_id,
+        started_at: crawl.started_at.clone(),
+        pages: archives.len(),
+        matching_pages: 0,
+        total: 0,
+    };
+    let mut urls = Vec::new();
+
+    for archive in &archives {
+        let count = match archive.to_page() {
+            Ok(page) => expression::count_matches(&page, selector),
+            Err(_) => 0,
+        };
+        if count == 0 {
+            continue;
+        }
+
+        crawl_count.matching_pages += 1;
+        crawl_count.total += count;
+        urls.push(UrlCount {
+            crawl_id,
+            started_at: crawl.started_at.clone(),
+            url: archive.url.clone(),
+            count,
+        });
+    }
+
+    Ok((crawl_count, urls))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+
+    #[test]
+    fn test_history_across_crawls() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute("INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', ''), (2, 'b.com', '')", [])
+            .unwrap();
+
+        let crawl_pages = [
+            (1, vec![("https://a.com/", "<p class=\"x\"></p>"), ("https://a.com/b", "<div></div>")]),
+            (2, vec![("https://b.com/", "<p class=\"x\"></p>")]),
+            (1, vec![("https://a.com/", "<p class=\"x\"></p><p class=\"x\"></p>"), ("https://a.com/b", "<p class=\"x\"></p>")]),
+        ];
+        for (site_id, pages) in crawl_pages {
+            let mut crawl = Crawl::new(None, site_id);
+            crawl.sync(&mut db).unwrap();
+            for (url, html) in pages {
+                Page::new(url, url, html, crawl.id).unwrap().sync(&mut db).unwrap();
+            }
+        }
+
+        let history = QueryHistory::build(1, ".x", &db).unwrap();
+
+        let totals: Vec<(i64, usize, usize, usize)> = history
+            .crawls
+            .iter()
+            .map(|crawl| (crawl.crawl_id, crawl.pages, crawl.matching_pages, crawl.total))
+            .collect();
+        assert_eq!(totals, vec![(1, 2, 1, 1), (3, 2, 2, 3)]);
+        assert_eq!(history.urls.len(), 3);
+        assert_eq!((history.urls[1].url.as_str(), history.urls[1].count), ("https://a.com/", 2));
+        assert!(history.to_json().unwrap().contains("\"matching_pages\": 2"));
+    }
+}
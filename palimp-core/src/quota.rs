@@ -0,0 +1,130 @@
+use crate::crawl::Crawl;
+use crate::database::Database;
+use std::error::Error;
+
+const QUOTA_SETTING: &str = "archive_quota_bytes";
+
+/// How much of its quota the page archive uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveUsage {
+    pub bytes: u64,
+    pub quota: Option<u64>,
+}
+
+impl ArchiveUsage {
+    pub fn fetch(db: &Database) -> Result<Self, Box<dyn Error>> {
+        // Shared bodies count once, as they are stored
+        let bytes: i64 = db.conn.query_row(
+            "SELECT (SELECT COALESCE(SUM(LENGTH(CAST(content AS BLOB))), 0) FROM blobs)
+                  + (SELECT COALESCE(SUM(LENGTH(CAST(headers AS BLOB))), 0) FROM pages)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(ArchiveUsage { bytes: bytes as u64, quota: quota(db)? })
+    }
+
+    pub fn is_over_quota(&self) -> bool {
+        self.quota.is_some_and(|quota| self.bytes > quota)
+    }
+}
+
+/// The archive quota in bytes, if one is set.
+pub fn quota(db: &Database) -> Result<Option<u64>, Box<dyn Error>> {
+    match db.setting(QUOTA_SETTING)? {
+        Some(value) => Ok(Some(value.parse::<u64>().map_err(|_| format!("Invalid archive quota '{}'", value))?)),
+        None => Ok(None),
+    }
+}
+
+pub fn set_quota(quota: Option<u64>, db: &Database) -> Result<(), Box<dyn Error>> {
+    db.set_setting(QUOTA_SETTING, quota.map(|quota| quota.to_string()).as_deref())
+}
+
+/// Purges crawls until the archive fits its quota and returns their IDs:
+/// deleted crawls first, then the oldest unpinned ones. Running crawls and
+/// each site's latest crawl are never pruned, so the archive can stay over
+/// quota when nothing else is left.
+/// SQLite reuses the freed space for later crawls rather than shrinking the
+/// file.
+pub fn prune(db: &mut Database) -> Result<Vec<i64>, Box<dyn Error>> {
+    let usage = ArchiveUsage::fetch(db)?;
+    if !usage.is_over_quota() {
+        return Ok(Vec::new());
+    }
+
+    let candidates: Vec<i64> = {
+        let mut stmt = db.conn.prepare(
+            "SELECT id FROM crawls
+             WHERE deleted_at IS NOT NULL
+                OR (pinned = 0 AND status != 'running'
+                    AND id NOT IN (SELECT MAX(id) FROM crawls WHERE deleted_at IS NULL GROUP BY site_id))
+             ORDER BY deleted_at IS NULL, deleted_at, id",
+        )?;
+        stmt.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?
+    };
+
+    // A crawl frees only the bodies no other crawl shares, so the usage is
+    // measured again after each one
+    db.in_transaction(|db| {
+        let mut pruned = Vec::new();
+        for crawl_id in candidates {
+            if !ArchiveUsage::fetch(db)?.is_over_quota() {
+                break;
+            }
+            Crawl::purge(crawl_id, db)?;
+            pruned.push(crawl_id);
+        }
+        Ok(pruned)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::CrawlStatus;
+    use crate::page::Page;
+    use crate::site::Site;
+
+    fn crawl_with_page(site_id: i64, html: &str, db: &mut Database) -> i64 {
+        let mut crawl = Crawl::new(None, site_id);
+        crawl.sync(db).unwrap();
+        let crawl_id = crawl.id.unwrap();
+        Crawl::set_status(crawl_id, CrawlStatus::Completed, db).unwrap();
+        Page::new("https://a.com/", "https://a.com/", html, Some(crawl_id)).unwrap().sync(db).unwrap();
+        crawl_id
+    }
+
+    #[test]
+    fn test_prune_to_quota() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let site_id = site.id.unwrap();
+
+        let page = |n: usize| format!("{}{}", n, "x".repeat(99));
+        let pinned = crawl_with_page(site_id, &page(1), &mut db);
+        let oldest = crawl_with_page(site_id, &page(2), &mut db);
+        let older = crawl_with_page(site_id, &page(3), &mut db);
+        let latest = crawl_with_page(site_id, &page(4), &mut db);
+        Crawl::set_pinned(pinned, true, &db).unwrap();
+
+        // No quota, nothing to prune
+        assert!(prune(&mut db).unwrap().is_empty());
+        assert_eq!(ArchiveUsage::fetch(&db).unwrap().bytes, 400);
+
+        set_quota(Some(250), &db).unwrap();
+        assert!(ArchiveUsage::fetch(&db).unwrap().is_over_quota());
+        assert_eq!(prune(&mut db).unwrap(), vec![oldest, older]);
+        assert_eq!(ArchiveUsage::fetch(&db).unwrap(), ArchiveUsage { bytes: 200, quota: Some(250) });
+
+        // The pinned and the latest crawl stay even when still over quota
+        set_quota(Some(10), &db).unwrap();
+        assert!(prune(&mut db).unwrap().is_empty());
+        let left: Vec<i64> = Crawl::fetch_all(&db).unwrap().iter().filter_map(|crawl| crawl.id).collect();
+        assert_eq!(left, vec![pinned, latest]);
+
+        set_quota(None, &db).unwrap();
+        assert_eq!(quota(&db).unwrap(), None);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::page::Page;
+
+/// How an archived page sent visitors elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectType {
+    /// The server answered with a 3xx and the client followed it.
+    Http,
+    /// The page itself redirects with `<meta http-equiv="refresh">`.
+    MetaRefresh,
+}
+
+impl RedirectType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedirectType::Http => "http",
+            RedirectType::MetaRefresh => "meta-refresh",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "http" => Some(RedirectType::Http),
+            "meta-refresh" => Some(RedirectType::MetaRefresh),
+            _ => None,
+        }
+    }
+}
+
+/// Detects how a page redirects and where to. A meta refresh wins over an
+/// HTTP redirect, since the HTTP hop stays visible in the final URL anyway.
+pub fn detect(page: &Page) -> Option<(RedirectType, String)> {
+    if let Some(target) = meta_refresh_target(page) {
+        return Some((RedirectType::MetaRefresh, target));
+    }
+
+    if !same_url(&page.url, &page.final_url) {
+        return Some((RedirectType::Http, page.final_url.clone()));
+    }
+
+    None
+}
+
+/// Resolved target of the page's meta refresh, if it points anywhere
+/// (a bare delay only reloads the page).
+pub fn meta_refresh_target(page: &Page) -> Option<String> {
+    page.meta_http_equiv("refresh")
+        .iter()
+        .find_map(|content| refresh_url(content))
+        .and_then(|url| page.resolve_url(&url))
+}
+
+/// Extracts the URL from a refresh `content` such as `0; url='/new'`.
+fn refresh_url(content: &str) -> Option<String> {
+    let (_, rest) = content.split_once([';', ','])?;
+    let rest = rest.trim();
+    let url = match rest.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url=") => &rest[4..],
+        _ => rest,
+    };
+    let url = url.trim().trim_matches(|c| c == '\'' || c == '"').trim();
+
+    if url.is_empty() {
+        None
+    } else {
+        Some(url.to_string())
+    }
+}
+
+/// Compares URLs after parsing, so `https://a.com` and `https://a.com/`
+/// don't count as a redirect.
+pub fn same_url(a: &str, b: &str) -> bool {
+    match (reqwest::Url::parse(a.trim()), reqwest::Url::parse(b.trim())) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.trim() == b.trim(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_meta_refresh_and_http_redirects() {
+        let html = r#"<html><head><meta http-equiv="Refresh" content="0; URL='/new-home'"></head></html>"#;
+        let page = Page::new("https://example.com/old", "https://example.com/old", html, None).unwrap();
+        assert_eq!(
+            detect(&page),
+            Some((RedirectType::MetaRefresh, "https://example.com/new-home".to_string()))
+        );
+
+        let reload = r#"<meta http-equiv="refresh" content="30">"#;
+        let page = Page::new("https://example.com", "https://example.com/", reload, None).unwrap();
+        assert_eq!(detect(&page), None);
+
+        let page = Page::new("http://example.com/a", "https://example.com/b", "<p>Moved</p>", None).unwrap();
+        assert_eq!(
+            detect(&page),
+            Some((RedirectType::Http, "https://example.com/b".to_string()))
+        );
+    }
+}
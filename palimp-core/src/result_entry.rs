@@ -1,36 +1,117 @@
 use crate::database::Database;
-use rusqlite::params;
+use crate::trash::{self, TrashKind};
+use rusqlite::{params, params_from_iter, Row, ToSql};
 use std::error::Error;
 
+/// How far a result has been triaged. Results start out new.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResultStatus {
+    #[default]
+    New,
+    Reviewed,
+    /// Looked at and deemed not worth acting on.
+    Ignored,
+}
+
+impl ResultStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResultStatus::New => "new",
+            ResultStatus::Reviewed => "reviewed",
+            ResultStatus::Ignored => "ignored",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "new" => Some(ResultStatus::New),
+            "reviewed" => Some(ResultStatus::Reviewed),
+            "ignored" => Some(ResultStatus::Ignored),
+            _ => None,
+        }
+    }
+}
+
+/// Narrows down [`ResultEntry::search`]. Unset fields match every result.
+#[derive(Debug, Clone, Default)]
+pub struct ResultFilter {
+    pub crawl_id: Option<i64>,
+    /// Results of a stored query.
+    pub query_id: Option<i64>,
+    pub selector: Option<String>,
+    pub min_count: Option<u32>,
+    pub max_count: Option<u32>,
+    /// Only pages whose URL contains this text.
+    pub url_pattern: Option<String>,
+    /// Only pages whose sitemap `<lastmod>` is on or after this date, e.g.
+    /// `2024-05-01`.
+    pub modified_since: Option<String>,
+    pub status: Option<ResultStatus>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl ResultFilter {
+    pub fn for_query(query_id: i64) -> Self {
+        Self { query_id: Some(query_id), ..Self::default() }
+    }
+
+    pub fn for_crawl_and_selector(crawl_id: i64, selector: &str) -> Self {
+        Self {
+            crawl_id: Some(crawl_id),
+            selector: Some(selector.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
 pub struct ResultEntry {
     pub id: Option<i64>,
+    pub query_id: i64,
     pub page_id: i64,
     pub selector: String,
     pub count: u32,
+    pub status: ResultStatus,
 }
 
+const COLUMNS: &str = "id, query_id, page_id, selector, count, status";
+
 impl ResultEntry {
-    pub fn new(id: Option<i64>, page_id: i64, selector: &str, count: u32) -> Self {
+    pub fn new(id: Option<i64>, query_id: i64, page_id: i64, selector: &str, count: u32) -> Self {
         Self {
             id,
+            query_id,
             page_id,
             selector: selector.to_string(),
             count,
+            status: ResultStatus::New,
         }
     }
 
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let status: String = row.get(5)?;
+        Ok(ResultEntry {
+            id: Some(row.get(0)?),
+            query_id: row.get(1)?,
+            page_id: row.get(2)?,
+            selector: row.get(3)?,
+            count: row.get(4)?, // rusqlite converts SQLite INTEGER to u32 automatically
+            status: ResultStatus::from_name(&status).unwrap_or_default(),
+        })
+    }
+
     pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
         match self.id {
             Some(existing_id) => {
                 database.conn.execute(
-                    "UPDATE results SET selector = ?1, count = ?2 WHERE id = ?3",
-                    params![self.selector, self.count, existing_id],
+                    "UPDATE results SET selector = ?1, count = ?2, status = ?3 WHERE id = ?4",
+                    params![self.selector, self.count, self.status.name(), existing_id],
                 )?;
             }
             None => {
                 database.conn.execute(
-                    "INSERT INTO results (page_id, selector, count) VALUES (?1, ?2, ?3)",
-                    params![self.page_id, self.selector, self.count],
+                    "INSERT INTO results (query_id, page_id, selector, count, status) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![self.query_id, self.page_id, self.selector, self.count, self.status.name()],
                 )?;
                 self.id = Some(database.conn.last_insert_rowid());
             }
@@ -39,16 +120,9 @@ impl ResultEntry {
     }
 
     pub fn fetch(id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
-        let sql = "SELECT id, page_id, selector, count FROM results WHERE id = ?1";
-
-        let entry = database.conn.query_row(sql, params![id], |row| {
-            Ok(ResultEntry {
-                id: Some(row.get(0)?),
-                page_id: row.get(1)?,
-                selector: row.get(2)?,
-                count: row.get(3)?, // rusqlite converts SQLite INTEGER to u32 automatically
-            })
-        })?;
+        let sql = format!("SELECT {} FROM results WHERE id = ?1 AND deleted_at IS NULL", COLUMNS);
+
+        let entry = database.conn.query_row(&sql, params![id], ResultEntry::from_row)?;
 
         Ok(entry)
     }
@@ -56,16 +130,9 @@ impl ResultEntry {
     pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
         let mut stmt = database
             .conn
-            .prepare("SELECT id, page_id, selector, count FROM results")?;
-
-        let entry_iter = stmt.query_map([], |row| {
-            Ok(ResultEntry {
-                id: Some(row.get(0)?),
-                page_id: row.get(1)?,
-                selector: row.get(2)?,
-                count: row.get(3)?,
-            })
-        })?;
+            .prepare(&format!("SELECT {} FROM results WHERE deleted_at IS NULL", COLUMNS))?;
+
+        let entry_iter = stmt.query_map([], ResultEntry::from_row)?;
 
         let mut entries = Vec::new();
         for entry in entry_iter {
@@ -75,25 +142,60 @@ impl ResultEntry {
         Ok(entries)
     }
 
-    pub fn fetch_by_crawl_and_selector(
-        crawl_id: i64,
-        selector: &str,
-        database: &Database,
-    ) -> Result<Vec<Self>, Box<dyn Error>> {
-        let mut stmt = database.conn.prepare(
-            "SELECT r.id, r.page_id, r.selector, r.count 
-             FROM results r 
-             INNER JOIN pages p ON r.page_id = p.id 
-             WHERE p.crawl_id = ?1 AND r.selector = ?2",
-        )?;
-
-        let entry_iter = stmt.query_map(params![crawl_id, selector], |row| {
-            Ok(ResultEntry {
-                id: Some(row.get(0)?),
-                page_id: row.get(1)?,
-                selector: row.get(2)?,
-                count: row.get(3)?,
-            })
+    /// Results matching `filter`, each with the URL of its page, in the order
+    /// they were stored.
+    pub fn search(filter: &ResultFilter, database: &Database) -> Result<Vec<(Self, String)>, Box<dyn Error>> {
+        let mut conditions = Vec::new();
+        let mut parameters: Vec<Box<dyn ToSql>> = Vec::new();
+        let mut condition = |sql: &str, value: Box<dyn ToSql>| {
+            parameters.push(value);
+            conditions.push(sql.replace('?', &format!("?{}", parameters.len())));
+        };
+
+        if let Some(crawl_id) = filter.crawl_id {
+            condition("p.crawl_id = ?", Box::new(crawl_id));
+        }
+        if let Some(query_id) = filter.query_id {
+            condition("r.query_id = ?", Box::new(query_id));
+        }
+        if let Some(selector) = &filter.selector {
+            condition("r.selector = ?", Box::new(selector.clone()));
+        }
+        if let Some(min_count) = filter.min_count {
+            condition("r.count >= ?", Box::new(min_count));
+        }
+        if let Some(max_count) = filter.max_count {
+            condition("r.count <= ?", Box::new(max_count));
+        }
+        if let Some(pattern) = &filter.url_pattern {
+            condition("instr(p.url, ?) > 0", Box::new(pattern.clone()));
+        }
+        if let Some(status) = filter.status {
+            condition("r.status = ?", Box::new(status.name()));
+        }
+        if let Some(date) = &filter.modified_since {
+            // W3C dates sort as text, a bare date before the times of that day
+            condition("p.sitemap_lastmod >= ?", Box::new(date.clone()));
+        }
+
+        let mut sql = String::from(
+            "SELECT r.id, r.query_id, r.page_id, r.selector, r.count, r.status, p.url
+             FROM results r
+             INNER JOIN pages p ON r.page_id = p.id
+             WHERE r.deleted_at IS NULL",
+        );
+        for condition in &conditions {
+            sql.push_str(" AND ");
+            sql.push_str(condition);
+        }
+        sql.push_str(" ORDER BY r.id");
+        // SQLite needs a LIMIT for an OFFSET; -1 means no limit
+        let limit = filter.limit.map_or(-1, |limit| limit as i64);
+        sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, filter.offset));
+
+        let mut stmt = database.conn.prepare(&sql)?;
+        let entry_iter = stmt.query_map(params_from_iter(parameters.iter()), |row| {
+            Ok((ResultEntry::from_row(row)?, row.get(6)?))
         })?;
 
         let mut entries = Vec::new();
@@ -104,10 +206,20 @@ impl ResultEntry {
         Ok(entries)
     }
 
-    pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
-        database
+    /// Sets the status of the results with the given IDs, returning how many
+    /// were found. Deleted results are left alone.
+    pub fn set_status(ids: &[i64], status: ResultStatus, database: &Database) -> Result<usize, Box<dyn Error>> {
+        let mut stmt = database
             .conn
-            .execute("DELETE FROM results WHERE id = ?1", params![id])?;
-        Ok(())
+            .prepare("UPDATE results SET status = ?1 WHERE id = ?2 AND deleted_at IS NULL")?;
+        let mut updated = 0;
+        for id in ids {
+            updated += stmt.execute(params![status.name(), id])?;
+        }
+        Ok(updated)
+    }
+
+    pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        trash::delete(TrashKind::Result, id, database)
     }
 }
@@ -0,0 +1,290 @@
+use crate::blob::content_hash;
+use crate::database::Database;
+use crate::expression::SelectorExpression;
+use crate::page::{tag_name, Page};
+use crate::page_archive::PageArchive;
+use crate::preview::{collapse_whitespace, shorten};
+use rusqlite::{params, OptionalExtension};
+use std::collections::HashMap;
+use std::error::Error;
+use tl::{Node, NodeHandle, Parser};
+
+/// Longest match text stored, in characters.
+const TEXT_MAX_CHARS: usize = 200;
+
+/// An element a selector matched on a page, and where it sits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultMatch {
+    /// Position among the matches on the page, from 0.
+    pub index: usize,
+    /// Element path from `body`, e.g. `body > div:nth-child(2) > h2`. An
+    /// element gets `:nth-child` only when siblings share its tag, so the
+    /// path survives unrelated elements being added elsewhere.
+    pub path: String,
+    /// Visible text, whitespace collapsed and shortened.
+    pub text: String,
+    /// Hash of the outer HTML, to tell a changed element from a moved one.
+    pub hash: String,
+}
+
+impl ResultMatch {
+    pub fn fetch_by_result_id(result_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT position, path, text, hash FROM result_matches WHERE result_id = ?1 ORDER BY position",
+        )?;
+
+        let match_iter = stmt.query_map(params![result_id], |row| {
+            Ok(ResultMatch {
+                index: row.get::<_, i64>(0)? as usize,
+                path: row.get(1)?,
+                text: row.get(2)?,
+                hash: row.get(3)?,
+            })
+        })?;
+
+        let mut matches = Vec::new();
+        for result_match in match_iter {
+            matches.push(result_match?);
+        }
+
+        Ok(matches)
+    }
+}
+
+/// The elements a CSS selector matches on a page, in document order.
+pub fn locate(page: &Page, selector: &str) -> Vec<ResultMatch> {
+    let parser = page.dom.parser();
+    let Some(nodes) = page.dom.query_selector(selector) else {
+        return Vec::new();
+    };
+    let nodes: Vec<NodeHandle> = nodes.collect();
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut paths = HashMap::new();
+    walk(page.dom.children(), parser, "", &mut paths);
+
+    nodes
+        .into_iter()
+        .filter_map(|handle| Some((handle, handle.get(parser)?)))
+        .enumerate()
+        .map(|(index, (handle, node))| ResultMatch {
+            index,
+            path: paths.get(&handle.get_inner()).cloned().unwrap_or_default(),
+            text: shorten(collapse_whitespace(&node.inner_text(parser)), TEXT_MAX_CHARS),
+            hash: content_hash(&collapse_whitespace(&node.outer_html(parser))),
+        })
+        .collect()
+}
+
+/// Records the path of every element under `siblings`, by node handle.
+fn walk(siblings: &[NodeHandle], parser: &Parser, path: &str, paths: &mut HashMap<u32, String>) {
+    let tags: Vec<(NodeHandle, String)> = siblings
+        .iter()
+        .filter_map(|handle| match handle.get(parser) {
+            Some(Node::Tag(tag)) => Some((*handle, tag_name(tag))),
+            _ => None,
+        })
+        .collect();
+
+    for (position, (handle, name)) in tags.iter().enumerate() {
+        let Some(Node::Tag(tag)) = handle.get(parser) else { continue };
+        let shared = tags.iter().filter(|(_, other)| other == name).count() > 1;
+        let segment = if shared {
+            format!("{}:nth-child({})", name, position + 1)
+        } else {
+            name.clone()
+        };
+        // Every page has the same root, so paths start below it
+        let path = match (path.is_empty(), name.as_str()) {
+            (true, "html") => String::new(),
+            (true, _) => segment,
+            (false, _) => format!("{} > {}", path, segment),
+        };
+
+        if !path.is_empty() {
+            paths.insert(handle.get_inner(), path.clone());
+        }
+        walk(tag.children().top().as_slice(), parser, &path, paths);
+    }
+}
+
+/// Stores the matches of a result, replacing earlier ones.
+pub fn record(result_id: i64, matches: &[ResultMatch], database: &Database) -> Result<(), Box<dyn Error>> {
+    database.conn.execute("DELETE FROM result_matches WHERE result_id = ?1", params![result_id])?;
+    for result_match in matches {
+        database.conn.execute(
+            "INSERT INTO result_matches (result_id, position, path, text, hash) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![result_id, result_match.index as i64, result_match.path, result_match.text, result_match.hash],
+        )?;
+    }
+    Ok(())
+}
+
+/// The matches of the crawl's latest query with `selector` on the page at
+/// `url`, none if that query found nothing there. Results stored before
+/// positions were recorded are located again on the archived page.
+pub fn for_page(crawl_id: i64, selector: &str, url: &str, database: &Database) -> Result<Vec<ResultMatch>, Box<dyn Error>> {
+    if SelectorExpression::parse(selector)?.is_compound() {
+        return Err("Match positions are only kept for single selectors".into());
+    }
+    let query_id: Option<i64> = database.conn.query_row(
+        "SELECT MAX(id) FROM queries WHERE crawl_id = ?1 AND selector = ?2 AND deleted_at IS NULL",
+        params![crawl_id, selector],
+        |row| row.get(0),
+    )?;
+    let query_id = query_id.ok_or_else(|| format!("Crawl {} has no query for '{}'", crawl_id, selector))?;
+
+    let result: Option<(i64, i64)> = database
+        .conn
+        .query_row(
+            "SELECT r.id, r.page_id FROM results r INNER JOIN pages p ON p.id = r.page_id
+             WHERE r.query_id = ?1 AND p.url = ?2 AND r.deleted_at IS NULL
+             ORDER BY r.id DESC LIMIT 1",
+            params![query_id, url],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((result_id, page_id)) = result else {
+        return Ok(Vec::new());
+    };
+
+    let matches = ResultMatch::fetch_by_result_id(result_id, database)?;
+    if !matches.is_empty() {
+        return Ok(matches);
+    }
+    Ok(locate(&PageArchive::fetch(page_id, database)?.to_page()?, selector))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchChangeKind {
+    /// The same element at another path.
+    Moved,
+    /// Another element at the same path.
+    Changed,
+    Removed,
+    Added,
+}
+
+impl MatchChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchChangeKind::Moved => "moved",
+            MatchChangeKind::Changed => "changed",
+            MatchChangeKind::Removed => "removed",
+            MatchChangeKind::Added => "added",
+        }
+    }
+}
+
+/// How a match differs between two versions of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchChange {
+    pub kind: MatchChangeKind,
+    /// The match before, unless it was added.
+    pub before: Option<ResultMatch>,
+    /// The match after, unless it was removed.
+    pub after: Option<ResultMatch>,
+}
+
+/// Pairs up the matches of two versions of a page. Matches with the same
+/// path and content are unchanged and left out; the rest are paired by
+/// content first, as moved, then by path, as changed.
+pub fn diff(before: &[ResultMatch], after: &[ResultMatch]) -> Vec<MatchChange> {
+    let mut remaining: Vec<Option<&ResultMatch>> = after.iter().map(Some).collect();
+    let mut take = |found: &dyn Fn(&ResultMatch) -> bool| {
+        let slot = remaining.iter_mut().find(|slot| slot.is_some_and(found))?;
+        slot.take()
+    };
+
+    let mut unpaired = Vec::new();
+    for old in before {
+        if take(&|new| new.path == old.path && new.hash == old.hash).is_none() {
+            unpaired.push(old);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for old in unpaired {
+        let (kind, new) = if let Some(new) = take(&|new| new.hash == old.hash) {
+            (MatchChangeKind::Moved, Some(new))
+        } else if let Some(new) = take(&|new| new.path == old.path) {
+            (MatchChangeKind::Changed, Some(new))
+        } else {
+            (MatchChangeKind::Removed, None)
+        };
+        changes.push(MatchChange {
+            kind,
+            before: Some(old.clone()),
+            after: new.cloned(),
+        });
+    }
+
+    changes.extend(remaining.into_iter().flatten().map(|new| MatchChange {
+        kind: MatchChangeKind::Added,
+        before: None,
+        after: Some(new.clone()),
+    }));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_and_diff_matches() {
+        let before = Page::new(
+            "https://a.com/",
+            "https://a.com/",
+            "<html><body><div><h2>Intro</h2></div><div><h2>Prices</h2><p>Old</p><h2>Contact</h2></div></body></html>",
+            None,
+        )
+        .unwrap();
+        let matches = locate(&before, "h2");
+        let paths: Vec<(usize, &str, &str)> = matches
+            .iter()
+            .map(|found| (found.index, found.path.as_str(), found.text.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                (0, "body > div:nth-child(1) > h2", "Intro"),
+                (1, "body > div:nth-child(2) > h2:nth-child(1)", "Prices"),
+                (2, "body > div:nth-child(2) > h2:nth-child(3)", "Contact"),
+            ]
+        );
+
+        // Intro moves into a new section, Contact changes, a heading is added
+        let after = Page::new(
+            "https://a.com/",
+            "https://a.com/",
+            "<body><div><p>Welcome</p></div><div><h2>Prices</h2><p>New</p><h2>Email us</h2></div>\
+             <h2>Jobs</h2><section><h2>Intro</h2></section></body>",
+            None,
+        )
+        .unwrap();
+        let changes = diff(&matches, &locate(&after, "h2"));
+        let summary: Vec<(MatchChangeKind, Option<&str>, Option<&str>)> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change.kind,
+                    change.before.as_ref().map(|found| found.path.as_str()),
+                    change.after.as_ref().map(|found| found.text.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (MatchChangeKind::Moved, Some("body > div:nth-child(1) > h2"), Some("Intro")),
+                (MatchChangeKind::Changed, Some("body > div:nth-child(2) > h2:nth-child(3)"), Some("Email us")),
+                (MatchChangeKind::Added, None, Some("Jobs")),
+            ]
+        );
+        assert!(diff(&matches, &matches).is_empty());
+        assert!(locate(&after, "table").is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::http_client::HTTPClient;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A `Disallow` or `Allow` line of a robots.txt group.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// The rules of one robots.txt that apply to a crawler: those of the groups
+/// naming its product token, or else those of the `*` groups.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsRules {
+    rules: Vec<Rule>,
+}
+
+impl RobotsRules {
+    /// Parses a robots.txt for the crawler whose user agent is `user_agent`,
+    /// e.g. `PalimpCralwer/0.1`. Unknown lines and `Sitemap` are ignored.
+    pub fn parse(robots_txt: &str, user_agent: &str) -> Self {
+        let token = product_token(user_agent);
+        let mut ours = Vec::new();
+        let mut anyone = Vec::new();
+        let mut named = false;
+
+        // Consecutive user-agent lines share the rules that follow them
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    named |= value.eq_ignore_ascii_case(&token);
+                    agents.push(value.to_ascii_lowercase());
+                }
+                key @ ("allow" | "disallow") => {
+                    in_rules = true;
+                    // An empty Disallow allows everything, which is the default
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = Rule {
+                        allow: key == "allow",
+                        pattern: value.to_string(),
+                    };
+                    if agents.contains(&token) {
+                        ours.push(rule.clone());
+                    }
+                    if agents.iter().any(|agent| agent == "*") {
+                        anyone.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        RobotsRules {
+            rules: if named { ours } else { anyone },
+        }
+    }
+
+    /// The `Disallow` pattern keeping the crawler from a URL, or `None` if it
+    /// may fetch it. The longest matching pattern decides, `Allow` winning a
+    /// tie; `*` matches any characters and a trailing `$` the end of the URL.
+    pub fn disallowing(&self, url: &str) -> Option<&str> {
+        let path = match reqwest::Url::parse(url) {
+            Ok(url) => match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            },
+            Err(_) => return None,
+        };
+        if path == "/robots.txt" {
+            return None;
+        }
+
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .filter(|rule| !rule.allow)
+            .map(|rule| rule.pattern.as_str())
+    }
+
+    pub fn is_allowed(&self, url: &str) -> bool {
+        self.disallowing(url).is_none()
+    }
+}
+
+/// `googlebot` for `Googlebot/2.1 (+http://www.google.com/bot.html)`.
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(['/', ' '])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or("")) else { return false };
+
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        // What's left after the last `*` must end the URL
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// The robots.txt rules of every host a crawl fetches from, for each user
+/// agent it sends. A URL is allowed only if all of them may fetch it, since
+/// rotation decides which one a request goes out with.
+#[derive(Debug, Default)]
+pub struct RobotsTxt {
+    user_agents: Vec<String>,
+    by_origin: HashMap<String, Vec<RobotsRules>>,
+    /// Origins whose robots.txt couldn't be fetched, with the reason. They
+    /// are crawled without rules.
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl RobotsTxt {
+    pub fn new(user_agents: Vec<String>) -> Self {
+        Self {
+            user_agents,
+            ..Self::default()
+        }
+    }
+
+    /// Fetches the robots.txt of the origins among `urls` it doesn't have
+    /// yet, each once. A missing robots.txt allows everything.
+    pub async fn fetch_for(&mut self, client: &HTTPClient, urls: impl Iterator<Item = String>) {
+        for url in urls {
+            let Some(origin) = origin(&url) else { continue };
+            if self.by_origin.contains_key(&origin) {
+                continue;
+            }
+            let rules = match fetch_rules(client, &origin, &self.user_agents).await {
+                Ok(rules) => rules,
+                Err(err) => {
+                    self.unreadable.push((format!("{}/robots.txt", origin), err.to_string()));
+                    Vec::new()
+                }
+            };
+            self.by_origin.insert(origin, rules);
+        }
+    }
+
+    /// The `Disallow` pattern of the URL's host keeping one of the crawl's
+    /// user agents from it.
+    pub fn disallowing(&self, url: &str) -> Option<&str> {
+        self.by_origin
+            .get(&origin(url)?)?
+            .iter()
+            .find_map(|rules| rules.disallowing(url))
+    }
+
+    pub fn is_allowed(&self, url: &str) -> bool {
+        self.disallowing(url).is_none()
+    }
+}
+
+async fn fetch_rules(client: &HTTPClient, origin: &str, user_agents: &[String]) -> Result<Vec<RobotsRules>, Box<dyn Error>> {
+    let robots_txt = client.get_robots_txt(&format!("{}/robots.txt", origin)).await?;
+    Ok(match robots_txt {
+        Some(robots_txt) => user_agents
+            .iter()
+            .map(|user_agent| RobotsRules::parse(&robots_txt, user_agent))
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+fn origin(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url.trim()).ok()?;
+    url.host_str()?;
+    Some(url.origin().ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::DEFAULT_USER_AGENT;
+
+    #[test]
+    fn test_rules_for_our_user_agent() {
+        let robots_txt = "
+# Everyone else
+User-agent: *
+Disallow: /
+
+User-agent: Googlebot
+User-agent: palimpcralwer
+Disallow: /private/
+Allow: /private/press/
+Disallow: /*.pdf$
+Disallow:
+
+Sitemap: https://a.com/sitemap.xml
+";
+        let rules = RobotsRules::parse(robots_txt, DEFAULT_USER_AGENT);
+        assert!(rules.is_allowed("https://a.com/"));
+        assert_eq!(rules.disallowing("https://a.com/private/plans"), Some("/private/"));
+        assert!(rules.is_allowed("https://a.com/private/press/launch"));
+        assert_eq!(rules.disallowing("https://a.com/files/report.pdf"), Some("/*.pdf$"));
+        assert!(rules.is_allowed("https://a.com/files/report.pdf?download=1"));
+
+        // Without a group of its own a crawler follows the `*` group
+        let rules = RobotsRules::parse(robots_txt, "OtherBot/1.0");
+        assert_eq!(rules.disallowing("https://a.com/"), Some("/"));
+        assert!(rules.is_allowed("https://a.com/robots.txt"));
+        let rules = RobotsRules::parse("User-agent: *\nDisallow: /search?q=\nAllow: /search?q=$", "OtherBot");
+        assert!(!rules.is_allowed("https://a.com/search?q=shoes"));
+        assert!(rules.is_allowed("https://a.com/search"));
+        assert!(RobotsRules::parse("", "OtherBot").is_allowed("https://a.com/admin"));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::database::Database;
+use crate::page_archive::PageArchive;
+use rusqlite::params;
+use std::error::Error;
+
+/// Markers wrapped around matched terms in search snippets.
+pub const HIGHLIGHT_START: &str = "«";
+pub const HIGHLIGHT_END: &str = "»";
+
+/// A page matching a full-text search, with a highlighted excerpt.
+pub struct SearchHit {
+    pub page_id: i64,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Adds the visible text of a page to the full-text index.
+pub fn index_page(page_id: i64, text: &str, database: &Database) -> Result<(), Box<dyn Error>> {
+    database.conn.execute(
+        "INSERT OR REPLACE INTO pages_fts (rowid, content) VALUES (?1, ?2)",
+        params![page_id, text],
+    )?;
+    Ok(())
+}
+
+/// Pages of a crawl archived before the full-text index existed.
+fn unindexed_pages(crawl_id: i64, database: &Database) -> Result<Vec<i64>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare(
+        "SELECT id FROM pages WHERE crawl_id = ?1 AND id NOT IN (SELECT rowid FROM pages_fts)",
+    )?;
+    let ids = stmt.query_map(params![crawl_id], |row| row.get(0))?;
+    Ok(ids.collect::<Result<Vec<i64>, _>>()?)
+}
+
+/// Whether [`index_crawl`] has pages of the crawl left to index.
+pub fn has_unindexed_pages(crawl_id: i64, database: &Database) -> Result<bool, Box<dyn Error>> {
+    Ok(!unindexed_pages(crawl_id, database)?.is_empty())
+}
+
+/// Indexes pages of a crawl archived before the full-text index existed.
+pub fn index_crawl(crawl_id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+    for page_id in unindexed_pages(crawl_id, database)? {
+        let archive = PageArchive::fetch(page_id, database)?;
+        if let Ok(page) = archive.to_page() {
+            index_page(page_id, &page.visible_text().join("\n"), database)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Searches the visible text of a crawl's indexed pages for a phrase, best
+/// matches first.
+pub fn search(crawl_id: i64, phrase: &str, database: &Database) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+    // Quote the phrase so FTS5 query syntax in user input is taken literally
+    let fts_query = format!("\"{}\"", phrase.replace('"', "\"\""));
+
+    let mut stmt = database.conn.prepare(
+        "SELECT p.id, p.url, snippet(pages_fts, 0, ?3, ?4, '…', 16)
+         FROM pages_fts
+         INNER JOIN pages p ON p.id = pages_fts.rowid
+         WHERE pages_fts MATCH ?1 AND p.crawl_id = ?2
+         ORDER BY rank",
+    )?;
+
+    let hit_iter = stmt.query_map(
+        params![fts_query, crawl_id, HIGHLIGHT_START, HIGHLIGHT_END],
+        |row| {
+            Ok(SearchHit {
+                page_id: row.get(0)?,
+                url: row.get(1)?,
+                snippet: row.get(2)?,
+            })
+        },
+    )?;
+
+    let mut hits = Vec::new();
+    for hit in hit_iter {
+        hits.push(hit?);
+    }
+
+    Ok(hits)
+}
@@ -0,0 +1,149 @@
+use crate::database::Database;
+use aws_lc_rs::aead::{Aad, Nonce, RandomizedNonceKey, AES_256_GCM, NONCE_LEN};
+use aws_lc_rs::{pbkdf2, rand};
+use rusqlite::{params, OptionalExtension};
+use std::error::Error;
+use std::num::NonZeroU32;
+
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+/// Encrypted on first unlock and decrypted on later ones to reject a wrong
+/// passphrase before it is used to store anything.
+const VERIFIER_PLAINTEXT: &str = "palimp-secrets";
+
+/// Encrypts credentials at rest with AES-256-GCM under a key derived from a
+/// passphrase. The salt and a verifier live in the `secret_keys` table; the
+/// passphrase itself is never stored.
+pub struct SecretStore {
+    key: RandomizedNonceKey,
+}
+
+/// The salt an archive key is derived with, and the verifier stored with it
+/// once the passphrase has been set.
+pub struct KeySalt {
+    salt: Vec<u8>,
+    verifier: Option<Vec<u8>>,
+}
+
+impl SecretStore {
+    /// Derives the archive key from `passphrase`. The first unlock of an
+    /// archive sets its passphrase; later unlocks fail if it doesn't match.
+    pub fn unlock(passphrase: &str, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let salt = Self::salt(database)?;
+        Self::derive(passphrase, &salt)?.verify(&salt, database)
+    }
+
+    /// Reads the archive's salt, or picks a new one if no passphrase is set yet.
+    pub fn salt(database: &Database) -> Result<KeySalt, Box<dyn Error>> {
+        match Self::stored_salt(database)? {
+            Some(salt) => Ok(salt),
+            None => {
+                let mut salt = [0u8; SALT_LEN];
+                rand::fill(&mut salt).map_err(|_| "Could not generate a random salt")?;
+                Ok(KeySalt { salt: salt.to_vec(), verifier: None })
+            }
+        }
+    }
+
+    fn stored_salt(database: &Database) -> Result<Option<KeySalt>, Box<dyn Error>> {
+        Ok(database
+            .conn
+            .query_row("SELECT salt, verifier FROM secret_keys WHERE id = 1", [], |row| {
+                Ok(KeySalt { salt: row.get(0)?, verifier: Some(row.get(1)?) })
+            })
+            .optional()?)
+    }
+
+    /// Runs PBKDF2 over the passphrase, which is slow on purpose: keep it off
+    /// the database lock and the async runtime.
+    pub fn derive(passphrase: &str, salt: &KeySalt) -> Result<Self, &'static str> {
+        if passphrase.is_empty() {
+            return Err("The secrets passphrase cannot be empty");
+        }
+
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).ok_or("Invalid PBKDF2 iteration count")?;
+        let mut key_bytes = [0u8; KEY_LEN];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            &salt.salt,
+            passphrase.as_bytes(),
+            &mut key_bytes,
+        );
+
+        let key = RandomizedNonceKey::new(&AES_256_GCM, &key_bytes).map_err(|_| "Could not create the secrets key")?;
+        Ok(Self { key })
+    }
+
+    /// Checks a key derived with `salt` against the archive's verifier, or
+    /// stores the salt and a new verifier if this unlock sets the passphrase.
+    pub fn verify(self, salt: &KeySalt, database: &Database) -> Result<Self, Box<dyn Error>> {
+        match Self::stored_salt(database)? {
+            Some(stored) if stored.salt != salt.salt => {
+                Err("The secrets passphrase was set while unlocking, try again".into())
+            }
+            Some(stored) => match stored.verifier.map(|verifier| self.decrypt(&verifier)) {
+                Some(Ok(plaintext)) if plaintext == VERIFIER_PLAINTEXT => Ok(self),
+                _ => Err("Wrong secrets passphrase".into()),
+            },
+            None => {
+                let verifier = self.encrypt(VERIFIER_PLAINTEXT)?;
+                database.conn.execute(
+                    "INSERT INTO secret_keys (id, salt, verifier) VALUES (1, ?1, ?2)",
+                    params![salt.salt, verifier],
+                )?;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Returns the random nonce followed by the ciphertext and tag.
+    pub fn encrypt(&self, plaintext: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut in_out = plaintext.as_bytes().to_vec();
+        let nonce = self
+            .key
+            .seal_in_place_append_tag(Aad::empty(), &mut in_out)
+            .map_err(|_| "Could not encrypt secret")?;
+
+        let mut sealed = nonce.as_ref().to_vec();
+        sealed.extend_from_slice(&in_out);
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<String, Box<dyn Error>> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Encrypted secret is truncated".into());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid secret nonce")?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| "Could not decrypt secret, it may have been tampered with")?;
+
+        Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_round_trip_and_reject_wrong_passphrase() {
+        let db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+
+        let store = SecretStore::unlock("correct horse", &db).unwrap();
+        let sealed = store.encrypt("Bearer abc123").unwrap();
+        assert!(!sealed.windows(6).any(|window| window == b"abc123"));
+
+        let reopened = SecretStore::unlock("correct horse", &db).unwrap();
+        assert_eq!(reopened.decrypt(&sealed).unwrap(), "Bearer abc123");
+
+        assert!(SecretStore::unlock("wrong", &db).is_err());
+    }
+}
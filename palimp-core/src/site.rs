@@ -1,11 +1,127 @@
+use crate::audit::soft_404::Soft404Heuristics;
 use crate::database::Database;
-use rusqlite::params;
+use crate::http_client::HTTPClient;
+use crate::sitemap::{SitemapLimits, SitemapStream};
+use crate::throttle::RequestPacer;
+use crate::trash::{self, TrashKind};
+use rusqlite::{params, OptionalExtension, Row};
 use std::error::Error;
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Where to fetch a site's pages from instead of the hosts in its sitemap,
+/// e.g. to crawl production URLs against a staging server.
+#[derive(Debug, PartialEq)]
+pub enum FetchOverride {
+    /// Connect to this address for every host, keeping URLs and Host headers.
+    Address(IpAddr),
+    /// Rewrite each URL's scheme, host and port to those of this base URL.
+    BaseUrl(String),
+}
+
+impl FetchOverride {
+    pub fn parse(value: &str) -> Result<Self, Box<dyn Error>> {
+        let value = value.trim();
+        if let Ok(ip) = value.parse::<IpAddr>() {
+            return Ok(FetchOverride::Address(ip));
+        }
+
+        let base = reqwest::Url::parse(value)
+            .map_err(|err| format!("Fetch override must be an IP address or a base URL, got {}: {}", value, err))?;
+        if base.host_str().is_none() {
+            return Err(format!("Fetch override URL has no host: {}", value).into());
+        }
+        Ok(FetchOverride::BaseUrl(value.to_string()))
+    }
+
+    /// URL to request for a sitemap URL. Only base URL overrides change it.
+    pub fn fetch_url(&self, url: &str) -> String {
+        let base = match self {
+            FetchOverride::Address(_) => return url.to_string(),
+            FetchOverride::BaseUrl(base) => base,
+        };
+
+        let (Ok(mut rewritten), Ok(base)) = (reqwest::Url::parse(url.trim()), reqwest::Url::parse(base)) else {
+            return url.to_string();
+        };
+
+        if rewritten.set_scheme(base.scheme()).is_err()
+            || rewritten.set_host(base.host_str()).is_err()
+            || rewritten.set_port(base.port()).is_err()
+        {
+            return url.to_string();
+        }
+
+        rewritten.to_string()
+    }
+}
+
+/// Why a new site was refused, see [`SiteValidation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SiteValidationError {
+    InvalidDomain { domain: String, reason: String },
+    InvalidSitemapUrl { url: String, reason: String },
+    SitemapUnreachable { url: String, message: String },
+    SitemapUnparsable { url: String, message: String },
+    /// The sitemap parsed but lists no pages, so a crawl would fetch nothing.
+    EmptySitemap { url: String },
+}
+
+impl fmt::Display for SiteValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteValidationError::InvalidDomain { domain, reason } => {
+                write!(f, "Invalid domain '{}': {}", domain, reason)
+            }
+            SiteValidationError::InvalidSitemapUrl { url, reason } => {
+                write!(f, "Invalid sitemap URL '{}': {}", url, reason)
+            }
+            SiteValidationError::SitemapUnreachable { url, message } => {
+                write!(f, "Could not fetch the sitemap at {}: {}", url, message)
+            }
+            SiteValidationError::SitemapUnparsable { url, message } => {
+                write!(f, "The sitemap at {} is not a valid sitemap: {}", url, message)
+            }
+            SiteValidationError::EmptySitemap { url } => write!(f, "The sitemap at {} lists no pages", url),
+        }
+    }
+}
+
+impl Error for SiteValidationError {}
+
+/// How thoroughly a new site is checked before it is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SiteValidation {
+    /// Checks the domain and sitemap URL, then fetches and parses the sitemap.
+    #[default]
+    Full,
+    /// Checks the domain and sitemap URL without going online.
+    Format,
+    /// Stores the site as given.
+    Skip,
+}
+
+const COLUMNS: &str = "id, domain, sitemap_url, insecure_tls, fetch_override, soft_404_min_text_bytes, soft_404_phrases,
+    max_requests_per_sec, min_request_delay_ms";
 
 pub struct Site {
     pub id: Option<i64>,
     pub domain: String,
     pub sitemap_url: String,
+    /// Accept invalid TLS certificates (e.g. self-signed staging servers).
+    pub insecure_tls: bool,
+    /// IP address or base URL to fetch pages from, see [`FetchOverride`].
+    pub fetch_override: Option<String>,
+    /// Overrides for the soft 404 audit; `None` keeps the defaults.
+    pub soft_404_min_text_bytes: Option<i64>,
+    /// Newline-separated error phrases for the soft 404 audit.
+    pub soft_404_phrases: Option<String>,
+    /// Requests a crawl of the site may start per second, across all the
+    /// pages it fetches at once.
+    pub max_requests_per_sec: Option<f64>,
+    /// Milliseconds between the start of two requests of a crawl.
+    pub min_request_delay_ms: Option<i64>,
 }
 
 impl Site {
@@ -14,22 +130,160 @@ impl Site {
             id,
             domain: domain.to_string(),
             sitemap_url: sitemap_url.to_string(),
+            insecure_tls: false,
+            fetch_override: None,
+            soft_404_min_text_bytes: None,
+            soft_404_phrases: None,
+            max_requests_per_sec: None,
+            min_request_delay_ms: None,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Site {
+            id: Some(row.get(0)?),
+            domain: row.get(1)?,
+            sitemap_url: row.get(2)?,
+            insecure_tls: row.get(3)?,
+            fetch_override: row.get(4)?,
+            soft_404_min_text_bytes: row.get(5)?,
+            soft_404_phrases: row.get(6)?,
+            max_requests_per_sec: row.get(7)?,
+            min_request_delay_ms: row.get(8)?,
+        })
+    }
+
+    pub fn fetch_override(&self) -> Result<Option<FetchOverride>, Box<dyn Error>> {
+        self.fetch_override.as_deref().map(FetchOverride::parse).transpose()
+    }
+
+    /// Root URL of the site, using the sitemap's scheme when the domain has
+    /// none.
+    pub fn base_url(&self) -> String {
+        if self.domain.contains("://") {
+            return self.domain.trim_end_matches('/').to_string();
+        }
+
+        let scheme = if self.sitemap_url.starts_with("http://") { "http" } else { "https" };
+        format!("{}://{}", scheme, self.domain.trim_end_matches('/'))
+    }
+
+    /// Checks that the domain is a host name, IP address or base URL and
+    /// that the sitemap URL is an absolute HTTP(S) URL.
+    pub fn validate_format(&self) -> Result<(), SiteValidationError> {
+        validate_domain(&self.domain).map_err(|reason| SiteValidationError::InvalidDomain {
+            domain: self.domain.clone(),
+            reason,
+        })?;
+        validate_http_url(&self.sitemap_url).map_err(|reason| SiteValidationError::InvalidSitemapUrl {
+            url: self.sitemap_url.clone(),
+            reason,
+        })?;
+        Ok(())
+    }
+
+    /// Fetches the sitemap, following a sitemap index, and returns the
+    /// number of URLs it lists.
+    pub async fn verify_sitemap(&self, client: &HTTPClient) -> Result<usize, SiteValidationError> {
+        let url = self.sitemap_url.trim();
+        let mut sitemap = SitemapStream::open(client, url, &SitemapLimits::default())
+            .await
+            .map_err(|err| SiteValidationError::SitemapUnreachable {
+                url: url.to_string(),
+                message: err.to_string(),
+            })?;
+
+        let mut count = 0;
+        while sitemap
+            .next()
+            .await
+            .map_err(|err| SiteValidationError::SitemapUnparsable {
+                url: url.to_string(),
+                message: err.to_string(),
+            })?
+            .is_some()
+        {
+            count += 1;
+        }
+        if count == 0 {
+            return Err(SiteValidationError::EmptySitemap { url: url.to_string() });
+        }
+        Ok(count)
+    }
+
+    /// Soft 404 heuristics with this site's overrides applied.
+    pub fn soft_404_heuristics(&self) -> Soft404Heuristics {
+        let mut heuristics = Soft404Heuristics::default();
+        if let Some(min_text_bytes) = self.soft_404_min_text_bytes {
+            heuristics.min_text_bytes = min_text_bytes.max(0) as usize;
+        }
+        if let Some(phrases) = &self.soft_404_phrases {
+            heuristics.phrases = phrases
+                .lines()
+                .map(str::trim)
+                .filter(|phrase| !phrase.is_empty())
+                .map(str::to_string)
+                .collect();
         }
+        heuristics
+    }
+
+    /// Paces a crawl's requests to the site's politeness settings, or `None`
+    /// when it has none.
+    pub fn request_pacer(&self) -> Result<Option<RequestPacer>, Box<dyn Error>> {
+        RequestPacer::from_limits(
+            self.max_requests_per_sec,
+            self.min_request_delay_ms.map(|ms| Duration::from_millis(ms.max(0) as u64)),
+        )
+    }
+
+    /// Checks that the politeness settings can be enforced.
+    pub fn validate_politeness(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(delay) = self.min_request_delay_ms
+            && delay < 0
+        {
+            return Err(format!("Delay between requests can't be negative, got {} ms", delay).into());
+        }
+        self.request_pacer()?;
+        Ok(())
     }
 
     pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
         match self.id {
             Some(existing_id) => {
                 database.conn.execute(
-                    "UPDATE sites SET domain = ?1, sitemap_url = ?2 WHERE id = ?3",
-                    params![self.domain, self.sitemap_url, existing_id],
+                    "UPDATE sites SET domain = ?1, sitemap_url = ?2, insecure_tls = ?3, fetch_override = ?4,
+                     soft_404_min_text_bytes = ?5, soft_404_phrases = ?6, max_requests_per_sec = ?7,
+                     min_request_delay_ms = ?8 WHERE id = ?9",
+                    params![
+                        self.domain,
+                        self.sitemap_url,
+                        self.insecure_tls,
+                        self.fetch_override,
+                        self.soft_404_min_text_bytes,
+                        self.soft_404_phrases,
+                        self.max_requests_per_sec,
+                        self.min_request_delay_ms,
+                        existing_id
+                    ],
                 )?;
                 Ok(())
             }
             None => {
                 database.conn.execute(
-                    "INSERT INTO sites (domain, sitemap_url) VALUES (?1, ?2)",
-                    params![self.domain, self.sitemap_url],
+                    "INSERT INTO sites (domain, sitemap_url, insecure_tls, fetch_override, soft_404_min_text_bytes, soft_404_phrases,
+                     max_requests_per_sec, min_request_delay_ms)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.domain,
+                        self.sitemap_url,
+                        self.insecure_tls,
+                        self.fetch_override,
+                        self.soft_404_min_text_bytes,
+                        self.soft_404_phrases,
+                        self.max_requests_per_sec,
+                        self.min_request_delay_ms
+                    ],
                 )?;
 
                 self.id = Some(database.conn.last_insert_rowid());
@@ -39,31 +293,33 @@ impl Site {
     }
 
     pub fn fetch(id: i64, database: &Database) -> Result<Self, Box<dyn Error>> {
-        let sql = "SELECT id, domain, sitemap_url FROM sites WHERE id = ?1";
-
-        let site = database.conn.query_row(sql, params![id], |row| {
-            Ok(Site {
-                id: Some(row.get(0)?),
-                domain: row.get(1)?,
-                sitemap_url: row.get(2)?, // rusqlite handles Option<String> automatically
-            })
-        })?;
+        let sql = format!("SELECT {} FROM sites WHERE id = ?1 AND deleted_at IS NULL", COLUMNS);
+
+        let site = database.conn.query_row(&sql, params![id], Self::from_row)?;
+
+        Ok(site)
+    }
+
+    /// The site with this domain and sitemap URL, if it was created before.
+    pub fn fetch_by_domain_and_sitemap(
+        domain: &str,
+        sitemap_url: &str,
+        database: &Database,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let sql = format!(
+            "SELECT {} FROM sites WHERE domain = ?1 AND sitemap_url = ?2 AND deleted_at IS NULL",
+            COLUMNS
+        );
+
+        let site = database.conn.query_row(&sql, params![domain, sitemap_url], Self::from_row).optional()?;
 
         Ok(site)
     }
 
     pub fn fetch_all(database: &Database) -> Result<Vec<Site>, Box<dyn Error>> {
-        let mut stmt = database
-            .conn
-            .prepare("SELECT id, domain, sitemap_url FROM sites")?;
-
-        let site_iter = stmt.query_map([], |row| {
-            Ok(Site {
-                id: Some(row.get(0)?),
-                domain: row.get(1)?,
-                sitemap_url: row.get(2)?,
-            })
-        })?;
+        let mut stmt = database.conn.prepare(&format!("SELECT {} FROM sites WHERE deleted_at IS NULL ORDER BY id", COLUMNS))?;
+
+        let site_iter = stmt.query_map([], Self::from_row)?;
 
         let mut sites = Vec::new();
         for site in site_iter {
@@ -73,10 +329,141 @@ impl Site {
         Ok(sites)
     }
 
+    /// Moves the site and its crawls to the trash, see [`trash::delete`].
     pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
-        database
-            .conn
-            .execute("DELETE FROM sites WHERE id = ?1", params![id])?;
-        Ok(())
+        trash::delete(TrashKind::Site, id, database)
+    }
+}
+
+/// A domain is a host with an optional port, e.g. `example.com:8080`, or a
+/// base URL such as `http://127.0.0.1:8080`.
+fn validate_domain(domain: &str) -> Result<(), String> {
+    let domain = domain.trim();
+    if domain.is_empty() {
+        return Err("it is empty".to_string());
+    }
+    if domain.contains("://") {
+        return validate_http_url(domain).and_then(|_| {
+            let url = reqwest::Url::parse(domain).map_err(|err| err.to_string())?;
+            if url.path() != "/" || url.query().is_some() || url.fragment().is_some() {
+                return Err("a base URL can't have a path, query or fragment".to_string());
+            }
+            Ok(())
+        });
+    }
+    if domain.trim_end_matches('/').contains(['/', '?', '#', '@']) {
+        return Err("expected a host name without a path, e.g. example.com".to_string());
+    }
+
+    let url = reqwest::Url::parse(&format!("http://{}", domain)).map_err(|err| err.to_string())?;
+    match url.domain() {
+        Some(host) => validate_host_name(host),
+        None if url.host_str().is_some() => Ok(()),
+        None => Err("it has no host".to_string()),
+    }
+}
+
+fn validate_host_name(host: &str) -> Result<(), String> {
+    if host == "localhost" {
+        return Ok(());
+    }
+    if host.len() > 253 {
+        return Err("it is longer than 253 characters".to_string());
+    }
+    if !host.trim_end_matches('.').contains('.') {
+        return Err("expected a fully qualified name, e.g. example.com".to_string());
+    }
+    for label in host.trim_end_matches('.').split('.') {
+        let valid = (1..=63).contains(&label.len())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-');
+        if !valid {
+            return Err(format!("'{}' is not a valid label", label));
+        }
+    }
+    Ok(())
+}
+
+fn validate_http_url(value: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(value.trim()).map_err(|err| err.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("expected an http or https URL, not {}", url.scheme()));
+    }
+    if url.host_str().is_none() {
+        return Err("it has no host".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::Crawl;
+
+    #[test]
+    fn test_fetch_override_parsing_and_rewrite() {
+        assert_eq!(
+            FetchOverride::parse("10.0.0.5").unwrap(),
+            FetchOverride::Address("10.0.0.5".parse().unwrap())
+        );
+        assert!(FetchOverride::parse("not a host").is_err());
+
+        let staging = FetchOverride::parse("http://staging.example.com:8080").unwrap();
+        assert_eq!(
+            staging.fetch_url("https://www.example.com/pricing?plan=pro"),
+            "http://staging.example.com:8080/pricing?plan=pro"
+        );
+
+        let address = FetchOverride::parse("10.0.0.5").unwrap();
+        assert_eq!(address.fetch_url("https://www.example.com/"), "https://www.example.com/");
+    }
+
+    #[test]
+    fn test_duplicate_sites_merge_on_upgrade() {
+        // Sites created twice before domain and sitemap were unique
+        let mut db = Database::new(":memory:").unwrap();
+        db.conn.execute_batch(
+            "CREATE TABLE sites (id INTEGER PRIMARY KEY, domain TEXT NOT NULL, sitemap_url TEXT NOT NULL);
+            INSERT INTO sites (domain, sitemap_url) VALUES
+                ('a.com', 'https://a.com/sitemap.xml'),
+                ('a.com', 'https://a.com/sitemap.xml'),
+                ('a.com', 'https://a.com/news.xml');
+            CREATE TABLE crawls (id INTEGER PRIMARY KEY, site_id INTEGER NOT NULL, started_at DATETIME);
+            INSERT INTO crawls (site_id, started_at) VALUES (1, '2024-01-01 00:00:00'), (2, '2024-01-02 00:00:00');",
+        ).unwrap();
+        db.seed().unwrap();
+
+        let sites: Vec<i64> = Site::fetch_all(&db).unwrap().iter().filter_map(|site| site.id).collect();
+        assert_eq!(sites, [1, 3]);
+        let crawl_sites: Vec<i64> = Crawl::fetch_all(&db).unwrap().iter().map(|crawl| crawl.site_id).collect();
+        assert_eq!(crawl_sites, [1, 1]);
+        assert!(Site::new(None, "a.com", "https://a.com/sitemap.xml").sync(&mut db).is_err());
+
+        // A deleted site frees its domain and sitemap
+        Site::delete(1, &db).unwrap();
+        Site::new(None, "a.com", "https://a.com/sitemap.xml").sync(&mut db).unwrap();
+        assert!(trash::restore(TrashKind::Site, 1, &db).unwrap_err().to_string().contains("delete it first"));
+    }
+
+    #[test]
+    fn test_validate_format() {
+        let valid = |domain: &str, sitemap: &str| Site::new(None, domain, sitemap).validate_format();
+        assert!(valid("example.com", "https://example.com/sitemap.xml").is_ok());
+        assert!(valid("www.example.co.uk:8443", "https://www.example.co.uk:8443/sitemap.xml").is_ok());
+        assert!(valid("localhost", "http://localhost/sitemap.xml").is_ok());
+        assert!(valid("10.0.0.5", "http://10.0.0.5/sitemap.xml").is_ok());
+        assert!(valid("http://127.0.0.1:41234", "http://127.0.0.1:41234/sitemap.xml").is_ok());
+
+        for domain in ["", "example", "exa mple.com", "-example.com", "example.com/blog", "ftp://example.com", "http://example.com/blog"] {
+            assert!(
+                matches!(valid(domain, "https://example.com/sitemap.xml"), Err(SiteValidationError::InvalidDomain { .. })),
+                "{} should be refused",
+                domain
+            );
+        }
+        for sitemap in ["sitemap", "/sitemap.xml", "ftp://example.com/sitemap.xml"] {
+            assert!(matches!(valid("example.com", sitemap), Err(SiteValidationError::InvalidSitemapUrl { .. })));
+        }
     }
 }
@@ -0,0 +1,394 @@
+//! The monitoring setup of an archive as a YAML document: its sites with
+//! their settings and monitors, and the user-defined suites. Exporting one
+//! archive's setup and importing it elsewhere reproduces it, so it can live
+//! in version control.
+//!
+//! ```yaml
+//! sites:
+//!   - domain: example.com
+//!     sitemap_url: https://example.com/sitemap.xml
+//!     fetch_override: 10.0.0.5
+//!     monitors:
+//!       - selector_count: .price
+//!         url_pattern: /products/
+//!       - text_change: 10.0
+//!     rules:
+//!       - selector: h1
+//!         count: 1
+//! suites:
+//!   - name: launch
+//!     checks:
+//!       - audit: noindex
+//!       - require: link[rel=canonical]
+//! ```
+
+use crate::assertion::Rule;
+use crate::database::Database;
+use crate::monitor::{Monitor, MonitorKind};
+use crate::site::{FetchOverride, Site};
+use crate::site_rule::SiteRule;
+use crate::suite::{Suite, SuiteCheck};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteConfig {
+    #[serde(default)]
+    pub sites: Vec<SiteEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suites: Vec<SuiteEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteEntry {
+    pub domain: String,
+    pub sitemap_url: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub insecure_tls: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_override: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft_404_min_text_bytes: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub soft_404_phrases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests_per_sec: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_request_delay_ms: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub monitors: Vec<MonitorEntry>,
+    /// Checked after every crawl, see [`SiteRule`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+}
+
+/// A monitor with exactly one of `selector_count` and `text_change` set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector_count: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_change: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SuiteEntry {
+    pub name: String,
+    pub checks: Vec<CheckEntry>,
+}
+
+/// A suite check with exactly one of `audit`, `require` and `forbid` set.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forbid: Option<String>,
+}
+
+/// What [`SiteConfig::apply`] changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConfigImport {
+    pub sites_created: usize,
+    pub sites_updated: usize,
+    pub monitors_added: usize,
+    pub rules_added: usize,
+    pub suites_saved: usize,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl SiteConfig {
+    /// The setup of every site that isn't in the trash, and of every
+    /// user-defined suite.
+    pub fn build(database: &Database) -> Result<Self, Box<dyn Error>> {
+        let mut sites = Vec::new();
+        for site in Site::fetch_all(database)? {
+            sites.push(SiteEntry::from_site(site, database)?);
+        }
+
+        let suites = Suite::fetch_all(database)?
+            .iter()
+            .map(|suite| SuiteEntry {
+                name: suite.name.clone(),
+                checks: suite.checks.iter().map(CheckEntry::from_check).collect(),
+            })
+            .collect();
+
+        Ok(SiteConfig { sites, suites })
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Brings the archive in line with the config in one transaction. Sites
+    /// are matched by domain and sitemap and their settings overwritten,
+    /// missing monitors and rules are added and suites replaced by name. Nothing
+    /// absent from the config is deleted.
+    pub fn apply(&self, database: &mut Database) -> Result<ConfigImport, Box<dyn Error>> {
+        // Refuse the whole file before touching anything
+        for entry in &self.sites {
+            entry.to_site(None)?;
+            for monitor in &entry.monitors {
+                monitor.to_kind()?;
+            }
+            for rule in &entry.rules {
+                rule.validate()?;
+            }
+        }
+        for entry in &self.suites {
+            entry.to_suite()?;
+            if Suite::built_in().iter().any(|suite| suite.name == entry.name) {
+                return Err(format!("'{}' is the name of a built-in suite", entry.name).into());
+            }
+        }
+
+        database.in_transaction(|database| {
+            let mut import = ConfigImport::default();
+
+            for entry in &self.sites {
+                let existing = Site::fetch_by_domain_and_sitemap(entry.domain.trim(), entry.sitemap_url.trim(), database)?;
+                let mut site = entry.to_site(existing.as_ref().and_then(|site| site.id))?;
+                match existing {
+                    Some(_) => import.sites_updated += 1,
+                    None => import.sites_created += 1,
+                }
+                site.sync(database)?;
+                let site_id = site.id.ok_or("Site without an ID")?;
+
+                let mut monitors = Monitor::fetch_by_site(site_id, database)?;
+                for monitor in &entry.monitors {
+                    let kind = monitor.to_kind()?;
+                    let url_pattern = monitor.url_pattern.as_deref();
+                    if monitors.iter().any(|m| m.kind == kind && m.url_pattern.as_deref() == url_pattern) {
+                        continue;
+                    }
+                    let mut added = Monitor::new(None, site_id, kind, url_pattern);
+                    added.sync(database)?;
+                    monitors.push(added);
+                    import.monitors_added += 1;
+                }
+
+                let mut site_rules = SiteRule::fetch_by_site(site_id, database)?;
+                for rule in &entry.rules {
+                    if site_rules.iter().any(|site_rule| site_rule.rule == *rule) {
+                        continue;
+                    }
+                    let mut added = SiteRule::new(None, site_id, rule.clone());
+                    added.sync(database)?;
+                    site_rules.push(added);
+                    import.rules_added += 1;
+                }
+            }
+
+            let suites = Suite::fetch_all(database)?;
+            for entry in &self.suites {
+                let mut suite = entry.to_suite()?;
+                suite.id = suites.iter().find(|existing| existing.name == suite.name).and_then(|existing| existing.id);
+                suite.sync(database)?;
+                import.suites_saved += 1;
+            }
+
+            Ok(import)
+        })
+    }
+}
+
+impl SiteEntry {
+    /// The settings, monitors and rules of a stored site.
+    pub fn from_site(site: Site, database: &Database) -> Result<Self, Box<dyn Error>> {
+        let site_id = site.id.ok_or("Site without an ID")?;
+        let monitors = Monitor::fetch_by_site(site_id, database)?
+            .iter()
+            .map(MonitorEntry::from_monitor)
+            .collect();
+        let rules = SiteRule::fetch_by_site(site_id, database)?
+            .into_iter()
+            .map(|site_rule| site_rule.rule)
+            .collect();
+        Ok(SiteEntry {
+            domain: site.domain,
+            sitemap_url: site.sitemap_url,
+            insecure_tls: site.insecure_tls,
+            fetch_override: site.fetch_override,
+            soft_404_min_text_bytes: site.soft_404_min_text_bytes,
+            soft_404_phrases: site
+                .soft_404_phrases
+                .map(|phrases| phrases.lines().map(str::to_string).collect())
+                .unwrap_or_default(),
+            max_requests_per_sec: site.max_requests_per_sec,
+            min_request_delay_ms: site.min_request_delay_ms,
+            monitors,
+            rules,
+        })
+    }
+
+    fn to_site(&self, id: Option<i64>) -> Result<Site, Box<dyn Error>> {
+        let mut site = Site::new(id, self.domain.trim(), self.sitemap_url.trim());
+        site.validate_format()?;
+        if let Some(fetch_override) = &self.fetch_override {
+            FetchOverride::parse(fetch_override)?;
+        }
+        site.insecure_tls = self.insecure_tls;
+        site.fetch_override = self.fetch_override.as_deref().map(|value| value.trim().to_string());
+        site.soft_404_min_text_bytes = self.soft_404_min_text_bytes;
+        site.soft_404_phrases = (!self.soft_404_phrases.is_empty()).then(|| self.soft_404_phrases.join("\n"));
+        site.max_requests_per_sec = self.max_requests_per_sec;
+        site.min_request_delay_ms = self.min_request_delay_ms;
+        site.validate_politeness()?;
+        Ok(site)
+    }
+}
+
+impl MonitorEntry {
+    fn from_monitor(monitor: &Monitor) -> Self {
+        let (selector_count, text_change) = match &monitor.kind {
+            MonitorKind::SelectorCount(selector) => (Some(selector.clone()), None),
+            MonitorKind::TextChange(threshold) => (None, Some(*threshold)),
+        };
+        MonitorEntry {
+            selector_count,
+            text_change,
+            url_pattern: monitor.url_pattern.clone(),
+        }
+    }
+
+    fn to_kind(&self) -> Result<MonitorKind, Box<dyn Error>> {
+        match (&self.selector_count, self.text_change) {
+            (Some(selector), None) => Ok(MonitorKind::SelectorCount(selector.clone())),
+            (None, Some(threshold)) => Ok(MonitorKind::TextChange(threshold)),
+            _ => Err("A monitor needs exactly one of selector_count and text_change".into()),
+        }
+    }
+}
+
+impl SuiteEntry {
+    fn to_suite(&self) -> Result<Suite, Box<dyn Error>> {
+        let checks = self.checks.iter().map(CheckEntry::to_check).collect::<Result<_, _>>()?;
+        Ok(Suite::new(None, &self.name, checks))
+    }
+}
+
+impl CheckEntry {
+    fn from_check(check: &SuiteCheck) -> Self {
+        let value = Some(check.value().to_string());
+        match check {
+            SuiteCheck::Audit(_) => CheckEntry { audit: value, ..Self::default() },
+            SuiteCheck::Require(_) => CheckEntry { require: value, ..Self::default() },
+            SuiteCheck::Forbid(_) => CheckEntry { forbid: value, ..Self::default() },
+        }
+    }
+
+    fn to_check(&self) -> Result<SuiteCheck, Box<dyn Error>> {
+        let (kind, value) = match (&self.audit, &self.require, &self.forbid) {
+            (Some(audit), None, None) => ("audit", audit),
+            (None, Some(selector), None) => ("require", selector),
+            (None, None, Some(selector)) => ("forbid", selector),
+            _ => return Err("A suite check needs exactly one of audit, require and forbid".into()),
+        };
+        SuiteCheck::from_parts(kind, value).ok_or_else(|| format!("Unknown audit '{}'", value).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::Audit;
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let yaml = "
+sites:
+  - domain: example.com
+    sitemap_url: https://example.com/sitemap.xml
+    insecure_tls: true
+    fetch_override: 10.0.0.5
+    soft_404_phrases: [not found, gone]
+    max_requests_per_sec: 2.0
+    monitors:
+      - selector_count: .price
+        url_pattern: /products/
+      - text_change: 10.0
+    rules:
+      - name: one h1
+        selector: h1
+        count: 1
+      - selector: .price
+        min: 1
+        url_pattern: /products/
+        severity: warning
+  - domain: other.com
+    sitemap_url: https://other.com/sitemap.xml
+suites:
+  - name: launch
+    checks:
+      - audit: noindex
+      - forbid: .lorem
+";
+        let config = SiteConfig::from_yaml(yaml).unwrap();
+        let import = config.apply(&mut db).unwrap();
+        assert_eq!(import, ConfigImport { sites_created: 2, sites_updated: 0, monitors_added: 2, rules_added: 2, suites_saved: 1 });
+
+        let site = &Site::fetch_all(&db).unwrap()[0];
+        assert!(site.insecure_tls);
+        assert_eq!(site.soft_404_phrases.as_deref(), Some("not found\ngone"));
+        assert_eq!(site.request_pacer().unwrap().unwrap().interval(), Duration::from_millis(500));
+        let suite = &Suite::fetch_all(&db).unwrap()[0];
+        assert_eq!(suite.checks, [SuiteCheck::Audit(Audit::Noindex), SuiteCheck::Forbid(".lorem".to_string())]);
+
+        // Exporting gives the same config back, and importing it again
+        // changes nothing
+        let exported = SiteConfig::build(&db).unwrap();
+        assert_eq!(exported, config);
+        assert_eq!(SiteConfig::from_yaml(&exported.to_yaml().unwrap()).unwrap(), config);
+        let import = exported.apply(&mut db).unwrap();
+        assert_eq!(import, ConfigImport { sites_created: 0, sites_updated: 2, monitors_added: 0, rules_added: 0, suites_saved: 1 });
+        assert_eq!(Monitor::fetch_all(&db).unwrap().len(), 2);
+        assert_eq!(SiteRule::fetch_all(&db).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_config_changes_nothing() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+
+        let bad_monitor = "
+sites:
+  - domain: example.com
+    sitemap_url: https://example.com/sitemap.xml
+  - domain: other.com
+    sitemap_url: https://other.com/sitemap.xml
+    monitors:
+      - selector_count: h1
+        text_change: 5.0
+";
+        let config = SiteConfig::from_yaml(bad_monitor).unwrap();
+        assert!(config.apply(&mut db).is_err());
+        assert!(Site::fetch_all(&db).unwrap().is_empty());
+
+        assert!(SiteConfig::from_yaml("sites:\n  - domain: a.com\n    sitemap: x\n").is_err());
+        let bad_domain = "sites:\n  - domain: not a domain\n    sitemap_url: https://a.com/sitemap.xml\n";
+        assert!(SiteConfig::from_yaml(bad_domain).unwrap().apply(&mut db).is_err());
+        let built_in = "suites:\n  - name: seo-basic\n    checks: []\n";
+        assert!(SiteConfig::from_yaml(built_in).unwrap().apply(&mut db).is_err());
+    }
+}
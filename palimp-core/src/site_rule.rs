@@ -0,0 +1,310 @@
+use crate::alert::Alert;
+use crate::assertion::{Rule, RuleSet, Severity};
+use crate::database::Database;
+use rusqlite::types::Type;
+use rusqlite::{params, OptionalExtension, Row};
+use std::error::Error;
+
+const COLUMNS: &str = "id, site_id, name, selector, count, min, max, url_pattern, severity";
+
+/// A baseline [`Rule`] attached to a site and checked after every completed
+/// crawl of it, unlike `palimp assert` which runs on demand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteRule {
+    pub id: Option<i64>,
+    pub site_id: i64,
+    pub rule: Rule,
+}
+
+impl SiteRule {
+    pub fn new(id: Option<i64>, site_id: i64, rule: Rule) -> Self {
+        Self { id, site_id, rule }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let severity: String = row.get(8)?;
+        Ok(SiteRule {
+            id: Some(row.get(0)?),
+            site_id: row.get(1)?,
+            rule: Rule {
+                name: row.get(2)?,
+                selector: row.get(3)?,
+                count: row.get::<_, Option<i64>>(4)?.map(|count| count as usize),
+                min: row.get::<_, Option<i64>>(5)?.map(|min| min as usize),
+                max: row.get::<_, Option<i64>>(6)?.map(|max| max as usize),
+                url_pattern: row.get(7)?,
+                severity: Severity::from_name(&severity).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(8, Type::Text, format!("unknown severity '{}'", severity).into())
+                })?,
+            },
+        })
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        self.rule.validate()?;
+        let rule = &self.rule;
+
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE site_rules SET name = ?1, selector = ?2, count = ?3, min = ?4, max = ?5, url_pattern = ?6, severity = ?7
+                     WHERE id = ?8",
+                    params![
+                        rule.name,
+                        rule.selector,
+                        rule.count.map(|count| count as i64),
+                        rule.min.map(|min| min as i64),
+                        rule.max.map(|max| max as i64),
+                        rule.url_pattern,
+                        rule.severity.name(),
+                        existing_id
+                    ],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO site_rules (site_id, name, selector, count, min, max, url_pattern, severity)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        self.site_id,
+                        rule.name,
+                        rule.selector,
+                        rule.count.map(|count| count as i64),
+                        rule.min.map(|min| min as i64),
+                        rule.max.map(|max| max as i64),
+                        rule.url_pattern,
+                        rule.severity.name()
+                    ],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::fetch_where("1 = 1", params![], database)
+    }
+
+    pub fn fetch_by_site(site_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        Self::fetch_where("site_id = ?1", params![site_id], database)
+    }
+
+    fn fetch_where(
+        condition: &str,
+        parameters: &[&dyn rusqlite::ToSql],
+        database: &Database,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare(&format!("SELECT {} FROM site_rules WHERE {} ORDER BY id", COLUMNS, condition))?;
+
+        let rule_iter = stmt.query_map(parameters, SiteRule::from_row)?;
+
+        let mut rules = Vec::new();
+        for rule in rule_iter {
+            rules.push(rule?);
+        }
+
+        Ok(rules)
+    }
+
+    /// Deletes the rule with its outcomes and alerts.
+    pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        let deleted = database.conn.execute("DELETE FROM site_rules WHERE id = ?1", params![id])?;
+        if deleted == 0 {
+            return Err(format!("Site rule {} not found", id).into());
+        }
+        Ok(())
+    }
+}
+
+/// How a site rule fared on one crawl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleOutcomeRecord {
+    pub id: Option<i64>,
+    pub rule_id: i64,
+    pub crawl_id: i64,
+    /// Pages the rule applied to.
+    pub checked: usize,
+    /// Pages it failed on.
+    pub violations: usize,
+    pub created_at: Option<String>,
+}
+
+impl RuleOutcomeRecord {
+    pub fn new(rule_id: i64, crawl_id: i64, checked: usize, violations: usize) -> Self {
+        Self {
+            id: None,
+            rule_id,
+            crawl_id,
+            checked,
+            violations,
+            created_at: None,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(RuleOutcomeRecord {
+            id: Some(row.get(0)?),
+            rule_id: row.get(1)?,
+            crawl_id: row.get(2)?,
+            checked: row.get::<_, i64>(3)? as usize,
+            violations: row.get::<_, i64>(4)? as usize,
+            created_at: Some(row.get(5)?),
+        })
+    }
+
+    pub fn passed(&self) -> bool {
+        self.violations == 0
+    }
+
+    /// Stores the outcome, replacing an earlier one of the same rule and
+    /// crawl.
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let sql = "INSERT INTO rule_outcomes (rule_id, crawl_id, checked, violations, created_at)
+                   VALUES (?1, ?2, ?3, ?4, palimp_now())
+                   ON CONFLICT (rule_id, crawl_id) DO UPDATE
+                   SET checked = excluded.checked, violations = excluded.violations, created_at = excluded.created_at
+                   RETURNING id, created_at";
+        let (new_id, time): (i64, String) = database.conn.query_row(
+            sql,
+            params![self.rule_id, self.crawl_id, self.checked as i64, self.violations as i64],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        self.id = Some(new_id);
+        self.created_at = Some(time);
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, rule_id, crawl_id, checked, violations, created_at
+             FROM rule_outcomes WHERE crawl_id = ?1 ORDER BY rule_id",
+        )?;
+
+        let outcome_iter = stmt.query_map(params![crawl_id], RuleOutcomeRecord::from_row)?;
+
+        let mut outcomes = Vec::new();
+        for outcome in outcome_iter {
+            outcomes.push(outcome?);
+        }
+
+        Ok(outcomes)
+    }
+
+    /// The outcome of the rule on the latest crawl before `crawl_id` it was
+    /// checked on.
+    pub fn fetch_previous(rule_id: i64, crawl_id: i64, database: &Database) -> Result<Option<Self>, Box<dyn Error>> {
+        let outcome = database
+            .conn
+            .query_row(
+                "SELECT id, rule_id, crawl_id, checked, violations, created_at
+                 FROM rule_outcomes WHERE rule_id = ?1 AND crawl_id < ?2
+                 ORDER BY crawl_id DESC LIMIT 1",
+                params![rule_id, crawl_id],
+                RuleOutcomeRecord::from_row,
+            )
+            .optional()?;
+        Ok(outcome)
+    }
+}
+
+/// Checks the site's rules against a crawl and stores their outcomes. A rule
+/// that fails after passing on its previous crawl, or on its first one,
+/// raises an alert naming the first page it fails on.
+pub fn evaluate(site_id: i64, crawl_id: i64, database: &mut Database) -> Result<Vec<Alert>, Box<dyn Error>> {
+    let site_rules = SiteRule::fetch_by_site(site_id, database)?;
+    if site_rules.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rules = RuleSet {
+        rules: site_rules.iter().map(|site_rule| site_rule.rule.clone()).collect(),
+    };
+    let report = rules.check(crawl_id, database)?;
+
+    let mut alerts = Vec::new();
+    for (site_rule, outcome) in site_rules.iter().zip(&report.outcomes) {
+        let rule_id = site_rule.id.ok_or("Site rule without an ID")?;
+        let previous = RuleOutcomeRecord::fetch_previous(rule_id, crawl_id, database)?;
+        RuleOutcomeRecord::new(rule_id, crawl_id, outcome.checked.len(), outcome.violations.len()).sync(database)?;
+
+        let started_failing = previous.is_none_or(|previous| previous.passed());
+        if let Some(first) = outcome.violations.first()
+            && started_failing
+        {
+            let message = format!(
+                "{} started failing on {} of {} pages ({}); this page has {} matches",
+                site_rule.rule.label(),
+                outcome.violations.len(),
+                outcome.checked.len(),
+                site_rule.rule.severity.name(),
+                first.count
+            );
+            let mut alert = Alert::for_rule(rule_id, crawl_id, &first.url, &message);
+            alert.sync(database)?;
+            alerts.push(alert);
+        }
+    }
+
+    Ok(alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::page::Page;
+
+    #[test]
+    fn test_alerts_when_rules_start_failing() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed'), (2, 1, 'completed'), (3, 1, 'completed');",
+            )
+            .unwrap();
+        let crawls = [
+            [("https://a.com/", "<h1>A</h1>"), ("https://a.com/b", "<h1>B</h1>")],
+            [("https://a.com/", "<h1>A</h1>"), ("https://a.com/b", "<p>B</p>")],
+            [("https://a.com/", "<p>A</p>"), ("https://a.com/b", "<p>B</p>")],
+        ];
+        for (crawl_id, pages) in (1..).zip(crawls) {
+            for (url, html) in pages {
+                Page::new(url, url, html, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+            }
+        }
+
+        let rule = Rule {
+            selector: "h1".to_string(),
+            count: Some(1),
+            ..Rule::default()
+        };
+        let mut site_rule = SiteRule::new(None, 1, rule);
+        site_rule.sync(&mut db).unwrap();
+        assert_eq!(SiteRule::fetch_by_site(1, &db).unwrap(), [site_rule.clone()]);
+
+        assert!(evaluate(1, 1, &mut db).unwrap().is_empty());
+
+        let alerts = evaluate(1, 2, &mut db).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule_id, site_rule.id);
+        assert_eq!(alerts[0].url, "https://a.com/b");
+        assert_eq!(
+            alerts[0].message,
+            "exactly 1 'h1' started failing on 1 of 2 pages (error); this page has 0 matches"
+        );
+
+        // Still failing, so no new alert, but the outcome is recorded
+        assert!(evaluate(1, 3, &mut db).unwrap().is_empty());
+        let outcomes = RuleOutcomeRecord::fetch_by_crawl_id(3, &db).unwrap();
+        assert_eq!((outcomes[0].checked, outcomes[0].violations), (2, 2));
+
+        SiteRule::delete(site_rule.id.unwrap(), &db).unwrap();
+        assert!(Alert::fetch_all(None, &db).unwrap().is_empty());
+        assert!(RuleOutcomeRecord::fetch_by_crawl_id(3, &db).unwrap().is_empty());
+    }
+}
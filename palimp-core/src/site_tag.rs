@@ -0,0 +1,89 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+/// Lowercases and checks a tag: letters, digits, `-`, `_`, `.` and `:`,
+/// e.g. `client-x` or `env:staging`.
+pub fn normalize(tag: &str) -> Result<String, Box<dyn Error>> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err("A tag can't be empty".into());
+    }
+    if let Some(invalid) = tag.chars().find(|c| !c.is_alphanumeric() && !"-_.:".contains(*c)) {
+        return Err(format!("Tag '{}' can't contain '{}'", tag, invalid).into());
+    }
+    Ok(tag)
+}
+
+/// Tags a site. Returns false if it already had the tag.
+pub fn add(site_id: i64, tag: &str, database: &Database) -> Result<bool, Box<dyn Error>> {
+    let added = database.conn.execute(
+        "INSERT OR IGNORE INTO site_tags (site_id, tag) VALUES (?1, ?2)",
+        params![site_id, normalize(tag)?],
+    )?;
+    Ok(added > 0)
+}
+
+/// Untags a site. Returns false if it didn't have the tag.
+pub fn remove(site_id: i64, tag: &str, database: &Database) -> Result<bool, Box<dyn Error>> {
+    let removed = database.conn.execute(
+        "DELETE FROM site_tags WHERE site_id = ?1 AND tag = ?2",
+        params![site_id, normalize(tag)?],
+    )?;
+    Ok(removed > 0)
+}
+
+/// A site's tags, sorted.
+pub fn fetch_by_site(site_id: i64, database: &Database) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare("SELECT tag FROM site_tags WHERE site_id = ?1 ORDER BY tag")?;
+    let tags = stmt
+        .query_map(params![site_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(tags)
+}
+
+/// The sites with a tag that aren't in the trash, by ID.
+pub fn site_ids(tag: &str, database: &Database) -> Result<Vec<i64>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare(
+        "SELECT s.id FROM sites s INNER JOIN site_tags t ON t.site_id = s.id
+         WHERE t.tag = ?1 AND s.deleted_at IS NULL ORDER BY s.id",
+    )?;
+    let ids = stmt
+        .query_map(params![normalize(tag)?], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, _>>()?;
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_sites() {
+        assert_eq!(normalize(" Client-X ").unwrap(), "client-x");
+        assert_eq!(normalize("env:staging").unwrap(), "env:staging");
+        assert!(normalize("  ").is_err());
+        assert!(normalize("client x").is_err());
+
+        let db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', ''), (2, 'b.com', ''), (3, 'c.com', '');
+                 UPDATE sites SET deleted_at = palimp_now() WHERE id = 3;",
+            )
+            .unwrap();
+        for site_id in 1..=3 {
+            assert!(add(site_id, "Client-X", &db).unwrap());
+        }
+        assert!(!add(1, "client-x", &db).unwrap());
+        add(1, "env:prod", &db).unwrap();
+
+        assert_eq!(fetch_by_site(1, &db).unwrap(), ["client-x", "env:prod"]);
+        assert_eq!(site_ids("CLIENT-X", &db).unwrap(), [1, 2]);
+        assert!(remove(2, "client-x", &db).unwrap());
+        assert!(!remove(2, "client-x", &db).unwrap());
+        assert_eq!(site_ids("client-x", &db).unwrap(), [1]);
+        assert!(site_ids("other", &db).unwrap().is_empty());
+    }
+}
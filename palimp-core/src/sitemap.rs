@@ -1,32 +1,430 @@
-use quick_xml::de::from_str;
-use serde::Deserialize;
+use crate::http_client::HTTPClient;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashSet;
 use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn parse_sitemap(xml_content: &str) -> Result<UrlSet, Box<dyn std::error::Error>> {
-    let sitemap: UrlSet = from_str(xml_content)?;
-    Ok(sitemap)
-}
+/// Levels of nested sitemap indexes followed by default. The protocol
+/// allows one, some sites nest a second.
+pub const DEFAULT_SITEMAP_DEPTH: usize = 2;
+
+/// Values `<changefreq>` may take; anything else is ignored.
+pub const CHANGE_FREQUENCIES: [&str; 7] = ["always", "hourly", "daily", "weekly", "monthly", "yearly", "never"];
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct UrlSet {
-    #[serde(rename = "url")]
     pub urls: Vec<SitemapUrl>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default)]
 pub struct SitemapUrl {
     pub loc: String,
+    /// `<lastmod>` as given, a W3C date such as `2024-05-01` or one with a
+    /// time.
+    pub lastmod: Option<String>,
+    /// `<changefreq>`, one of [`CHANGE_FREQUENCIES`].
+    pub changefreq: Option<String>,
+    /// `<priority>`, from 0.0 to 1.0.
+    pub priority: Option<f64>,
+    /// `<image:image>` entries of the URL.
+    pub images: Vec<SitemapImageEntry>,
+}
+
+#[derive(Debug)]
+pub struct SitemapImageEntry {
+    pub loc: String,
 }
 
 pub struct Sitemap {
     pub urlset: UrlSet,
+    /// Child sitemap URLs when the document is a `<sitemapindex>`, whose
+    /// `urlset` is then empty.
+    pub sitemaps: Vec<String>,
 }
 
 impl Sitemap {
+    /// Parses a whole sitemap that is already in memory, every entry at
+    /// once. Crawls read sitemaps through a [`SitemapStream`] instead.
     pub fn new(sitemap_content: &str) -> Result<Sitemap, Box<dyn Error>> {
-        let urlset = parse_sitemap(sitemap_content)?;
+        if is_sitemap_index(sitemap_content) {
+            let sitemaps = SitemapReader::index_entries(sitemap_content)
+                .map(|entry| entry.map(|entry| entry.loc))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(Sitemap { urlset: UrlSet { urls: Vec::new() }, sitemaps });
+        }
+
+        let urls = SitemapReader::from_content(sitemap_content).collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Sitemap { urlset: UrlSet { urls }, sitemaps: Vec::new() })
+    }
+}
+
+/// Whether the document's root element is a `<sitemapindex>`.
+pub fn is_sitemap_index(sitemap_content: &str) -> bool {
+    root_is_index(sitemap_content.as_bytes())
+}
+
+fn root_is_index(source: impl BufRead) -> bool {
+    let mut reader = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(start)) | Ok(Event::Empty(start)) => return start.local_name().as_ref() == b"sitemapindex",
+            Ok(Event::Eof) | Err(_) => return false,
+            _ => {}
+        }
+    }
+}
+
+/// How far a sitemap index is followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SitemapLimits {
+    /// Levels of nested indexes followed; 0 ignores index files.
+    pub max_depth: usize,
+    /// URLs taken across all sitemaps; no further sitemaps are fetched
+    /// once reached.
+    pub max_urls: Option<usize>,
+}
+
+impl Default for SitemapLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_SITEMAP_DEPTH,
+            max_urls: None,
+        }
+    }
+}
+
+/// The `<url>` entries behind a sitemap URL, read one at a time: the
+/// sitemap itself, or every sitemap reached from a sitemap index, depth
+/// first. Each document is downloaded to a temporary file and parsed from
+/// there as entries are asked for, so only the entry at hand is in memory.
+pub struct SitemapStream<'a> {
+    client: &'a HTTPClient,
+    limits: SitemapLimits,
+    seen: HashSet<String>,
+    /// Sitemaps still to fetch with their depth, the next one last.
+    pending: Vec<(String, usize)>,
+    /// The URL set being read, dropped before its file.
+    current: Option<(SitemapReader<BufReader<File>>, SpooledSitemap)>,
+    urls: usize,
+    /// Sitemap requests made, index files included.
+    pub requests: usize,
+    /// Child sitemaps left out, with the reason.
+    pub skipped: Vec<(String, String)>,
+    /// Whether `max_urls` cut the URLs short.
+    pub capped: bool,
+    /// Bytes of sitemap XML downloaded, index files aside.
+    pub bytes: u64,
+}
+
+impl<'a> SitemapStream<'a> {
+    /// Fetches the sitemap at `url`, which has to work. Child sitemaps of an
+    /// index are fetched as the entries get to them; those that can't be
+    /// fetched or parsed are skipped.
+    pub async fn open(client: &'a HTTPClient, url: &str, limits: &SitemapLimits) -> Result<Self, Box<dyn Error>> {
+        let url = url.trim().to_string();
+        let mut stream = Self {
+            client,
+            limits: *limits,
+            seen: HashSet::from([url.clone()]),
+            pending: Vec::new(),
+            current: None,
+            urls: 0,
+            requests: 0,
+            skipped: Vec::new(),
+            capped: false,
+            bytes: 0,
+        };
+        stream.read_document(url, 0).await?;
+        Ok(stream)
+    }
+
+    /// The next `<url>` entry, `None` once every sitemap was read or the URL
+    /// cap is reached.
+    pub async fn next(&mut self) -> Result<Option<SitemapUrl>, Box<dyn Error>> {
+        loop {
+            let at_cap = self.limits.max_urls.is_some_and(|max| self.urls >= max);
+            if let Some((reader, _)) = self.current.as_mut() {
+                match reader.next() {
+                    Some(Ok(_)) if at_cap => {
+                        self.cap();
+                        return Ok(None);
+                    }
+                    Some(Ok(entry)) => {
+                        self.urls += 1;
+                        return Ok(Some(entry));
+                    }
+                    Some(Err(err)) => return Err(err),
+                    None => self.current = None,
+                }
+                continue;
+            }
+
+            let Some((url, depth)) = self.pending.pop() else { return Ok(None) };
+            if at_cap {
+                self.cap();
+                return Ok(None);
+            }
+            if let Err(err) = self.read_document(url.clone(), depth).await {
+                self.skipped.push((url, err.to_string()));
+            }
+        }
+    }
+
+    /// Up to `size` next entries, none once the sitemaps are read.
+    pub async fn next_batch(&mut self, size: usize) -> Result<Vec<SitemapUrl>, Box<dyn Error>> {
+        let mut batch = Vec::new();
+        while batch.len() < size {
+            match self.next().await? {
+                Some(entry) => batch.push(entry),
+                None => break,
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Stops reading with entries or sitemaps left over.
+    fn cap(&mut self) {
+        self.capped = true;
+        self.current = None;
+        self.pending.clear();
+    }
+
+    /// Downloads a sitemap: a URL set becomes the one read next, the
+    /// children of an index are queued.
+    async fn read_document(&mut self, url: String, depth: usize) -> Result<(), Box<dyn Error>> {
+        self.requests += 1;
+        let spooled = SpooledSitemap::download(self.client, &url).await?;
+
+        if !root_is_index(spooled.reader()?) {
+            self.bytes += spooled.bytes;
+            self.current = Some((SitemapReader::new(spooled.reader()?), spooled));
+            return Ok(());
+        }
+
+        if depth >= self.limits.max_depth {
+            let reason = format!("sitemap index nested more than {} level(s) deep", self.limits.max_depth);
+            self.skipped.push((url, reason));
+            return Ok(());
+        }
+        let children = SitemapReader::index(spooled.reader()?).collect::<Result<Vec<_>, _>>()?;
+        for child in children.into_iter().rev() {
+            if self.seen.insert(child.loc.clone()) {
+                self.pending.push((child.loc, depth + 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sitemaps spooled by this process, to name their files.
+static SPOOLED: AtomicUsize = AtomicUsize::new(0);
+
+/// Taken names skipped before spooling gives up.
+const MAX_SPOOL_ATTEMPTS: usize = 100;
+
+/// A downloaded sitemap in a temporary file, removed on drop.
+struct SpooledSitemap {
+    path: PathBuf,
+    bytes: u64,
+}
+
+impl SpooledSitemap {
+    async fn download(client: &HTTPClient, url: &str) -> Result<Self, Box<dyn Error>> {
+        let (mut spooled, file) = Self::create()?;
+        let mut file = BufWriter::new(file);
+        spooled.bytes = client.get_sitemap(url, &mut file).await?;
+        file.flush()?;
+        Ok(spooled)
+    }
+
+    /// A new file in the temporary directory. The names are predictable, so
+    /// a path that already exists, e.g. a symlink planted by another user,
+    /// is never opened; the next name is tried instead.
+    fn create() -> std::io::Result<(Self, File)> {
+        let mut attempts = 0;
+        loop {
+            let name = format!("palimp-sitemap-{}-{}.xml", std::process::id(), SPOOLED.fetch_add(1, Ordering::Relaxed));
+            let path = std::env::temp_dir().join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((Self { path, bytes: 0 }, file)),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists && attempts < MAX_SPOOL_ATTEMPTS => attempts += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn reader(&self) -> std::io::Result<BufReader<File>> {
+        File::open(&self.path).map(BufReader::new)
+    }
+}
+
+impl Drop for SpooledSitemap {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
 
-        Ok(Sitemap { urlset: urlset })
+/// Streams the `<url>` entries of a sitemap one at a time. Elements are
+/// matched by local name, so namespace prefixes (`image:loc`) don't matter.
+pub struct SitemapReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    finished: bool,
+    /// `url`, or `sitemap` for the entries of a sitemap index.
+    entry_element: &'static [u8],
+}
+
+impl<'a> SitemapReader<&'a [u8]> {
+    pub fn from_content(sitemap_content: &'a str) -> Self {
+        Self::new(sitemap_content.as_bytes())
+    }
+
+    /// Streams the `<sitemap>` entries of a sitemap index, see
+    /// [`SitemapReader::index`].
+    pub fn index_entries(sitemap_content: &'a str) -> Self {
+        Self::index(sitemap_content.as_bytes())
+    }
+}
+
+impl<R: BufRead> SitemapReader<R> {
+    pub fn new(source: R) -> Self {
+        Self {
+            reader: Reader::from_reader(source),
+            buf: Vec::new(),
+            finished: false,
+            entry_element: b"url",
+        }
+    }
+
+    /// Streams the `<sitemap>` entries of a sitemap index instead, each
+    /// `loc` being a child sitemap.
+    pub fn index(source: R) -> Self {
+        Self {
+            entry_element: b"sitemap",
+            ..Self::new(source)
+        }
+    }
+
+    fn next_url(&mut self) -> Result<Option<SitemapUrl>, Box<dyn Error>> {
+        let mut entry: Option<SitemapUrl> = None;
+        let mut image: Option<SitemapImageEntry> = None;
+        let mut text: Option<String> = None;
+
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(start) => match start.local_name().as_ref() {
+                    name if name == self.entry_element => entry = Some(SitemapUrl::default()),
+                    b"image" if entry.is_some() => image = Some(SitemapImageEntry { loc: String::new() }),
+                    b"loc" if entry.is_some() => text = Some(String::new()),
+                    b"lastmod" | b"changefreq" | b"priority" if entry.is_some() && image.is_none() => {
+                        text = Some(String::new())
+                    }
+                    _ => {}
+                },
+                Event::Text(content) => {
+                    if let Some(text) = text.as_mut() {
+                        text.push_str(&content.xml_content()?);
+                    }
+                }
+                Event::CData(content) => {
+                    if let Some(text) = text.as_mut() {
+                        text.push_str(&content.decode()?);
+                    }
+                }
+                Event::GeneralRef(reference) => {
+                    if let Some(text) = text.as_mut() {
+                        if let Some(ch) = reference.resolve_char_ref()? {
+                            text.push(ch);
+                        } else {
+                            let name = reference.decode()?;
+                            let resolved = resolve_predefined_entity(&name)
+                                .ok_or_else(|| format!("Unknown entity &{}; in sitemap", name))?;
+                            text.push_str(resolved);
+                        }
+                    }
+                }
+                Event::End(end) => match end.local_name().as_ref() {
+                    b"loc" => {
+                        let loc = text.take().unwrap_or_default().trim().to_string();
+                        match (image.as_mut(), entry.as_mut()) {
+                            (Some(image), _) => image.loc = loc,
+                            (None, Some(entry)) => entry.loc = loc,
+                            _ => {}
+                        }
+                    }
+                    b"lastmod" => {
+                        let lastmod = text.take().unwrap_or_default().trim().to_string();
+                        if let Some(entry) = entry.as_mut()
+                            && !lastmod.is_empty()
+                        {
+                            entry.lastmod = Some(lastmod);
+                        }
+                    }
+                    b"changefreq" => {
+                        let changefreq = text.take().unwrap_or_default().trim().to_lowercase();
+                        if let Some(entry) = entry.as_mut()
+                            && CHANGE_FREQUENCIES.contains(&changefreq.as_str())
+                        {
+                            entry.changefreq = Some(changefreq);
+                        }
+                    }
+                    b"priority" => {
+                        let priority = text.take().unwrap_or_default().trim().parse::<f64>().ok();
+                        if let Some(entry) = entry.as_mut() {
+                            entry.priority = priority.filter(|priority| (0.0..=1.0).contains(priority));
+                        }
+                    }
+                    b"image" => {
+                        if let (Some(image), Some(entry)) = (image.take(), entry.as_mut())
+                            && !image.loc.is_empty()
+                        {
+                            entry.images.push(image);
+                        }
+                    }
+                    name if name == self.entry_element => {
+                        if let Some(entry) = entry.take()
+                            && !entry.loc.is_empty()
+                        {
+                            return Ok(Some(entry));
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for SitemapReader<R> {
+    type Item = Result<SitemapUrl, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_url() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
     }
 }
 
@@ -53,4 +451,96 @@ mod tests {
         assert_eq!(sitemap.urlset.urls[0].loc, "https://example.com/");
         assert_eq!(sitemap.urlset.urls[1].loc, "https://example.com/about");
     }
+
+    #[test]
+    fn test_parse_image_sitemap() {
+        let xml = r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+                    xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+                <url>
+                    <loc>https://example.com/</loc>
+                    <image:image>
+                        <image:loc>https://example.com/hero.jpg</image:loc>
+                    </image:image>
+                    <image:image>
+                        <image:loc>https://example.com/logo.png</image:loc>
+                    </image:image>
+                </url>
+                <url>
+                    <loc>https://example.com/about</loc>
+                </url>
+            </urlset>
+        "#;
+
+        let sitemap = Sitemap::new(xml).expect("Failed to parse sitemap");
+        assert_eq!(sitemap.urlset.urls[0].images.len(), 2);
+        assert_eq!(sitemap.urlset.urls[0].images[1].loc, "https://example.com/logo.png");
+        assert!(sitemap.urlset.urls[1].images.is_empty());
+    }
+
+    #[test]
+    fn test_parse_sitemap_index() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://example.com/sitemap-pages.xml</loc><lastmod>2024-01-01</lastmod></sitemap>
+                <sitemap><loc>https://example.com/sitemap-blog.xml</loc></sitemap>
+            </sitemapindex>"#;
+
+        assert!(is_sitemap_index(xml));
+        assert!(!is_sitemap_index("<urlset><url><loc>https://example.com/</loc></url></urlset>"));
+        let sitemap = Sitemap::new(xml).unwrap();
+        assert!(sitemap.urlset.urls.is_empty());
+        assert_eq!(sitemap.sitemaps, ["https://example.com/sitemap-pages.xml", "https://example.com/sitemap-blog.xml"]);
+    }
+
+    #[test]
+    fn test_parse_freshness_hints() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <url><loc>https://example.com/</loc><lastmod> 2024-05-01T10:00:00+00:00 </lastmod>
+                 <changefreq>Daily</changefreq><priority>0.8</priority></url>
+            <url><loc>https://example.com/old</loc><changefreq>sometimes</changefreq><priority>2</priority></url>
+        </urlset>"#;
+
+        let urls = Sitemap::new(xml).unwrap().urlset.urls;
+        assert_eq!(urls[0].lastmod.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+        assert_eq!(urls[0].changefreq.as_deref(), Some("daily"));
+        assert_eq!(urls[0].priority, Some(0.8));
+        assert_eq!((urls[1].lastmod.as_deref(), urls[1].changefreq.as_deref(), urls[1].priority), (None, None, None));
+    }
+
+    #[test]
+    fn test_spool_skips_taken_paths() {
+        // Whatever sits at the next name is left alone
+        let next = SPOOLED.load(Ordering::Relaxed);
+        let taken = std::env::temp_dir().join(format!("palimp-sitemap-{}-{}.xml", std::process::id(), next));
+        std::fs::write(&taken, "planted").unwrap();
+
+        let (spooled, _) = SpooledSitemap::create().unwrap();
+        assert_ne!(spooled.path, taken);
+        drop(spooled);
+        assert_eq!(std::fs::read_to_string(&taken).unwrap(), "planted");
+        std::fs::remove_file(&taken).unwrap();
+    }
+
+    #[test]
+    fn test_streams_urls() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <url><loc> https://example.com/?a=1&amp;b=2 </loc><lastmod>2024-01-01</lastmod></url>
+            <url><loc><![CDATA[https://example.com/cdata]]></loc></url>
+            <url><lastmod>2024-01-01</lastmod></url>
+            <url><loc>https://example.com/last</loc></url>
+        </urlset>"#;
+
+        let mut reader = SitemapReader::from_content(xml);
+        assert_eq!(reader.next().unwrap().unwrap().loc, "https://example.com/?a=1&b=2");
+        assert_eq!(reader.next().unwrap().unwrap().loc, "https://example.com/cdata");
+        assert_eq!(reader.next().unwrap().unwrap().loc, "https://example.com/last");
+        assert!(reader.next().is_none());
+
+        let mut broken = SitemapReader::from_content("<urlset><url><loc>https://a.com/</loc></url><url></urlset>");
+        assert!(broken.next().unwrap().is_ok());
+        assert!(broken.next().unwrap().is_err());
+        assert!(broken.next().is_none());
+    }
 }
@@ -0,0 +1,218 @@
+use crate::alert::Alert;
+use crate::database::Database;
+use crate::sitemap::SitemapUrl;
+use rusqlite::types::Type;
+use rusqlite::{params, OptionalExtension};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapChangeKind {
+    Added,
+    Removed,
+}
+
+impl SitemapChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SitemapChangeKind::Added => "added",
+            SitemapChangeKind::Removed => "removed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "added" => Some(SitemapChangeKind::Added),
+            "removed" => Some(SitemapChangeKind::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// A URL that entered or left a site's sitemap between two crawls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapChange {
+    pub crawl_id: i64,
+    /// The crawl whose sitemap this one is compared with.
+    pub previous_crawl_id: i64,
+    pub url: String,
+    pub kind: SitemapChangeKind,
+}
+
+impl SitemapChange {
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT crawl_id, previous_crawl_id, url, kind FROM sitemap_changes
+             WHERE crawl_id = ?1 ORDER BY kind DESC, url",
+        )?;
+
+        let change_iter = stmt.query_map(params![crawl_id], |row| {
+            let kind: String = row.get(3)?;
+            Ok(SitemapChange {
+                crawl_id: row.get(0)?,
+                previous_crawl_id: row.get(1)?,
+                url: row.get(2)?,
+                kind: SitemapChangeKind::from_name(&kind).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(3, Type::Text, format!("unknown sitemap change '{}'", kind).into())
+                })?,
+            })
+        })?;
+
+        let mut changes = Vec::new();
+        for change in change_iter {
+            changes.push(change?);
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Keeps the URLs a crawl's sitemap listed, to compare the next crawl with,
+/// and their freshness hints, which the pages take over once fetched.
+pub fn record_sitemap_url(crawl_id: i64, entry: &SitemapUrl, database: &Database) -> Result<(), Box<dyn Error>> {
+    database.conn.execute(
+        "INSERT OR IGNORE INTO sitemap_urls (crawl_id, url, lastmod, changefreq, priority) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![crawl_id, entry.loc, entry.lastmod, entry.changefreq, entry.priority],
+    )?;
+    Ok(())
+}
+
+fn sitemap_urls(crawl_id: i64, database: &Database) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare("SELECT url FROM sitemap_urls WHERE crawl_id = ?1")?;
+    let urls = stmt
+        .query_map(params![crawl_id], |row| row.get(0))?
+        .collect::<Result<BTreeSet<String>, _>>()?;
+    Ok(urls)
+}
+
+/// Compares the sitemap of a crawl with the one of the site's previous
+/// completed crawl, stores the URLs added and removed and returns an alert
+/// for each direction. Crawls of a URL list have no sitemap and are skipped.
+pub fn evaluate(site_id: i64, crawl_id: i64, database: &mut Database) -> Result<Vec<Alert>, Box<dyn Error>> {
+    let current = sitemap_urls(crawl_id, database)?;
+    if current.is_empty() {
+        return Ok(Vec::new());
+    }
+    let previous_id: Option<i64> = database
+        .conn
+        .query_row(
+            "SELECT c.id FROM crawls c
+             WHERE c.site_id = ?1 AND c.id < ?2 AND c.status = 'completed' AND c.deleted_at IS NULL
+               AND EXISTS (SELECT 1 FROM sitemap_urls s WHERE s.crawl_id = c.id)
+             ORDER BY c.id DESC LIMIT 1",
+            params![site_id, crawl_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(previous_id) = previous_id else {
+        return Ok(Vec::new());
+    };
+    let previous = sitemap_urls(previous_id, database)?;
+
+    database.conn.execute("DELETE FROM sitemap_changes WHERE crawl_id = ?1", params![crawl_id])?;
+    let mut alerts = Vec::new();
+    for (kind, urls) in [
+        (SitemapChangeKind::Removed, previous.difference(&current).collect::<Vec<_>>()),
+        (SitemapChangeKind::Added, current.difference(&previous).collect::<Vec<_>>()),
+    ] {
+        for url in &urls {
+            database.conn.execute(
+                "INSERT INTO sitemap_changes (crawl_id, previous_crawl_id, url, kind) VALUES (?1, ?2, ?3, ?4)",
+                params![crawl_id, previous_id, url, kind.name()],
+            )?;
+        }
+
+        let Some(first) = urls.first() else { continue };
+        let (section, in_section) = largest_section(&urls);
+        let direction = match kind {
+            SitemapChangeKind::Added => "added to",
+            SitemapChangeKind::Removed => "removed from",
+        };
+        let message = format!(
+            "{} URL(s) {} the sitemap since crawl {}, {} of them under {}",
+            urls.len(),
+            direction,
+            previous_id,
+            in_section,
+            section
+        );
+        let mut alert = Alert::for_crawl(crawl_id, first, &message);
+        alert.sync(database)?;
+        alerts.push(alert);
+    }
+
+    Ok(alerts)
+}
+
+/// The first path segment most of the URLs share, e.g. `/blog/`, and how
+/// many of them are under it. A whole section going missing is the usual
+/// sign of an accidental deindexing.
+fn largest_section(urls: &[&String]) -> (String, usize) {
+    let mut sections: BTreeMap<String, usize> = BTreeMap::new();
+    for url in urls {
+        let section = match reqwest::Url::parse(url) {
+            Ok(parsed) => match parsed.path_segments().and_then(|mut segments| segments.next()) {
+                // A page at the top level, not a directory
+                Some(first) if !first.is_empty() && parsed.path().len() > first.len() + 1 => format!("/{}/", first),
+                _ => "/".to_string(),
+            },
+            Err(_) => "/".to_string(),
+        };
+        *sections.entry(section).or_default() += 1;
+    }
+    // Ties go to the first section alphabetically
+    sections
+        .into_iter()
+        .fold((String::from("/"), 0), |best, (section, count)| if count > best.1 { (section, count) } else { best })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sitemap_changes_between_crawls() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO sites (id, domain, sitemap_url) VALUES (1, 'a.com', '');
+                 INSERT INTO crawls (id, site_id, status) VALUES (1, 1, 'completed'), (2, 1, 'completed'), (3, 1, 'completed');",
+            )
+            .unwrap();
+        let first = ["https://a.com/", "https://a.com/about", "https://a.com/blog/one", "https://a.com/blog/two"];
+        let second = ["https://a.com/", "https://a.com/about", "https://a.com/new"];
+        for (crawl_id, urls) in [(1, &first[..]), (2, &second[..])] {
+            for url in urls {
+                let entry = SitemapUrl { loc: url.to_string(), ..SitemapUrl::default() };
+                record_sitemap_url(crawl_id, &entry, &db).unwrap();
+            }
+        }
+
+        // The first crawl has nothing to compare with, a URL list crawl no sitemap
+        assert!(evaluate(1, 1, &mut db).unwrap().is_empty());
+        assert!(evaluate(1, 3, &mut db).unwrap().is_empty());
+
+        let alerts = evaluate(1, 2, &mut db).unwrap();
+        let messages: Vec<(&str, &str)> = alerts.iter().map(|alert| (alert.url.as_str(), alert.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                ("https://a.com/blog/one", "2 URL(s) removed from the sitemap since crawl 1, 2 of them under /blog/"),
+                ("https://a.com/new", "1 URL(s) added to the sitemap since crawl 1, 1 of them under /"),
+            ]
+        );
+
+        let changes = SitemapChange::fetch_by_crawl_id(2, &db).unwrap();
+        let urls: Vec<(&str, SitemapChangeKind)> = changes.iter().map(|change| (change.url.as_str(), change.kind)).collect();
+        assert_eq!(
+            urls,
+            [
+                ("https://a.com/blog/one", SitemapChangeKind::Removed),
+                ("https://a.com/blog/two", SitemapChangeKind::Removed),
+                ("https://a.com/new", SitemapChangeKind::Added),
+            ]
+        );
+        assert!(changes.iter().all(|change| change.previous_crawl_id == 1));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+pub struct SitemapImage {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub page_url: String,
+    pub image_url: String,
+}
+
+impl SitemapImage {
+    pub fn new(id: Option<i64>, crawl_id: i64, page_url: &str, image_url: &str) -> Self {
+        Self {
+            id,
+            crawl_id,
+            page_url: page_url.to_string(),
+            image_url: image_url.to_string(),
+        }
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE sitemap_images SET page_url = ?1, image_url = ?2 WHERE id = ?3",
+                    params![self.page_url, self.image_url, existing_id],
+                )?;
+            }
+            None => {
+                database.conn.execute(
+                    "INSERT INTO sitemap_images (crawl_id, page_url, image_url) VALUES (?1, ?2, ?3)",
+                    params![self.crawl_id, self.page_url, self.image_url],
+                )?;
+                self.id = Some(database.conn.last_insert_rowid());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare(
+            "SELECT id, crawl_id, page_url, image_url FROM sitemap_images WHERE crawl_id = ?1",
+        )?;
+
+        let image_iter = stmt.query_map(params![crawl_id], |row| {
+            Ok(SitemapImage {
+                id: Some(row.get(0)?),
+                crawl_id: row.get(1)?,
+                page_url: row.get(2)?,
+                image_url: row.get(3)?,
+            })
+        })?;
+
+        let mut images = Vec::new();
+        for image in image_iter {
+            images.push(image?);
+        }
+
+        Ok(images)
+    }
+}
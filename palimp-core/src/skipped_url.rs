@@ -0,0 +1,54 @@
+use crate::database::Database;
+use rusqlite::params;
+use std::error::Error;
+
+/// A URL a crawl left out on purpose, e.g. because the site's robots.txt
+/// disallows it. Unlike a [`CrawlError`](crate::crawl_error::CrawlError) it
+/// was never requested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedUrl {
+    pub id: Option<i64>,
+    pub crawl_id: i64,
+    pub url: String,
+    pub reason: String,
+}
+
+impl SkippedUrl {
+    pub fn new(id: Option<i64>, crawl_id: i64, url: &str, reason: &str) -> Self {
+        Self {
+            id,
+            crawl_id,
+            url: url.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Records the URL unless the crawl skipped it before.
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let inserted = database.conn.execute(
+            "INSERT OR IGNORE INTO skipped_urls (crawl_id, url, reason) VALUES (?1, ?2, ?3)",
+            params![self.crawl_id, self.url, self.reason],
+        )?;
+        if inserted > 0 {
+            self.id = Some(database.conn.last_insert_rowid());
+        }
+        Ok(())
+    }
+
+    pub fn fetch_by_crawl_id(crawl_id: i64, database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database
+            .conn
+            .prepare("SELECT id, crawl_id, url, reason FROM skipped_urls WHERE crawl_id = ?1 ORDER BY id")?;
+        let skipped = stmt
+            .query_map(params![crawl_id], |row| {
+                Ok(SkippedUrl {
+                    id: Some(row.get(0)?),
+                    crawl_id: row.get(1)?,
+                    url: row.get(2)?,
+                    reason: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(skipped)
+    }
+}
@@ -0,0 +1,151 @@
+use crate::database::Database;
+use rusqlite::types::Value;
+use std::error::Error;
+use std::io::Write;
+
+/// Statements [`run`] accepts, by their first keyword.
+const READ_KEYWORDS: [&str; 3] = ["SELECT", "WITH", "VALUES"];
+
+/// Rows returned by a raw SQL query, with the column names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl SqlRows {
+    /// Writes the rows as CSV with a header and returns the number of rows
+    /// written.
+    pub fn write_csv(&self, delimiter: u8, writer: impl Write) -> Result<usize, Box<dyn Error>> {
+        let mut wtr = csv::WriterBuilder::new().delimiter(delimiter).from_writer(writer);
+        wtr.write_record(&self.columns)?;
+        for row in &self.rows {
+            wtr.write_record(row.iter().map(display))?;
+        }
+        wtr.flush()?;
+        Ok(self.rows.len())
+    }
+}
+
+/// Runs a single read-only statement against the archive, for analyses the
+/// built-in reports don't cover. Anything that could change the database,
+/// or a transaction or ATTACH, is refused before it runs.
+pub fn run(sql: &str, database: &Database) -> Result<SqlRows, Box<dyn Error>> {
+    let first_word = sql.trim_start().split(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or("");
+    if !READ_KEYWORDS.iter().any(|keyword| keyword.eq_ignore_ascii_case(first_word)) {
+        return Err("Only SELECT, WITH or VALUES statements can be run".into());
+    }
+
+    let mut stmt = database.conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err("Only read-only statements can be run".into());
+    }
+
+    let columns: Vec<String> = stmt.column_names().into_iter().map(str::to_string).collect();
+    let column_count = columns.len();
+    let rows = stmt
+        .query_map([], |row| (0..column_count).map(|i| row.get::<_, Value>(i)).collect())?
+        .collect::<Result<Vec<Vec<Value>>, _>>()?;
+
+    Ok(SqlRows { columns, rows })
+}
+
+/// Formats a value for display, blobs by their size.
+pub fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(value) => value.to_string(),
+        Value::Real(value) => value.to_string(),
+        Value::Text(value) => value.clone(),
+        Value::Blob(bytes) => format!("<{} bytes>", bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawl::Crawl;
+    use crate::page::Page;
+    use crate::page_archive::PageArchive;
+    use crate::query::Query;
+    use crate::result_entry::ResultEntry;
+    use crate::site::Site;
+
+    #[test]
+    fn test_read_only_queries() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        Site::new(None, "a.com", "https://a.com/sitemap.xml").sync(&mut db).unwrap();
+
+        let rows = run("SELECT id, domain, NULL AS note FROM sites", &db).unwrap();
+        assert_eq!(rows.columns, vec!["id", "domain", "note"]);
+        assert_eq!(rows.rows, vec![vec![Value::Integer(1), Value::Text("a.com".to_string()), Value::Null]]);
+
+        let mut csv = Vec::new();
+        assert_eq!(rows.write_csv(b';', &mut csv).unwrap(), 1);
+        assert_eq!(String::from_utf8(csv).unwrap(), "id;domain;note\n1;a.com;\n");
+
+        let rows = run("with n(x) as (values (1), (2)) select sum(x) from n", &db).unwrap();
+        assert_eq!(rows.rows, vec![vec![Value::Integer(3)]]);
+
+        for sql in [
+            "DELETE FROM sites",
+            "PRAGMA query_only = OFF",
+            "ATTACH ':memory:' AS other",
+            "WITH x AS (SELECT 1) DELETE FROM sites",
+            "SELECT 1; DELETE FROM sites",
+        ] {
+            assert!(run(sql, &db).is_err(), "{}", sql);
+        }
+        assert_eq!(run("SELECT COUNT(*) FROM sites", &db).unwrap().rows, vec![vec![Value::Integer(1)]]);
+    }
+
+    #[test]
+    fn test_reporting_views() {
+        let mut db = Database::new(":memory:").unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let site_id = site.id.unwrap();
+        let mut crawl_ids = Vec::new();
+        for html in ["<h1>Old</h1>", "<h1>New</h1><h1>Two</h1>"] {
+            let mut crawl = Crawl::new(None, site_id);
+            crawl.sync(&mut db).unwrap();
+            let crawl_id = crawl.id.unwrap();
+            crawl_ids.push(crawl_id);
+            Page::new("https://a.com/", "https://a.com/", html, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+        }
+        let mut query = Query::new(None, crawl_ids[1], "h1");
+        query.sync(&mut db).unwrap();
+        let page_id = PageArchive::fetch_by_crawl_id(crawl_ids[1], &db).unwrap()[0].id;
+        ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 2).sync(&mut db).unwrap();
+
+        let rows = run("SELECT site_id, domain, crawl_id, pages, errors FROM latest_crawls", &db).unwrap();
+        assert_eq!(
+            rows.rows,
+            vec![vec![
+                Value::Integer(site_id),
+                Value::Text("a.com".to_string()),
+                Value::Integer(crawl_ids[1]),
+                Value::Integer(1),
+                Value::Integer(0),
+            ]]
+        );
+
+        let rows = run("SELECT crawl_id, selector, matching_pages, matches FROM query_totals", &db).unwrap();
+        assert_eq!(
+            rows.rows,
+            vec![vec![Value::Integer(crawl_ids[1]), Value::Text("h1".to_string()), Value::Integer(1), Value::Integer(2)]]
+        );
+
+        let rows = run("SELECT url, crawl_id, bytes FROM latest_pages", &db).unwrap();
+        assert_eq!(
+            rows.rows,
+            vec![vec![Value::Text("https://a.com/".to_string()), Value::Integer(crawl_ids[1]), Value::Integer(24)]]
+        );
+
+        // Seeding again rebuilds the views
+        db.seed().unwrap();
+        assert_eq!(run("SELECT COUNT(*) FROM latest_pages", &db).unwrap().rows, vec![vec![Value::Integer(1)]]);
+    }
+}
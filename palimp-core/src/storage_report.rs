@@ -0,0 +1,127 @@
+use crate::blob::content_hash;
+use crate::page::encode_headers;
+use crate::page_archive::PageArchive;
+use std::collections::HashSet;
+
+/// One archived page and what it takes to store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSize {
+    pub url: String,
+    pub raw_bytes: u64,
+}
+
+/// How much space a crawl's pages take. Bodies are stored once per
+/// distinct content, so a page repeating an earlier body only costs its
+/// headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageReport {
+    pub crawl_id: i64,
+    pub pages: usize,
+    /// HTML as downloaded.
+    pub raw_bytes: u64,
+    /// Headers and the bodies this crawl added to the archive.
+    pub stored_bytes: u64,
+    /// Pages whose HTML is identical to an earlier page of the crawl or to
+    /// a page of the site's previous crawl.
+    pub duplicate_pages: usize,
+    pub duplicate_bytes: u64,
+    /// The biggest pages, largest first.
+    pub largest_pages: Vec<PageSize>,
+}
+
+impl StorageReport {
+    /// Builds the report for `pages`, finding duplicates also among the
+    /// `previous` crawl's pages and listing the `top` largest pages.
+    pub fn build(crawl_id: i64, pages: &[PageArchive], previous: &[PageArchive], top: usize) -> Self {
+        let mut seen: HashSet<String> = previous.iter().map(|page| content_hash(&page.html_content)).collect();
+        let mut report = StorageReport {
+            crawl_id,
+            pages: pages.len(),
+            raw_bytes: 0,
+            stored_bytes: 0,
+            duplicate_pages: 0,
+            duplicate_bytes: 0,
+            largest_pages: Vec::new(),
+        };
+
+        for page in pages {
+            let raw_bytes = page.html_content.len() as u64;
+            report.raw_bytes += raw_bytes;
+            report.stored_bytes += encode_headers(&page.headers).len() as u64;
+            if seen.insert(content_hash(&page.html_content)) {
+                report.stored_bytes += raw_bytes;
+            } else {
+                report.duplicate_pages += 1;
+                report.duplicate_bytes += raw_bytes;
+            }
+        }
+
+        let mut sizes: Vec<PageSize> = pages.iter()
+            .map(|page| PageSize { url: page.url.clone(), raw_bytes: page.html_content.len() as u64 })
+            .collect();
+        sizes.sort_by(|a, b| b.raw_bytes.cmp(&a.raw_bytes).then_with(|| a.url.cmp(&b.url)));
+        sizes.truncate(top);
+        report.largest_pages = sizes;
+
+        report
+    }
+
+    /// Share of the raw size saved by storing shared bodies once, negative
+    /// when the headers cost more than that saves.
+    pub fn savings_ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - self.stored_bytes as f64 / self.raw_bytes as f64
+    }
+
+    /// Share of the raw size taken by duplicate bodies.
+    pub fn duplicate_ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 0.0;
+        }
+        self.duplicate_bytes as f64 / self.raw_bytes as f64
+    }
+
+    pub fn average_page_bytes(&self) -> u64 {
+        self.raw_bytes.checked_div(self.pages as u64).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(url: &str, html: &str) -> PageArchive {
+        let mut page = PageArchive::new(0, 2, url, url, html);
+        page.headers = vec![("etag".to_string(), "v1".to_string())];
+        page
+    }
+
+    #[test]
+    fn test_storage_report() {
+        let previous = vec![page("https://a.com/", "<p>home</p>")];
+        let pages = vec![
+            page("https://a.com/", "<p>home</p>"),
+            page("https://a.com/big", &"x".repeat(100)),
+            page("https://a.com/copy", &"x".repeat(100)),
+            page("https://a.com/new", "<p>new</p>"),
+        ];
+
+        let report = StorageReport::build(2, &pages, &previous, 2);
+        assert_eq!(report.pages, 4);
+        assert_eq!(report.raw_bytes, 221);
+        assert_eq!(report.stored_bytes, 110 + 4 * encode_headers(&pages[0].headers).len() as u64);
+        assert!(report.savings_ratio() > 0.0);
+        // Home is unchanged since the previous crawl, copy repeats big
+        assert_eq!(report.duplicate_pages, 2);
+        assert_eq!(report.duplicate_bytes, 111);
+        assert_eq!(
+            report.largest_pages.iter().map(|page| page.url.as_str()).collect::<Vec<_>>(),
+            vec!["https://a.com/big", "https://a.com/copy"]
+        );
+        assert_eq!(report.average_page_bytes(), 55);
+
+        assert_eq!(StorageReport::build(3, &[], &[], 5).savings_ratio(), 0.0);
+    }
+}
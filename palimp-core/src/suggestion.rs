@@ -0,0 +1,198 @@
+use crate::database::Database;
+use crate::page::{tag_attribute, tag_name, Page};
+use crate::page_archive::PageArchive;
+use crate::preview::{collapse_whitespace, shorten};
+use std::error::Error;
+use tl::{HTMLTag, Node, NodeHandle, Parser};
+
+/// Most selectors suggested for one example.
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Longest element text shown with a suggestion, in characters.
+const TEXT_MAX_CHARS: usize = 80;
+
+/// Attributes that name what an element holds, tried before the others.
+const PREFERRED_ATTRIBUTES: [&str; 3] = ["itemprop", "name", "role"];
+
+/// Elements whose text is never what a query is after.
+const SKIPPED_ELEMENTS: [&str; 4] = ["head", "script", "style", "noscript"];
+
+/// A selector matching exactly the element with the example text on the
+/// sample page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorSuggestion {
+    pub selector: String,
+    /// Text of the matched element, shortened.
+    pub text: String,
+    /// Other pages of the crawl the selector matches at least once, a hint
+    /// of whether it picks out the same thing across the site.
+    pub other_pages: usize,
+}
+
+/// Selectors that match only the innermost element containing `example`,
+/// compared case-insensitively, most robust first: IDs and microdata, then
+/// other attributes, then classes. Each comes with the text of the element
+/// it matches.
+///
+/// The selector engine doesn't match combinators like `.product .price`, so
+/// an element is only told apart by its own tag, attributes and classes.
+pub fn candidates(page: &Page, example: &str) -> Vec<(String, String)> {
+    let needle = collapse_whitespace(example).to_lowercase();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let parser = page.dom.parser();
+    let mut targets = Vec::new();
+    find_targets(page.dom.children(), parser, &needle, &mut targets);
+
+    let mut suggestions: Vec<(String, String)> = Vec::new();
+    for target in targets {
+        let Some(Node::Tag(tag)) = target.get(parser) else { continue };
+        let text = shorten(collapse_whitespace(&tag.inner_text(parser)), TEXT_MAX_CHARS);
+        for selector in simple_selectors(tag) {
+            if !suggestions.iter().any(|(known, _)| known == &selector) && matches_only(page, &selector, target) {
+                suggestions.push((selector, text.clone()));
+            }
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// Suggests selectors for the text of an example on an archived page and
+/// counts the other pages of its crawl each one matches.
+pub fn suggest(crawl_id: i64, url: &str, example: &str, database: &Database) -> Result<Vec<SelectorSuggestion>, Box<dyn Error>> {
+    let sample = PageArchive::fetch_by_crawl_and_url(crawl_id, url, database)?;
+    let candidates = candidates(&sample.to_page()?, example);
+    if candidates.is_empty() {
+        return Err(format!("No selector picks out just the element holding '{}' on {}", example, url).into());
+    }
+
+    let mut other_pages = vec![0; candidates.len()];
+    for archive in PageArchive::fetch_by_crawl_id(crawl_id, database)? {
+        if archive.id == sample.id {
+            continue;
+        }
+        let Ok(page) = archive.to_page() else { continue };
+        for (count, (selector, _)) in other_pages.iter_mut().zip(&candidates) {
+            if page.dom.query_selector(selector).is_some_and(|mut nodes| nodes.next().is_some()) {
+                *count += 1;
+            }
+        }
+    }
+
+    Ok(candidates
+        .into_iter()
+        .zip(other_pages)
+        .map(|((selector, text), other_pages)| SelectorSuggestion { selector, text, other_pages })
+        .collect())
+}
+
+/// Collects the elements whose text contains `needle` while none of their
+/// child elements' does. Returns whether any was found below `siblings`.
+fn find_targets(siblings: &[NodeHandle], parser: &Parser, needle: &str, targets: &mut Vec<NodeHandle>) -> bool {
+    let mut found = false;
+    for handle in siblings {
+        let Some(Node::Tag(tag)) = handle.get(parser) else { continue };
+        if SKIPPED_ELEMENTS.contains(&tag_name(tag).as_str()) {
+            continue;
+        }
+        if !collapse_whitespace(&tag.inner_text(parser)).to_lowercase().contains(needle) {
+            continue;
+        }
+
+        if !find_targets(tag.children().top().as_slice(), parser, needle, targets) {
+            targets.push(*handle);
+        }
+        found = true;
+    }
+    found
+}
+
+/// Selectors for an element on its own. Values the selector engine can't
+/// parse unquoted, e.g. with spaces, are left out.
+fn simple_selectors(tag: &HTMLTag) -> Vec<String> {
+    let name = tag_name(tag);
+    let mut selectors = Vec::new();
+    if let Some(id) = tag.attributes().id().map(|id| id.as_utf8_str().into_owned())
+        && is_identifier(&id)
+    {
+        selectors.push(format!("#{}", id));
+    }
+    // Microdata and form names first, then e.g. `data-testid`
+    let mut attributes: Vec<String> = PREFERRED_ATTRIBUTES.iter().map(|attribute| attribute.to_string()).collect();
+    attributes.extend(
+        tag.attributes()
+            .iter()
+            .map(|(key, _)| key.to_ascii_lowercase())
+            .filter(|key| !PREFERRED_ATTRIBUTES.contains(&key.as_str()) && !["id", "class", "style"].contains(&key.as_str())),
+    );
+    for attribute in attributes {
+        if let Some(value) = tag_attribute(tag, &attribute)
+            && is_identifier(&attribute)
+            && is_identifier(&value)
+        {
+            selectors.push(format!("{}[{}={}]", name, attribute, value));
+        }
+    }
+
+    let classes: Vec<String> = tag
+        .attributes()
+        .class_iter()
+        .into_iter()
+        .flatten()
+        .filter(|class| is_identifier(class))
+        .map(str::to_string)
+        .collect();
+    for class in &classes {
+        selectors.push(format!("{}.{}", name, class));
+    }
+    if classes.len() > 1 {
+        selectors.push(format!("{}.{}", name, classes.join(".")));
+    }
+    selectors.push(name);
+    selectors
+}
+
+fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn matches_only(page: &Page, selector: &str, target: NodeHandle) -> bool {
+    let Some(nodes) = page.dom.query_selector(selector) else {
+        return false;
+    };
+    let nodes: Vec<NodeHandle> = nodes.take(2).collect();
+    nodes.len() == 1 && nodes[0].get_inner() == target.get_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_match_only_the_example() {
+        let page = Page::new(
+            "https://a.com/p",
+            "https://a.com/p",
+            r#"<html><head><title>Blue Widget</title></head><body>
+                 <div class="product"><h1 class="title main">Blue Widget</h1>
+                   <span class="price sale" data-testid="sale-price" itemprop="price">19.99</span></div>
+                 <div class="related"><span class="price">5.00</span></div>
+               </body></html>"#,
+            None,
+        )
+        .unwrap();
+
+        let selectors: Vec<String> = candidates(&page, " 19.99 ").into_iter().map(|(selector, _)| selector).collect();
+        assert_eq!(
+            selectors,
+            ["span[itemprop=price]", "span[data-testid=sale-price]", "span.sale", "span.price.sale"]
+        );
+
+        let headings = candidates(&page, "blue widget");
+        assert_eq!(headings[0], ("h1.title".to_string(), "Blue Widget".to_string()));
+        assert!(headings.iter().all(|(selector, _)| selector != "title"));
+        assert!(candidates(&page, "Not on the page").is_empty());
+    }
+}
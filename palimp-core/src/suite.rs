@@ -0,0 +1,221 @@
+use crate::audit::Audit;
+use crate::database::Database;
+use crate::page::Page;
+use rusqlite::params;
+use std::error::Error;
+
+/// A single check of a suite: a built-in audit, or a selector that every
+/// page must (or must not) match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuiteCheck {
+    Audit(Audit),
+    Require(String),
+    Forbid(String),
+}
+
+impl SuiteCheck {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SuiteCheck::Audit(_) => "audit",
+            SuiteCheck::Require(_) => "require",
+            SuiteCheck::Forbid(_) => "forbid",
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            SuiteCheck::Audit(audit) => audit.name(),
+            SuiteCheck::Require(selector) | SuiteCheck::Forbid(selector) => selector,
+        }
+    }
+
+    pub fn from_parts(kind: &str, value: &str) -> Option<SuiteCheck> {
+        match kind {
+            "audit" => Audit::from_name(value).map(SuiteCheck::Audit),
+            "require" => Some(SuiteCheck::Require(value.to_string())),
+            "forbid" => Some(SuiteCheck::Forbid(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// A named group of checks runnable against a crawl in one go.
+pub struct Suite {
+    pub id: Option<i64>,
+    pub name: String,
+    pub checks: Vec<SuiteCheck>,
+}
+
+/// Combined pass/fail outcome of a suite for one page.
+pub struct PageSummary {
+    pub url: String,
+    pub failures: Vec<String>,
+}
+
+impl PageSummary {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Suite {
+    pub fn new(id: Option<i64>, name: &str, checks: Vec<SuiteCheck>) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            checks,
+        }
+    }
+
+    /// Suites shipped with palimp. They are not stored in the database.
+    pub fn built_in() -> Vec<Suite> {
+        let require = |selector: &str| SuiteCheck::Require(selector.to_string());
+        let forbid = |selector: &str| SuiteCheck::Forbid(selector.to_string());
+
+        vec![
+            Suite::new(
+                None,
+                "seo-basic",
+                vec![
+                    SuiteCheck::Audit(Audit::Noindex),
+                    require("title"),
+                    require("meta[name=description]"),
+                    require("h1"),
+                    require("link[rel=canonical]"),
+                ],
+            ),
+            Suite::new(
+                None,
+                "privacy",
+                vec![
+                    SuiteCheck::Audit(Audit::SecurityHeaders),
+                    forbid("script[src*=google-analytics.com]"),
+                    forbid("script[src*=googletagmanager.com]"),
+                    forbid("script[src*=connect.facebook.net]"),
+                ],
+            ),
+            Suite::new(None, "accessibility", vec![SuiteCheck::Audit(Audit::Accessibility)]),
+        ]
+    }
+
+    pub fn sync(&mut self, database: &mut Database) -> Result<(), Box<dyn Error>> {
+        let suite_id = match self.id {
+            Some(existing_id) => {
+                database.conn.execute(
+                    "UPDATE suites SET name = ?1 WHERE id = ?2",
+                    params![self.name, existing_id],
+                )?;
+                existing_id
+            }
+            None => {
+                database.conn.execute("INSERT INTO suites (name) VALUES (?1)", params![self.name])?;
+                let new_id = database.conn.last_insert_rowid();
+                self.id = Some(new_id);
+                new_id
+            }
+        };
+
+        database.conn.execute("DELETE FROM suite_checks WHERE suite_id = ?1", params![suite_id])?;
+        for check in &self.checks {
+            database.conn.execute(
+                "INSERT INTO suite_checks (suite_id, kind, value) VALUES (?1, ?2, ?3)",
+                params![suite_id, check.kind(), check.value()],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn fetch_all(database: &Database) -> Result<Vec<Self>, Box<dyn Error>> {
+        let mut stmt = database.conn.prepare("SELECT id, name FROM suites ORDER BY id")?;
+        let suite_iter = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut check_stmt = database
+            .conn
+            .prepare("SELECT kind, value FROM suite_checks WHERE suite_id = ?1 ORDER BY id")?;
+
+        let mut suites = Vec::new();
+        for suite in suite_iter {
+            let (id, name) = suite?;
+            let check_iter = check_stmt.query_map(params![id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+
+            let mut checks = Vec::new();
+            for check in check_iter {
+                let (kind, value) = check?;
+                if let Some(check) = SuiteCheck::from_parts(&kind, &value) {
+                    checks.push(check);
+                }
+            }
+
+            suites.push(Suite::new(Some(id), &name, checks));
+        }
+
+        Ok(suites)
+    }
+
+    pub fn delete(id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+        database
+            .conn
+            .execute("DELETE FROM suites WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn audits(&self) -> Vec<Audit> {
+        self.checks
+            .iter()
+            .filter_map(|check| match check {
+                SuiteCheck::Audit(audit) => Some(*audit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Evaluates the selector checks of the suite against a page.
+    pub fn check_selectors(&self, page: &Page) -> Vec<String> {
+        let count = |selector: &str| page.dom.query_selector(selector).map(|nodes| nodes.count()).unwrap_or(0);
+
+        self.checks
+            .iter()
+            .filter_map(|check| match check {
+                SuiteCheck::Require(selector) if count(selector) == 0 => {
+                    Some(format!("required selector '{}' not found", selector))
+                }
+                SuiteCheck::Forbid(selector) if count(selector) > 0 => {
+                    Some(format!("forbidden selector '{}' found", selector))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite_selector_checks() {
+        let suite = Suite::new(
+            None,
+            "custom",
+            vec![
+                SuiteCheck::Require("h1".to_string()),
+                SuiteCheck::Require("title".to_string()),
+                SuiteCheck::Forbid(".debug-banner".to_string()),
+            ],
+        );
+
+        let html = r#"<html><body><h1>Hi</h1><div class="debug-banner"></div></body></html>"#;
+        let page = Page::new("http://test.com", "http://test.com", html, None).unwrap();
+
+        assert_eq!(
+            suite.check_selectors(&page),
+            vec![
+                "required selector 'title' not found".to_string(),
+                "forbidden selector '.debug-banner' found".to_string(),
+            ]
+        );
+    }
+}
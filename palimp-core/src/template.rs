@@ -0,0 +1,90 @@
+/// A named, ready-to-run selector for a common extraction.
+pub struct SelectorTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub selector: &'static str,
+}
+
+const TEMPLATES: [SelectorTemplate; 10] = [
+    SelectorTemplate {
+        name: "canonical",
+        description: "Canonical link",
+        selector: "link[rel=canonical]",
+    },
+    SelectorTemplate {
+        name: "hreflang",
+        description: "Alternate language links",
+        selector: "link[hreflang]",
+    },
+    SelectorTemplate {
+        name: "ga-script",
+        description: "Google Analytics / Tag Manager script",
+        selector: "script[src*=googletagmanager.com]",
+    },
+    SelectorTemplate {
+        name: "h1",
+        description: "Top-level headings",
+        selector: "h1",
+    },
+    SelectorTemplate {
+        name: "title",
+        description: "Document title",
+        selector: "title",
+    },
+    SelectorTemplate {
+        name: "meta-description",
+        description: "Meta description",
+        selector: "meta[name=description]",
+    },
+    SelectorTemplate {
+        name: "meta-robots",
+        description: "Robots meta tag",
+        selector: "meta[name=robots]",
+    },
+    SelectorTemplate {
+        name: "structured-data",
+        description: "JSON-LD structured data blocks",
+        selector: "script[type$=json]",
+    },
+    SelectorTemplate {
+        name: "iframes",
+        description: "Embedded iframes",
+        selector: "iframe",
+    },
+    SelectorTemplate {
+        name: "new-tab-links",
+        description: "Links opening in a new tab",
+        selector: "a[target=_blank]",
+    },
+];
+
+pub fn all() -> &'static [SelectorTemplate] {
+    &TEMPLATES
+}
+
+pub fn find(name: &str) -> Option<&'static SelectorTemplate> {
+    TEMPLATES.iter().find(|template| template.name == name)
+}
+
+/// Resolves `@name` references to the template's selector; anything else is
+/// returned unchanged.
+pub fn resolve_selector(input: &str) -> Result<String, String> {
+    match input.strip_prefix('@') {
+        Some(name) => find(name)
+            .map(|template| template.selector.to_string())
+            .ok_or_else(|| format!("Unknown selector template '{}'", name)),
+        None => Ok(input.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_selector() {
+        assert_eq!(resolve_selector("@canonical").unwrap(), "link[rel=canonical]");
+        assert_eq!(resolve_selector("div > h1").unwrap(), "div > h1");
+        assert!(resolve_selector("@nope").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! A fake site served from localhost, for exercising crawls end-to-end
+//! without the network:
+//!
+//! ```ignore
+//! let site = FakeSite::builder()
+//!     .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+//!     .missing("/gone")
+//!     .start()
+//!     .await;
+//! let site_id = site.add_to(&app).await?;
+//! app.new_crawl(site_id, 4, |_| {}).await?;
+//! ```
+//!
+//! Enabled by the `test-utils` feature.
+
+use crate::site::SiteValidation;
+use crate::Application;
+use std::error::Error;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+struct FakePage {
+    path: String,
+    status: u16,
+    html: String,
+}
+
+/// Pages of a [`FakeSite`] before it starts serving.
+#[derive(Default)]
+pub struct FakeSiteBuilder {
+    pages: Vec<FakePage>,
+    urls_per_sitemap: Option<usize>,
+    /// Path, content type and body of files served but not in the sitemap.
+    files: Vec<(String, String, String)>,
+}
+
+impl FakeSiteBuilder {
+    /// Serves `html` at `path` and lists it in the sitemap.
+    pub fn page(self, path: &str, html: &str) -> Self {
+        self.with_status(path, 200, html)
+    }
+
+    /// Lists `path` in the sitemap but answers it with a 404.
+    pub fn missing(self, path: &str) -> Self {
+        self.with_status(path, 404, &fixtures::page("Not Found", "<h1>Not Found</h1>"))
+    }
+
+    /// Serves `html` at `path` with any status, listed in the sitemap.
+    pub fn with_status(mut self, path: &str, status: u16, html: &str) -> Self {
+        self.pages.push(FakePage {
+            path: path.to_string(),
+            status,
+            html: html.to_string(),
+        });
+        self
+    }
+
+    /// Serves `body` at `path` with `content_type`, without listing it in
+    /// the sitemap, e.g. a stylesheet the pages reference.
+    pub fn file(mut self, path: &str, content_type: &str, body: &str) -> Self {
+        self.files.push((path.to_string(), content_type.to_string(), body.to_string()));
+        self
+    }
+
+    /// Serves `/sitemap.xml` as a sitemap index of `/sitemap-1.xml`,
+    /// `/sitemap-2.xml` and so on, each listing up to `urls` pages.
+    pub fn sitemap_index(mut self, urls: usize) -> Self {
+        self.urls_per_sitemap = Some(urls.max(1));
+        self
+    }
+
+    /// Starts serving the pages and `/sitemap.xml` on a free local port.
+    pub async fn start(self) -> FakeSite {
+        let server = MockServer::start().await;
+        let urls: Vec<String> = self.pages.iter()
+            .map(|page| format!("{}{}", server.uri(), page.path))
+            .collect();
+
+        let sitemaps = match self.urls_per_sitemap {
+            Some(per_sitemap) => {
+                let children: Vec<(String, String)> = urls
+                    .chunks(per_sitemap)
+                    .enumerate()
+                    .map(|(i, chunk)| (format!("/sitemap-{}.xml", i + 1), fixtures::sitemap(chunk)))
+                    .collect();
+                let locs: Vec<String> = children.iter().map(|(child, _)| format!("{}{}", server.uri(), child)).collect();
+                let mut sitemaps = vec![("/sitemap.xml".to_string(), fixtures::sitemap_index(&locs))];
+                sitemaps.extend(children);
+                sitemaps
+            }
+            None => vec![("/sitemap.xml".to_string(), fixtures::sitemap(&urls))],
+        };
+        for (sitemap_path, body) in sitemaps {
+            Mock::given(method("GET"))
+                .and(path(sitemap_path.as_str()))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/xml"))
+                .mount(&server)
+                .await;
+        }
+
+        for page in &self.pages {
+            Mock::given(method("GET"))
+                .and(path(page.path.as_str()))
+                .respond_with(ResponseTemplate::new(page.status).set_body_raw(page.html.clone(), "text/html; charset=utf-8"))
+                .mount(&server)
+                .await;
+        }
+
+        for (file_path, content_type, body) in &self.files {
+            Mock::given(method("GET"))
+                .and(path(file_path.as_str()))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body.clone(), content_type.as_str()))
+                .mount(&server)
+                .await;
+        }
+
+        FakeSite { server, urls }
+    }
+}
+
+/// A site with a sitemap and pages, served by a local mock server until
+/// dropped.
+pub struct FakeSite {
+    server: MockServer,
+    urls: Vec<String>,
+}
+
+impl FakeSite {
+    pub fn builder() -> FakeSiteBuilder {
+        FakeSiteBuilder::default()
+    }
+
+    /// The base URL, e.g. `http://127.0.0.1:41234`, usable as a site domain.
+    pub fn base_url(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn sitemap_url(&self) -> String {
+        self.url("/sitemap.xml")
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server.uri(), path)
+    }
+
+    /// The page URLs listed in the sitemap, in the order they were added.
+    pub fn page_urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Registers the site with `app` and returns its id.
+    pub async fn add_to(&self, app: &Application) -> Result<i64, Box<dyn Error>> {
+        // Without fetching the sitemap, so request counts only cover the crawl
+        let site = app.new_site_with_validation(&self.base_url(), &self.sitemap_url(), SiteValidation::Format).await?;
+        site.id.ok_or_else(|| "Fake site was not saved".into())
+    }
+
+    /// Number of requests the site has answered so far, sitemap included.
+    pub async fn request_count(&self) -> usize {
+        self.server.received_requests().await.map_or(0, |requests| requests.len())
+    }
+}
+
+/// Documents to serve from a [`FakeSite`].
+pub mod fixtures {
+    /// A minimal HTML page with `body` as its content.
+    pub fn page(title: &str, body: &str) -> String {
+        format!(
+            "<!DOCTYPE html><html><head><title>{}</title></head><body>{}</body></html>",
+            title, body
+        )
+    }
+
+    /// A product page with a `.price` and an `h1`, handy for selector tests.
+    pub fn product_page(name: &str, price: &str) -> String {
+        page(name, &format!("<h1>{}</h1><span class=\"price\">{}</span>", name, price))
+    }
+
+    /// A sitemap listing `urls`.
+    pub fn sitemap(urls: &[String]) -> String {
+        let entries: String = urls.iter()
+            .map(|url| format!("<url><loc>{}</loc></url>", url))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}</urlset>",
+            entries
+        )
+    }
+
+    /// A sitemap index pointing at the sitemaps at `locs`.
+    pub fn sitemap_index(locs: &[String]) -> String {
+        let entries: String = locs.iter()
+            .map(|loc| format!("<sitemap><loc>{}</loc></sitemap>", loc))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}</sitemapindex>",
+            entries
+        )
+    }
+}
@@ -0,0 +1,115 @@
+use crate::page::Page;
+use crate::page_archive::PageArchive;
+use std::collections::{BTreeSet, HashMap};
+
+/// Resource references inspected for external hosts, with the kind each
+/// selector stands for.
+const RESOURCE_SELECTORS: [(&str, &str, &str); 6] = [
+    ("script[src]", "src", "script"),
+    ("img[src]", "src", "image"),
+    ("iframe[src]", "src", "iframe"),
+    ("link[rel=stylesheet]", "href", "stylesheet"),
+    ("link[as=font]", "href", "font"),
+    ("link[rel=preconnect]", "href", "preconnect"),
+];
+
+/// An external domain referenced by a crawl and how widely it is used.
+pub struct DomainUsage {
+    pub domain: String,
+    pub page_count: usize,
+    pub kinds: Vec<String>,
+}
+
+/// Aggregates every external domain referenced by the archived pages,
+/// sorted by the number of pages referencing it.
+pub fn aggregate(site_domain: &str, pages: &[PageArchive]) -> Vec<DomainUsage> {
+    let mut usage: HashMap<String, (usize, BTreeSet<String>)> = HashMap::new();
+
+    for archive in pages {
+        let page = match archive.to_page() {
+            Ok(page) => page,
+            Err(_) => continue,
+        };
+
+        let mut page_domains: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for (domain, kind) in external_references(&page, site_domain) {
+            page_domains.entry(domain).or_default().insert(kind.to_string());
+        }
+
+        for (domain, kinds) in page_domains {
+            let entry = usage.entry(domain).or_default();
+            entry.0 += 1;
+            entry.1.extend(kinds);
+        }
+    }
+
+    let mut report: Vec<DomainUsage> = usage
+        .into_iter()
+        .map(|(domain, (page_count, kinds))| DomainUsage {
+            domain,
+            page_count,
+            kinds: kinds.into_iter().collect(),
+        })
+        .collect();
+
+    report.sort_by(|a, b| b.page_count.cmp(&a.page_count).then_with(|| a.domain.cmp(&b.domain)));
+    report
+}
+
+fn external_references(page: &Page, site_domain: &str) -> Vec<(String, &'static str)> {
+    let mut references = Vec::new();
+
+    for (selector, attribute, kind) in RESOURCE_SELECTORS {
+        for value in page.attribute_values(selector, attribute) {
+            let host = page
+                .resolve_url(&value)
+                .and_then(|url| reqwest::Url::parse(&url).ok())
+                .and_then(|url| url.host_str().map(|h| h.to_ascii_lowercase()));
+
+            if let Some(host) = host
+                && !is_same_site(&host, site_domain)
+            {
+                references.push((host, kind));
+            }
+        }
+    }
+
+    references
+}
+
+/// Whether `host` belongs to the site, treating subdomains as first-party.
+pub fn is_same_site(host: &str, site_domain: &str) -> bool {
+    let host = host.trim_start_matches("www.");
+    let domain = site_domain.trim().to_ascii_lowercase();
+    let domain = domain.trim_start_matches("www.");
+
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(id: i64, html: &str) -> PageArchive {
+        let url = format!("https://example.com/{}", id);
+        PageArchive::new(id, 1, &url, &url, html)
+    }
+
+    #[test]
+    fn test_aggregates_external_domains_by_page() {
+        let pages = vec![
+            archive(1, r#"<script src="https://cdn.tracker.io/t.js"></script><img src="/logo.png"><img src="https://img.example.com/a.png">"#),
+            archive(2, r#"<script src="https://cdn.tracker.io/t.js"></script><iframe src="https://www.youtube.com/embed/x"></iframe>"#),
+            archive(3, r#"<img src="https://cdn.tracker.io/pixel.gif"><img src="https://cdn.tracker.io/pixel2.gif">"#),
+        ];
+
+        let report = aggregate("www.example.com", &pages);
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].domain, "cdn.tracker.io");
+        assert_eq!(report[0].page_count, 3);
+        assert_eq!(report[0].kinds, vec!["image", "script"]);
+        assert_eq!(report[1].domain, "www.youtube.com");
+        assert_eq!(report[1].page_count, 1);
+    }
+}
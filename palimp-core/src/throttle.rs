@@ -0,0 +1,339 @@
+use crate::http_client::ServerError;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// A response this many times slower than the host's running average counts
+/// as a sign of strain.
+const LATENCY_SPIKE_FACTOR: f64 = 3.0;
+/// Responses faster than this never count as slow, however spiky.
+const LATENCY_SPIKE_FLOOR: Duration = Duration::from_millis(500);
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_EWMA_WEIGHT: f64 = 0.2;
+/// Minimum time between two reductions, so one burst of failures from
+/// requests that were already in flight only halves the limit once.
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// Longest a pacer waits between two requests, so the slowest rate allowed
+/// is one request every 10 minutes.
+pub const MAX_REQUEST_INTERVAL: Duration = Duration::from_secs(600);
+
+struct LimiterState {
+    limit: usize,
+    in_flight: usize,
+    successes: usize,
+    latency_ewma: Option<f64>,
+    last_decrease: Option<Instant>,
+}
+
+/// Concurrency limit for a single host that adapts to how the server copes:
+/// it is halved when errors or latency spike and grows back by one after a
+/// full window of healthy responses (AIMD).
+pub struct AdaptiveLimiter {
+    state: Mutex<LimiterState>,
+    notify: Notify,
+    max_limit: usize,
+}
+
+impl AdaptiveLimiter {
+    pub fn new(max_limit: usize) -> Self {
+        let max_limit = max_limit.max(1);
+        Self {
+            state: Mutex::new(LimiterState {
+                limit: max_limit,
+                in_flight: 0,
+                successes: 0,
+                latency_ewma: None,
+                last_decrease: None,
+            }),
+            notify: Notify::new(),
+            max_limit,
+        }
+    }
+
+    /// Current number of requests allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Waits until a request may be issued under the current limit.
+    pub async fn acquire(self: &Arc<Self>) -> Permit {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return Permit {
+                        limiter: Arc::clone(self),
+                        started: Instant::now(),
+                    };
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    fn record(&self, strained: bool, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let latency_secs = latency.as_secs_f64();
+
+        let slow = match state.latency_ewma {
+            Some(average) => latency > LATENCY_SPIKE_FLOOR && latency_secs > average * LATENCY_SPIKE_FACTOR,
+            None => false,
+        };
+
+        state.latency_ewma = Some(match state.latency_ewma {
+            Some(average) => average + LATENCY_EWMA_WEIGHT * (latency_secs - average),
+            None => latency_secs,
+        });
+
+        if strained || slow {
+            state.successes = 0;
+            let cooling_down = state
+                .last_decrease
+                .is_some_and(|at| at.elapsed() < DECREASE_COOLDOWN);
+            if !cooling_down && state.limit > 1 {
+                state.limit = (state.limit / 2).max(1);
+                state.last_decrease = Some(Instant::now());
+            }
+        } else {
+            state.successes += 1;
+            if state.successes >= state.limit && state.limit < self.max_limit {
+                state.limit += 1;
+                state.successes = 0;
+                self.notify.notify_waiters();
+            }
+        }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        drop(state);
+        self.notify.notify_waiters();
+    }
+}
+
+/// A slot in a host's concurrency limit, returned when dropped.
+pub struct Permit {
+    limiter: Arc<AdaptiveLimiter>,
+    started: Instant,
+}
+
+impl Permit {
+    /// Feeds the outcome of the request made under this permit back into the
+    /// host's limit.
+    pub fn record<T>(&self, result: &Result<T, Box<dyn Error>>) {
+        let strained = match result {
+            Ok(_) => false,
+            Err(err) => is_server_strain(err.as_ref()),
+        };
+        self.limiter.record(strained, self.started.elapsed());
+    }
+}
+
+impl Permit {
+    /// Waits for the pacer's next turn before the request is made. The wait
+    /// doesn't count towards the request's latency.
+    pub async fn pace(&mut self, pacer: &RequestPacer) {
+        pacer.wait().await;
+        self.started = Instant::now();
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// Spaces out the requests of a crawl, however many run at once, so the
+/// server sees at most one request starting per interval.
+pub struct RequestPacer {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RequestPacer {
+    /// Intervals longer than `MAX_REQUEST_INTERVAL` are shortened to it.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.min(MAX_REQUEST_INTERVAL),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// A pacer for the stricter of a requests per second limit and a minimum
+    /// delay between requests, or `None` when neither is set. Fails for a
+    /// rate or delay asking for more than `MAX_REQUEST_INTERVAL` between
+    /// requests.
+    pub fn from_limits(max_requests_per_sec: Option<f64>, min_delay: Option<Duration>) -> Result<Option<Self>, Box<dyn Error>> {
+        let from_rate = match max_requests_per_sec {
+            Some(rate) => Some(
+                Duration::try_from_secs_f64(1.0 / rate)
+                    .ok()
+                    .filter(|interval| rate.is_finite() && *interval <= MAX_REQUEST_INTERVAL)
+                    .ok_or_else(|| {
+                        format!(
+                            "Requests per second must be at least one every {} s and finite, got {}",
+                            MAX_REQUEST_INTERVAL.as_secs(),
+                            rate
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+        if let Some(delay) = min_delay
+            && delay > MAX_REQUEST_INTERVAL
+        {
+            return Err(format!(
+                "Delay between requests can't be over {} s, got {} ms",
+                MAX_REQUEST_INTERVAL.as_secs(),
+                delay.as_millis()
+            )
+            .into());
+        }
+        Ok(match (from_rate, min_delay) {
+            (None, None) => None,
+            (rate, delay) => Some(Self::new(rate.unwrap_or_default().max(delay.unwrap_or_default()))),
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Takes the next free turn and waits for it. The first request goes
+    /// right away.
+    pub async fn wait(&self) {
+        let turn = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let turn = next.map_or(now, |next| next.max(now));
+            *next = Some(turn.checked_add(self.interval).unwrap_or(turn));
+            turn
+        };
+        tokio::time::sleep_until(turn.into()).await;
+    }
+}
+
+/// Whether an error means the server is struggling (connection failures,
+/// 429 and 5xx responses) rather than serving something we can't archive.
+pub fn is_server_strain(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<ServerError>()
+        .is_some_and(|server_error| server_error.is_overload())
+}
+
+/// Adaptive limiters keyed by host, created on first use.
+pub struct HostLimiters {
+    limiters: Mutex<HashMap<String, Arc<AdaptiveLimiter>>>,
+    max_per_host: usize,
+}
+
+impl HostLimiters {
+    pub fn new(max_per_host: usize) -> Self {
+        Self {
+            limiters: Mutex::new(HashMap::new()),
+            max_per_host,
+        }
+    }
+
+    pub fn for_url(&self, url: &str) -> Arc<AdaptiveLimiter> {
+        let host = host_of(url);
+        let mut limiters = self.limiters.lock().unwrap();
+        Arc::clone(
+            limiters
+                .entry(host)
+                .or_insert_with(|| Arc::new(AdaptiveLimiter::new(self.max_per_host))),
+        )
+    }
+}
+
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url.trim())
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(|host| host.to_lowercase()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strain() -> Result<(), Box<dyn Error>> {
+        Err(Box::new(ServerError::Status {
+            url: "https://example.com/".to_string(),
+            status: 503,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_limit_halves_on_strain_and_recovers() {
+        let limiter = Arc::new(AdaptiveLimiter::new(8));
+
+        limiter.acquire().await.record(&strain());
+        assert_eq!(limiter.limit(), 4);
+
+        // Failures inside the cooldown don't compound
+        limiter.acquire().await.record(&strain());
+        assert_eq!(limiter.limit(), 4);
+
+        for _ in 0..4 {
+            limiter.acquire().await.record(&Ok::<(), Box<dyn Error>>(()));
+        }
+        assert_eq!(limiter.limit(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_non_server_errors_do_not_throttle() {
+        let limiter = Arc::new(AdaptiveLimiter::new(3));
+        let unarchivable: Result<(), Box<dyn Error>> = Err("Document type is not text/html".into());
+        limiter.acquire().await.record(&unarchivable);
+        assert_eq!(limiter.limit(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pacer_spaces_out_concurrent_requests() {
+        assert!(RequestPacer::from_limits(None, None).unwrap().is_none());
+        let interval = |rate, delay| RequestPacer::from_limits(rate, delay).map(|pacer| pacer.unwrap().interval());
+        assert_eq!(interval(Some(4.0), None).unwrap(), Duration::from_millis(250));
+        assert_eq!(interval(Some(4.0), Some(Duration::from_millis(400))).unwrap(), Duration::from_millis(400));
+        assert_eq!(interval(Some(1.0 / 600.0), None).unwrap(), MAX_REQUEST_INTERVAL);
+        for rate in [1e-20, 0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(interval(Some(rate), None).is_err(), "{} should be refused", rate);
+        }
+        assert!(interval(None, Some(Duration::from_secs(3600))).is_err());
+        assert_eq!(RequestPacer::new(Duration::MAX).interval(), MAX_REQUEST_INTERVAL);
+
+        let pacer = Arc::new(RequestPacer::new(Duration::from_millis(50)));
+        let started = Instant::now();
+        let mut turns = futures::future::join_all((0..4).map(|_| {
+            let pacer = Arc::clone(&pacer);
+            async move {
+                pacer.wait().await;
+                started.elapsed()
+            }
+        }))
+        .await;
+        turns.sort();
+        assert!(turns[0] < Duration::from_millis(50));
+        assert!(turns[3] >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_limiters_are_shared_per_host() {
+        let limiters = HostLimiters::new(2);
+        let a = limiters.for_url("https://Example.com/a");
+        let b = limiters.for_url("https://example.com/b");
+        let c = limiters.for_url("https://cdn.example.com/c");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+}
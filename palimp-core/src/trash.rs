@@ -0,0 +1,393 @@
+use crate::database::Database;
+use rusqlite::{params, OptionalExtension};
+use std::error::Error;
+use std::time::Duration;
+
+/// What can be deleted and restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrashKind {
+    /// Takes its crawls along.
+    Site,
+    /// Takes its queries along.
+    Crawl,
+    /// Takes its results along.
+    Query,
+    Result,
+}
+
+impl TrashKind {
+    pub const ALL: [TrashKind; 4] = [TrashKind::Site, TrashKind::Crawl, TrashKind::Query, TrashKind::Result];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrashKind::Site => "site",
+            TrashKind::Crawl => "crawl",
+            TrashKind::Query => "query",
+            TrashKind::Result => "result",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn table(&self) -> &'static str {
+        match self {
+            TrashKind::Site => "sites",
+            TrashKind::Crawl => "crawls",
+            TrashKind::Query => "queries",
+            TrashKind::Result => "results",
+        }
+    }
+}
+
+/// A deleted row that can still be restored. Rows deleted along with their
+/// site, crawl or query are not listed separately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashEntry {
+    pub kind: TrashKind,
+    pub id: i64,
+    pub label: String,
+    pub deleted_at: String,
+}
+
+/// Rows removed for good by [`purge`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeCounts {
+    pub sites: usize,
+    pub crawls: usize,
+    pub queries: usize,
+    pub results: usize,
+}
+
+impl PurgeCounts {
+    pub fn total(&self) -> usize {
+        self.sites + self.crawls + self.queries + self.results
+    }
+}
+
+/// Marks a row deleted. Everything deleted along with it gets the same
+/// deletion batch, which is how [`restore`] finds it again; rows deleted
+/// before keep theirs and stay deleted, even if that was the same second.
+/// Deleting a deleted row does nothing.
+pub fn delete(kind: TrashKind, id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+    let table = kind.table();
+    let deleted_at: Option<Option<String>> = database
+        .conn
+        .query_row(&format!("SELECT deleted_at FROM {} WHERE id = ?1", table), [id], |row| row.get(0))
+        .optional()?;
+    match deleted_at {
+        None => return Err(format!("No {} with ID {}", kind.name(), id).into()),
+        Some(Some(_)) => return Ok(()),
+        Some(None) => {}
+    }
+
+    atomically(database, || {
+        let now: String = database.conn.query_row("SELECT palimp_now()", [], |row| row.get(0))?;
+        let batch: i64 = database.conn.query_row(
+            "SELECT 1 + MAX(
+                 (SELECT COALESCE(MAX(deleted_batch), 0) FROM sites),
+                 (SELECT COALESCE(MAX(deleted_batch), 0) FROM crawls),
+                 (SELECT COALESCE(MAX(deleted_batch), 0) FROM queries),
+                 (SELECT COALESCE(MAX(deleted_batch), 0) FROM results)
+             )",
+            [],
+            |row| row.get(0),
+        )?;
+        database.conn.execute(
+            &format!("UPDATE {} SET deleted_at = ?1, deleted_batch = ?3 WHERE id = ?2", table),
+            params![now, id, batch],
+        )?;
+
+        let queries = match kind {
+            TrashKind::Site => "crawl_id IN (SELECT id FROM crawls WHERE site_id = ?2 AND deleted_batch = ?3)",
+            TrashKind::Crawl => "crawl_id = ?2",
+            TrashKind::Query => "id = ?2",
+            TrashKind::Result => return Ok(()),
+        };
+        if kind == TrashKind::Site {
+            database.conn.execute(
+                "UPDATE crawls SET deleted_at = ?1, deleted_batch = ?3 WHERE site_id = ?2 AND deleted_at IS NULL",
+                params![now, id, batch],
+            )?;
+        }
+        if kind != TrashKind::Query {
+            database.conn.execute(
+                &format!(
+                    "UPDATE queries SET deleted_at = ?1, deleted_batch = ?3 WHERE {} AND deleted_at IS NULL",
+                    queries
+                ),
+                params![now, id, batch],
+            )?;
+        }
+        database.conn.execute(
+            &format!(
+                "UPDATE results SET deleted_at = ?1, deleted_batch = ?3 WHERE deleted_at IS NULL
+                 AND query_id IN (SELECT id FROM queries WHERE {} AND deleted_batch = ?3)",
+                queries
+            ),
+            params![now, id, batch],
+        )?;
+        Ok(())
+    })
+}
+
+/// Brings back a deleted row and everything that was deleted with it. A
+/// crawl, query or result whose site, crawl or query is still deleted can't be
+/// restored on its own.
+pub fn restore(kind: TrashKind, id: i64, database: &Database) -> Result<(), Box<dyn Error>> {
+    let table = kind.table();
+    let deleted: Option<(Option<String>, Option<i64>)> = database
+        .conn
+        .query_row(&format!("SELECT deleted_at, deleted_batch FROM {} WHERE id = ?1", table), [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    let batch = match deleted {
+        None => return Err(format!("No {} with ID {}", kind.name(), id).into()),
+        Some((None, _)) => return Err(format!("The {} with ID {} is not deleted", kind.name(), id).into()),
+        Some((Some(_), batch)) => batch,
+    };
+
+    let parent = match kind {
+        TrashKind::Site => None,
+        TrashKind::Crawl => Some(("site", "SELECT s.id, s.deleted_at FROM crawls c JOIN sites s ON s.id = c.site_id WHERE c.id = ?1")),
+        TrashKind::Query => Some(("crawl", "SELECT c.id, c.deleted_at FROM queries q JOIN crawls c ON c.id = q.crawl_id WHERE q.id = ?1")),
+        TrashKind::Result => Some(("query", "SELECT q.id, q.deleted_at FROM results r JOIN queries q ON q.id = r.query_id WHERE r.id = ?1")),
+    };
+    if let Some((parent_kind, sql)) = parent {
+        let (parent_id, parent_deleted_at): (i64, Option<String>) =
+            database.conn.query_row(sql, [id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        if parent_deleted_at.is_some() {
+            return Err(format!("The {} with ID {} is deleted, restore it first", parent_kind, parent_id).into());
+        }
+    }
+    if kind == TrashKind::Site {
+        // Only one site may have a given domain and sitemap
+        let twin: Option<i64> = database
+            .conn
+            .query_row(
+                "SELECT t.id FROM sites s JOIN sites t ON t.domain = s.domain AND t.sitemap_url = s.sitemap_url
+                 WHERE s.id = ?1 AND t.deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(twin) = twin {
+            return Err(format!("Site {} has the same domain and sitemap, delete it first", twin).into());
+        }
+    }
+
+    atomically(database, || {
+        database.conn.execute(
+            &format!("UPDATE {} SET deleted_at = NULL, deleted_batch = NULL WHERE id = ?1", table),
+            [id],
+        )?;
+
+        let queries = match kind {
+            TrashKind::Site => "crawl_id IN (SELECT id FROM crawls WHERE site_id = ?2)",
+            TrashKind::Crawl => "crawl_id = ?2",
+            TrashKind::Query => "id = ?2",
+            TrashKind::Result => return Ok(()),
+        };
+        database.conn.execute(
+            &format!(
+                "UPDATE results SET deleted_at = NULL, deleted_batch = NULL WHERE deleted_batch = ?1
+                 AND query_id IN (SELECT id FROM queries WHERE {})",
+                queries
+            ),
+            params![batch, id],
+        )?;
+        if kind != TrashKind::Query {
+            database.conn.execute(
+                &format!(
+                    "UPDATE queries SET deleted_at = NULL, deleted_batch = NULL WHERE {} AND deleted_batch = ?1",
+                    queries
+                ),
+                params![batch, id],
+            )?;
+        }
+        if kind == TrashKind::Site {
+            database.conn.execute(
+                "UPDATE crawls SET deleted_at = NULL, deleted_batch = NULL WHERE site_id = ?2 AND deleted_batch = ?1",
+                params![batch, id],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Deleted rows that can be restored, most recently deleted first.
+pub fn list(database: &Database) -> Result<Vec<TrashEntry>, Box<dyn Error>> {
+    let mut stmt = database.conn.prepare(
+        "SELECT 'site', id, domain, deleted_at FROM sites WHERE deleted_at IS NOT NULL
+         UNION ALL
+         SELECT 'crawl', c.id, s.domain || ', started ' || COALESCE(c.started_at, '?'), c.deleted_at
+         FROM crawls c JOIN sites s ON s.id = c.site_id
+         WHERE c.deleted_at IS NOT NULL AND s.deleted_batch IS NOT c.deleted_batch
+         UNION ALL
+         SELECT 'query', q.id, q.selector || ' on crawl ' || q.crawl_id, q.deleted_at
+         FROM queries q JOIN crawls c ON c.id = q.crawl_id
+         WHERE q.deleted_at IS NOT NULL AND c.deleted_batch IS NOT q.deleted_batch
+         UNION ALL
+         SELECT 'result', r.id, r.selector || ' on ' || p.url, r.deleted_at
+         FROM results r JOIN pages p ON p.id = r.page_id JOIN queries q ON q.id = r.query_id
+         WHERE r.deleted_at IS NOT NULL AND q.deleted_batch IS NOT r.deleted_batch
+         ORDER BY 4 DESC, 2 DESC",
+    )?;
+    let entries = stmt
+        .query_map([], |row| {
+            let kind: String = row.get(0)?;
+            Ok(TrashEntry {
+                kind: TrashKind::from_name(&kind).unwrap_or(TrashKind::Site),
+                id: row.get(1)?,
+                label: row.get(2)?,
+                deleted_at: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Removes deleted rows for good, or only those deleted at least
+/// `older_than` ago. Purged crawls free their pages.
+pub fn purge(older_than: Option<Duration>, database: &mut Database) -> Result<PurgeCounts, Box<dyn Error>> {
+    let seconds = older_than.map_or(0, |older_than| older_than.as_secs()) as i64;
+    database.in_transaction(|database| {
+        let purge = |table: &str| -> Result<usize, Box<dyn Error>> {
+            Ok(database.conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE deleted_at IS NOT NULL
+                     AND deleted_at <= datetime(palimp_now(), '-' || ?1 || ' seconds')",
+                    table
+                ),
+                [seconds],
+            )?)
+        };
+        // Children first, so rows deleted along with a parent are counted
+        // before the parent's cascade removes them
+        Ok(PurgeCounts {
+            results: purge("results")?,
+            queries: purge("queries")?,
+            crawls: purge("crawls")?,
+            sites: purge("sites")?,
+        })
+    })
+}
+
+/// Runs `f` in a savepoint, so it nests in a caller's transaction.
+fn atomically(database: &Database, f: impl FnOnce() -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    database.conn.execute_batch("SAVEPOINT trash")?;
+    match f() {
+        Ok(()) => {
+            database.conn.execute_batch("RELEASE trash")?;
+            Ok(())
+        }
+        Err(err) => {
+            let _ = database.conn.execute_batch("ROLLBACK TO trash; RELEASE trash");
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::crawl::Crawl;
+    use crate::page::Page;
+    use crate::page_archive::PageArchive;
+    use crate::query::Query;
+    use crate::result_entry::ResultEntry;
+    use crate::site::Site;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_delete_restore_and_purge() {
+        let clock = Arc::new(ManualClock::at_unix(1_700_000_000));
+        let mut db = Database::with_clock(":memory:", clock.clone()).unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let site_id = site.id.unwrap();
+        let mut crawl_ids = Vec::new();
+        for _ in 0..2 {
+            let mut crawl = Crawl::new(None, site_id);
+            crawl.sync(&mut db).unwrap();
+            let crawl_id = crawl.id.unwrap();
+            crawl_ids.push(crawl_id);
+            Page::new("https://a.com/", "https://a.com/", "<h1>A</h1>", Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+            let mut query = Query::new(None, crawl_id, "h1");
+            query.sync(&mut db).unwrap();
+            let page_id = PageArchive::fetch_by_crawl_id(crawl_id, &db).unwrap()[0].id;
+            ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 1).sync(&mut db).unwrap();
+        }
+        let updated_at = |table: &str, id: i64| -> String {
+            db.conn.query_row(&format!("SELECT updated_at FROM {} WHERE id = ?1", table), [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(updated_at("sites", site_id), "2023-11-14 22:13:20");
+
+        // The first crawl goes on its own, then the site with the second
+        clock.advance(Duration::from_secs(60));
+        Crawl::delete(crawl_ids[0], &db).unwrap();
+        assert_eq!(updated_at("crawls", crawl_ids[0]), "2023-11-14 22:14:20");
+        clock.advance(Duration::from_secs(60));
+        Site::delete(site_id, &db).unwrap();
+        assert!(Site::fetch_all(&db).unwrap().is_empty());
+        assert!(Crawl::fetch_all(&db).unwrap().is_empty());
+        assert!(Query::fetch_all(&db).unwrap().is_empty());
+        assert!(ResultEntry::fetch_all(&db).unwrap().is_empty());
+
+        let listed: Vec<(TrashKind, i64)> = list(&db).unwrap().iter().map(|entry| (entry.kind, entry.id)).collect();
+        assert_eq!(listed, vec![(TrashKind::Site, site_id), (TrashKind::Crawl, crawl_ids[0])]);
+        assert!(restore(TrashKind::Crawl, crawl_ids[0], &db).unwrap_err().to_string().contains("restore it first"));
+
+        // Restoring the site brings back only what went with it
+        restore(TrashKind::Site, site_id, &db).unwrap();
+        let crawls: Vec<i64> = Crawl::fetch_all(&db).unwrap().iter().filter_map(|crawl| crawl.id).collect();
+        assert_eq!(crawls, vec![crawl_ids[1]]);
+        assert_eq!(Query::fetch_all(&db).unwrap().len(), 1);
+        assert_eq!(ResultEntry::fetch_all(&db).unwrap().len(), 1);
+        assert!(restore(TrashKind::Site, site_id, &db).is_err());
+
+        // Purging only takes rows deleted long enough ago
+        assert_eq!(purge(Some(Duration::from_secs(3_600)), &mut db).unwrap().total(), 0);
+        clock.advance(Duration::from_secs(3_600));
+        let purged = purge(Some(Duration::from_secs(3_600)), &mut db).unwrap();
+        assert_eq!(purged, PurgeCounts { sites: 0, crawls: 1, queries: 1, results: 1 });
+        assert!(list(&db).unwrap().is_empty());
+        assert!(Crawl::fetch(crawl_ids[0], &db).is_err());
+        assert_eq!(PageArchive::fetch_by_crawl_id(crawl_ids[0], &db).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_restore_in_the_same_second() {
+        let clock = Arc::new(ManualClock::at_unix(1_700_000_000));
+        let mut db = Database::with_clock(":memory:", clock).unwrap();
+        db.seed().unwrap();
+        let mut site = Site::new(None, "a.com", "https://a.com/sitemap.xml");
+        site.sync(&mut db).unwrap();
+        let site_id = site.id.unwrap();
+        let mut crawl = Crawl::new(None, site_id);
+        crawl.sync(&mut db).unwrap();
+        let crawl_id = crawl.id.unwrap();
+        let mut queries = Vec::new();
+        for selector in ["h1", "h2"] {
+            let mut query = Query::new(None, crawl_id, selector);
+            query.sync(&mut db).unwrap();
+            queries.push(query.id.unwrap());
+        }
+
+        // A query on its own, then its site, without the clock moving
+        Query::delete(queries[0], &db).unwrap();
+        Site::delete(site_id, &db).unwrap();
+        let listed: Vec<(TrashKind, i64)> = list(&db).unwrap().iter().map(|entry| (entry.kind, entry.id)).collect();
+        assert_eq!(listed, vec![(TrashKind::Site, site_id), (TrashKind::Query, queries[0])]);
+
+        // The site brings back its crawl and the query deleted with it only
+        restore(TrashKind::Site, site_id, &db).unwrap();
+        let restored: Vec<i64> = Query::fetch_all(&db).unwrap().iter().filter_map(|query| query.id).collect();
+        assert_eq!(restored, vec![queries[1]]);
+        assert_eq!(Crawl::fetch_all(&db).unwrap().len(), 1);
+    }
+}
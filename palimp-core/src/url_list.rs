@@ -0,0 +1,58 @@
+use crate::sitemap::SitemapReader;
+use std::error::Error;
+
+/// Reads the URLs of a list handed in for an ad-hoc crawl: a sitemap when
+/// the content is XML, one URL per line otherwise. Blank lines and `#`
+/// comments are skipped and repeated URLs kept once, in first-seen order.
+pub fn parse(content: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let candidates: Vec<String> = if content.trim_start().starts_with('<') {
+        SitemapReader::from_content(content)
+            .map(|entry| entry.map(|entry| entry.loc))
+            .collect::<Result<_, _>>()?
+    } else {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let mut urls: Vec<String> = Vec::new();
+    for candidate in candidates {
+        let url = reqwest::Url::parse(candidate.trim()).map_err(|e| format!("Invalid URL '{}': {}", candidate, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Invalid URL '{}': only http and https can be crawled", candidate).into());
+        }
+        if !urls.contains(&candidate) {
+            urls.push(candidate);
+        }
+    }
+
+    if urls.is_empty() {
+        return Err("No URLs found".into());
+    }
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines_and_sitemap() {
+        let list = "# pricing pages\nhttps://a.com/pricing\n\n  https://a.com/plans  \nhttps://a.com/pricing\n";
+        assert_eq!(parse(list).unwrap(), vec!["https://a.com/pricing", "https://a.com/plans"]);
+
+        let sitemap = r#"<?xml version="1.0"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://a.com/</loc></url>
+                <url><loc>https://a.com/about</loc></url>
+            </urlset>"#;
+        assert_eq!(parse(sitemap).unwrap(), vec!["https://a.com/", "https://a.com/about"]);
+
+        assert!(parse("ftp://a.com/file").is_err());
+        assert!(parse("not a url").is_err());
+        assert!(parse("# nothing\n").is_err());
+    }
+}
@@ -1,19 +1,43 @@
-use palimp_core::{Application};
-use palimp_core::crawl::Crawl;
+use palimp_core::{Application, CrawlResult};
+use palimp_core::clock::ManualClock;
+use palimp_core::crawl::{Crawl, CrawlMode, CrawlOptions, CrawlStatus, StopSignal};
+use palimp_core::link_crawl::LinkFollowing;
+use palimp_core::asset::{AssetKind, AssetLimits};
+use palimp_core::database::Database;
+use palimp_core::estimate::DEFAULT_PAGE_BYTES;
+use palimp_core::http_client::HTTPClient;
 use palimp_core::query::Query;
 use palimp_core::result_entry::ResultEntry;
+use palimp_core::site::{Site, SiteValidation, SiteValidationError};
+use palimp_core::sitemap::SitemapLimits;
+use palimp_core::test_utils::{fixtures, FakeSite};
+use rusqlite::types::Value;
+use std::sync::Arc;
+use std::time::Duration;
 
 async fn create_test_app() -> Application {
     // Use in-memory database for testing
     Application::new(":memory:").expect("Failed to create application with in-memory DB")
 }
 
+/// An in-memory database with the schema in place, for a test to seed before
+/// handing it to [`app_with`].
+fn seeded_database(clock: Arc<ManualClock>) -> Database {
+    let db = Database::with_clock(":memory:", clock).unwrap();
+    db.seed().unwrap();
+    db
+}
+
+fn app_with(db: Database) -> Application {
+    Application::with_database(db, HTTPClient::new().unwrap()).expect("Failed to create application")
+}
+
 #[tokio::test]
 async fn test_site_lifecycle() {
     let app = create_test_app().await;
 
     // 1. Create a new site
-    app.new_site("example.com", "https://example.com/sitemap.xml")
+    app.new_site_with_validation("example.com", "https://example.com/sitemap.xml", SiteValidation::Format)
         .await
         .expect("Failed to create site");
 
@@ -23,36 +47,97 @@ async fn test_site_lifecycle() {
     assert_eq!(sites[0].domain, "example.com");
     assert_eq!(sites[0].sitemap_url, "https://example.com/sitemap.xml");
 
-    // 3. Delete site
+    // 3. Edit it, keeping its ID
     let site_id = sites[0].id.expect("Site ID should be present");
+    let edited = app.update_site(site_id, Some("www.example.com"), None).await.expect("Failed to edit site");
+    assert_eq!(edited.id, Some(site_id));
+    assert_eq!(app.list_sites().await.unwrap()[0].domain, "www.example.com");
+    assert_eq!(app.list_sites().await.unwrap()[0].sitemap_url, "https://example.com/sitemap.xml");
+    assert!(app.update_site(site_id, None, Some("sitemap.xml")).await.is_err());
+    let other = app
+        .new_site_with_validation("example.org", "https://example.org/sitemap.xml", SiteValidation::Format)
+        .await
+        .unwrap();
+    let taken = app.update_site(other.id.unwrap(), Some("www.example.com"), Some("https://example.com/sitemap.xml")).await;
+    assert!(taken.err().unwrap().to_string().contains("already has this domain"));
+    app.delete_site(other.id.unwrap()).await.unwrap();
+
+    // 4. Delete site
     app.delete_site(site_id).await.expect("Failed to delete site");
 
-    // 4. Verify deletion
+    // 5. Verify deletion
     let sites_after = app.list_sites().await.expect("Failed to list sites");
     assert_eq!(sites_after.len(), 0);
 }
 
 #[tokio::test]
-async fn test_crawl_lifecycle() {
+async fn test_new_site_validation() {
+    let site = FakeSite::builder().page("/", &fixtures::page("Home", "<h1>Welcome</h1>")).start().await;
+    let empty = FakeSite::builder().start().await;
     let app = create_test_app().await;
 
-    // Setup: Create a site first
-    app.new_site("example.com", "https://example.com/sitemap.xml").await.unwrap();
-    let sites = app.list_sites().await.unwrap();
-    let site_id = sites[0].id.unwrap();
+    let refusal = |result: Result<Site, Box<dyn std::error::Error>>| {
+        result.err().expect("The site should be refused").downcast::<SiteValidationError>().map(|err| *err).expect("Expected a validation error")
+    };
+    assert!(matches!(
+        refusal(app.new_site("test.com", "sitemap").await),
+        SiteValidationError::InvalidSitemapUrl { .. }
+    ));
+    assert!(matches!(
+        refusal(app.new_site("not a domain", "https://test.com/sitemap.xml").await),
+        SiteValidationError::InvalidDomain { .. }
+    ));
+    assert!(matches!(
+        refusal(app.new_site("127.0.0.1:1", "http://127.0.0.1:1/sitemap.xml").await),
+        SiteValidationError::SitemapUnreachable { .. }
+    ));
+    assert_eq!(
+        refusal(app.new_site(&empty.base_url(), &empty.sitemap_url()).await),
+        SiteValidationError::EmptySitemap { url: empty.sitemap_url() }
+    );
+    assert!(app.list_sites().await.unwrap().is_empty());
 
-    // Manually create a crawl (since new_crawl requires network/mocking)
-    // We access the internal DB to simulate a crawl being added
-    {
-        let mut db_lock = app.db.lock().await;
-        let mut crawl = Crawl::new(None, site_id);
-        crawl.sync(&mut db_lock).expect("Failed to sync manual crawl");
-    }
+    let created = app.new_site(&site.base_url(), &site.sitemap_url()).await.expect("A reachable sitemap should pass");
+    app.new_site_with_validation("test.com", "sitemap", SiteValidation::Skip).await.unwrap();
+    assert_eq!(app.list_sites().await.unwrap().len(), 2);
+
+    // Creating it again returns the stored site
+    let again = app.new_site(&format!(" {}", site.base_url()), &site.sitemap_url()).await.unwrap();
+    assert_eq!(again.id, created.id);
+    assert_eq!(app.list_sites().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_crawl_lifecycle() {
+    // Seed a site and a crawl (since new_crawl requires network/mocking)
+    // at a fixed time before the application opens the database
+    let clock = Arc::new(ManualClock::at_unix(1_704_164_645));
+    let mut db = seeded_database(clock.clone());
+    let mut site = Site::new(None, "example.com", "https://example.com/sitemap.xml");
+    site.sync(&mut db).unwrap();
+    let site_id = site.id.unwrap();
+    let mut first = Crawl::new(None, site_id);
+    first.sync(&mut db).expect("Failed to sync manual crawl");
+    clock.advance(Duration::from_secs(60));
+    Crawl::new(None, site_id).sync(&mut db).unwrap();
+    clock.advance(Duration::from_secs(30));
+    Crawl::set_status(first.id.unwrap(), CrawlStatus::Completed, &db).unwrap();
+
+    let app = app_with(db);
 
     // 1. List crawls
     let crawls = app.list_crawls().await.expect("Failed to list crawls");
-    assert_eq!(crawls.len(), 1);
+    assert_eq!(crawls.len(), 2);
     assert_eq!(crawls[0].site_id, site_id);
+    let mut started: Vec<_> = crawls.iter().filter_map(|crawl| crawl.started_at.clone()).collect();
+    started.sort();
+    assert_eq!(started, ["2024-01-02 03:04:05", "2024-01-02 03:05:05"]);
+
+    // Only the completed crawl has finished, 90 seconds after its start
+    let first = crawls.iter().find(|crawl| crawl.id == first.id).unwrap();
+    assert_eq!(first.finished_at.as_deref(), Some("2024-01-02 03:05:35"));
+    assert_eq!(first.duration, Some(Duration::from_secs(90)));
+    assert!(crawls.iter().any(|crawl| crawl.finished_at.is_none() && crawl.duration.is_none()));
 
     // 2. Delete crawl
     let crawl_id = crawls[0].id.expect("Crawl ID should be present");
@@ -60,7 +145,42 @@ async fn test_crawl_lifecycle() {
 
     // 3. Verify deletion
     let crawls_after = app.list_crawls().await.expect("Failed to list crawls");
-    assert_eq!(crawls_after.len(), 0);
+    assert_eq!(crawls_after.len(), 1);
+}
+
+#[tokio::test]
+async fn test_bulk_delete() {
+    let app = create_test_app().await;
+
+    app.new_site_with_validation("example.com", "https://example.com/sitemap.xml", SiteValidation::Format).await.unwrap();
+    let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
+
+    let crawl_ids: Vec<i64> = {
+        let mut db = app.db.lock().await;
+        (0..3)
+            .map(|_| {
+                let mut crawl = Crawl::new(None, site_id);
+                crawl.sync(&mut db).unwrap();
+                crawl.id.unwrap()
+            })
+            .collect()
+    };
+    {
+        let mut db = app.db.lock().await;
+        for selector in ["h1", "h2"] {
+            Query::new(None, crawl_ids[2], selector).sync(&mut db).unwrap();
+        }
+    }
+
+    app.delete_crawls(&crawl_ids[..2]).await.expect("Failed to delete crawls");
+
+    let crawls_after = app.list_crawls().await.unwrap();
+    assert_eq!(crawls_after.len(), 1);
+    assert_eq!(crawls_after[0].id, Some(crawl_ids[2]));
+
+    let query_ids: Vec<i64> = app.list_queries().await.unwrap().iter().filter_map(|q| q.id).collect();
+    app.delete_queries(&query_ids).await.expect("Failed to delete queries");
+    assert!(app.list_queries().await.unwrap().is_empty());
 }
 
 #[tokio::test]
@@ -68,7 +188,7 @@ async fn test_query_lifecycle() {
     let app = create_test_app().await;
 
     // Setup: Site -> Crawl
-    app.new_site("test.com", "sitemap").await.unwrap();
+    app.new_site_with_validation("test.com", "https://test.com/sitemap.xml", SiteValidation::Format).await.unwrap();
     let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
     
     let crawl_id = {
@@ -90,12 +210,25 @@ async fn test_query_lifecycle() {
     assert_eq!(queries.len(), 1);
     assert_eq!(queries[0].selector, "div > h1");
     assert_eq!(queries[0].crawl_id, crawl_id);
+    assert_eq!(queries[0].label(), "div > h1");
 
-    // 2. Delete query
+    // 2. Name and describe it; blank values clear, None keeps
     let query_id = queries[0].id.unwrap();
+    let query = app
+        .describe_query(query_id, Some(" Missing canonical check "), Some("Pages without a canonical link"))
+        .await
+        .expect("Failed to describe query");
+    assert_eq!(query.label(), "Missing canonical check");
+    let query = app.describe_query(query_id, None, Some("")).await.unwrap();
+    assert_eq!(query.name.as_deref(), Some("Missing canonical check"));
+    assert_eq!(query.description, None);
+    assert_eq!(app.list_queries().await.unwrap()[0].label(), "Missing canonical check");
+    assert!(app.describe_query(query_id + 1, Some("x"), None).await.is_err());
+
+    // 3. Delete query
     app.delete_query(query_id).await.expect("Failed to delete query");
 
-    // 3. Verify deletion
+    // 4. Verify deletion
     let queries_after = app.list_queries().await.expect("Failed to list queries");
     assert_eq!(queries_after.len(), 0);
 }
@@ -108,7 +241,7 @@ async fn test_result_lifecycle() {
     // We need a Page to link a ResultEntry to it.
     // Page struct is public, but let's see if we can use it.
     
-    app.new_site("test.com", "sitemap").await.unwrap();
+    app.new_site_with_validation("test.com", "https://test.com/sitemap.xml", SiteValidation::Format).await.unwrap();
     let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
 
     let crawl_id = {
@@ -136,10 +269,12 @@ async fn test_result_lifecycle() {
         db.conn.last_insert_rowid()
     };
 
-    // 1. Manually create a ResultEntry linked to the page
+    // 1. Manually create a ResultEntry linked to the page and its query
     {
         let mut db = app.db.lock().await;
-        let mut entry = ResultEntry::new(None, page_id, "h1", 1);
+        let mut query = Query::new(None, crawl_id, "h1");
+        query.sync(&mut db).expect("Failed to sync query");
+        let mut entry = ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 1);
         entry.sync(&mut db).expect("Failed to sync result entry");
     }
 
@@ -159,3 +294,841 @@ async fn test_result_lifecycle() {
     assert_eq!(results_after.len(), 0);
 }
 
+
+#[tokio::test]
+async fn test_search_results() {
+    use palimp_core::page::Page;
+    use palimp_core::result_entry::ResultFilter;
+
+    let mut db = seeded_database(Arc::new(ManualClock::at_unix(0)));
+    let mut site = Site::new(None, "test.com", "sitemap");
+    site.sync(&mut db).unwrap();
+    let mut crawl = Crawl::new(None, site.id.unwrap());
+    crawl.sync(&mut db).unwrap();
+    let crawl_id = crawl.id.unwrap();
+    for (url, items) in [("http://test.com/a", 1), ("http://test.com/blog/b", 3), ("http://test.com/blog/c", 5)] {
+        let html = format!("<ul>{}</ul>", "<li>x</li>".repeat(items));
+        Page::new(url, url, &html, Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+    }
+
+    let app = app_with(db);
+    app.query(crawl_id, "li").await.unwrap();
+    app.query(crawl_id, "ul").await.unwrap();
+    let li_query = app.list_queries().await.unwrap().into_iter().find(|q| q.selector == "li").unwrap();
+
+    let urls = |results: Vec<(ResultEntry, String)>| results.into_iter().map(|(_, url)| url).collect::<Vec<_>>();
+
+    let all = app.search_results(&ResultFilter::default()).await.unwrap();
+    assert_eq!(all.len(), 6);
+
+    let by_query = app.search_results(&ResultFilter::for_query(li_query.id.unwrap())).await.unwrap();
+    assert_eq!(by_query.len(), 3);
+    assert!(by_query.iter().all(|(entry, _)| entry.selector == "li"));
+
+    let filter = ResultFilter {
+        crawl_id: Some(crawl_id),
+        selector: Some("li".to_string()),
+        min_count: Some(2),
+        url_pattern: Some("/blog/".to_string()),
+        ..ResultFilter::default()
+    };
+    assert_eq!(urls(app.search_results(&filter).await.unwrap()), ["http://test.com/blog/b", "http://test.com/blog/c"]);
+
+    let filter = ResultFilter { max_count: Some(4), limit: Some(1), offset: 1, ..filter };
+    assert!(app.search_results(&filter).await.unwrap().is_empty());
+
+    let filter = ResultFilter { selector: Some("li".to_string()), limit: Some(1), offset: 1, ..ResultFilter::default() };
+    assert_eq!(urls(app.search_results(&filter).await.unwrap()), ["http://test.com/blog/b"]);
+
+    // A second query of the same selector keeps its own results
+    app.query(crawl_id, "li").await.unwrap();
+    let li_again = app.list_queries().await.unwrap().into_iter().rfind(|q| q.selector == "li").unwrap();
+    assert_ne!(li_again.id, li_query.id);
+    assert_eq!(app.list_results_for_query(li_query.id.unwrap()).await.unwrap().len(), 3);
+    app.delete_query(li_query.id.unwrap()).await.unwrap();
+    let remaining = app.list_results_for_query(li_again.id.unwrap()).await.unwrap();
+    assert_eq!(remaining.len(), 3);
+    assert!(remaining.iter().all(|(entry, _)| entry.query_id == li_again.id.unwrap()));
+    assert_eq!(app.search_results(&ResultFilter::default()).await.unwrap().len(), 6);
+}
+
+#[tokio::test]
+async fn test_result_triage() {
+    use palimp_core::page::Page;
+    use palimp_core::result_entry::{ResultFilter, ResultStatus};
+
+    let mut db = seeded_database(Arc::new(ManualClock::at_unix(0)));
+    let mut site = Site::new(None, "test.com", "sitemap");
+    site.sync(&mut db).unwrap();
+    let mut crawl = Crawl::new(None, site.id.unwrap());
+    crawl.sync(&mut db).unwrap();
+    let crawl_id = crawl.id.unwrap();
+    for url in ["http://test.com/a", "http://test.com/b", "http://test.com/blog/c"] {
+        Page::new(url, url, "<h1>x</h1>", Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+    }
+
+    let app = app_with(db);
+    let results = app.query(crawl_id, "h1").await.unwrap();
+    assert!(results.iter().all(|entry| entry.status == ResultStatus::New));
+    let ids: Vec<i64> = results.iter().filter_map(|entry| entry.id).collect();
+
+    let by_status = |status| ResultFilter { status: Some(status), ..ResultFilter::default() };
+
+    assert_eq!(app.set_result_status(&[ids[0], ids[1] + 100], ResultStatus::Reviewed).await.unwrap(), 1);
+    let filter = ResultFilter { url_pattern: Some("/blog/".to_string()), ..by_status(ResultStatus::New) };
+    assert_eq!(app.set_matching_result_status(&filter, ResultStatus::Ignored).await.unwrap(), 1);
+
+    let new = app.search_results(&by_status(ResultStatus::New)).await.unwrap();
+    assert_eq!(new.iter().map(|(entry, _)| entry.id.unwrap()).collect::<Vec<_>>(), [ids[1]]);
+    let reviewed = app.search_results(&by_status(ResultStatus::Reviewed)).await.unwrap();
+    assert_eq!(reviewed[0].1, "http://test.com/a");
+    let ignored = app.search_results(&by_status(ResultStatus::Ignored)).await.unwrap();
+    assert_eq!(ignored[0].1, "http://test.com/blog/c");
+
+    // Deleted results are left alone
+    app.delete_result(ids[2]).await.unwrap();
+    assert_eq!(app.set_result_status(&ids, ResultStatus::New).await.unwrap(), 2);
+    assert!(app.search_results(&by_status(ResultStatus::Ignored)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_search_pages() {
+    let app = create_test_app().await;
+
+    app.new_site_with_validation("test.com", "https://test.com/sitemap.xml", SiteValidation::Format).await.unwrap();
+    let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
+
+    let crawl_id = {
+        let mut db = app.db.lock().await;
+        let mut crawl = Crawl::new(None, site_id);
+        crawl.sync(&mut db).unwrap();
+        crawl.id.unwrap()
+    };
+
+    {
+        use palimp_core::page::Page;
+        let mut db = app.db.lock().await;
+        let pricing = "<html><body><h1>Pricing</h1><p>The enterprise plan includes support.</p></body></html>";
+        let about = "<html><body><h1>About us</h1><p>We build crawlers.</p></body></html>";
+        Page::new("http://test.com/pricing", "http://test.com/pricing", pricing, Some(crawl_id))
+            .unwrap()
+            .sync(&mut db)
+            .unwrap();
+        Page::new("http://test.com/about", "http://test.com/about", about, Some(crawl_id))
+            .unwrap()
+            .sync(&mut db)
+            .unwrap();
+    }
+
+    let hits = app.search_pages(crawl_id, "enterprise plan").await.expect("Failed to search pages");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].url, "http://test.com/pricing");
+    assert!(hits[0].snippet.contains("«enterprise"));
+
+    let no_hits = app.search_pages(crawl_id, "nothing like this").await.unwrap();
+    assert!(no_hits.is_empty());
+}
+
+#[tokio::test]
+async fn test_site_dashboard() {
+    let app = create_test_app().await;
+
+    app.new_site_with_validation("test.com", "https://test.com/sitemap.xml", SiteValidation::Format).await.unwrap();
+    let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
+
+    {
+        use palimp_core::crawl_error::{CrawlError, CrawlErrorKind};
+        use palimp_core::page::Page;
+        let mut db = app.db.lock().await;
+
+        let mut first = Crawl::new(None, site_id);
+        first.sync(&mut db).unwrap();
+        let first_id = first.id.unwrap();
+        Page::new("http://test.com/", "http://test.com/", "<h1>Home</h1>", Some(first_id))
+            .unwrap()
+            .sync(&mut db)
+            .unwrap();
+
+        let mut second = Crawl::new(None, site_id);
+        second.sync(&mut db).unwrap();
+        let second_id = second.id.unwrap();
+        let mut query = Query::new(None, second_id, "h1").with_name(Some("Headings"));
+        query.sync(&mut db).unwrap();
+        for path in ["a", "b", "c"] {
+            let url = format!("http://test.com/{}", path);
+            Page::new(&url, &url, "<h1>Title</h1>", Some(second_id))
+                .unwrap()
+                .sync(&mut db)
+                .unwrap();
+            let page_id = db.conn.last_insert_rowid();
+            ResultEntry::new(None, query.id.unwrap(), page_id, "h1", 1).sync(&mut db).unwrap();
+        }
+        CrawlError::new(None, second_id, "http://test.com/d", CrawlErrorKind::Fetch, "timeout")
+            .sync(&mut db)
+            .unwrap();
+    }
+
+    let dashboard = app.site_dashboard(site_id).await.expect("Failed to build dashboard");
+    assert_eq!(dashboard.site.domain, "test.com");
+    assert_eq!(dashboard.crawl_trend.len(), 2);
+    assert_eq!(dashboard.crawl_trend[0].page_count, 1);
+
+    let latest = dashboard.latest_crawl.expect("Missing latest crawl");
+    assert_eq!(latest.page_count, 3);
+    assert_eq!(latest.error_count, 1);
+    assert!((latest.error_rate() - 0.25).abs() < f64::EPSILON);
+
+    assert_eq!(dashboard.recent_queries.len(), 1);
+    assert_eq!(dashboard.recent_queries[0].selector, "h1");
+    assert_eq!(dashboard.recent_queries[0].label(), "Headings");
+    assert_eq!(dashboard.recent_queries[0].matching_pages, 3);
+    assert_eq!(dashboard.recent_queries[0].total_count, 3);
+}
+
+#[tokio::test]
+async fn test_crawl_frontier() {
+    use palimp_core::frontier::FrontierUrl;
+
+    let app = create_test_app().await;
+    app.new_site_with_validation("example.com", "https://example.com/sitemap.xml", SiteValidation::Format).await.unwrap();
+    let site_id = app.list_sites().await.unwrap()[0].id.unwrap();
+
+    let crawl_id = {
+        let mut db_lock = app.db.lock().await;
+        let mut crawl = Crawl::new(None, site_id);
+        crawl.sync(&mut db_lock).unwrap();
+        let crawl_id = crawl.id.unwrap();
+        for url in ["https://example.com/a", "https://example.com/b", "https://example.com/a"] {
+            FrontierUrl::new(None, crawl_id, url).sync(&mut db_lock).unwrap();
+        }
+        FrontierUrl::complete(crawl_id, "https://example.com/a", &db_lock).unwrap();
+        crawl_id
+    };
+
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 2);
+
+    // Deleting the crawl drops what it had left to fetch
+    app.delete_crawl(crawl_id).await.unwrap();
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_new_crawl_against_fake_site() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .missing("/gone")
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    app.new_crawl(site_id, 2, move |update| recorded.lock().unwrap().push(update))
+        .await
+        .expect("Crawl should finish");
+
+    let updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert!(matches!(updates[0], CrawlResult::CrawlStarted(3)));
+    let succeeded = updates.iter().filter(|update| matches!(update, CrawlResult::PageSucceeded(_))).count();
+    let failed: Vec<&String> = updates.iter()
+        .filter_map(|update| match update {
+            CrawlResult::PageFailed(url, _) => Some(url),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(succeeded, 2);
+    assert_eq!(failed, vec![&site.url("/gone")]);
+
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    let results = app.query(crawl_id, ".price").await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 2);
+    // The sitemap, its robots.txt and the three pages
+    assert_eq!(site.request_count().await, 5);
+}
+
+#[tokio::test]
+async fn test_estimate_crawl() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    // Never crawled, so page sizes are a guess
+    let estimate = app.estimate_crawl(site_id).await.unwrap();
+    assert_eq!(estimate.urls, 2);
+    assert_eq!(estimate.requests(), 3);
+    assert_eq!(estimate.measured_crawl_id, None);
+    assert_eq!(estimate.page_bytes, DEFAULT_PAGE_BYTES);
+    assert_eq!(site.request_count().await, 1);
+
+    app.new_crawl(site_id, 2, |_| {}).await.unwrap();
+    let crawl_id = app.list_crawls().await.unwrap()[0].id;
+
+    let estimate = app.estimate_crawl(site_id).await.unwrap();
+    assert_eq!(estimate.measured_crawl_id, crawl_id);
+    let total = fixtures::page("Home", "<h1>Welcome</h1>").len() + fixtures::product_page("Pro", "$10").len();
+    assert_eq!(estimate.page_bytes, (total as f64 / 2.0).round() as u64);
+}
+
+#[tokio::test]
+async fn test_crawl_bandwidth_budget() {
+    let home = fixtures::page("Home", "<h1>Welcome</h1>");
+    let site = FakeSite::builder()
+        .page("/", &home)
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .page("/about", &fixtures::page("About", "<p>Us</p>"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    // One page at a time, so the first page uses up the budget
+    let options = CrawlOptions::new(1).with_max_bytes(1);
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    app.new_crawl_with_options(site_id, options, move |update| recorded.lock().unwrap().push(update))
+        .await
+        .unwrap();
+
+    let crawl = app.list_crawls().await.unwrap().remove(0);
+    let crawl_id = crawl.id.unwrap();
+    assert_eq!(crawl.status, CrawlStatus::Interrupted);
+    assert_eq!(crawl.bytes_fetched, home.len() as u64);
+    let updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert!(updates.iter().any(|update| matches!(update, CrawlResult::Warning(message) if message.contains("budget"))));
+    assert!(matches!(updates.last(), Some(CrawlResult::Interrupted(id, 2)) if *id == crawl_id));
+
+    // Resuming without a budget fetches the rest and keeps counting
+    app.resume_crawl(crawl_id, CrawlOptions::new(2), |_| {}).await.unwrap();
+    let crawl = app.list_crawls().await.unwrap().remove(0);
+    assert_eq!(crawl.status, CrawlStatus::Completed);
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 3);
+    assert!(crawl.bytes_fetched > home.len() as u64);
+}
+
+#[tokio::test]
+async fn test_crawl_politeness() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/a", &fixtures::page("A", "<p>A</p>"))
+        .page("/b", &fixtures::page("B", "<p>B</p>"))
+        .page("/c", &fixtures::page("C", "<p>C</p>"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    assert!(app.set_site_politeness(site_id, Some(0.0), None).await.is_err());
+    // Too slow to pace, which would otherwise overflow the interval
+    assert!(app.set_site_politeness(site_id, Some(1e-20), None).await.is_err());
+    assert!(app.set_site_politeness(site_id, None, Some(u64::MAX)).await.is_err());
+    // The stricter of the two wins: 100 ms rather than 50 ms
+    app.set_site_politeness(site_id, Some(20.0), Some(100)).await.unwrap();
+    let stored = app.list_sites().await.unwrap().remove(0);
+    assert_eq!((stored.max_requests_per_sec, stored.min_request_delay_ms), (Some(20.0), Some(100)));
+
+    let started = std::time::Instant::now();
+    app.new_crawl(site_id, 4, |_| {}).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 4);
+
+    app.set_site_politeness(site_id, None, None).await.unwrap();
+    assert!(app.list_sites().await.unwrap()[0].request_pacer().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_crawl_respects_robots_txt() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/private/plans", &fixtures::page("Plans", "<p>Secret</p>"))
+        .page("/private/press/launch", &fixtures::page("Launch", "<p>News</p>"))
+        .file(
+            "/robots.txt",
+            "text/plain",
+            "User-agent: *\nDisallow: /private/\nAllow: /private/press/\n\nUser-agent: browser\nDisallow: /private/\n",
+        )
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+    let newest_crawl = || async { app.list_crawls().await.unwrap().into_iter().filter_map(|crawl| crawl.id).max().unwrap() };
+
+    let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = warnings.clone();
+    app.new_crawl(site_id, 2, move |update| {
+        if let CrawlResult::Warning(message) = update {
+            recorded.lock().unwrap().push(message);
+        }
+    })
+    .await
+    .unwrap();
+    let crawl_id = newest_crawl().await;
+    let mut urls = app.list_page_urls(crawl_id).await.unwrap();
+    urls.sort();
+    assert_eq!(urls, [site.url("/"), site.url("/private/press/launch")]);
+    let skipped = app.list_skipped_urls(crawl_id).await.unwrap();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].url, site.url("/private/plans"));
+    assert!(skipped[0].reason.contains("/private/"));
+    assert!(app.list_crawl_errors(crawl_id).await.unwrap().is_empty());
+    assert_eq!(*warnings.lock().unwrap(), ["Left out 1 URLs disallowed by robots.txt"]);
+
+    app.new_crawl_with_options(site_id, CrawlOptions::new(2).with_respect_robots(false), |_| {})
+        .await
+        .unwrap();
+    let crawl_id = newest_crawl().await;
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 3);
+    assert!(app.list_skipped_urls(crawl_id).await.unwrap().is_empty());
+
+    // The rules for the agents actually sent apply, every rotated one
+    // included
+    let options = CrawlOptions::new(2).with_user_agents(vec!["Bot/2".to_string(), "Browser/1".to_string()]);
+    app.new_crawl_with_options(site_id, options, |_| {}).await.unwrap();
+    let crawl_id = newest_crawl().await;
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap(), [site.url("/")]);
+    assert_eq!(app.list_skipped_urls(crawl_id).await.unwrap().len(), 2);
+    let client = HTTPClient::builder().default_header("user-agent", "Browser/3").build().unwrap();
+    assert_eq!(client.user_agent(), "Browser/3");
+}
+
+#[tokio::test]
+async fn test_resume_crawl_without_frontier() {
+    use palimp_core::frontier::FrontierUrl;
+
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .missing("/gone")
+        .page("/about", &fixtures::page("About", "<p>Us</p>"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    // Cut short after two pages, one of them failing
+    let stop = StopSignal::new();
+    let signal = stop.clone();
+    let options = CrawlOptions::new(1).with_stop_signal(stop);
+    app.new_crawl_with_options(site_id, options, move |update| {
+        if matches!(update, CrawlResult::PageFailed(_, _)) {
+            signal.stop();
+        }
+    })
+    .await
+    .unwrap();
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 2);
+
+    // As if the process died before anything was queued, the frontier's gone
+    {
+        let db = app.db.lock().await;
+        FrontierUrl::clear(crawl_id, &db).unwrap();
+        Crawl::set_status(crawl_id, CrawlStatus::Running, &db).unwrap();
+    }
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    app.resume_crawl(crawl_id, CrawlOptions::new(2), move |update| {
+        if let CrawlResult::PageSucceeded(url) | CrawlResult::PageFailed(url, _) = update {
+            recorded.lock().unwrap().push(url);
+        }
+    })
+    .await
+    .unwrap();
+
+    // Only the page it had neither archived nor failed on is fetched
+    assert_eq!(*updates.lock().unwrap(), [site.url("/about")]);
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 3);
+    assert_eq!(app.list_crawl_errors(crawl_id).await.unwrap().len(), 1);
+    assert_eq!(app.list_crawls().await.unwrap()[0].status, CrawlStatus::Completed);
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 0);
+    assert!(app.resume_crawl(crawl_id, CrawlOptions::new(2), |_| {}).await.is_err());
+}
+
+#[tokio::test]
+async fn test_cancel_crawl() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .page("/about", &fixtures::page("About", "<p>Us</p>"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+    let newest_crawl = || async { app.list_crawls().await.unwrap().into_iter().max_by_key(|crawl| crawl.id).unwrap() };
+
+    // Cancelled after the first page, one at a time
+    let stop = StopSignal::new();
+    let signal = stop.clone();
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    let options = CrawlOptions::new(1).with_stop_signal(stop);
+    app.new_crawl_with_options(site_id, options, move |update| {
+        if matches!(update, CrawlResult::PageSucceeded(_)) {
+            signal.cancel();
+        }
+        recorded.lock().unwrap().push(update);
+    })
+    .await
+    .unwrap();
+
+    let crawl = newest_crawl().await;
+    let crawl_id = crawl.id.unwrap();
+    assert_eq!(crawl.status, CrawlStatus::Cancelled);
+    assert!(crawl.finished_at.is_some());
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 1);
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 0);
+    let updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert!(matches!(updates.last(), Some(CrawlResult::Cancelled(id, 2)) if *id == crawl_id));
+    assert!(app.resume_crawl(crawl_id, CrawlOptions::new(1), |_| {}).await.is_err());
+    assert!(app.cancel_crawl(crawl_id).await.is_err());
+
+    // An interrupted crawl is given up afterwards
+    let stop = StopSignal::new();
+    let signal = stop.clone();
+    let options = CrawlOptions::new(1).with_stop_signal(stop);
+    app.new_crawl_with_options(site_id, options, move |update| {
+        if matches!(update, CrawlResult::PageSucceeded(_)) {
+            signal.stop();
+        }
+    })
+    .await
+    .unwrap();
+    let crawl_id = newest_crawl().await.id.unwrap();
+    assert_eq!(newest_crawl().await.status, CrawlStatus::Interrupted);
+    assert_eq!(app.cancel_crawl(crawl_id).await.unwrap(), 2);
+    assert_eq!(newest_crawl().await.status, CrawlStatus::Cancelled);
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 0);
+    assert!(app.cancel_crawl(99).await.is_err());
+}
+
+#[tokio::test]
+async fn test_crawl_archives_assets() {
+    let site = FakeSite::builder()
+        .page(
+            "/",
+            &fixtures::page(
+                "Home",
+                r#"<link rel="stylesheet" href="/site.css"><script src="/app.js"></script>
+                   <link rel="preload" as="fetch" href="/api/data"><script src="https://cdn.example/lib.js"></script>"#,
+            ),
+        )
+        .page("/about", &fixtures::page("About", r#"<link rel="stylesheet" href="/site.css"><script src="/big.js"></script>"#))
+        .file("/site.css", "text/css", "h1 { color: red }")
+        .file("/app.js", "text/plain", "init();")
+        .file("/api/data", "text/plain", r#"{"@type": "Product", "name": "Pro"}"#)
+        .file("/big.js", "application/javascript", &"x".repeat(2048))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = warnings.clone();
+    let limits = AssetLimits::default().with_max_bytes(AssetKind::Js, 1024);
+    let options = CrawlOptions::new(2).with_assets(limits);
+    app.new_crawl_with_options(site_id, options, move |update| {
+        if let CrawlResult::Warning(message) = update {
+            recorded.lock().unwrap().push(message);
+        }
+    })
+    .await
+    .unwrap();
+
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    let assets = app.list_assets(crawl_id).await.unwrap();
+    let found: Vec<(String, AssetKind)> = assets.iter().map(|asset| (asset.url.clone(), asset.kind)).collect();
+    assert_eq!(
+        found,
+        [(site.url("/api/data"), AssetKind::Json), (site.url("/app.js"), AssetKind::Js), (site.url("/site.css"), AssetKind::Css)]
+    );
+    let css = app.fetch_asset(crawl_id, &site.url("/site.css")).await.unwrap();
+    assert_eq!((css.content.as_str(), css.size()), ("h1 { color: red }", 17));
+    let warnings = warnings.lock().unwrap().clone();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("big.js") && warnings[0].contains("1024 byte limit"));
+    // The shared stylesheet was fetched once: sitemap, robots.txt, 2 pages
+    // and 4 assets
+    assert_eq!(site.request_count().await, 8);
+
+    // Off unless asked for
+    app.new_crawl(site_id, 2, |_| {}).await.unwrap();
+    let newest = app.list_crawls().await.unwrap().into_iter().max_by_key(|crawl| crawl.id).unwrap();
+    assert!(app.list_assets(newest.id.unwrap()).await.unwrap().is_empty());
+    assert!(app.fetch_asset(newest.id.unwrap(), &site.url("/site.css")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_crawl_tagged_sites() {
+    let shop = FakeSite::builder()
+        .page("/", &fixtures::page("Shop", "<h1>Shop</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .start()
+        .await;
+    let blog = FakeSite::builder().page("/", &fixtures::page("Blog", "<h1>Blog</h1>")).missing("/gone").start().await;
+    let untagged = FakeSite::builder().page("/", &fixtures::page("Other", "<p>Other</p>")).start().await;
+    let app = create_test_app().await;
+    let shop_id = shop.add_to(&app).await.unwrap();
+    let blog_id = blog.add_to(&app).await.unwrap();
+    let untagged_id = untagged.add_to(&app).await.unwrap();
+    // A site whose sitemap can't be fetched doesn't stop the others
+    let broken = app
+        .new_site_with_validation(&format!("{}/broken", blog.base_url()), &blog.url("/gone"), SiteValidation::Skip)
+        .await
+        .unwrap();
+    let broken_id = broken.id.unwrap();
+    for site_id in [shop_id, blog_id, broken_id] {
+        assert!(app.tag_site(site_id, "Client-X").await.unwrap());
+    }
+    assert!(!app.tag_site(shop_id, "client-x").await.unwrap());
+    assert!(app.tag_site(99, "client-x").await.is_err());
+    assert_eq!(app.list_site_tags(shop_id).await.unwrap(), ["client-x"]);
+
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    let crawls = app
+        .new_crawls(Some("client-x"), CrawlOptions::new(2), 4, move |site_id, update| {
+            if matches!(update, CrawlResult::PageSucceeded(_)) {
+                recorded.lock().unwrap().push(site_id);
+            }
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(crawls.iter().map(|crawl| crawl.site_id).collect::<Vec<_>>(), [shop_id, blog_id, broken_id]);
+    assert_eq!((crawls[0].pages_succeeded, crawls[0].pages_failed), (2, 0));
+    assert_eq!(crawls[1].pages_succeeded, 1);
+    assert!(crawls[0].crawl_id.is_some() && crawls[1].crawl_id.is_some());
+    assert!(crawls[0].error.is_none() && crawls[1].error.is_none());
+    assert!(crawls[2].error.is_some());
+    let mut pages_by_site = updates.lock().unwrap().clone();
+    pages_by_site.sort();
+    assert_eq!(pages_by_site, [shop_id, shop_id, blog_id]);
+    assert_eq!(untagged.request_count().await, 0);
+    let crawled_sites: Vec<i64> = app.list_crawls().await.unwrap().into_iter().map(|crawl| crawl.site_id).collect();
+    assert!(!crawled_sites.contains(&untagged_id));
+
+    // Without a tag every site is crawled, tagged or not
+    assert!(app.untag_site(blog_id, "client-x").await.unwrap());
+    assert!(!app.untag_site(blog_id, "client-x").await.unwrap());
+    let crawls = app.new_crawls(None, CrawlOptions::new(2), 1, |_, _| {}).await.unwrap();
+    assert_eq!(crawls.len(), 4);
+    assert_eq!(crawls.iter().find(|crawl| crawl.site_id == untagged_id).unwrap().pages_succeeded, 1);
+    assert!(app.new_crawls(Some("nobody"), CrawlOptions::new(2), 4, |_, _| {}).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_crawl_following_links() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", r#"<a href="/pricing">Pricing</a> <a href="https://elsewhere.example/">Out</a>"#))
+        .page("/pricing", &fixtures::page("Pricing", r#"<a href="/">Home</a> <a href="about#team">About</a>"#))
+        .page("/about", &fixtures::page("About", r#"<a href="/jobs">Jobs</a>"#))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+    let newest_crawl = || async { app.list_crawls().await.unwrap().into_iter().filter_map(|crawl| crawl.id).max().unwrap() };
+
+    // Two hops from the root, without reading the sitemap
+    let following = LinkFollowing { max_depth: 2, ..LinkFollowing::default() };
+    let options = CrawlOptions::new(2).with_mode(CrawlMode::FollowLinks(following));
+    app.new_crawl_with_options(site_id, options, |_| {}).await.unwrap();
+    let mut urls = app.list_page_urls(newest_crawl().await).await.unwrap();
+    urls.sort();
+    assert_eq!(urls, [site.url("/"), site.url("/about"), site.url("/pricing")]);
+    assert_eq!(site.request_count().await, 4);
+
+    // From a seed, capped at two pages
+    let following = LinkFollowing { seeds: vec![site.url("/pricing")], max_depth: 5, max_pages: 2 };
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    let options = CrawlOptions::new(2).with_mode(CrawlMode::FollowLinks(following));
+    app.new_crawl_with_options(site_id, options, move |update| recorded.lock().unwrap().push(update))
+        .await
+        .unwrap();
+    let crawl_id = newest_crawl().await;
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap(), [site.url("/pricing"), site.url("/")]);
+    assert_eq!(app.pending_urls(crawl_id).await.unwrap(), 0);
+    let updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert!(updates.iter().any(|update| matches!(update, CrawlResult::Warning(message) if message.contains("first 2 pages"))));
+
+    // A limit of 0 still fetches the seed, and nothing past it
+    let following = LinkFollowing { seeds: vec![site.url("/pricing")], max_depth: 5, max_pages: 0 };
+    let options = CrawlOptions::new(2).with_mode(CrawlMode::FollowLinks(following));
+    app.new_crawl_with_options(site_id, options, |_| {}).await.unwrap();
+    assert_eq!(app.list_page_urls(newest_crawl().await).await.unwrap(), [site.url("/pricing")]);
+}
+
+#[tokio::test]
+async fn test_crawl_rotates_user_agents() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .page("/about", &fixtures::page("About", "<p>Us</p>"))
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    // One page at a time, so the agents are used in a known order
+    let options = CrawlOptions::new(1).with_user_agents(vec!["Browser/1".to_string(), "Bot/2".to_string()]);
+    app.new_crawl_with_options(site_id, options, |_| {}).await.unwrap();
+
+    let rows = app.raw_query("SELECT user_agent FROM pages ORDER BY id").await.unwrap();
+    let agents: Vec<&Value> = rows.rows.iter().map(|row| &row[0]).collect();
+    let expected = ["Browser/1", "Bot/2", "Browser/1"].map(|agent| Value::Text(agent.to_string()));
+    assert_eq!(agents, expected.iter().collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_crawl_follows_sitemap_index() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .page("/pricing", &fixtures::product_page("Pro", "$10"))
+        .page("/about", &fixtures::page("About", "<p>Us</p>"))
+        .sitemap_index(2)
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let site_id = site.add_to(&app).await.unwrap();
+
+    let estimate = app.estimate_crawl(site_id).await.unwrap();
+    assert_eq!((estimate.urls, estimate.sitemap_requests), (3, 3));
+
+    app.new_crawl(site_id, 2, |_| {}).await.unwrap();
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 3);
+
+    // The cap is reached with the first child sitemap, the second is never fetched
+    let requests = site.request_count().await;
+    let limits = SitemapLimits { max_urls: Some(2), ..SitemapLimits::default() };
+    let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = updates.clone();
+    app.new_crawl_with_options(site_id, CrawlOptions::new(2).with_sitemap_limits(limits), move |update| {
+        recorded.lock().unwrap().push(update)
+    })
+    .await
+    .unwrap();
+    let crawl_id = app.list_crawls().await.unwrap().iter().filter_map(|crawl| crawl.id).max().unwrap();
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap().len(), 2);
+    assert_eq!(site.request_count().await - requests, 5);
+    let updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert!(updates.iter().any(|update| matches!(update, CrawlResult::Warning(message) if message.contains("first 2"))));
+}
+
+#[tokio::test]
+async fn test_crawl_of_broken_sitemap_leaves_no_crawl() {
+    let site = FakeSite::builder()
+        .page("/", &fixtures::page("Home", "<h1>Welcome</h1>"))
+        .file("/broken.xml", "application/xml", "<urlset><url><loc>/</loc></url><url></urlset>")
+        .start()
+        .await;
+    let app = create_test_app().await;
+    let broken = app
+        .new_site_with_validation(&site.base_url(), &site.url("/broken.xml"), SiteValidation::Format)
+        .await
+        .unwrap();
+
+    assert!(app.new_crawl(broken.id.unwrap(), 2, |_| {}).await.is_err());
+    assert!(app.list_crawls().await.unwrap().is_empty());
+    assert_eq!(site.request_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_read_only() {
+    use palimp_core::export::ExportOptions;
+    use palimp_core::jobs::Worker;
+    use palimp_core::page::Page;
+    use palimp_core::ReadOnlyError;
+
+    let mut db = seeded_database(Arc::new(ManualClock::at_unix(0)));
+    let mut site = Site::new(None, "test.com", "https://test.com/sitemap.xml");
+    site.sync(&mut db).unwrap();
+    let site_id = site.id.unwrap();
+    let mut crawl = Crawl::new(None, site_id);
+    crawl.sync(&mut db).unwrap();
+    let crawl_id = crawl.id.unwrap();
+    Page::new("http://test.com/", "http://test.com/", "<h1>Home</h1>", Some(crawl_id)).unwrap().sync(&mut db).unwrap();
+    // A crawl archived before pages were indexed for search
+    let mut legacy = Crawl::new(None, site_id);
+    legacy.sync(&mut db).unwrap();
+    let legacy_id = legacy.id.unwrap();
+    Page::new("http://test.com/", "http://test.com/", "<h1>Old</h1>", Some(legacy_id)).unwrap().sync(&mut db).unwrap();
+    db.conn.execute("DELETE FROM pages_fts WHERE rowid = (SELECT MAX(id) FROM pages)", []).unwrap();
+
+    let app = app_with(db).with_read_only(true);
+    assert!(app.is_read_only());
+    let refused = |result: Result<(), Box<dyn std::error::Error>>| {
+        result.is_err_and(|err| err.downcast_ref::<ReadOnlyError>().is_some())
+    };
+
+    // Listing, querying, exporting and viewing pages work
+    assert_eq!(app.list_sites().await.unwrap().len(), 1);
+    assert_eq!(app.query(crawl_id, "h1").await.unwrap().len(), 1);
+    let mut csv = Vec::new();
+    assert_eq!(app.export_results(crawl_id, "h1", &ExportOptions::default(), &mut csv).await.unwrap(), 1);
+    assert_eq!(app.list_page_urls(crawl_id).await.unwrap(), ["http://test.com/"]);
+    assert_eq!(app.page_history(site_id, "http://test.com/").await.unwrap().versions.len(), 2);
+    assert_eq!(app.search_pages(crawl_id, "Home").await.unwrap().len(), 1);
+
+    // Crawls, deletions and edits don't
+    assert!(refused(app.new_crawl(site_id, 1, |_| {}).await));
+    assert!(refused(app.delete_site(site_id).await));
+    assert!(refused(app.delete_crawl(crawl_id).await));
+    assert!(refused(app.set_crawl_pinned(crawl_id, true).await));
+    assert!(refused(app.update_site(site_id, Some("other.com"), None).await.map(|_| ())));
+    assert!(refused(Worker::new(&app).run_next(&StopSignal::new(), |_| {}).await.map(|_| ())));
+    // Searching would index the older crawl first
+    assert!(refused(app.search_pages(legacy_id, "Old").await.map(|_| ())));
+    assert_eq!(app.list_crawls().await.unwrap().len(), 2);
+    assert_eq!(app.list_sites().await.unwrap()[0].domain, "test.com");
+}
+
+#[tokio::test]
+async fn test_audit_log() {
+    use palimp_core::audit_log::AuditAction;
+
+    let site = FakeSite::builder().page("/", &fixtures::page("Home", "<h1>Welcome</h1>")).start().await;
+    let app = create_test_app().await.with_actor("alice");
+    let site_id = site.add_to(&app).await.unwrap();
+
+    app.new_crawl(site_id, 1, |_| {}).await.expect("Crawl should finish");
+    let crawl_id = app.list_crawls().await.unwrap()[0].id.unwrap();
+    app.query_with_details(crawl_id, "h1", Some("Headings"), None).await.unwrap();
+    let query_id = app.list_queries().await.unwrap()[0].id.unwrap();
+
+    app.delete_query(query_id).await.unwrap();
+    app.delete_crawls(&[crawl_id]).await.unwrap();
+    app.delete_site(site_id).await.unwrap();
+    app.purge_trash(None).await.unwrap();
+    // A refused deletion leaves no trace
+    assert!(app.delete_site(site_id).await.is_err());
+
+    let events = app.list_audit_log(None, 10).await.unwrap();
+    let actions: Vec<AuditAction> = events.iter().map(|event| event.action).collect();
+    assert_eq!(
+        actions,
+        [
+            AuditAction::PurgeTrash,
+            AuditAction::DeleteSite,
+            AuditAction::DeleteCrawl,
+            AuditAction::DeleteQuery,
+            AuditAction::StartCrawl,
+        ]
+    );
+    assert!(events.iter().all(|event| event.actor.as_deref() == Some("alice") && event.created_at.is_some()));
+    assert_eq!(events[0].detail, "1 sites, 1 crawls, 1 queries, 1 results");
+    assert_eq!(events[2].target_id, Some(crawl_id));
+    assert_eq!(events[3].detail, format!("'Headings' on crawl {}", crawl_id));
+    assert_eq!(events[4].target_id, Some(site_id));
+
+    let starts = app.list_audit_log(Some(AuditAction::StartCrawl), 10).await.unwrap();
+    assert_eq!(starts.len(), 1);
+    assert_eq!(app.list_audit_log(None, 2).await.unwrap().len(), 2);
+}
@@ -1,3 +1,4 @@
 fn main() {
-    slint_build::compile("ui/appwindow.slint").unwrap();
+    let config = slint_build::CompilerConfiguration::new().with_bundled_translations("lang");
+    slint_build::compile_with_config("ui/appwindow.slint", config).unwrap();
 }
@@ -1,4 +1,12 @@
 use palimp_core::{Application, CrawlResult};
+use palimp_core::crawl::{CrawlOptions, StopSignal};
+use palimp_core::estimate::{format_bytes, format_duration, CrawlEstimate};
+use palimp_core::export::ExportOptions;
+use palimp_core::http_client::HTTPClient;
+use palimp_core::jobs::{Job, JobKind, Worker};
+use palimp_core::preview::SelectorPreview;
+use palimp_core::result_entry::ResultFilter;
+use slint::winit_030::{winit::event::WindowEvent, EventResult, WinitWindowAccessor};
 use slint::{Model, ModelRc, SharedString, StandardListViewItem, VecModel, Weak};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -7,29 +15,71 @@ use tokio::sync::mpsc;
 
 slint::include_modules!();
 
-// Commands sent from UI to Logic Thread
+mod tray;
+
+// Commands sent from UI to Logic Thread. Some have no UI trigger yet.
+#[allow(dead_code)]
 enum AppCommand {
     AddSite { domain: String, sitemap: String },
     DeleteSite { id: i64 },
     LoadCrawlsForSite { site_id: i64 },
     StartCrawl { site_id: i64, concurrency: usize },
     DeleteCrawl { id: i64 },
+    DeleteCrawls { site_id: i64, ids: Vec<i64> },
+    PinCrawl { site_id: i64, id: i64, pinned: bool },
+    DeleteResults { ids: Vec<i64> },
     RunQuery { crawl_id: i64, selector: String },
+    Search { crawl_id: i64, phrase: String },
+    LoadPreviewPages { crawl_id: i64 },
+    PreviewSelector { crawl_id: i64, url: String, selector: String },
+    PreviewUrl { url: String, selector: String },
+    LoadTrends { site_id: i64, selector: String },
+    LoadPageHistory { site_id: i64, url: String },
+    DiffVersions { url: String, before_crawl_id: i64, after_crawl_id: i64 },
+    ExportResults { crawl_id: i64, selector: String, path: std::path::PathBuf },
     RefreshAll,
 }
 
+// Crawls running from the crawl dialog, stopped and awaited on exit
+type ActiveCrawls = Arc<Mutex<Vec<(StopSignal, std::thread::JoinHandle<()>)>>>;
+
+// URLs of a file dropped onto the window
+struct UrlListFile { name: String, urls: Vec<String> }
+
 // Data structures for passing to UI thread (Send-safe)
 #[derive(Clone)]
+#[allow(dead_code)]
 struct SiteData { id: String, domain: String, sitemap: String }
 #[derive(Clone)]
-struct CrawlData { id: String, started_at: String }
+struct CrawlData { id: String, started_at: String, duration: String, pinned: bool }
 #[derive(Clone)]
 struct ResultData { id: String, page_url: String, count: String }
 
+/// Environment variable selecting the UI language, e.g. `de` or `en`.
+const LANG_ENV: &str = "PALIMP_LANG";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let ui = AppWindow::new()?;
     let ui_weak = ui.as_weak();
 
+    // The UI language follows the system locale; PALIMP_LANG overrides it
+    if let Ok(lang) = std::env::var(LANG_ENV)
+        && let Err(e) = slint::select_bundled_translation(&lang)
+    {
+        eprintln!("Cannot select language '{}': {}", lang, e);
+    }
+
+    // Built-in selector templates for the query panel
+    let templates = palimp_core::template::all();
+    let template_names: Vec<SharedString> = templates.iter()
+        .map(|t| SharedString::from(format!("{} — {}", t.name, t.description)))
+        .collect();
+    let template_selectors: Vec<SharedString> = templates.iter()
+        .map(|t| SharedString::from(t.selector))
+        .collect();
+    ui.set_template_names(ModelRc::from(Rc::new(VecModel::from(template_names))));
+    ui.set_template_selectors(ModelRc::from(Rc::new(VecModel::from(template_selectors))));
+
     // Channel for communication: UI -> Logic
     let (tx, mut rx) = mpsc::channel::<AppCommand>(32);
     
@@ -42,6 +92,52 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Map site index to site ID
     let site_index_map = Arc::new(Mutex::new(Vec::<i64>::new()));
 
+    // Spawn Job Worker Thread, so long jobs never block the logic thread
+    let worker_stop = StopSignal::new();
+    let worker_handle = {
+        let stop = worker_stop.clone();
+        let tx = tx.clone();
+        let ui_weak = ui.as_weak();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build tokio runtime");
+
+            rt.block_on(async move {
+                let app = match open_app() {
+                    Ok(app) => app,
+                    Err(e) => {
+                        eprintln!("Failed to initialize job worker: {}", e);
+                        return;
+                    }
+                };
+
+                let result = Worker::new(&app)
+                    .run(std::time::Duration::from_secs(1), &stop, |job| {
+                        println!("Job {} {}: {}", job.id.unwrap_or(0), job.status.name(), job.kind.describe());
+                        if let Some(message) = &job.message {
+                            println!("  {}", message);
+                        }
+
+                        // Show the new crawl once its job is done, and tell the
+                        // user in case the window is closed to the tray
+                        if let JobKind::Crawl { site_id, .. } = job.kind
+                            && job.status.is_finished()
+                        {
+                            let _ = tx.try_send(AppCommand::LoadCrawlsForSite { site_id });
+                            notify_job_finished(job, &ui_weak);
+                        }
+                    })
+                    .await;
+
+                if let Err(e) = result {
+                    eprintln!("Job worker stopped: {}", e);
+                }
+            });
+        })
+    };
+
     // Spawn Logic Thread (Single-threaded Tokio Runtime)
     let results_cache_clone = Arc::clone(&results_cache);
     let selected_crawl_cache_clone = Arc::clone(&selected_crawl_cache);
@@ -54,7 +150,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .expect("Failed to build tokio runtime");
 
         rt.block_on(async move {
-            let app = match Application::new("palimp.db") {
+            let app = match open_app() {
                 Ok(app) => Arc::new(app),
                 Err(e) => {
                     eprintln!("Failed to initialize application: {}", e);
@@ -89,48 +185,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         refresh_crawls_for_site(&app, &ui_weak_for_thread, site_id).await;
                         
                         // Check if we have a previously selected crawl for this site
-                        if let Ok(cache) = selected_crawl_cache_clone.lock() {
-                            if let Some(&crawl_id) = cache.get(&site_id) {
-                                // Restore cached results for the previously selected crawl
-                                if let Ok(results_cache) = results_cache_clone.lock() {
-                                    if let Some(cached_results) = results_cache.get(&crawl_id) {
-                                        let cached_results = cached_results.clone();
-                                        let ui_weak_clone = ui_weak_for_thread.clone();
-                                        let _ = ui_weak_clone.upgrade_in_event_loop(move |ui| {
-                                            let mut items = Vec::new();
-                                            for r in cached_results {
-                                                let row = Rc::new(VecModel::from(vec![
-                                                    StandardListViewItem::from(SharedString::from(r.id)),
-                                                    StandardListViewItem::from(SharedString::from(r.page_url)),
-                                                    StandardListViewItem::from(SharedString::from(r.count)),
-                                                ]));
-                                                items.push(ModelRc::from(row));
-                                            }
-                                            ui.set_results(ModelRc::from(Rc::new(VecModel::from(items))));
-                                        });
-                                    }
-                                }
-                            }
+                        if let Ok(cache) = selected_crawl_cache_clone.lock()
+                            && let Some(&crawl_id) = cache.get(&site_id)
+                            // Restore cached results for the previously selected crawl
+                            && let Ok(results_cache) = results_cache_clone.lock()
+                            && let Some(cached_results) = results_cache.get(&crawl_id)
+                        {
+                            let cached_results = cached_results.clone();
+                            let ui_weak_clone = ui_weak_for_thread.clone();
+                            let _ = ui_weak_clone.upgrade_in_event_loop(move |ui| {
+                                set_result_rows(&ui, cached_results);
+                            });
                         }
                     }
                     AppCommand::StartCrawl { site_id, concurrency } => {
-                        println!("Starting crawl for site {}...", site_id);
-                        let result = app.new_crawl(site_id, concurrency, |res| {
-                             match res {
-                                CrawlResult::CrawlStarted(total) => println!("Crawling {} pages...", total),
-                                CrawlResult::PageSucceeded(url) => println!("  [OK] {}", url),
-                                CrawlResult::PageFailed(url, err) => eprintln!("  [ERR] {}: {}", url, err),
-                            }
-                        }).await;
-                        
-                        if let Err(e) = result {
-                            eprintln!("Crawl failed: {}", e);
-                        } else {
-                            println!("Crawl finished.");
+                        match app.submit_job(JobKind::Crawl { site_id, max_concurrent: concurrency }, 0).await {
+                            Ok(job) => println!("Crawl of site {} queued as job {}.", site_id, job.id.unwrap_or(0)),
+                            Err(e) => eprintln!("Error queueing crawl: {}", e),
                         }
-                        
-                        // Refresh crawls for this site
-                        refresh_crawls_for_site(&app, &ui_weak_for_thread, site_id).await;
                     }
                     AppCommand::DeleteCrawl { id } => {
                         if let Err(e) = app.delete_crawl(id).await {
@@ -138,12 +210,52 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                         // Clear results when crawl is deleted
                         let _ = ui_weak_for_thread.upgrade_in_event_loop(|ui| {
-                            ui.set_results(ModelRc::from(Rc::new(VecModel::from(vec![]))));
+                            set_result_rows(&ui, Vec::new());
                         });
                         
                         // Note: We'd need to know which site to refresh. For now, we'll need to track this.
                         // Simplified: just clear the UI results
                     }
+                    AppCommand::DeleteCrawls { site_id, ids } => {
+                        if let Err(e) = app.delete_crawls(&ids).await {
+                            eprintln!("Error deleting crawls: {}", e);
+                        } else if let Ok(mut cache) = results_cache_clone.lock() {
+                            cache.retain(|crawl_id, _| !ids.contains(crawl_id));
+                        }
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(|ui| {
+                            set_result_rows(&ui, Vec::new());
+                        });
+                        refresh_crawls_for_site(&app, &ui_weak_for_thread, site_id).await;
+                    }
+                    AppCommand::PinCrawl { site_id, id, pinned } => {
+                        if let Err(e) = app.set_crawl_pinned(id, pinned).await {
+                            eprintln!("Error pinning crawl: {}", e);
+                        }
+                        refresh_crawls_for_site(&app, &ui_weak_for_thread, site_id).await;
+                    }
+                    AppCommand::DeleteResults { ids } => {
+                        if let Err(e) = app.delete_results(&ids).await {
+                            eprintln!("Error deleting results: {}", e);
+                            continue;
+                        }
+                        let deleted: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                        if let Ok(mut cache) = results_cache_clone.lock() {
+                            for results in cache.values_mut() {
+                                results.retain(|r| !deleted.contains(&r.id));
+                            }
+                        }
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let remaining: Vec<ResultData> = ui.get_results().iter()
+                                .map(|row| ResultData {
+                                    id: row.row_data(1).map(|c| c.text.to_string()).unwrap_or_default(),
+                                    page_url: row.row_data(2).map(|c| c.text.to_string()).unwrap_or_default(),
+                                    count: row.row_data(3).map(|c| c.text.to_string()).unwrap_or_default(),
+                                })
+                                .filter(|r| !deleted.contains(&r.id))
+                                .collect();
+                            set_result_rows(&ui, remaining);
+                        });
+                    }
                     AppCommand::RunQuery { crawl_id, selector } => {
                         if let Err(e) = app.query(crawl_id, &selector).await {
                             eprintln!("Error running query: {}", e);
@@ -156,35 +268,186 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .filter(|q| q.crawl_id == crawl_id)
                             .max_by_key(|q| q.id);
                         
-                        if let Some(query) = latest_query {
-                            if let Some(query_id) = query.id {
-                                // Fetch results
-                                let results = app.list_results_for_query(query_id).await.unwrap_or_default();
-                                let data: Vec<ResultData> = results.into_iter().map(|(r, url)| ResultData {
-                                    id: r.id.unwrap_or(0).to_string(),
-                                    page_url: url,
-                                    count: r.count.to_string(),
-                                }).collect();
-                                
-                                // Cache results for this crawl
-                                if let Ok(mut cache) = results_cache_clone.lock() {
-                                    cache.insert(crawl_id, data.clone());
-                                }
-                                
-                                // Update UI
-                                let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
-                                    let mut items = Vec::new();
-                                    for r in data {
-                                        let row = Rc::new(VecModel::from(vec![
-                                            StandardListViewItem::from(SharedString::from(r.id)),
-                                            StandardListViewItem::from(SharedString::from(r.page_url)),
-                                            StandardListViewItem::from(SharedString::from(r.count)),
-                                        ]));
-                                        items.push(ModelRc::from(row));
-                                    }
-                                    ui.set_results(ModelRc::from(Rc::new(VecModel::from(items))));
-                                });
+                        if let Some(query) = latest_query
+                            && let Some(query_id) = query.id
+                        {
+                            // Fetch results
+                            let results = app.search_results(&ResultFilter::for_query(query_id)).await.unwrap_or_default();
+                            let data: Vec<ResultData> = results.into_iter().map(|(r, url)| ResultData {
+                                id: r.id.unwrap_or(0).to_string(),
+                                page_url: url,
+                                count: r.count.to_string(),
+                            }).collect();
+                            
+                            // Cache results for this crawl
+                            if let Ok(mut cache) = results_cache_clone.lock() {
+                                cache.insert(crawl_id, data.clone());
                             }
+                            
+                            // Update UI
+                            let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                                set_result_rows(&ui, data);
+                            });
+                        }
+                    }
+                    AppCommand::Search { crawl_id, phrase } => {
+                        let hits = match app.search_pages(crawl_id, &phrase).await {
+                            Ok(hits) => hits,
+                            Err(e) => {
+                                eprintln!("Error searching pages: {}", e);
+                                continue;
+                            }
+                        };
+
+                        let data: Vec<(String, String)> = hits.into_iter()
+                            .map(|hit| (hit.url, hit.snippet.replace('\n', " ")))
+                            .collect();
+
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let mut items = Vec::new();
+                            for (url, snippet) in data {
+                                let row = Rc::new(VecModel::from(vec![
+                                    StandardListViewItem::from(SharedString::from(url)),
+                                    StandardListViewItem::from(SharedString::from(snippet)),
+                                ]));
+                                items.push(ModelRc::from(row));
+                            }
+                            ui.set_search_results(ModelRc::from(Rc::new(VecModel::from(items))));
+                        });
+                    }
+                    AppCommand::LoadPreviewPages { crawl_id } => {
+                        let urls = match app.list_page_urls(crawl_id).await {
+                            Ok(urls) => urls,
+                            Err(e) => {
+                                eprintln!("Error loading pages: {}", e);
+                                continue;
+                            }
+                        };
+
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let urls: Vec<SharedString> = urls.into_iter().map(SharedString::from).collect();
+                            ui.set_preview_pages(ModelRc::from(Rc::new(VecModel::from(urls))));
+                            set_preview(&ui, Ok(SelectorPreview::default()));
+                        });
+                    }
+                    AppCommand::PreviewSelector { crawl_id, url, selector } => {
+                        let preview = app.preview_selector(crawl_id, &url, &selector).await
+                            .map_err(|e| e.to_string());
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            set_preview(&ui, preview);
+                        });
+                    }
+                    AppCommand::PreviewUrl { url, selector } => {
+                        let preview = app.preview(&url, &selector).await
+                            .map_err(|e| e.to_string());
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            set_preview(&ui, preview);
+                        });
+                    }
+                    AppCommand::LoadTrends { site_id, selector } => {
+                        let history = match app.query_history(site_id, &selector).await {
+                            Ok(history) => history,
+                            Err(e) => {
+                                eprintln!("Error loading trends: {}", e);
+                                continue;
+                            }
+                        };
+
+                        let counts: Vec<usize> = history.crawls.iter().map(|c| c.total).collect();
+                        let pages: Vec<usize> = history.crawls.iter().map(|c| c.pages).collect();
+                        let max = counts.iter().chain(&pages).copied().max().unwrap_or(0);
+                        let count_path = trend_path(&counts, max);
+                        let pages_path = trend_path(&pages, max);
+                        let crawl_count = history.crawls.len();
+
+                        let data: Vec<[String; 5]> = history.crawls.into_iter()
+                            .map(|c| [
+                                c.crawl_id.to_string(),
+                                c.started_at.unwrap_or_default(),
+                                c.pages.to_string(),
+                                c.matching_pages.to_string(),
+                                c.total.to_string(),
+                            ])
+                            .collect();
+
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let mut items = Vec::new();
+                            for cells in data {
+                                let row = Rc::new(VecModel::from(
+                                    cells.into_iter()
+                                        .map(|cell| StandardListViewItem::from(SharedString::from(cell)))
+                                        .collect::<Vec<_>>()
+                                ));
+                                items.push(ModelRc::from(row));
+                            }
+                            ui.set_trend_rows(ModelRc::from(Rc::new(VecModel::from(items))));
+                            ui.set_trend_count_path(SharedString::from(count_path));
+                            ui.set_trend_pages_path(SharedString::from(pages_path));
+                            ui.set_trend_max(max as i32);
+                            ui.set_trend_crawls(crawl_count as i32);
+                        });
+                    }
+                    AppCommand::LoadPageHistory { site_id, url } => {
+                        let history = match app.page_history(site_id, &url).await {
+                            Ok(history) => history,
+                            Err(e) => {
+                                eprintln!("Error loading page history: {}", e);
+                                continue;
+                            }
+                        };
+
+                        let crawl_ids: Vec<String> = history.versions.iter()
+                            .filter(|v| v.hash.is_some())
+                            .map(|v| v.crawl_id.to_string())
+                            .collect();
+                        let data: Vec<[String; 6]> = history.versions.into_iter()
+                            .map(|v| [
+                                v.crawl_id.to_string(),
+                                v.started_at.unwrap_or_default(),
+                                v.status.name().to_string(),
+                                v.hash.as_deref().map(|hash| hash[..12].to_string()).unwrap_or_default(),
+                                if v.hash.is_some() { format_bytes(v.bytes) } else { String::new() },
+                                if v.changed { "✓".to_string() } else { String::new() },
+                            ])
+                            .collect();
+
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let mut items = Vec::new();
+                            for cells in data {
+                                let row = Rc::new(VecModel::from(
+                                    cells.into_iter()
+                                        .map(|cell| StandardListViewItem::from(SharedString::from(cell)))
+                                        .collect::<Vec<_>>()
+                                ));
+                                items.push(ModelRc::from(row));
+                            }
+                            ui.set_history_rows(ModelRc::from(Rc::new(VecModel::from(items))));
+                            let crawl_ids: Vec<SharedString> = crawl_ids.into_iter().map(SharedString::from).collect();
+                            ui.set_history_crawl_ids(ModelRc::from(Rc::new(VecModel::from(crawl_ids))));
+                            ui.set_history_diff(SharedString::new());
+                        });
+                    }
+                    AppCommand::DiffVersions { url, before_crawl_id, after_crawl_id } => {
+                        let diff = app.text_diff(&url, before_crawl_id, after_crawl_id).await.map_err(|e| e.to_string());
+                        let _ = ui_weak_for_thread.upgrade_in_event_loop(move |ui| {
+                            let strings = ui.global::<Strings>();
+                            let diff = match diff {
+                                Ok(diff) if diff.is_empty() => strings.get_text_unchanged(),
+                                Ok(diff) => SharedString::from(diff),
+                                Err(e) => strings.invoke_diff_failed(e.into()),
+                            };
+                            ui.set_history_diff(diff);
+                        });
+                    }
+                    AppCommand::ExportResults { crawl_id, selector, path } => {
+                        let written = match (ExportOptions::from_env(), std::fs::File::create(&path)) {
+                            (Ok(options), Ok(file)) => app.export_results(crawl_id, &selector, &options, file).await,
+                            (Err(e), _) => Err(e),
+                            (_, Err(e)) => Err(e.into()),
+                        };
+                        match written {
+                            Ok(rows) => println!("Successfully exported {} results to: {}", rows, path.display()),
+                            Err(e) => eprintln!("Error exporting results: {}", e),
                         }
                     }
                     AppCommand::RefreshAll => {
@@ -229,142 +492,44 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let site_index_map_clone = Arc::clone(&site_index_map);
     ui.on_site_selected(move |site_index| {
         // Look up site ID from index
-        if let Ok(map) = site_index_map_clone.lock() {
-            if site_index >= 0 && (site_index as usize) < map.len() {
-                let site_id = map[site_index as usize];
-                let _ = tx_clone.blocking_send(AppCommand::LoadCrawlsForSite { site_id });
-            }
+        if let Ok(map) = site_index_map_clone.lock()
+            && site_index >= 0 && (site_index as usize) < map.len()
+        {
+            let site_id = map[site_index as usize];
+            let _ = tx_clone.blocking_send(AppCommand::LoadCrawlsForSite { site_id });
         }
     });
 
+    let active_crawls: ActiveCrawls = Arc::new(Mutex::new(Vec::new()));
+
     // Open Add Crawl Dialog
-    let tx_clone = tx.clone();
     let ui_weak_clone_for_crawl = ui_weak.clone();
+    let active_crawls_clone = Arc::clone(&active_crawls);
     ui.on_open_add_crawl_dialog(move |site_id_str| {
-        let dialog = AddCrawlDialog::new().unwrap();
-        let dialog_weak = dialog.as_weak();
-        let _tx_clone_inner = tx_clone.clone();
-        let site_id_str_clone = site_id_str.to_string();
-        let ui_weak_for_crawl = ui_weak_clone_for_crawl.clone();
-
-        dialog.on_start(move |_, concurrency_str| {
-            if let Ok(site_id) = site_id_str_clone.parse::<i64>() {
-                let concurrency = concurrency_str.parse::<usize>().unwrap_or(5);
-                let dialog_weak_inner = dialog_weak.clone();
-                let ui_weak_inner = ui_weak_for_crawl.clone();
-                
-                // Spawn the crawl in a background thread
-                std::thread::spawn(move || {
-                    let runtime = tokio::runtime::Runtime::new().unwrap();
-                    runtime.block_on(async {
-                        let app = match Application::new("palimp.db") {
-                            Ok(app) => app,
-                            Err(e) => {
-                                eprintln!("Failed to create application: {}", e);
-                                return;
-                            }
-                        };
-                        
-                        let total_pages = Arc::new(Mutex::new(0_usize));
-                        let processed_pages = Arc::new(Mutex::new(0_usize));
-                        let logs = Arc::new(Mutex::new(String::new()));
-                        
-                        let total_clone = Arc::clone(&total_pages);
-                        let processed_clone = Arc::clone(&processed_pages);
-                        let logs_clone = Arc::clone(&logs);
-                        let dialog_weak_clone = dialog_weak_inner.clone();
-                        
-                        let _result = app.new_crawl(site_id, concurrency, move |res| {
-                            match res {
-                                CrawlResult::CrawlStarted(total) => {
-                                    *total_clone.lock().unwrap() = total;
-                                    let log_entry = format!("Starting crawl of {} pages...\n", total);
-                                    let mut log_text = logs_clone.lock().unwrap();
-                                    log_text.push_str(&log_entry);
-                                    
-                                    let log_display = log_text.clone();
-                                    let dialog_weak_update = dialog_weak_clone.clone();
-                                    let _ = slint::invoke_from_event_loop(move || {
-                                        if let Some(d) = dialog_weak_update.upgrade() {
-                                            d.set_log_text(SharedString::from(log_display));
-                                        }
-                                    });
-                                }
-                                CrawlResult::PageSucceeded(url) => {
-                                    let mut processed = processed_clone.lock().unwrap();
-                                    *processed += 1;
-                                    let total = *total_clone.lock().unwrap();
-                                    
-                                    let log_entry = format!("[OK] {}\n", url);
-                                    let mut log_text = logs_clone.lock().unwrap();
-                                    log_text.push_str(&log_entry);
-                                    
-                                    let log_display = log_text.clone();
-                                    let progress = if total > 0 {
-                                        *processed as f32 / total as f32
-                                    } else {
-                                        0.0
-                                    };
-                                    
-                                    let dialog_weak_update = dialog_weak_clone.clone();
-                                    let _ = slint::invoke_from_event_loop(move || {
-                                        if let Some(d) = dialog_weak_update.upgrade() {
-                                            d.set_log_text(SharedString::from(log_display));
-                                            d.set_progress(progress);
-                                        }
-                                    });
-                                }
-                                CrawlResult::PageFailed(url, err) => {
-                                    let mut processed = processed_clone.lock().unwrap();
-                                    *processed += 1;
-                                    let total = *total_clone.lock().unwrap();
-                                    
-                                    let log_entry = format!("[ERR] {}: {}\n", url, err);
-                                    let mut log_text = logs_clone.lock().unwrap();
-                                    log_text.push_str(&log_entry);
-                                    
-                                    let log_display = log_text.clone();
-                                    let progress = if total > 0 {
-                                        *processed as f32 / total as f32
-                                    } else {
-                                        0.0
-                                    };
-                                    
-                                    let dialog_weak_update = dialog_weak_clone.clone();
-                                    let _ = slint::invoke_from_event_loop(move || {
-                                        if let Some(d) = dialog_weak_update.upgrade() {
-                                            d.set_log_text(SharedString::from(log_display));
-                                            d.set_progress(progress);
-                                        }
-                                    });
-                                }
-                            }
-                        }).await;
-                        
-                        // Crawl completed
-                        let _ = slint::invoke_from_event_loop(move || {
-                            if let Some(d) = dialog_weak_inner.upgrade() {
-                                d.set_is_crawling(false);
-                                d.set_crawl_completed(true);
-                                d.set_progress(1.0);
-                            }
-                        });
-                        
-                        // Refresh crawls list
-                        refresh_crawls_for_site(&app, &ui_weak_inner, site_id).await;
-                    });
-                });
-            }
-        });
-        
-        let dialog_weak = dialog.as_weak();
-        dialog.on_cancel_clicked(move || {
-            if let Some(d) = dialog_weak.upgrade() {
-                let _ = d.hide();
-            }
-        });
+        open_crawl_dialog(&site_id_str, None, ui_weak_clone_for_crawl.clone(), Arc::clone(&active_crawls_clone));
+    });
 
-        dialog.show().unwrap();
+    // Dropping a .txt or .xml URL list offers a crawl of those URLs
+    let ui_weak_clone = ui.as_weak();
+    let active_crawls_clone = Arc::clone(&active_crawls);
+    ui.window().on_winit_window_event(move |_, event| {
+        let WindowEvent::DroppedFile(path) = event else {
+            return EventResult::Propagate;
+        };
+        let Some(ui) = ui_weak_clone.upgrade() else {
+            return EventResult::Propagate;
+        };
+
+        let site_id = ui.get_selected_site_id();
+        if site_id.is_empty() {
+            eprintln!("Select a site before dropping a URL list.");
+            return EventResult::PreventDefault;
+        }
+        match read_url_list(path) {
+            Ok(list) => open_crawl_dialog(&site_id, Some(list), ui.as_weak(), Arc::clone(&active_crawls_clone)),
+            Err(e) => eprintln!("Cannot crawl {}: {}", path.display(), e),
+        }
+        EventResult::PreventDefault
     });
 
     // Delete crawl
@@ -375,6 +540,65 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    let tx_clone = tx.clone();
+    let ui_weak_clone = ui_weak.clone();
+    ui.on_request_pin_crawl(move |id_str, pinned| {
+        let Some(ui) = ui_weak_clone.upgrade() else { return };
+        if let (Ok(site_id), Ok(id)) = (ui.get_selected_site_id().parse::<i64>(), id_str.parse::<i64>()) {
+            let _ = tx_clone.blocking_send(AppCommand::PinCrawl { site_id, id, pinned });
+        }
+    });
+
+    // Multi-selection of crawls, one flag per crawl card
+    let ui_weak_clone = ui_weak.clone();
+    ui.on_crawl_toggled(move |index| {
+        let Some(ui) = ui_weak_clone.upgrade() else { return };
+        let selection = ui.get_crawl_selection();
+        if let Some(selected) = selection.row_data(index as usize) {
+            selection.set_row_data(index as usize, !selected);
+        }
+        ui.set_crawl_selected_count(selection.iter().filter(|&selected| selected).count() as i32);
+    });
+
+    let tx_clone = tx.clone();
+    let ui_weak_clone = ui_weak.clone();
+    ui.on_request_delete_selected_crawls(move || {
+        let Some(ui) = ui_weak_clone.upgrade() else { return };
+        let Ok(site_id) = ui.get_selected_site_id().parse::<i64>() else { return };
+        let ids: Vec<i64> = ui.get_crawls().iter()
+            .zip(ui.get_crawl_selection().iter())
+            .filter(|(_, selected)| *selected)
+            .filter_map(|(row, _)| row.row_data(0)?.text.parse().ok())
+            .collect();
+        if !ids.is_empty() {
+            let _ = tx_clone.blocking_send(AppCommand::DeleteCrawls { site_id, ids });
+        }
+    });
+
+    // Multi-selection of results, marked in the first column
+    let ui_weak_clone = ui_weak.clone();
+    ui.on_result_toggled(move |row_index| {
+        let Some(ui) = ui_weak_clone.upgrade() else { return };
+        let rows = ui.get_results();
+        if let Some(row) = rows.row_data(row_index as usize)
+            && let Some(mark) = row.row_data(0)
+        {
+            let toggled = if mark.text.is_empty() { SELECTED_MARK } else { "" };
+            row.set_row_data(0, StandardListViewItem::from(SharedString::from(toggled)));
+        }
+        ui.set_result_selected_count(selected_result_ids(&ui).len() as i32);
+    });
+
+    let tx_clone = tx.clone();
+    let ui_weak_clone = ui_weak.clone();
+    ui.on_request_delete_selected_results(move || {
+        let Some(ui) = ui_weak_clone.upgrade() else { return };
+        let ids = selected_result_ids(&ui);
+        if !ids.is_empty() {
+            let _ = tx_clone.blocking_send(AppCommand::DeleteResults { ids });
+        }
+    });
+
     // Run query
     let tx_clone = tx.clone();
     ui.on_request_run_query(move |crawl_id_str, selector| {
@@ -386,7 +610,74 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // Plot a selector across the crawls of a site
+    let tx_clone = tx.clone();
+    ui.on_request_trends(move |site_id_str, selector| {
+        if let Ok(site_id) = site_id_str.parse::<i64>() {
+            let _ = tx_clone.blocking_send(AppCommand::LoadTrends {
+                site_id,
+                selector: selector.to_string()
+            });
+        }
+    });
+
+    // List every version of a URL
+    let tx_clone = tx.clone();
+    ui.on_request_page_history(move |site_id_str, url| {
+        if let Ok(site_id) = site_id_str.parse::<i64>() {
+            let _ = tx_clone.blocking_send(AppCommand::LoadPageHistory {
+                site_id,
+                url: url.to_string()
+            });
+        }
+    });
+
+    // Diff two versions of the listed URL
+    let tx_clone = tx.clone();
+    ui.on_request_version_diff(move |url, before, after| {
+        if let (Ok(before_crawl_id), Ok(after_crawl_id)) = (before.parse::<i64>(), after.parse::<i64>()) {
+            let _ = tx_clone.blocking_send(AppCommand::DiffVersions {
+                url: url.to_string(),
+                before_crawl_id,
+                after_crawl_id
+            });
+        }
+    });
+
+    // Search page text
+    let tx_clone = tx.clone();
+    ui.on_request_search(move |crawl_id_str, phrase| {
+        if let Ok(crawl_id) = crawl_id_str.parse::<i64>() {
+            let _ = tx_clone.blocking_send(AppCommand::Search {
+                crawl_id,
+                phrase: phrase.to_string()
+            });
+        }
+    });
+
+    // Selector preview on a sample page
+    let tx_clone = tx.clone();
+    ui.on_request_preview(move |crawl_id_str, url, selector| {
+        if let Ok(crawl_id) = crawl_id_str.parse::<i64>() {
+            let _ = tx_clone.blocking_send(AppCommand::PreviewSelector {
+                crawl_id,
+                url: url.to_string(),
+                selector: selector.to_string()
+            });
+        }
+    });
+
+    // Selector preview on a URL fetched live
+    let tx_clone = tx.clone();
+    ui.on_request_live_preview(move |url, selector| {
+        let _ = tx_clone.blocking_send(AppCommand::PreviewUrl {
+            url: url.to_string(),
+            selector: selector.to_string()
+        });
+    });
+
     // Crawl selected - restore cached results and remember selection per site
+    let tx_clone = tx.clone();
     let results_cache_clone = Arc::clone(&results_cache);
     let _selected_crawl_cache_clone = Arc::clone(&selected_crawl_cache);
     let ui_weak_clone = ui_weak.clone();
@@ -398,107 +689,425 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let cached_results = cached_results.clone();
                     let ui_weak_inner = ui_weak_clone.clone();
                     let _ = ui_weak_inner.upgrade_in_event_loop(move |ui| {
-                        let mut items = Vec::new();
-                        for r in cached_results {
-                            let row = Rc::new(VecModel::from(vec![
-                                StandardListViewItem::from(SharedString::from(r.id)),
-                                StandardListViewItem::from(SharedString::from(r.page_url)),
-                                StandardListViewItem::from(SharedString::from(r.count)),
-                            ]));
-                            items.push(ModelRc::from(row));
-                        }
-                        ui.set_results(ModelRc::from(Rc::new(VecModel::from(items))));
+                        set_result_rows(&ui, cached_results);
                     });
                 } else {
                     // No cache, clear results
                     let ui_weak_inner = ui_weak_clone.clone();
                     let _ = ui_weak_inner.upgrade_in_event_loop(|ui| {
-                        ui.set_results(ModelRc::from(Rc::new(VecModel::from(vec![]))));
+                        set_result_rows(&ui, Vec::new());
                     });
                 }
             }
             
+            // Search results always belong to the previously selected crawl
+            let _ = ui_weak_clone.upgrade_in_event_loop(|ui| {
+                ui.set_search_results(ModelRc::from(Rc::new(VecModel::from(vec![]))));
+            });
+
+            // Sample pages for the selector builder
+            let _ = tx_clone.blocking_send(AppCommand::LoadPreviewPages { crawl_id });
+
             // Remember this crawl selection for the current site
             // Note: We'd need to track current site_id. For simplicity, we'll store it globally for now.
             // This is a simplification - in a real app, we'd track site_id -> crawl_id mapping
         }
     });
 
-    // Export CSV
-    let _results_cache_clone = Arc::clone(&results_cache);
-    let ui_weak_clone = ui_weak.clone();
-    ui.on_request_export_csv(move || {
-        // Get current results from UI
-        let ui_weak_inner = ui_weak_clone.clone();
-        
-        slint::spawn_local(async move {
-            if let Some(ui) = ui_weak_inner.upgrade() {
-                let results_model = ui.get_results();
-                
-                // Convert results to Vec
-                let mut results_data = Vec::new();
-                for i in 0..results_model.row_count() {
-                    let row = results_model.row_data(i).unwrap();
-                    if row.row_count() >= 3 {
-                        results_data.push((
-                            row.row_data(0).unwrap().text.to_string(),
-                            row.row_data(1).unwrap().text.to_string(),
-                            row.row_data(2).unwrap().text.to_string(),
-                        ));
-                    }
-                }
-                
-                if results_data.is_empty() {
-                    println!("No results to export");
-                    return;
-                }
-                
-                // Use rfd to show save dialog
-                let file_path = rfd::FileDialog::new()
-                    .add_filter("CSV", &["csv"])
-                    .set_file_name("query_results.csv")
-                    .save_file();
+    // Export CSV, with the columns and delimiter from the PALIMP_EXPORT_* variables
+    let tx_clone = tx.clone();
+    ui.on_request_export_csv(move |crawl_id_str, selector| {
+        let Ok(crawl_id) = crawl_id_str.parse::<i64>() else {
+            return;
+        };
+
+        // Use rfd to show save dialog
+        let file_path = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("query_results.csv")
+            .save_file();
+
+        if let Some(path) = file_path {
+            let _ = tx_clone.blocking_send(AppCommand::ExportResults {
+                crawl_id,
+                selector: selector.to_string(),
+                path,
+            });
+        }
+    });
+
+    // With a tray icon, closing the window keeps Palimp and its job worker
+    // running in the background until Quit is picked from the tray menu
+    let in_tray = tray::spawn(ui.as_weak());
+
+    ui.window().on_close_requested(move || {
+        if !in_tray {
+            let _ = slint::quit_event_loop();
+        }
+        slint::CloseRequestResponse::HideWindow
+    });
+    ui.show()?;
+    slint::run_event_loop_until_quit()?;
+
+    // Let running crawls finish the pages in flight before exiting
+    let crawls = active_crawls.lock().map(|mut crawls| std::mem::take(&mut *crawls)).unwrap_or_default();
+    for (stop, _) in &crawls {
+        stop.stop();
+    }
+    for (_, handle) in crawls {
+        let _ = handle.join();
+    }
+    worker_stop.stop();
+    let _ = worker_handle.join();
+
+    Ok(())
+}
+
+// -- Helper Functions --
+
+/// Raises a desktop notification for a finished crawl job, with the alerts
+/// it raised. The summary is worded on the UI thread, in the UI's language.
+fn notify_job_finished(job: &Job, ui: &Weak<AppWindow>) {
+    let JobKind::Crawl { site_id, .. } = job.kind else { return };
+    let status = job.status.name();
+    let body = job.message.clone().unwrap_or_default();
+
+    let _ = ui.upgrade_in_event_loop(move |ui| {
+        let summary = ui.global::<Strings>().invoke_crawl_job_finished(site_id as i32, status.into());
+
+        // Delivery can block on the session bus, so keep it off the UI thread
+        std::thread::spawn(move || {
+            if let Err(e) = notify_rust::Notification::new().appname("Palimp").summary(&summary).body(&body).show() {
+                eprintln!("Failed to show notification: {}", e);
+            }
+        });
+    });
+}
+
+/// Appends a line to the crawl dialog's log. `line` words it on the UI
+/// thread, in the UI's language; lines keep the order they were sent in.
+fn append_log(
+    dialog: &Weak<AddCrawlDialog>,
+    line: impl FnOnce(&Strings<'_>) -> SharedString + Send + 'static,
+) {
+    let _ = dialog.upgrade_in_event_loop(move |d| {
+        let entry = line(&d.global::<Strings>());
+        d.set_log_text(SharedString::from(format!("{}{}\n", d.get_log_text(), entry)));
+    });
+}
+
+/// Reads a dropped `.txt` (one URL per line) or `.xml` (sitemap) file.
+fn read_url_list(path: &std::path::Path) -> Result<UrlListFile, Box<dyn std::error::Error>> {
+    let is_list = path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("txt") || extension.eq_ignore_ascii_case("xml"));
+    if !is_list {
+        return Err("only .txt and .xml URL lists can be dropped".into());
+    }
+
+    let urls = palimp_core::url_list::parse(&std::fs::read_to_string(path)?)?;
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    Ok(UrlListFile { name, urls })
+}
+
+/// Shows the crawl dialog for a site. With a URL list, the crawl fetches
+/// those URLs instead of the sitemap.
+fn open_crawl_dialog(site_id_str: &str, url_list: Option<UrlListFile>, ui_weak: Weak<AppWindow>, active_crawls: ActiveCrawls) {
+    let dialog = AddCrawlDialog::new().unwrap();
+    let dialog_weak = dialog.as_weak();
+    let site_id_str_clone = site_id_str.to_string();
+    let ui_weak_for_crawl = ui_weak;
+    let active_crawls_inner = active_crawls;
+
+    if let Some(list) = &url_list {
+        dialog.set_url_count(list.urls.len() as i32);
+        dialog.set_url_source(SharedString::from(list.name.as_str()));
+    }
+
+    // A sitemap crawl shows what it would cost before it is started
+    if url_list.is_none() && let Ok(site_id) = site_id_str.parse::<i64>() {
+        show_crawl_estimate(&dialog, site_id);
+    }
+
+    // Closing the dialog stops the crawl, leaving it resumable
+    let stop = StopSignal::new();
+    let stop_for_start = stop.clone();
+
+    dialog.on_start(move |_, concurrency_str| {
+        if let Ok(site_id) = site_id_str_clone.parse::<i64>() {
+            let concurrency = concurrency_str.parse::<usize>().unwrap_or(5);
+            let dialog_weak_inner = dialog_weak.clone();
+            let ui_weak_inner = ui_weak_for_crawl.clone();
+            let stop_inner = stop_for_start.clone();
+            let urls = url_list.as_ref().map(|list| list.urls.clone());
+            
+            // Spawn the crawl in a background thread
+            let handle = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Runtime::new().unwrap();
+                runtime.block_on(async {
+                    let app = match open_app() {
+                        Ok(app) => app,
+                        Err(e) => {
+                            eprintln!("Failed to create application: {}", e);
+                            return;
+                        }
+                    };
                     
-                if let Some(path) = file_path {
-                    match std::fs::File::create(&path) {
-                        Ok(file) => {
-                            let mut wtr = csv::Writer::from_writer(file);
-                            
-                            // Write header
-                            if let Err(e) = wtr.write_record(&["ID", "Page URL", "Count"]) {
-                                eprintln!("Error writing CSV header: {}", e);
-                                return;
+                    let total_pages = Arc::new(Mutex::new(0_usize));
+                    let processed_pages = Arc::new(Mutex::new(0_usize));
+                    
+                    let total_clone = Arc::clone(&total_pages);
+                    let processed_clone = Arc::clone(&processed_pages);
+                    let dialog_weak_clone = dialog_weak_inner.clone();
+                    // Share of the pages done, counting the one just finished
+                    let advance = move || {
+                        let mut processed = processed_clone.lock().unwrap();
+                        *processed += 1;
+                        let total = *total_pages.lock().unwrap();
+                        if total > 0 { *processed as f32 / total as f32 } else { 0.0 }
+                    };
+                    
+                    let options = CrawlOptions::new(concurrency).with_stop_signal(stop_inner);
+                    let on_update = move |res: CrawlResult| {
+                        let dialog = &dialog_weak_clone;
+                        match res {
+                            CrawlResult::CrawlStarted(total) => {
+                                *total_clone.lock().unwrap() = total;
+                                append_log(dialog, move |strings| strings.invoke_log_started(total as i32));
                             }
-                            
-                            // Write data
-                            for (id, url, count) in results_data {
-                                if let Err(e) = wtr.write_record(&[id, url, count]) {
-                                    eprintln!("Error writing CSV row: {}", e);
-                                    return;
-                                }
+                            CrawlResult::PageSucceeded(url) => {
+                                let progress = advance();
+                                append_log(dialog, move |strings| strings.invoke_log_page_ok(url.into()));
+                                let _ = dialog.upgrade_in_event_loop(move |d| d.set_progress(progress));
                             }
-                            
-                            if let Err(e) = wtr.flush() {
-                                eprintln!("Error flushing CSV: {}", e);
-                                return;
+                            CrawlResult::PageFailed(url, err) => {
+                                let progress = advance();
+                                append_log(dialog, move |strings| strings.invoke_log_page_failed(url.into(), err.into()));
+                                let _ = dialog.upgrade_in_event_loop(move |d| d.set_progress(progress));
+                            }
+                            CrawlResult::AlertRaised(url, message) => {
+                                append_log(dialog, move |strings| strings.invoke_log_alert(url.into(), message.into()));
+                            }
+                            CrawlResult::Interrupted(crawl_id, pending) => {
+                                append_log(dialog, move |strings| {
+                                    strings.invoke_log_interrupted(crawl_id as i32, pending as i32)
+                                });
+                            }
+                            CrawlResult::Cancelled(crawl_id, dropped) => {
+                                append_log(dialog, move |strings| {
+                                    strings.invoke_log_cancelled(crawl_id as i32, dropped as i32)
+                                });
+                            }
+                            CrawlResult::Stats(stats) => {
+                                let requests_per_sec = format!("{:.1}", stats.requests_per_sec());
+                                let error_rate = format!("{:.1}%", stats.error_rate() * 100.0);
+                                let average_latency = format!("{} ms", stats.average_latency().as_millis());
+                                let bytes_downloaded = format_bytes(stats.bytes);
+
+                                let dialog_weak_update = dialog.clone();
+                                let _ = slint::invoke_from_event_loop(move || {
+                                    if let Some(d) = dialog_weak_update.upgrade() {
+                                        d.set_requests_per_sec(SharedString::from(requests_per_sec));
+                                        d.set_error_rate(SharedString::from(error_rate));
+                                        d.set_average_latency(SharedString::from(average_latency));
+                                        d.set_bytes_downloaded(SharedString::from(bytes_downloaded));
+                                    }
+                                });
+                            }
+                            CrawlResult::Warning(message) => {
+                                append_log(dialog, move |strings| strings.invoke_log_warning(message.into()));
                             }
-                            
-                            println!("Successfully exported results to: {}", path.display());
                         }
-                        Err(e) => {
-                            eprintln!("Error creating file: {}", e);
+                    };
+                    let _result = match urls {
+                        Some(urls) => app.new_crawl_from_urls(site_id, urls, options, on_update).await,
+                        None => app.new_crawl_with_options(site_id, options, on_update).await,
+                    };
+                    
+                    // Crawl completed
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(d) = dialog_weak_inner.upgrade() {
+                            d.set_is_crawling(false);
+                            d.set_crawl_completed(true);
+                            d.set_progress(1.0);
                         }
-                    }
-                }
+                    });
+                    
+                    // Refresh crawls list
+                    refresh_crawls_for_site(&app, &ui_weak_inner, site_id).await;
+                });
+            });
+
+            if let Ok(mut crawls) = active_crawls_inner.lock() {
+                crawls.push((stop_for_start.clone(), handle));
             }
-        }).unwrap();
+        }
+    });
+    
+    // Gives the running crawl up, keeping the pages fetched so far
+    let stop_for_stop = stop.clone();
+    dialog.on_stop_clicked(move || {
+        stop_for_stop.cancel();
     });
 
-    ui.run()?;
-    Ok(())
+    let dialog_weak = dialog.as_weak();
+    let stop_for_cancel = stop.clone();
+    dialog.on_cancel_clicked(move || {
+        stop_for_cancel.stop();
+        if let Some(d) = dialog_weak.upgrade() {
+            let _ = d.hide();
+        }
+    });
+
+    dialog.window().on_close_requested(move || {
+        stop.stop();
+        slint::CloseRequestResponse::HideWindow
+    });
+
+    dialog.show().unwrap();
 }
 
-// -- Helper Functions --
+
+/// Shown in the first results column for selected rows.
+const SELECTED_MARK: &str = "✓";
+
+/// Fetches the site's crawl estimate in the background and keeps the
+/// dialog's projection in step with the concurrency typed into it.
+fn show_crawl_estimate(dialog: &AddCrawlDialog, site_id: i64) {
+    let estimate: Arc<Mutex<Option<CrawlEstimate>>> = Arc::new(Mutex::new(None));
+    dialog.set_estimate_text(dialog.global::<Strings>().get_estimating());
+
+    let dialog_weak = dialog.as_weak();
+    let estimate_for_edit = Arc::clone(&estimate);
+    dialog.on_concurrency_edited(move |concurrency| {
+        let concurrency = concurrency.parse::<usize>().unwrap_or(5);
+        if let Some(estimate) = estimate_for_edit.lock().unwrap().as_ref()
+            && let Some(d) = dialog_weak.upgrade()
+        {
+            d.set_estimate_text(SharedString::from(estimate_text(&d.global::<Strings>(), estimate, concurrency)));
+        }
+    });
+
+    let dialog_weak = dialog.as_weak();
+    std::thread::spawn(move || {
+        let result = tokio::runtime::Runtime::new()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime.block_on(async {
+                    let app = open_app().map_err(|e| e.to_string())?;
+                    app.estimate_crawl(site_id).await.map_err(|e| e.to_string())
+                })
+            });
+        let error = match result {
+            Ok(result) => {
+                *estimate.lock().unwrap() = Some(result);
+                None
+            }
+            Err(e) => Some(e),
+        };
+        let _ = dialog_weak.upgrade_in_event_loop(move |d| {
+            let strings = d.global::<Strings>();
+            let text = match (error, estimate.lock().unwrap().as_ref()) {
+                (Some(error), _) => strings.invoke_estimate_failed(error.into()).into(),
+                (None, Some(estimate)) => {
+                    estimate_text(&strings, estimate, d.get_concurrency_value().parse::<usize>().unwrap_or(5))
+                }
+                (None, None) => String::new(),
+            };
+            d.set_estimate_text(SharedString::from(text));
+        });
+    });
+}
+
+/// The estimate worded for the dialog, in the UI's language.
+fn estimate_text(strings: &Strings<'_>, estimate: &CrawlEstimate, concurrency: usize) -> String {
+    let options = CrawlOptions::new(concurrency);
+    let mut text = String::from(strings.invoke_estimate_summary(
+        estimate.urls as i32,
+        estimate.hosts as i32,
+        estimate.requests() as i32,
+        format_duration(estimate.duration(&options)).into(),
+        format_bytes(estimate.bandwidth()).into(),
+        options.max_concurrent as i32,
+    ));
+    if estimate.duplicate_urls > 0 {
+        text.push('\n');
+        text.push_str(&strings.invoke_estimate_duplicates(estimate.duplicate_urls as i32));
+    }
+    if estimate.measured_crawl_id.is_none() {
+        text.push('\n');
+        text.push_str(&strings.get_no_earlier_crawl());
+    }
+    text
+}
+
+/// Opens the archive, caching fetched pages on disk if `PALIMP_HTTP_CACHE` is set.
+fn open_app() -> Result<Application, Box<dyn std::error::Error>> {
+    Application::with_http_client("palimp.db", HTTPClient::from_env()?)
+}
+
+/// Shows query results, with nothing selected.
+fn set_result_rows(ui: &AppWindow, data: Vec<ResultData>) {
+    let items: Vec<ModelRc<StandardListViewItem>> = data.into_iter()
+        .map(|r| {
+            ModelRc::from(Rc::new(VecModel::from(vec![
+                StandardListViewItem::from(SharedString::new()),
+                StandardListViewItem::from(SharedString::from(r.id)),
+                StandardListViewItem::from(SharedString::from(r.page_url)),
+                StandardListViewItem::from(SharedString::from(r.count)),
+            ])))
+        })
+        .collect();
+    ui.set_results(ModelRc::from(Rc::new(VecModel::from(items))));
+    ui.set_result_selected_count(0);
+}
+
+/// Shows the elements a previewed selector matched, or why the selector
+/// could not be evaluated.
+fn set_preview(ui: &AppWindow, preview: Result<SelectorPreview, String>) {
+    let (matches, error) = match preview {
+        Ok(preview) => (preview.matches, String::new()),
+        Err(e) => (Vec::new(), e),
+    };
+    ui.set_preview_count(matches.len() as i32);
+    ui.set_preview_error(SharedString::from(error));
+
+    let items: Vec<ModelRc<StandardListViewItem>> = matches.into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            ModelRc::from(Rc::new(VecModel::from(vec![
+                StandardListViewItem::from(SharedString::from((i + 1).to_string())),
+                StandardListViewItem::from(SharedString::from(m.text)),
+                StandardListViewItem::from(SharedString::from(m.html)),
+            ])))
+        })
+        .collect();
+    ui.set_preview_rows(ModelRc::from(Rc::new(VecModel::from(items))));
+}
+
+fn selected_result_ids(ui: &AppWindow) -> Vec<i64> {
+    ui.get_results().iter()
+        .filter(|row| row.row_data(0).is_some_and(|mark| !mark.text.is_empty()))
+        .filter_map(|row| row.row_data(1)?.text.parse().ok())
+        .collect()
+}
+
+/// SVG path commands drawing `values` as a line in a 100×100 viewbox, with
+/// `max` at the top edge.
+fn trend_path(values: &[usize], max: usize) -> String {
+    let scale = |value: usize| if max == 0 { 100.0 } else { 100.0 - value as f64 * 100.0 / max as f64 };
+    let step = if values.len() > 1 { 100.0 / (values.len() - 1) as f64 } else { 0.0 };
+
+    let mut commands: Vec<String> = values.iter().enumerate()
+        .map(|(i, &value)| format!("{} {:.2} {:.2}", if i == 0 { "M" } else { "L" }, i as f64 * step, scale(value)))
+        .collect();
+
+    // A single crawl is drawn as a flat line so it stays visible
+    if let [only] = values {
+        commands.push(format!("L 100 {:.2}", scale(*only)));
+    }
+    commands.join(" ")
+}
 
 async fn refresh_sites(app: &Application, ui_weak: &Weak<AppWindow>, site_index_map: &Arc<Mutex<Vec<i64>>>) {
     let sites = match app.list_sites().await {
@@ -558,6 +1167,8 @@ async fn refresh_crawls_for_site(app: &Application, ui_weak: &Weak<AppWindow>, s
         CrawlData {
             id: c.id.unwrap_or(0).to_string(),
             started_at: c.started_at.unwrap_or_default(),
+            duration: c.duration.map(format_duration).unwrap_or_default(),
+            pinned: c.pinned,
         }
     }).collect();
 
@@ -567,9 +1178,14 @@ async fn refresh_crawls_for_site(app: &Application, ui_weak: &Weak<AppWindow>, s
             let row = Rc::new(VecModel::from(vec![
                 StandardListViewItem::from(SharedString::from(c.id)),
                 StandardListViewItem::from(SharedString::from(c.started_at)),
+                StandardListViewItem::from(if c.pinned { "pinned" } else { "" }),
+                StandardListViewItem::from(SharedString::from(c.duration)),
             ]));
             items.push(ModelRc::from(row));
         }
+        let selection = vec![false; items.len()];
         ui.set_crawls(ModelRc::from(Rc::new(VecModel::from(items))));
+        ui.set_crawl_selection(ModelRc::from(Rc::new(VecModel::from(selection))));
+        ui.set_crawl_selected_count(0);
     });
 }
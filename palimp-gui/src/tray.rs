@@ -0,0 +1,111 @@
+// Tray icon that keeps Palimp running with its window closed, so queued
+// crawls carry on in the background. Uses the StatusNotifierItem protocol,
+// so there is only a tray on Linux; elsewhere closing the window quits.
+
+#[cfg(target_os = "linux")]
+pub use sni::spawn;
+
+/// No tray to keep running in, so closing the window quits.
+#[cfg(not(target_os = "linux"))]
+pub fn spawn(_ui: slint::Weak<crate::AppWindow>) -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+mod sni {
+    use crate::{AppWindow, Strings};
+    use ksni::menu::StandardItem;
+    use ksni::TrayMethods;
+    use slint::{ComponentHandle, Weak};
+
+    struct PalimpTray {
+        ui: Weak<AppWindow>,
+        /// Menu labels in the UI's language, read before the tray thread
+        /// starts since only the UI thread can translate.
+        show_label: String,
+        quit_label: String,
+    }
+
+    impl ksni::Tray for PalimpTray {
+        fn id(&self) -> String {
+            "palimp".into()
+        }
+
+        fn title(&self) -> String {
+            "Palimp".into()
+        }
+
+        fn icon_name(&self) -> String {
+            "web-browser".into()
+        }
+
+        fn activate(&mut self, _x: i32, _y: i32) {
+            show_window(&self.ui);
+        }
+
+        fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+            vec![
+                StandardItem {
+                    label: self.show_label.clone(),
+                    activate: Box::new(|tray: &mut Self| show_window(&tray.ui)),
+                    ..Default::default()
+                }
+                .into(),
+                ksni::MenuItem::Separator,
+                StandardItem {
+                    label: self.quit_label.clone(),
+                    activate: Box::new(|_| {
+                        let _ = slint::invoke_from_event_loop(|| {
+                            let _ = slint::quit_event_loop();
+                        });
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ]
+        }
+    }
+
+    fn show_window(ui: &Weak<AppWindow>) {
+        let _ = ui.upgrade_in_event_loop(|ui| {
+            let _ = ui.show();
+        });
+    }
+
+    /// Shows the tray icon from a background thread and returns whether it is
+    /// up. Without a tray host, e.g. on a bare window manager, it is not.
+    /// Called on the UI thread.
+    pub fn spawn(ui: Weak<AppWindow>) -> bool {
+        let Some(window) = ui.upgrade() else { return false };
+        let strings = window.global::<Strings>();
+        let tray = PalimpTray {
+            ui,
+            show_label: strings.get_show_window().into(),
+            quit_label: strings.get_quit().into(),
+        };
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build tokio runtime");
+
+            rt.block_on(async move {
+                match tray.spawn().await {
+                    Ok(_handle) => {
+                        let _ = ready_tx.send(true);
+                        // The icon lives as long as the application
+                        std::future::pending::<()>().await;
+                    }
+                    Err(e) => {
+                        eprintln!("No system tray available: {}", e);
+                        let _ = ready_tx.send(false);
+                    }
+                }
+            });
+        });
+
+        ready_rx.recv().unwrap_or(false)
+    }
+}
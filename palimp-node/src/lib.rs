@@ -0,0 +1,248 @@
+//! Node.js bindings, so JS dashboards can trigger crawls and read results
+//! without shelling out to the CLI.
+//!
+//! ```js
+//! const { Palimp } = require('./palimp.node');
+//! const palimp = new Palimp('palimp.db');
+//! const summary = await palimp.crawl(1);
+//! const { results } = await palimp.query(summary.crawlId, 'h1');
+//! ```
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use palimp_core::CrawlResult;
+use palimp_core::blocking::Application;
+use palimp_core::result_entry::{ResultEntry, ResultStatus};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::sync::oneshot;
+
+/// Pages fetched concurrently when the caller does not say.
+const DEFAULT_MAX_CONCURRENT: u32 = 5;
+
+/// Runs `call` on a thread of its own and resolves with its result. The
+/// core's futures are not `Send`, so they cannot run on the Node runtime;
+/// the blocking application drives them on its private runtime instead.
+async fn run<T, F>(app: &Arc<Application>, call: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Application) -> std::result::Result<T, Box<dyn std::error::Error>> + Send + 'static,
+{
+    let app = Arc::clone(app);
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(call(&app).map_err(|e| e.to_string()));
+    });
+    rx.await
+        .map_err(|_| Error::from_reason("palimp call panicked"))?
+        .map_err(Error::from_reason)
+}
+
+#[napi(object)]
+pub struct Site {
+    pub id: i64,
+    pub domain: String,
+    pub sitemap_url: String,
+}
+
+#[napi(object)]
+pub struct Crawl {
+    pub id: i64,
+    pub site_id: i64,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub status: String,
+}
+
+#[napi(object)]
+pub struct CrawlSummary {
+    pub crawl_id: Option<i64>,
+    pub pages: u32,
+    pub failed: u32,
+    pub alerts: u32,
+}
+
+#[napi(object)]
+pub struct QueryResult {
+    pub id: i64,
+    pub page_url: String,
+    pub count: u32,
+    /// `new`, `reviewed` or `ignored`.
+    pub status: String,
+}
+
+/// Narrows down `searchResults`; unset fields match every result.
+#[napi(object)]
+pub struct ResultFilter {
+    pub crawl_id: Option<i64>,
+    pub query_id: Option<i64>,
+    pub selector: Option<String>,
+    pub min_count: Option<u32>,
+    pub max_count: Option<u32>,
+    /// Only pages whose URL contains this text.
+    pub url_pattern: Option<String>,
+    /// `new`, `reviewed` or `ignored`.
+    pub status: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl From<ResultFilter> for palimp_core::result_entry::ResultFilter {
+    fn from(filter: ResultFilter) -> Self {
+        Self {
+            crawl_id: filter.crawl_id,
+            query_id: filter.query_id,
+            selector: filter.selector,
+            min_count: filter.min_count,
+            max_count: filter.max_count,
+            url_pattern: filter.url_pattern,
+            status: filter.status.as_deref().and_then(ResultStatus::from_name),
+            limit: filter.limit.map(|limit| limit as usize),
+            offset: filter.offset.unwrap_or(0) as usize,
+        }
+    }
+}
+
+#[napi(object)]
+pub struct QueryResults {
+    pub query_id: i64,
+    pub results: Vec<QueryResult>,
+}
+
+#[napi]
+pub struct Palimp {
+    app: Arc<Application>,
+}
+
+#[napi]
+impl Palimp {
+    /// Opens (or creates) the archive at `dbPath`. With `readOnly`, crawls,
+    /// deletions and settings changes are rejected.
+    #[napi(constructor)]
+    pub fn new(db_path: String, read_only: Option<bool>) -> Result<Self> {
+        let app = Application::new(&db_path).map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(Self { app: Arc::new(app.with_read_only(read_only.unwrap_or(false))) })
+    }
+
+    #[napi]
+    pub async fn list_sites(&self) -> Result<Vec<Site>> {
+        let sites = run(&self.app, |app| app.list_sites()).await?;
+        Ok(sites
+            .into_iter()
+            .map(|site| Site {
+                id: site.id.unwrap_or(0),
+                domain: site.domain,
+                sitemap_url: site.sitemap_url,
+            })
+            .collect())
+    }
+
+    #[napi]
+    pub async fn list_crawls(&self) -> Result<Vec<Crawl>> {
+        let crawls = run(&self.app, |app| app.list_crawls()).await?;
+        Ok(crawls
+            .into_iter()
+            .map(|crawl| Crawl {
+                id: crawl.id.unwrap_or(0),
+                site_id: crawl.site_id,
+                started_at: crawl.started_at,
+                finished_at: crawl.finished_at,
+                duration_seconds: crawl.duration.map(|duration| duration.as_secs() as i64),
+                status: crawl.status.name().to_string(),
+            })
+            .collect())
+    }
+
+    /// Crawls a site's sitemap and resolves once every page is fetched.
+    #[napi]
+    pub async fn crawl(&self, site_id: i64, max_concurrent: Option<u32>) -> Result<CrawlSummary> {
+        let pages = Arc::new(AtomicU32::new(0));
+        let failed = Arc::new(AtomicU32::new(0));
+        let alerts = Arc::new(AtomicU32::new(0));
+
+        let (p, f, a) = (pages.clone(), failed.clone(), alerts.clone());
+        let max_concurrent = max_concurrent.unwrap_or(DEFAULT_MAX_CONCURRENT) as usize;
+        let crawl_id = run(&self.app, move |app| {
+            app.new_crawl(site_id, max_concurrent, move |res| {
+                let counter = match res {
+                    CrawlResult::PageSucceeded(_) => &p,
+                    CrawlResult::PageFailed(_, _) => &f,
+                    CrawlResult::AlertRaised(_, _) => &a,
+                    _ => return,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            })?;
+
+            // The crawl just finished is the newest one of the site
+            let crawls = app.list_crawls()?;
+            Ok(crawls.into_iter().filter(|crawl| crawl.site_id == site_id).filter_map(|crawl| crawl.id).max())
+        })
+        .await?;
+
+        Ok(CrawlSummary {
+            crawl_id,
+            pages: pages.load(Ordering::Relaxed),
+            failed: failed.load(Ordering::Relaxed),
+            alerts: alerts.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Runs a selector (or `@template`) over a crawl and stores the counts.
+    #[napi]
+    pub async fn query(&self, crawl_id: i64, selector: String) -> Result<QueryResults> {
+        let selector = palimp_core::template::resolve_selector(&selector).map_err(Error::from_reason)?;
+        let query_id = run(&self.app, move |app| {
+            app.query(crawl_id, &selector)?;
+
+            // The query just run is the newest one of the crawl
+            let queries = app.list_queries()?;
+            queries
+                .into_iter()
+                .filter(|query| query.crawl_id == crawl_id)
+                .filter_map(|query| query.id)
+                .max()
+                .ok_or_else(|| "Query was not stored".into())
+        })
+        .await?;
+
+        self.results(query_id).await
+    }
+
+    /// Results of a query stored earlier.
+    #[napi]
+    pub async fn results(&self, query_id: i64) -> Result<QueryResults> {
+        let results = run(&self.app, move |app| app.list_results_for_query(query_id)).await?;
+        Ok(QueryResults { query_id, results: to_query_results(results) })
+    }
+
+    /// Stored results matching `filter`, across queries and crawls.
+    #[napi]
+    pub async fn search_results(&self, filter: ResultFilter) -> Result<Vec<QueryResult>> {
+        let filter = filter.into();
+        let results = run(&self.app, move |app| app.search_results(&filter)).await?;
+        Ok(to_query_results(results))
+    }
+
+    /// Marks results as `new`, `reviewed` or `ignored`, resolving to how many
+    /// were found.
+    #[napi]
+    pub async fn set_result_status(&self, result_ids: Vec<i64>, status: String) -> Result<u32> {
+        let status = ResultStatus::from_name(&status)
+            .ok_or_else(|| Error::from_reason(format!("Unknown status '{}'", status)))?;
+        let updated = run(&self.app, move |app| app.set_result_status(&result_ids, status)).await?;
+        Ok(updated as u32)
+    }
+}
+
+fn to_query_results(results: Vec<(ResultEntry, String)>) -> Vec<QueryResult> {
+    results
+        .into_iter()
+        .map(|(result, page_url)| QueryResult {
+            id: result.id.unwrap_or(0),
+            page_url,
+            count: result.count,
+            status: result.status.name().to_string(),
+        })
+        .collect()
+}